@@ -0,0 +1,117 @@
+// wfemto - A toy text editor
+// Written in 2025 by Dana Larose <ywg.dana@gmail.com>
+//
+// To the extent possible under law, the author(s) have dedicated all copyright
+// and related and neighboring rights to this software to the public domain
+// worldwide. This software is distributed without any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication along
+// with this software. If not,
+// see <http://creativecommons.org/publicdomain/zero/1.0/>.
+
+// Command line parsing. Kept free of SDL so that --help and --version (and
+// bad flags) can be dealt with before we try to open a window.
+
+use std::path::Path;
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Args {
+    pub file: Option<String>,
+    pub line: Option<usize>, // 1-based, as the user typed it
+    pub col: Option<usize>,  // 1-based
+    pub readonly: bool,
+    pub session: Option<String>, // Named session to open as well
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Invocation {
+    Run(Args),
+    Help,
+    Version,
+}
+
+pub fn usage() -> String {
+    let mut s = String::from("Usage: wfemto [options] [+LINE] [FILE[:LINE[:COL]]]\n\n");
+    s.push_str("Options:\n");
+    s.push_str("  +LINE         open FILE with the cursor on line LINE\n");
+    s.push_str("  --readonly    open FILE read-only\n");
+    s.push_str("  --session NAME  open the files of a session saved with save-session\n");
+    s.push_str("  --version     print version information and exit\n");
+    s.push_str("  --help        print this message and exit\n");
+    s.push_str("\nExit status: 0 ok, 1 error while running, 2 bad arguments,\n");
+    s.push_str("3 SDL couldn't start (no display?), 4 font missing or unreadable\n");
+
+    s
+}
+
+pub fn parse<I: Iterator<Item = String>>(mut args: I) -> Result<Invocation, String> {
+    let mut parsed = Args::default();
+    let mut flags_done = false;
+
+    while let Some(arg) = args.next() {
+        if !flags_done && arg == "--" {
+            flags_done = true;
+        } else if !flags_done && (arg == "--help" || arg == "-h") {
+            return Ok(Invocation::Help);
+        } else if !flags_done && arg == "--version" {
+            return Ok(Invocation::Version);
+        } else if !flags_done && arg == "--readonly" {
+            parsed.readonly = true;
+        } else if !flags_done && arg == "--session" {
+            parsed.session = Some(args.next().ok_or("--session needs a session name")?);
+        } else if let Some(name) = arg.strip_prefix("--session=").filter(|_| !flags_done) {
+            parsed.session = Some(name.to_string());
+        } else if !flags_done && arg.starts_with('+') {
+            let line = parse_number(&arg[1..])
+                .ok_or_else(|| format!("invalid line number: {}", arg))?;
+            parsed.line = Some(line);
+        } else if !flags_done && arg.starts_with('-') && arg.len() > 1 {
+            return Err(format!("unknown option: {}", arg));
+        } else if parsed.file.is_some() {
+            return Err(format!("unexpected argument: {}", arg));
+        } else {
+            let (file, line, col) = split_location(&arg);
+            parsed.file = Some(file);
+            if line.is_some() {
+                parsed.line = line;
+                parsed.col = col;
+            }
+        }
+    }
+
+    Ok(Invocation::Run(parsed))
+}
+
+fn parse_number(s: &str) -> Option<usize> {
+    if s.is_empty() || !s.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+
+    s.parse().ok()
+}
+
+// Compilers report locations as file:line:col, so accept that form. A file
+// that genuinely has a colon in its name wins if it exists on disk.
+fn split_location(arg: &str) -> (String, Option<usize>, Option<usize>) {
+    if Path::new(arg).exists() {
+        return (arg.to_string(), None, None);
+    }
+
+    let parts: Vec<&str> = arg.rsplitn(3, ':').collect();
+    match parts.as_slice() {
+        [col, line, file] if !file.is_empty() => {
+            match (parse_number(line), parse_number(col)) {
+                (Some(l), Some(c)) => (file.to_string(), Some(l), Some(c)),
+                _ => match parse_number(col) {
+                    Some(l) => (format!("{}:{}", file, line), Some(l), None),
+                    None => (arg.to_string(), None, None),
+                },
+            }
+        }
+        [line, file] if !file.is_empty() => match parse_number(line) {
+            Some(l) => (file.to_string(), Some(l), None),
+            None => (arg.to_string(), None, None),
+        },
+        _ => (arg.to_string(), None, None),
+    }
+}
@@ -0,0 +1,2190 @@
+// wfemto - A toy text editor
+// Written in 2025 by Dana Larose <ywg.dana@gmail.com>
+//
+// To the extent possible under law, the author(s) have dedicated all copyright
+// and related and neighboring rights to this software to the public domain
+// worldwide. This software is distributed without any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication along
+// with this software. If not,
+// see <http://creativecommons.org/publicdomain/zero/1.0/>.
+
+use std::fs::{self, File};
+use std::io::{BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::SystemTime;
+
+use crate::WindowInfo;
+use crate::editorconfig::{self, EndOfLine, IndentStyle};
+use crate::encoding::Encoding;
+use crate::gitdiff::{self, GitMark};
+use crate::highlight::{self, Language, State};
+use crate::indent::{self, Indent};
+use crate::jumps::{Jump, JumpList};
+use crate::outline::{self, Heading};
+use crate::reflow;
+use crate::text::{self, byte_idx, char_len, col_slice, display_col};
+use crate::undo::{Change, ChangeKind, UndoStack};
+use crate::undofile;
+
+static NEXT_BUFFER_ID: AtomicU64 = AtomicU64::new(1);
+
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LineEnding {
+    Lf,
+    Crlf,
+    Cr,
+}
+
+impl LineEnding {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::Crlf => "\r\n",
+            LineEnding::Cr => "\r",
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            LineEnding::Lf => "LF",
+            LineEnding::Crlf => "CRLF",
+            LineEnding::Cr => "CR",
+        }
+    }
+}
+
+/// Cursor and scroll position within a buffer. A pane that isn't focused
+/// keeps one of these so it can show a different spot in the same buffer.
+#[derive(Clone, Copy)]
+pub struct View {
+    pub buffer_col: usize,
+    pub buffer_row: usize,
+    pub desired_col: usize,
+    pub top_line: usize,
+    pub buffer_col_offset: usize,
+}
+
+/// The text of one open file plus everything that's specific to it: where the
+/// cursor is, how it's scrolled, and whether it has unsaved changes.
+///
+/// buffer_row/buffer_col are the one true cursor position. Where that lands
+/// on screen is derived from the viewport (top_line and buffer_col_offset),
+/// which every edit and movement adjusts via ensure_cursor_visible().
+pub struct Buffer {
+    pub id: u64,                   // Stays the same however buffers are reordered
+    pub lines: Vec<String>,        // Never empty; an empty file is one empty line
+    pub buffer_col: usize,
+    pub buffer_row: usize,
+    pub desired_col: usize,        // Column vertical movement tries to return to
+    pub top_line: usize,           // First buffer line shown in the window
+    pub buffer_col_offset: usize,  // Horizontal scroll offset
+    pub anchor: Option<(usize, usize)>, // Other end of the selection (row, col)
+    pub line_anchor: Option<(usize, usize)>, // The anchor, while the selection is of whole lines
+    pub filename: String,
+    pub is_modified: bool,
+    pub read_only: bool,
+    pub language: Option<&'static Language>, // None means plain text
+    pub tab_width: usize,
+    pub indent: Indent,            // What Tab and auto-indent insert
+    pub auto_indent: bool,
+    pub persistent_undo: bool,     // Keep the undo history between sessions
+    pub indent_locked: bool,       // Indent was chosen by hand; don't second-guess it
+    pub indent_from: &'static str, // What decided on indent, for the settings command
+    pub line_ending: LineEnding,
+    pub encoding: Encoding,        // What the file is read and written as
+    mixed_endings: usize,          // Line breaks loaded that weren't line_ending
+    pub final_newline: bool,       // Whether the last line ends with a line break
+    pub bom: bool,
+    pub trim_trailing_whitespace: bool, // On save
+    pub fill_column: usize,        // Width paragraphs are reflowed to
+    pub auto_wrap: bool,           // Typing past fill_column breaks the line
+    pub word_chars: String,        // Count as part of words along with letters, digits and _
+    pub disk_mtime: Option<SystemTime>, // When the file was last loaded or saved
+    pub watch: bool,               // Reload whenever the file changes on disk
+    pub tail: bool,                // Read what's appended to the file and keep the end in view
+    pub copy: bool,                // A second buffer on a file that's already open
+    disk_len: u64,                 // Bytes of the file read so far
+    hl_states: Vec<State>,         // Highlighter state at the start of each line...
+    hl_valid: usize,               // ...of which this many are up to date
+    line_starts: Vec<(usize, usize)>, // Chars and bytes in the lines before each line...
+    starts_valid: usize,           // ...of which this many are up to date
+    outline: Vec<Heading>,         // Markdown headings in the lines...
+    fences: Vec<usize>,            // ...and code fences...
+    outline_valid: usize,          // ...before this row
+    pub generation: u64,           // Bumped on every change to the text
+    pub git_marks: Vec<GitMark>,   // Empty unless the file is tracked by git
+    history: UndoStack,
+    jumps: JumpList,
+}
+
+/// The lines an edit is about to touch, captured so that once it's done the
+/// edit can be recorded for undo.
+struct Before {
+    row: usize,
+    old: Vec<String>,
+    line_count: usize,
+    cursor: (usize, usize),
+}
+
+impl Buffer {
+    pub fn new() -> Self {
+        Buffer {
+            id: NEXT_BUFFER_ID.fetch_add(1, Ordering::Relaxed),
+            lines: vec![String::new()],
+            buffer_col: 0,
+            buffer_row: 0,
+            desired_col: 0,
+            top_line: 0,
+            buffer_col_offset: 0,
+            anchor: None,
+            line_anchor: None,
+            filename: String::from("filename.txt"),
+            is_modified: false,
+            read_only: false,
+            language: None,
+            tab_width: 4,
+            indent: Indent::Spaces(4),
+            auto_indent: true,
+            persistent_undo: false,
+            disk_mtime: None,
+            watch: false,
+            tail: false,
+            copy: false,
+            disk_len: 0,
+            indent_locked: false,
+            indent_from: "default",
+            line_ending: LineEnding::Lf,
+            encoding: Encoding::Utf8,
+            mixed_endings: 0,
+            final_newline: true,
+            bom: false,
+            trim_trailing_whitespace: false,
+            fill_column: 72,
+            auto_wrap: false,
+            word_chars: String::new(),
+            hl_states: Vec::new(),
+            hl_valid: 0,
+            line_starts: Vec::new(),
+            starts_valid: 0,
+            outline: Vec::new(),
+            fences: Vec::new(),
+            outline_valid: 0,
+            generation: 0,
+            git_marks: Vec::new(),
+            history: UndoStack::new(),
+            jumps: JumpList::new(),
+        }
+    }
+
+    pub fn view(&self) -> View {
+        View {
+            buffer_col: self.buffer_col,
+            buffer_row: self.buffer_row,
+            desired_col: self.desired_col,
+            top_line: self.top_line,
+            buffer_col_offset: self.buffer_col_offset,
+        }
+    }
+
+    /// Restore a saved view. The buffer may have been edited from another
+    /// pane since the view was saved, so clamp it to the current text.
+    pub fn set_view(&mut self, view: View) {
+        self.buffer_row = view.buffer_row;
+        self.buffer_col = view.buffer_col;
+        self.clamp_cursor();
+        self.desired_col = view.desired_col;
+        self.top_line = view.top_line.min(self.buffer_row);
+        self.buffer_col_offset = view.buffer_col_offset.min(self.buffer_col);
+    }
+
+    /// The selected range as ((row, col), (row, col)) with the start first,
+    /// or None if nothing is selected.
+    pub fn selection(&self) -> Option<((usize, usize), (usize, usize))> {
+        if let Some((start, end)) = self.line_selection() {
+            // Whole lines go with their line breaks. The last line has none
+            // of its own, so it takes the one before it.
+            let line_end = |row: usize| (row, char_len(&self.lines[row]));
+            return match (start, end) {
+                (_, end) if end < self.lines.len() => Some(((start, 0), (end, 0))),
+                (0, end) if line_end(end - 1) == (0, 0) => None,
+                (0, end) => Some(((0, 0), line_end(end - 1))),
+                (start, end) => Some((line_end(start - 1), line_end(end - 1))),
+            };
+        }
+
+        let anchor = self.anchor?;
+        let cursor = (self.buffer_row, self.buffer_col);
+        match anchor.cmp(&cursor) {
+            std::cmp::Ordering::Less => Some((anchor, cursor)),
+            std::cmp::Ordering::Greater => Some((cursor, anchor)),
+            std::cmp::Ordering::Equal => None,
+        }
+    }
+
+    /// Rows start..end, if the selection is of whole lines. Their
+    /// selection() includes the line breaks.
+    pub fn line_selection(&self) -> Option<(usize, usize)> {
+        let anchor = self.anchor.filter(|&a| self.line_anchor == Some(a))?;
+        let (from, to) = (anchor.0, self.buffer_row);
+        Some((from.min(to), from.max(to) + 1))
+    }
+
+    /// The selected text. Whole lines come with a line break after the last
+    /// one, even the buffer's last line.
+    pub fn selected_text(&self) -> Option<String> {
+        if let Some((start, end)) = self.line_selection() {
+            return Some(self.lines[start..end].iter().map(|line| format!("{}\n", line)).collect());
+        }
+
+        self.selection().map(|(start, end)| self.text_between(start, end))
+    }
+
+    /// Select the cursor's line in full, or every line a selection touches.
+    /// With whole lines already selected, take in the line below as well.
+    pub fn select_line(&mut self, window_info: &WindowInfo) {
+        if self.line_selection().is_some() {
+            self.buffer_row = (self.buffer_row + 1).min(self.lines.len() - 1);
+        } else {
+            let (start, end) = self.selection().map_or((self.buffer_row, self.buffer_row), |((s, _), (e, _))| (s, e));
+            self.anchor = Some((start, 0));
+            self.line_anchor = self.anchor;
+            self.buffer_row = end;
+        }
+
+        self.clamp_cursor();
+        self.settle_cursor(window_info);
+    }
+
+    /// Called before a cursor movement: with Shift held the movement extends
+    /// the selection (starting one if needed), otherwise it drops it.
+    pub fn select_while_moving(&mut self, selecting: bool) {
+        if !selecting {
+            self.anchor = None;
+        } else if self.anchor.is_none() {
+            self.anchor = Some((self.buffer_row, self.buffer_col));
+            self.line_anchor = None;
+        }
+    }
+
+    /// Remove the selected text, leaving the cursor where the selection
+    /// began. Returns false if there was nothing selected.
+    pub fn delete_selection(&mut self, window_info: &WindowInfo) -> bool {
+        let selection = self.selection();
+        self.anchor = None;
+
+        let Some(((start_row, start_col), (end_row, end_col))) = selection else {
+            return false;
+        };
+
+        let before = self.before_change(start_row, end_row);
+        if start_row == end_row {
+            let line = &mut self.lines[start_row];
+            let range = byte_idx(line, start_col)..byte_idx(line, end_col);
+            line.replace_range(range, "");
+        } else {
+            let end_line = &self.lines[end_row];
+            let tail = end_line[byte_idx(end_line, end_col)..].to_string();
+            let start_at = byte_idx(&self.lines[start_row], start_col);
+            self.lines[start_row].truncate(start_at);
+            self.lines[start_row].push_str(&tail);
+            self.lines.drain(start_row + 1..=end_row);
+        }
+
+        self.buffer_row = start_row;
+        self.buffer_col = start_col;
+        self.after_change(before, ChangeKind::Other);
+        self.settle_cursor(window_info);
+
+        true
+    }
+
+    /// Cursor row relative to the top of the viewport
+    pub fn scr_row(&self) -> usize {
+        self.buffer_row - self.top_line
+    }
+
+    /// Record an edit to line `row` (and possibly everything after it, if
+    /// lines were added or removed there).
+    fn mark_changed(&mut self, row: usize) {
+        self.is_modified = true;
+        self.generation += 1;
+        // A line's own starting state depends only on the lines above it
+        self.hl_valid = self.hl_valid.min(row + 1);
+        self.starts_valid = self.starts_valid.min(row + 1);
+        self.outline_valid = self.outline_valid.min(row);
+    }
+
+    /// Snapshot lines first..=last ahead of an edit confined to them
+    fn before_change(&self, first: usize, last: usize) -> Before {
+        Before {
+            row: first,
+            old: self.lines[first..=last].to_vec(),
+            line_count: self.lines.len(),
+            cursor: (self.buffer_row, self.buffer_col),
+        }
+    }
+
+    /// Record the edit begun with before_change(). The lines it produced are
+    /// the old range grown or shrunk by however many lines were added or
+    /// removed.
+    fn after_change(&mut self, before: Before, kind: ChangeKind) {
+        let new_len = (before.old.len() + self.lines.len()) - before.line_count;
+        let new = self.lines[before.row..before.row + new_len].to_vec();
+        gitdiff::splice(&mut self.git_marks, before.row, before.old.len(), new_len);
+        self.jumps.splice(before.row, before.old.len(), new_len);
+        self.history.record(Change {
+            row: before.row,
+            old: before.old,
+            new,
+            cursor_before: before.cursor,
+            cursor_after: (self.buffer_row, self.buffer_col),
+            kind,
+            group: 0,
+        });
+        self.mark_changed(before.row);
+    }
+
+    pub fn undo(&mut self, window_info: &WindowInfo) -> bool {
+        if self.read_only {
+            return false;
+        }
+
+        let changes = self.history.pop_undo();
+        for c in &changes {
+            self.lines.splice(c.row..c.row + c.new.len(), c.old.iter().cloned());
+            gitdiff::splice(&mut self.git_marks, c.row, c.new.len(), c.old.len());
+            self.jumps.splice(c.row, c.new.len(), c.old.len());
+            (self.buffer_row, self.buffer_col) = c.cursor_before;
+            self.mark_changed(c.row);
+        }
+
+        self.after_history(window_info);
+        !changes.is_empty()
+    }
+
+    pub fn redo(&mut self, window_info: &WindowInfo) -> bool {
+        if self.read_only {
+            return false;
+        }
+
+        let changes = self.history.pop_redo();
+        for c in &changes {
+            self.lines.splice(c.row..c.row + c.old.len(), c.new.iter().cloned());
+            gitdiff::splice(&mut self.git_marks, c.row, c.old.len(), c.new.len());
+            self.jumps.splice(c.row, c.old.len(), c.new.len());
+            (self.buffer_row, self.buffer_col) = c.cursor_after;
+            self.mark_changed(c.row);
+        }
+
+        self.after_history(window_info);
+        !changes.is_empty()
+    }
+
+    /// Start a run of edits that undo and redo as one step
+    pub fn begin_undo_group(&mut self) {
+        self.history.begin_group();
+    }
+
+    pub fn end_undo_group(&mut self) {
+        self.history.end_group();
+    }
+
+    fn after_history(&mut self, window_info: &WindowInfo) {
+        self.is_modified = !self.history.is_clean();
+        self.anchor = None;
+        self.clamp_cursor();
+        self.settle_cursor(window_info);
+    }
+
+    /// Replace lines start..end (at least one line) with `new` as one
+    /// undoable step, keeping the cursor on the same line number, or as near
+    /// as the new text allows.
+    pub fn replace_lines(&mut self, start: usize, end: usize, new: Vec<String>, window_info: &WindowInfo) {
+        if self.read_only || self.lines[start..end] == new[..] {
+            return;
+        }
+
+        self.anchor = None;
+        let before = self.before_change(start, end - 1);
+        self.lines.splice(start..end, new);
+        if self.lines.is_empty() {
+            self.lines.push(String::new());
+        }
+        self.clamp_cursor();
+        self.after_change(before, ChangeKind::Other);
+        self.ensure_cursor_visible(window_info, window_info.scroll_margin);
+    }
+
+    /// Delete the lines in start..end that `keep` doesn't, as one undoable
+    /// step. The cursor stays on its line or, if that went, moves to the
+    /// nearest one left after it. Returns how many lines went.
+    pub fn retain_rows(&mut self, start: usize, end: usize, keep: &[bool], window_info: &WindowInfo) -> usize {
+        let removed = keep.iter().filter(|k| !**k).count();
+        if removed == 0 || self.read_only {
+            return 0;
+        }
+
+        let (row, col) = (self.buffer_row, self.buffer_col);
+        let (new_row, new_col) = if row < start {
+            (row, col)
+        } else if row >= end {
+            (row - removed, col)
+        } else {
+            let kept_above = keep[..row - start].iter().filter(|k| **k).count();
+            (start + kept_above, if keep[row - start] { col } else { 0 })
+        };
+
+        let new = self.lines[start..end].iter().zip(keep)
+            .filter(|(_, k)| **k)
+            .map(|(line, _)| line.clone())
+            .collect();
+        self.replace_lines(start, end, new, window_info);
+        self.goto_position(new_row.min(self.lines.len() - 1), new_col, window_info);
+
+        removed
+    }
+
+    /// The text from `start` to `end` (both (row, col), start first), with
+    /// line breaks as \n
+    pub fn text_between(&self, start: (usize, usize), end: (usize, usize)) -> String {
+        let (start_row, start_col) = start;
+        let (end_row, end_col) = end;
+        if start_row == end_row {
+            return col_slice(&self.lines[start_row], start_col, end_col).to_string();
+        }
+
+        let mut text = col_slice(&self.lines[start_row], start_col, usize::MAX).to_string();
+        for line in &self.lines[start_row + 1..end_row] {
+            text.push('\n');
+            text.push_str(line);
+        }
+        text.push('\n');
+        text.push_str(col_slice(&self.lines[end_row], 0, end_col));
+
+        text
+    }
+
+    /// Replace the text from `start` to `end` with `text` as one undoable
+    /// step, leaving the new text selected.
+    pub fn replace_text(&mut self, start: (usize, usize), end: (usize, usize), text: &str, window_info: &WindowInfo) {
+        let (start_row, start_col) = start;
+        let (end_row, end_col) = end;
+        let end_row = end_row.min(self.lines.len() - 1);
+
+        let head = col_slice(&self.lines[start_row], 0, start_col);
+        let tail = col_slice(&self.lines[end_row], end_col, usize::MAX);
+        let joined = format!("{}{}{}", head, text, tail);
+        let new: Vec<String> = joined.split('\n').map(String::from).collect();
+
+        // Where the inserted text ends, for the cursor
+        let inserted: Vec<&str> = text.split('\n').collect();
+        let last = inserted.len() - 1;
+        let cursor_col = char_len(inserted[last]) + if last == 0 { start_col } else { 0 };
+
+        let generation = self.generation;
+        self.replace_lines(start_row, end_row + 1, new, window_info);
+        self.buffer_row = start_row + last;
+        self.buffer_col = cursor_col;
+        self.clamp_cursor();
+        if self.generation != generation {
+            self.history.set_cursor_after((self.buffer_row, self.buffer_col));
+        }
+        self.anchor = Some(start);
+        self.settle_cursor(window_info);
+    }
+
+    /// Insert text (which may span lines) at the cursor, replacing any
+    /// selection, as one undoable step. Returns where the text starts; the
+    /// cursor ends up just after it.
+    pub fn insert_text(&mut self, text: &str, window_info: &WindowInfo) -> (usize, usize) {
+        self.clamp_cursor();
+        let (start, end) = self.selection()
+            .unwrap_or(((self.buffer_row, self.buffer_col), (self.buffer_row, self.buffer_col)));
+        if self.read_only {
+            return start;
+        }
+
+        self.replace_text(start, end, text, window_info);
+        self.anchor = None;
+
+        start
+    }
+
+    /// Put another file's lines in at the cursor as one undoable step. The
+    /// current line is split around them, and the cursor is left at the end
+    /// of the last one. Returns how many lines went in.
+    pub fn insert_file(&mut self, filename: &str, window_info: &WindowInfo) -> Result<usize, String> {
+        let bytes = fs::read(filename).map_err(|e| e.to_string())?;
+        if bytes.contains(&0) {
+            return Err(String::from("looks like a binary file"));
+        }
+        let lines = decode(bytes, Encoding::Utf8)?.lines;
+        if lines.len() == 1 && lines[0].is_empty() {
+            return Ok(0);
+        }
+
+        self.anchor = None;
+        self.clamp_cursor();
+        let split_before = self.buffer_col > 0;
+        let split_after = self.buffer_col < char_len(&self.lines[self.buffer_row]);
+        let mut text = lines.join("\n");
+        if split_before {
+            text.insert(0, '\n');
+        }
+        if split_after {
+            text.push('\n');
+        }
+        self.insert_text(&text, window_info);
+
+        if split_after {
+            let row = self.buffer_row - 1;
+            self.goto_position(row, char_len(&self.lines[row]), window_info);
+        }
+
+        Ok(lines.len())
+    }
+
+    /// The rows start..end a line-wise command works on: every line the
+    /// selection touches, or the whole buffer if nothing is selected. A
+    /// selection ending at the very start of a line doesn't include it.
+    pub fn selected_rows(&self) -> (usize, usize) {
+        if let Some(rows) = self.line_selection() {
+            return rows;
+        }
+
+        match self.selection() {
+            Some(((start_row, _), (end_row, 0))) if end_row > start_row => (start_row, end_row),
+            Some(((start_row, _), (end_row, _))) => (start_row, end_row + 1),
+            None => (0, self.lines.len()),
+        }
+    }
+
+    /// Select rows start..end in full
+    pub fn select_rows(&mut self, start: usize, end: usize, window_info: &WindowInfo) {
+        let last = end.max(start + 1).min(self.lines.len()) - 1;
+        self.anchor = Some((start, 0));
+        self.buffer_row = last;
+        self.buffer_col = char_len(&self.lines[last]);
+        self.settle_cursor(window_info);
+    }
+
+    /// Set the name the buffer is saved under, which also decides how it's
+    /// highlighted.
+    pub fn set_filename(&mut self, filename: &str) {
+        self.filename = filename.to_string();
+        self.language = highlight::language_for(filename);
+        self.hl_valid = 0;
+        self.outline_valid = 0;
+        if let Some(lang) = self.language {
+            log_debug!("highlighting {} as {}", filename, lang.name);
+        }
+    }
+
+    /// Bring the cached highlighter states up to date for lines before `end`
+    pub fn update_highlight(&mut self, end: usize) {
+        let Some(lang) = self.language else {
+            return;
+        };
+
+        let end = end.min(self.lines.len());
+        self.hl_states.resize(self.lines.len(), State::Normal);
+        if self.hl_valid == 0 {
+            self.hl_states[0] = State::Normal;
+            self.hl_valid = 1;
+        }
+
+        while self.hl_valid < end {
+            let row = self.hl_valid - 1;
+            let (_, next) = highlight::highlight_line(&self.lines[row], lang, self.hl_states[row]);
+            self.hl_states[row + 1] = next;
+            self.hl_valid += 1;
+        }
+    }
+
+    /// Highlighter state at the start of a line. Only meaningful for lines
+    /// update_highlight() has been asked to cover.
+    pub fn hl_state(&self, row: usize) -> State {
+        if row < self.hl_valid {
+            self.hl_states[row]
+        } else {
+            State::Normal
+        }
+    }
+
+    /// Whether the buffer has headings to make an outline of
+    pub fn has_outline(&self) -> bool {
+        self.language.is_some_and(|lang| lang.markdown)
+    }
+
+    /// Bring the cached Markdown headings up to date, rescanning from the
+    /// first changed line down
+    pub fn update_outline(&mut self) {
+        if !self.has_outline() {
+            return;
+        }
+
+        let valid = self.outline_valid;
+        self.outline.truncate(self.outline.partition_point(|h| h.row < valid));
+        self.fences.truncate(self.fences.partition_point(|&row| row < valid));
+        if valid < self.lines.len() {
+            outline::scan(&self.lines, valid, &mut self.outline, &mut self.fences);
+            self.outline_valid = self.lines.len();
+        }
+    }
+
+    /// The Markdown headings, as of the last update_outline()
+    pub fn outline(&self) -> &[Heading] {
+        &self.outline
+    }
+
+    /// How far (row, col) is from the start of the buffer, as (chars, bytes).
+    /// A line break counts as one char but as however many bytes the line
+    /// ending takes, and a BOM is counted, so the byte offset is where the
+    /// position falls in the saved file.
+    pub fn offset_of(&mut self, row: usize, col: usize) -> (usize, usize) {
+        self.update_line_starts(row + 1);
+        let (chars, bytes) = self.line_starts[row];
+        let prefix = col_slice(&self.lines[row], 0, col);
+        let ending = self.line_ending.as_str().len();
+        let bom = if self.bom { UTF8_BOM.len() } else { 0 };
+
+        (chars + row + char_len(prefix), bom + bytes + row * ending + prefix.len())
+    }
+
+    /// Bring the cached line start offsets up to date for lines before `end`
+    fn update_line_starts(&mut self, end: usize) {
+        let end = end.min(self.lines.len());
+        self.line_starts.resize(self.lines.len(), (0, 0));
+        self.starts_valid = self.starts_valid.clamp(1, self.lines.len());
+        self.line_starts[0] = (0, 0);
+
+        while self.starts_valid < end {
+            let row = self.starts_valid - 1;
+            let (chars, bytes) = self.line_starts[row];
+            let line = &self.lines[row];
+            self.line_starts[row + 1] = (chars + char_len(line), bytes + line.len());
+            self.starts_valid += 1;
+        }
+    }
+
+    /// Pull the cursor back inside the buffer: onto an existing line and no
+    /// further right than the end of that line.
+    fn clamp_cursor(&mut self) {
+        self.buffer_row = self.buffer_row.min(self.lines.len() - 1);
+        self.buffer_col = self.buffer_col.min(char_len(&self.lines[self.buffer_row]));
+    }
+
+    /// Adjust the viewport so the cursor is visible, keeping `margin` lines
+    /// of context above and below it when there are lines to show. A margin
+    /// too big for the window just keeps the cursor centered.
+    pub fn ensure_cursor_visible(&mut self, window_info: &WindowInfo, margin: usize) {
+        let rows = (window_info.rows as usize).max(1);
+        let cols = (window_info.cols as usize).max(1);
+        let margin = margin.min((rows - 1) / 2);
+
+        let above = margin.min(self.buffer_row);
+        let below = margin.min(self.lines.len() - 1 - self.buffer_row);
+        if self.buffer_row < self.top_line + above {
+            self.top_line = self.buffer_row - above;
+        } else if self.buffer_row + below >= self.top_line + rows {
+            self.top_line = self.buffer_row + below + 1 - rows;
+        }
+
+        // Never scroll further than needed to put the last line at the
+        // bottom, so a file shorter than the window is shown from the top.
+        self.top_line = self.top_line.min(self.lines.len().saturating_sub(rows));
+
+        // Horizontal scrolling counts screen columns, since a tab takes up
+        // more than one.
+        if self.buffer_col < self.buffer_col_offset {
+            self.buffer_col_offset = self.buffer_col;
+        } else {
+            let line = &self.lines[self.buffer_row.min(self.lines.len() - 1)];
+            let cursor_x = display_col(line, self.buffer_col, self.tab_width);
+            while cursor_x - display_col(line, self.buffer_col_offset, self.tab_width) >= cols {
+                self.buffer_col_offset += 1;
+            }
+        }
+    }
+
+    /// For anything other than vertical movement: the column the cursor
+    /// ended up in becomes the one later Up/Down moves aim for.
+    fn settle_cursor(&mut self, window_info: &WindowInfo) {
+        self.desired_col = self.buffer_col;
+        self.ensure_cursor_visible(window_info, window_info.scroll_margin);
+    }
+
+    pub fn insert_char(&mut self, c: char, window_info: &WindowInfo) {
+        if self.read_only {
+            return;
+        }
+
+        // Typing over a selection is undone in one go
+        let replacing = self.selection().is_some();
+        if replacing {
+            self.history.begin_group();
+            self.delete_selection(window_info);
+        }
+
+        self.clamp_cursor();
+        let before = self.before_change(self.buffer_row, self.buffer_row);
+        let line = &mut self.lines[self.buffer_row];
+        line.insert(byte_idx(line, self.buffer_col), c);
+        self.buffer_col += 1;
+        self.after_change(before, ChangeKind::Typing);
+        if self.auto_wrap {
+            self.wrap_at_fill_column();
+        }
+
+        if replacing {
+            self.history.end_group();
+        }
+
+        self.settle_cursor(window_info);
+    }
+
+    /// Once the cursor's line runs past the fill column, break it at a space
+    /// and carry its prefix, like the "// " of a comment, onto the new line.
+    /// The cursor goes with the word it's in.
+    fn wrap_at_fill_column(&mut self) {
+        let row = self.buffer_row;
+        let Some(at) = reflow::wrap_point(&self.lines[row], self.fill_column) else {
+            return;
+        };
+
+        let before = self.before_change(row, row);
+        let line = &mut self.lines[row];
+        let prefix = reflow::line_prefix(line).to_string();
+        let rest = line.split_off(byte_idx(line, at));
+        line.truncate(line.trim_end().len());
+        let moved = rest.trim_start();
+        if self.buffer_col >= at {
+            let skipped = char_len(&rest) - char_len(moved);
+            self.buffer_col = char_len(&prefix) + (self.buffer_col - at).saturating_sub(skipped);
+            self.buffer_row += 1;
+        }
+        self.lines.insert(row + 1, prefix + moved);
+        self.after_change(before, ChangeKind::Other);
+    }
+
+    pub fn backspace(&mut self, window_info: &WindowInfo) {
+        if self.read_only || self.delete_selection(window_info) {
+            return;
+        }
+
+        self.clamp_cursor();
+        if self.buffer_col > 0 {
+            let before = self.before_change(self.buffer_row, self.buffer_row);
+            let line = &mut self.lines[self.buffer_row];
+            line.remove(byte_idx(line, self.buffer_col - 1));
+            self.buffer_col -= 1;
+            self.after_change(before, ChangeKind::Other);
+        } else if self.buffer_row > 0 {
+            let before = self.before_change(self.buffer_row - 1, self.buffer_row);
+            let current_line = self.lines.remove(self.buffer_row);
+            self.buffer_row -= 1;
+            self.buffer_col = char_len(&self.lines[self.buffer_row]);
+            self.lines[self.buffer_row].push_str(&current_line);
+            self.after_change(before, ChangeKind::Other);
+        }
+
+        self.settle_cursor(window_info);
+    }
+
+    /// Delete the character under the cursor, or at the end of a line pull
+    /// the next line up onto this one.
+    pub fn delete_forward(&mut self, window_info: &WindowInfo) {
+        if self.read_only || self.delete_selection(window_info) {
+            return;
+        }
+
+        self.clamp_cursor();
+        if self.buffer_col < char_len(&self.lines[self.buffer_row]) {
+            let before = self.before_change(self.buffer_row, self.buffer_row);
+            let line = &mut self.lines[self.buffer_row];
+            line.remove(byte_idx(line, self.buffer_col));
+            self.after_change(before, ChangeKind::Other);
+        } else if self.buffer_row < self.lines.len() - 1 {
+            let before = self.before_change(self.buffer_row, self.buffer_row + 1);
+            let next_line = self.lines.remove(self.buffer_row + 1);
+            self.lines[self.buffer_row].push_str(&next_line);
+            self.after_change(before, ChangeKind::Other);
+            self.settle_cursor(window_info);
+        }
+    }
+
+    pub fn insert_newline(&mut self, window_info: &WindowInfo) {
+        if self.read_only {
+            return;
+        }
+
+        self.history.begin_group();
+        self.delete_selection(window_info);
+        self.clamp_cursor();
+
+        // Split line at cursor
+        let before = self.before_change(self.buffer_row, self.buffer_row);
+        let new_indent = self.indent_for_new_line(self.buffer_col);
+        let line = &mut self.lines[self.buffer_row];
+        let rest_of_line = line.split_off(byte_idx(line, self.buffer_col));
+
+        // The new line's indent takes the place of any the rest of the line
+        // had, but without auto-indent its spaces are the user's own
+        let rest = if self.auto_indent { rest_of_line.trim_start_matches([' ', '\t']) } else { &rest_of_line };
+        self.buffer_col = char_len(&new_indent);
+        self.lines.insert(self.buffer_row + 1, new_indent + rest);
+        self.buffer_row += 1;
+        self.after_change(before, ChangeKind::Other);
+        self.history.end_group();
+
+        self.settle_cursor(window_info);
+    }
+
+    /// Swap the two chars around the cursor, or with `words` the word it's
+    /// in and the next one, as one undoable step. Only within the line.
+    pub fn transpose(&mut self, words: bool, window_info: &WindowInfo) -> Result<(), String> {
+        if self.read_only {
+            return Err(format!("{} is read-only", self.filename));
+        }
+
+        self.clamp_cursor();
+        let row = self.buffer_row;
+        let line = &self.lines[row];
+        let (new, col) = if words {
+            text::transpose_words(line, self.buffer_col)?
+        } else {
+            text::transpose_chars(line, self.buffer_col)?
+        };
+
+        self.replace_lines(row, row + 1, vec![new], window_info);
+        self.goto_position(row, col, window_info);
+        Ok(())
+    }
+
+    /// Start a new, empty line below the cursor's line (or above it) and
+    /// put the cursor on it, without splitting the line the cursor was on.
+    /// It's indented as if Enter had been pressed at the end of the line
+    /// (or to match the line, above it).
+    pub fn open_line(&mut self, above: bool, window_info: &WindowInfo) {
+        if self.read_only {
+            return;
+        }
+
+        self.anchor = None;
+        self.clamp_cursor();
+        let row = self.buffer_row;
+        let before = self.before_change(row, row);
+        let new_indent = if above {
+            if self.auto_indent { indent::leading(&self.lines[row]).to_string() } else { String::new() }
+        } else {
+            self.indent_for_new_line(char_len(&self.lines[row]))
+        };
+
+        self.buffer_row = if above { row } else { row + 1 };
+        self.buffer_col = char_len(&new_indent);
+        self.lines.insert(self.buffer_row, new_indent);
+        self.after_change(before, ChangeKind::Other);
+
+        self.settle_cursor(window_info);
+    }
+
+    /// With auto-indent on, a new line starts with the same whitespace as
+    /// the one it was split from at `col`, plus a level if that ended by
+    /// opening a bracket.
+    fn indent_for_new_line(&self, col: usize) -> String {
+        if !self.auto_indent {
+            return String::new();
+        }
+
+        let line = &self.lines[self.buffer_row];
+        let before_cursor = col_slice(line, 0, col);
+        let mut lead = indent::leading(before_cursor).to_string();
+        if before_cursor.trim_end().ends_with(['{', '(', '[']) {
+            lead.push_str(&self.indent.unit());
+        }
+
+        lead
+    }
+
+    /// Tab: with several lines selected, indent them all; otherwise insert
+    /// one level of indentation at the cursor.
+    pub fn insert_tab(&mut self, window_info: &WindowInfo) {
+        match self.selection() {
+            Some(((start_row, _), (end_row, _))) if start_row != end_row => self.indent_block(window_info),
+            _ => {
+                self.clamp_cursor();
+                let x = display_col(&self.lines[self.buffer_row], self.buffer_col, self.tab_width);
+                let fill = self.indent.fill_from(x);
+                self.insert_text(&fill, window_info);
+            }
+        }
+    }
+
+    /// Rows a block indent/dedent works on: the selected lines, or just the
+    /// cursor's line
+    fn block_rows(&self) -> (usize, usize) {
+        match self.selection() {
+            Some(_) => self.selected_rows(),
+            None => (self.buffer_row, self.buffer_row + 1),
+        }
+    }
+
+    fn reindent_block<F>(&mut self, f: F, window_info: &WindowInfo)
+    where
+        F: Fn(&str) -> String,
+    {
+        if self.read_only {
+            return;
+        }
+
+        self.clamp_cursor();
+        let (start, end) = self.block_rows();
+        let had_selection = self.anchor.is_some();
+        let old_len = char_len(&self.lines[self.buffer_row]);
+        let new: Vec<String> = self.lines[start..end].iter().map(|l| f(l)).collect();
+        let (row, col) = (self.buffer_row, self.buffer_col);
+
+        self.replace_lines(start, end, new, window_info);
+        if had_selection {
+            self.select_rows(start, end, window_info);
+        } else {
+            // Keep the cursor on the same character
+            let new_len = char_len(&self.lines[row]);
+            let col = (col + new_len).saturating_sub(old_len);
+            self.goto_position(row, col, window_info);
+        }
+    }
+
+    /// Add a level of indentation to each non-blank line in the block
+    pub fn indent_block(&mut self, window_info: &WindowInfo) {
+        let unit = self.indent.unit();
+        self.reindent_block(|line| {
+            if line.trim().is_empty() { line.to_string() } else { format!("{}{}", unit, line) }
+        }, window_info);
+    }
+
+    /// Take a level of indentation (a tab, or up to a unit's worth of
+    /// spaces) off each line in the block
+    pub fn dedent_block(&mut self, window_info: &WindowInfo) {
+        let width = match self.indent {
+            Indent::Tabs => self.tab_width,
+            Indent::Spaces(n) => n,
+        };
+        self.reindent_block(|line| {
+            if let Some(rest) = line.strip_prefix('\t') {
+                return rest.to_string();
+            }
+            let spaces = line.len() - line.trim_start_matches(' ').len();
+            line[spaces.min(width)..].to_string()
+        }, window_info);
+    }
+
+    pub fn move_cursor_left(&mut self, window_info: &WindowInfo) {
+        self.clamp_cursor();
+        if self.buffer_col > 0 {
+            self.buffer_col -= 1;
+        } else if self.buffer_row > 0 {
+            self.buffer_row -= 1;
+            self.buffer_col = char_len(&self.lines[self.buffer_row]);
+        }
+
+        self.settle_cursor(window_info);
+    }
+
+    pub fn move_cursor_right(&mut self, window_info: &WindowInfo) {
+        self.clamp_cursor();
+        if self.buffer_col < char_len(&self.lines[self.buffer_row]) {
+            self.buffer_col += 1;
+        } else if self.buffer_row < self.lines.len() - 1 {
+            self.buffer_row += 1;
+            self.buffer_col = 0;
+        }
+
+        self.settle_cursor(window_info);
+    }
+
+    pub fn move_cursor_up(&mut self, window_info: &WindowInfo) {
+        if self.buffer_row > 0 {
+            self.buffer_row -= 1;
+        }
+
+        self.buffer_col = self.desired_col;
+        self.clamp_cursor();
+        self.ensure_cursor_visible(window_info, window_info.scroll_margin);
+    }
+
+    pub fn move_cursor_down(&mut self, window_info: &WindowInfo) {
+        if self.buffer_row < self.lines.len() - 1 {
+            self.buffer_row += 1;
+        }
+
+        self.buffer_col = self.desired_col;
+        self.clamp_cursor();
+        self.ensure_cursor_visible(window_info, window_info.scroll_margin);
+    }
+
+    /// Move a screenful up, scrolling the view by the same amount so the
+    /// cursor stays where it was on screen
+    pub fn page_up(&mut self, window_info: &WindowInfo) {
+        let rows = (window_info.rows as usize).saturating_sub(1).max(1);
+        self.buffer_row = self.buffer_row.saturating_sub(rows);
+        self.top_line = self.top_line.saturating_sub(rows);
+
+        self.buffer_col = self.desired_col;
+        self.clamp_cursor();
+        self.ensure_cursor_visible(window_info, window_info.scroll_margin);
+    }
+
+    pub fn page_down(&mut self, window_info: &WindowInfo) {
+        let rows = (window_info.rows as usize).saturating_sub(1).max(1);
+        self.buffer_row = (self.buffer_row + rows).min(self.lines.len() - 1);
+        self.top_line += rows;
+
+        self.buffer_col = self.desired_col;
+        self.clamp_cursor();
+        self.ensure_cursor_visible(window_info, window_info.scroll_margin);
+    }
+
+    /// Move to the blank line past the end of this paragraph (or the one
+    /// before its start), or the first or last line if there isn't one
+    pub fn move_paragraph(&mut self, forward: bool, window_info: &WindowInfo) {
+        let row = paragraph_boundary(&self.lines, self.buffer_row, forward);
+        self.goto_position(row, 0, window_info);
+        self.desired_col = 0;
+    }
+
+    /// Move a word left or right: past any spaces, then past a run of word
+    /// chars or of punctuation. At the end of a line, to the next one.
+    pub fn move_word(&mut self, forward: bool, window_info: &WindowInfo) {
+        self.clamp_cursor();
+        let (row, col) = (self.buffer_row, self.buffer_col);
+        let line = &self.lines[row];
+        let (row, col) = match forward {
+            true if col == char_len(line) && row + 1 < self.lines.len() => (row + 1, 0),
+            true => (row, text::next_word_end(line, col, &self.word_chars)),
+            false if col == 0 && row > 0 => (row - 1, char_len(&self.lines[row - 1])),
+            false => (row, text::prev_word_start(line, col, &self.word_chars)),
+        };
+        self.goto_position(row, col, window_info);
+    }
+
+    /// Delete the cursor's line, line break and all, as one undoable step.
+    /// The cursor keeps its column on the line that moves up into its place,
+    /// or the line above if it was the last. The only line is just emptied.
+    pub fn delete_line(&mut self, window_info: &WindowInfo) {
+        if self.read_only || self.lines == [""] {
+            return;
+        }
+
+        self.clamp_cursor();
+        let (row, col) = (self.buffer_row, self.buffer_col);
+        self.replace_lines(row, row + 1, Vec::new(), window_info);
+        self.goto_position(row.min(self.lines.len() - 1), col, window_info);
+    }
+
+    pub fn move_to_line_start(&mut self, window_info: &WindowInfo) {
+        self.buffer_col = 0;
+        self.settle_cursor(window_info);
+    }
+
+    pub fn move_to_line_end(&mut self, window_info: &WindowInfo) {
+        self.clamp_cursor();
+        self.buffer_col = char_len(&self.lines[self.buffer_row]);
+        self.settle_cursor(window_info);
+    }
+
+    /// Put the cursor on the given (0-based) line and column, clamped to the
+    /// buffer, with the line centered vertically where possible.
+    pub fn goto_line(&mut self, line: usize, col: usize, window_info: &WindowInfo) {
+        self.buffer_row = line;
+        self.buffer_col = col;
+        self.clamp_cursor();
+
+        self.top_line = self.buffer_row.saturating_sub(window_info.rows as usize / 2);
+        self.settle_cursor(window_info);
+    }
+
+    /// Scroll so `top` is the first line shown (or as near as the end of
+    /// the buffer allows), taking the cursor along if it would be left off
+    /// screen
+    pub fn scroll_to(&mut self, top: usize, window_info: &WindowInfo) {
+        let rows = (window_info.rows as usize).max(1);
+        self.top_line = top.min(self.lines.len().saturating_sub(rows));
+
+        let last = (self.top_line + rows).min(self.lines.len()) - 1;
+        if self.buffer_row < self.top_line || self.buffer_row > last {
+            self.buffer_row = self.buffer_row.clamp(self.top_line, last);
+            self.buffer_col = self.desired_col;
+            self.clamp_cursor();
+        }
+    }
+
+    /// Remember where the cursor is before it jumps somewhere far off
+    pub fn mark_jump(&mut self) {
+        self.jumps.push(self.here());
+    }
+
+    pub fn jump_back(&mut self, window_info: &WindowInfo) -> bool {
+        let here = self.here();
+        self.jumps.back(here).map(|jump| self.restore_jump(jump, window_info)).is_some()
+    }
+
+    pub fn jump_forward(&mut self, window_info: &WindowInfo) -> bool {
+        self.jumps.forward().map(|jump| self.restore_jump(jump, window_info)).is_some()
+    }
+
+    fn here(&self) -> Jump {
+        Jump { row: self.buffer_row, col: self.buffer_col, top_line: self.top_line }
+    }
+
+    /// Put the cursor and the view back as they were
+    fn restore_jump(&mut self, jump: Jump, window_info: &WindowInfo) {
+        self.anchor = None;
+        self.top_line = jump.top_line.min(self.lines.len() - 1);
+        self.goto_position(jump.row.min(self.lines.len() - 1), jump.col, window_info);
+    }
+
+    /// Move the cursor somewhere nearby, scrolling only as far as needed
+    pub fn goto_position(&mut self, row: usize, col: usize, window_info: &WindowInfo) {
+        self.buffer_row = row;
+        self.buffer_col = col;
+        self.clamp_cursor();
+        self.settle_cursor(window_info);
+    }
+
+    pub fn save(&mut self) -> Result<(), String> {
+        if self.read_only {
+            return Err(format!("{} is read-only", self.filename));
+        }
+
+        if self.trim_trailing_whitespace {
+            self.trim_trailing();
+        }
+
+        let mut text = String::new();
+        if self.bom {
+            text.push('\u{FEFF}');
+        }
+        // An empty buffer is an empty file, not a lone line break
+        let ending = self.line_ending.as_str();
+        let empty = self.lines.len() == 1 && self.lines[0].is_empty();
+        for (i, line) in self.lines.iter().enumerate() {
+            text.push_str(line);
+            if i + 1 < self.lines.len() || (self.final_newline && !empty) {
+                text.push_str(ending);
+            }
+        }
+
+        // Found out before the file is touched
+        let bytes = self.encoding.encode(&text).map_err(|c| {
+            let row = self.lines.iter().position(|l| l.contains(c)).unwrap_or(0);
+            format!("'{}' on line {} can't be written as {}", c, row + 1, self.encoding.name())
+        })?;
+        let mut writer = BufWriter::new(File::create(&self.filename).map_err(|e| e.to_string())?);
+        writer.write_all(&bytes).map_err(|e| e.to_string())?;
+        writer.flush().map_err(|e| e.to_string())?;
+
+        self.is_modified = false;
+        self.history.mark_clean();
+        self.disk_mtime = file_mtime(&self.filename);
+        self.disk_len = fs::metadata(&self.filename).map_or(0, |m| m.len());
+        log_info!("saved {} ({} lines)", self.filename, self.lines.len());
+        self.save_history();
+
+        Ok(())
+    }
+
+    /// Whether the file has been written to by something else since it was
+    /// loaded or saved
+    pub fn changed_on_disk(&self) -> bool {
+        let mtime = file_mtime(&self.filename);
+        mtime.is_some() && mtime != self.disk_mtime
+    }
+
+    /// Load the file again, keeping the cursor where it was. A cursor on the
+    /// last line stays on the last line, however long the file has grown,
+    /// so a log being written to can be followed like tail -f.
+    pub fn reload(&mut self, window_info: &WindowInfo) -> Result<(), String> {
+        let at_end = self.buffer_row + 1 == self.lines.len();
+        let (row, col, top_line) = (self.buffer_row, self.buffer_col, self.top_line);
+
+        let filename = self.filename.clone();
+        self.load(&filename)?;
+        if at_end {
+            self.goto_position(self.lines.len() - 1, 0, window_info);
+        } else {
+            self.top_line = top_line.min(self.lines.len() - 1);
+            self.goto_position(row, col, window_info);
+        }
+
+        Ok(())
+    }
+
+    /// Whether a tailed buffer is keeping up with the end of the file. Moving
+    /// off the last line pauses that until the cursor goes back.
+    pub fn following(&self) -> bool {
+        self.tail && self.buffer_row + 1 == self.lines.len()
+    }
+
+    /// Read just what's been added to the end of the file since it was last
+    /// read, rather than all of it again. A file that's shrunk has been
+    /// truncated or rotated, so that's loaded from scratch.
+    pub fn read_appended(&mut self, window_info: &WindowInfo) -> Result<(), String> {
+        let len = fs::metadata(&self.filename).map_err(|e| e.to_string())?.len();
+        if len < self.disk_len || self.encoding != Encoding::Utf8 {
+            log_info!("{} shrank, reloading it", self.filename);
+            return self.reload(window_info);
+        }
+
+        let mut file = File::open(&self.filename).map_err(|e| e.to_string())?;
+        file.seek(SeekFrom::Start(self.disk_len)).map_err(|e| e.to_string())?;
+        let mut bytes = Vec::new();
+        file.take(len - self.disk_len).read_to_end(&mut bytes).map_err(|e| e.to_string())?;
+
+        // A write can stop partway through a character; the rest of it is
+        // read next time
+        let valid = match std::str::from_utf8(&bytes) {
+            Ok(_) => bytes.len(),
+            Err(e) if e.error_len().is_none() => e.valid_up_to(),
+            Err(e) => return Err(format!("not valid UTF-8 (at byte {})", self.disk_len as usize + e.valid_up_to())),
+        };
+        let text = std::str::from_utf8(&bytes[..valid]).map_err(|e| e.to_string())?;
+        let at_start = self.disk_len <= if self.bom { UTF8_BOM.len() as u64 } else { 0 };
+        self.disk_len += valid as u64;
+        self.disk_mtime = file_mtime(&self.filename);
+        if text.is_empty() {
+            return Ok(());
+        }
+
+        // The new text carries on the last line unless that was finished
+        let following = self.following();
+        let last = self.lines.len() - 1;
+        let separator = if self.line_ending == LineEnding::Cr { '\r' } else { '\n' };
+        let body = text.strip_suffix(separator).unwrap_or(text);
+        let mut parts = body.split(separator).map(|l| l.strip_suffix('\r').unwrap_or(l).to_string());
+        if (at_start || !self.final_newline)
+            && let Some(first) = parts.next()
+        {
+            self.lines[last].push_str(&first);
+        }
+        self.lines.extend(parts);
+        self.final_newline = text.ends_with(separator);
+
+        self.generation += 1;
+        self.hl_valid = self.hl_valid.min(last + 1);
+        self.starts_valid = self.starts_valid.min(last + 1);
+        self.outline_valid = self.outline_valid.min(last);
+        if following {
+            self.goto_position(self.lines.len() - 1, 0, window_info);
+        }
+        log_debug!("read {} more bytes of {}", valid, self.filename);
+
+        Ok(())
+    }
+
+    /// Save with `ending` from now on. Stray line breaks of other kinds left
+    /// in the middle of lines at load are split at, as one undoable step.
+    /// Returns how many of the file's line breaks didn't match its ending.
+    pub fn convert_line_endings(&mut self, ending: LineEnding, window_info: &WindowInfo) -> usize {
+        if self.lines.iter().any(|l| l.contains(['\r', '\n'])) {
+            let split: Vec<String> = self.lines.iter()
+                .flat_map(|l| l.replace("\r\n", "\n").split(['\r', '\n']).map(String::from).collect::<Vec<_>>())
+                .collect();
+            let count = self.lines.len();
+            self.replace_lines(0, count, split, window_info);
+        }
+
+        let mixed = std::mem::take(&mut self.mixed_endings);
+        if ending != self.line_ending || mixed > 0 {
+            self.line_ending = ending;
+            self.is_modified = true;
+        }
+
+        mixed
+    }
+
+    /// Load the file again, reading it as `encoding` this time. It'll be
+    /// saved that way too. If it can't be read like that, nothing changes.
+    pub fn reopen_as(&mut self, encoding: Encoding, window_info: &WindowInfo) -> Result<(), String> {
+        let old = std::mem::replace(&mut self.encoding, encoding);
+        let reopened = self.reload(window_info);
+        if reopened.is_err() {
+            self.encoding = old;
+        }
+
+        reopened
+    }
+
+    /// Keep the undo history for the next time this file is opened. Only
+    /// worth doing while the text matches the file.
+    pub fn save_history(&self) {
+        let empty = self.history.undo.is_empty() && self.history.redo.is_empty();
+        if self.persistent_undo && !self.is_modified && !empty && undofile::wanted(&self.filename) {
+            undofile::save(&self.filename, &self.lines, &self.history);
+        }
+    }
+
+    /// Strip trailing whitespace from every line, as one undoable step
+    fn trim_trailing(&mut self) {
+        if !self.lines.iter().any(|l| l.ends_with([' ', '\t'])) {
+            return;
+        }
+
+        let last = self.lines.len() - 1;
+        let before = self.before_change(0, last);
+        for line in &mut self.lines {
+            let len = line.trim_end_matches([' ', '\t']).len();
+            line.truncate(len);
+        }
+        self.clamp_cursor();
+        self.after_change(before, ChangeKind::Other);
+    }
+
+    /// The lines of the file as it is on disk, read as this buffer's
+    /// encoding, or None if there's no such file
+    pub fn disk_lines(&self) -> Result<Option<Vec<String>>, String> {
+        match fs::read(&self.filename) {
+            Ok(bytes) => Ok(Some(decode(bytes, self.encoding)?.lines)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    pub fn load(&mut self, filename: &str) -> Result<(), String> {
+        let bytes = fs::read(filename).map_err(|e| e.to_string())?;
+        self.disk_len = bytes.len() as u64;
+
+        let decoded = decode(bytes, self.encoding)?;
+        self.lines = decoded.lines;
+        self.line_ending = decoded.line_ending;
+        self.mixed_endings = decoded.mixed_endings;
+        self.final_newline = decoded.final_newline;
+        self.bom = decoded.bom;
+
+        self.set_filename(filename);
+        self.disk_mtime = file_mtime(filename);
+        if !self.indent_locked
+            && let Some(detected) = indent::detect(&self.lines)
+        {
+            log_debug!("{} looks indented with {}", filename, detected.describe());
+            self.indent = detected;
+            self.indent_from = "detected";
+        }
+        self.apply_editorconfig();
+
+        self.anchor = None;
+        self.buffer_col = 0;
+        self.buffer_row = 0;
+        self.desired_col = 0;
+        self.top_line = 0;
+        self.buffer_col_offset = 0;
+        self.is_modified = false;
+        self.history = UndoStack::new();
+        if self.persistent_undo
+            && let Some(history) = undofile::load(filename, &self.lines)
+        {
+            log_info!("restored {} undo steps for {}", history.undo.len(), filename);
+            self.history = history;
+        }
+        self.git_marks.clear();
+        self.jumps.clear();
+        self.starts_valid = 0;
+        self.outline_valid = 0;
+        self.generation += 1;
+
+        log_info!("loaded {} ({} lines, {})", filename, self.lines.len(), self.line_ending.name());
+
+        Ok(())
+    }
+
+    /// Use whatever .editorconfig files say about this buffer's file. Their
+    /// indentation beats what was detected, but not one set by hand.
+    pub fn apply_editorconfig(&mut self) {
+        let props = editorconfig::properties_for(Path::new(&self.filename));
+        if props == editorconfig::Properties::default() {
+            return;
+        }
+        log_debug!("editorconfig for {}: {:?}", self.filename, props);
+
+        if let Some(width) = props.tab_width {
+            self.tab_width = width;
+        }
+        if !self.indent_locked {
+            let size = props.indent_size.or(props.tab_width).unwrap_or(self.tab_width);
+            if props.indent_style.is_some() || props.indent_size.is_some() {
+                self.indent_from = "editorconfig";
+            }
+            match props.indent_style {
+                Some(IndentStyle::Tab) => self.indent = Indent::Tabs,
+                Some(IndentStyle::Space) => self.indent = Indent::Spaces(size),
+                None if props.indent_size.is_some() => {
+                    if let Indent::Spaces(_) = self.indent {
+                        self.indent = Indent::Spaces(size);
+                    }
+                }
+                None => {}
+            }
+        }
+
+        if let Some(eol) = props.end_of_line {
+            self.line_ending = match eol {
+                EndOfLine::Lf => LineEnding::Lf,
+                EndOfLine::Crlf => LineEnding::Crlf,
+                EndOfLine::Cr => LineEnding::Cr,
+            };
+        }
+        if let Some(trim) = props.trim_trailing_whitespace {
+            self.trim_trailing_whitespace = trim;
+        }
+        if let Some(final_newline) = props.insert_final_newline {
+            self.final_newline = final_newline;
+        }
+        match props.charset.as_deref() {
+            Some("utf-8") => self.bom = false,
+            Some("utf-8-bom") => self.bom = true,
+            Some(other) => log_debug!("ignoring editorconfig charset {}", other),
+            None => {}
+        }
+    }
+}
+
+/// The row a paragraph move from `row` lands on: the blank line past the
+/// end of the paragraph it's in, or if it's on a blank line already, past
+/// the next paragraph, as vi's { and } do. Lines with only whitespace count
+/// as blank. With no blank line to find, the first or last line.
+pub fn paragraph_boundary(lines: &[String], row: usize, forward: bool) -> usize {
+    let blank = |r: usize| lines[r].trim().is_empty();
+    let last = lines.len() - 1;
+    let mut r = row.min(last);
+    if forward {
+        while r < last && blank(r) {
+            r += 1;
+        }
+        while r < last && !blank(r) {
+            r += 1;
+        }
+    } else {
+        while r > 0 && blank(r) {
+            r -= 1;
+        }
+        while r > 0 && !blank(r) {
+            r -= 1;
+        }
+    }
+
+    r
+}
+
+/// What a file's bytes come to as lines of text
+struct Decoded {
+    lines: Vec<String>,
+    line_ending: LineEnding,
+    mixed_endings: usize,
+    final_newline: bool,
+    bom: bool,
+}
+
+fn decode(bytes: Vec<u8>, encoding: Encoding) -> Result<Decoded, String> {
+    let mut text = encoding.decode(bytes)?;
+    let bom = text.starts_with('\u{FEFF}');
+    if bom {
+        text.remove(0);
+    }
+
+    // Whichever line ending turns up first is taken to be the file's
+    let line_ending = match text.find(['\n', '\r']) {
+        Some(i) if text[i..].starts_with("\r\n") => LineEnding::Crlf,
+        Some(i) if text[i..].starts_with('\r') => LineEnding::Cr,
+        _ => LineEnding::Lf,
+    };
+    let crlf = text.matches("\r\n").count();
+    let lf = text.matches('\n').count() - crlf;
+    let cr = text.matches('\r').count() - crlf;
+    let mixed_endings = match line_ending {
+        LineEnding::Lf => crlf + cr,
+        LineEnding::Crlf => lf + cr,
+        LineEnding::Cr => crlf + lf,
+    };
+    let separator = if line_ending == LineEnding::Cr { '\r' } else { '\n' };
+    let final_newline = text.is_empty() || text.ends_with(separator);
+
+    let body = text.strip_suffix(separator).unwrap_or(&text);
+    let lines = body.split(separator)
+        .map(|l| l.strip_suffix('\r').unwrap_or(l).to_string())
+        .collect();
+
+    Ok(Decoded { lines, line_ending, mixed_endings, final_newline, bom })
+}
+
+fn file_mtime(filename: &str) -> Option<SystemTime> {
+    fs::metadata(filename).and_then(|m| m.modified()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A window `rows` lines high that keeps `margin` lines around the cursor
+    fn window(rows: u32, margin: usize) -> WindowInfo {
+        WindowInfo {
+            rows,
+            cols: 80,
+            char_width: 8,
+            char_height: 16,
+            scroll_margin: margin,
+            text_top: 0,
+            status_y: 0,
+            prompt_y: 0,
+            margin_left: 0,
+            margin_top: 0,
+            text_left: 0,
+            scale: 1.0,
+        }
+    }
+
+    fn buffer(lines: &[&str]) -> Buffer {
+        let mut buf = Buffer::new();
+        buf.lines = lines.iter().map(|line| line.to_string()).collect();
+        buf
+    }
+
+    /// A buffer of lines "line 1", "line 2" and so on
+    fn numbered(count: usize) -> Buffer {
+        let mut buf = Buffer::new();
+        buf.lines = (1..=count).map(|n| format!("line {}", n)).collect();
+        buf
+    }
+
+    /// `buf` scrolled to show `top` first, with the cursor at `row`, `col`
+    fn placed(mut buf: Buffer, top: usize, row: usize, col: usize) -> Buffer {
+        buf.top_line = top;
+        buf.buffer_row = row;
+        buf.buffer_col = col;
+        buf
+    }
+
+    /// A file name in the temp directory no other test uses
+    fn temp_file(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!("wfemto-{}-{}", std::process::id(), name));
+        path.to_string_lossy().into_owned()
+    }
+
+    fn type_text(buf: &mut Buffer, text: &str, wi: &WindowInfo) {
+        text.chars().for_each(|c| buf.insert_char(c, wi));
+    }
+
+    #[test]
+    fn end_then_typing_appends_to_the_line() {
+        let wi = window(20, 3);
+        let mut buf = buffer(&["first", "second line"]);
+        buf.move_cursor_down(&wi);
+        buf.move_to_line_end(&wi);
+        type_text(&mut buf, "!", &wi);
+
+        assert_eq!(buf.lines, ["first", "second line!"]);
+        assert_eq!((buf.buffer_row, buf.buffer_col), (1, 12));
+    }
+
+    #[test]
+    fn up_onto_a_shorter_line_types_at_its_end() {
+        let wi = window(20, 3);
+        let mut buf = buffer(&["ab", "a longer line"]);
+        buf.goto_position(1, 13, &wi);
+        buf.move_cursor_up(&wi);
+        assert_eq!((buf.buffer_row, buf.buffer_col), (0, 2));
+
+        type_text(&mut buf, "c", &wi);
+        assert_eq!(buf.lines[0], "abc");
+        assert_eq!((buf.buffer_row, buf.buffer_col), (0, 3));
+    }
+
+    #[test]
+    fn up_and_down_return_to_the_column_they_left() {
+        let wi = window(20, 3);
+        let mut buf = buffer(&["a long first line", "", "a long third line"]);
+        buf.goto_position(0, 10, &wi);
+        buf.move_cursor_down(&wi);
+        assert_eq!((buf.buffer_row, buf.buffer_col), (1, 0));
+        buf.move_cursor_down(&wi);
+        assert_eq!((buf.buffer_row, buf.buffer_col), (2, 10));
+    }
+
+    #[test]
+    fn home_then_backspace_joins_onto_the_line_above() {
+        let wi = window(20, 3);
+        let mut buf = buffer(&["one", "two"]);
+        buf.goto_position(1, 2, &wi);
+        buf.move_to_line_start(&wi);
+        buf.backspace(&wi);
+
+        assert_eq!(buf.lines, ["onetwo"]);
+        assert_eq!((buf.buffer_row, buf.buffer_col), (0, 3));
+        type_text(&mut buf, "-", &wi);
+        assert_eq!(buf.lines, ["one-two"]);
+    }
+
+    #[test]
+    fn home_then_backspace_on_the_first_line_does_nothing() {
+        let wi = window(20, 3);
+        let mut buf = buffer(&["only"]);
+        buf.move_to_line_end(&wi);
+        buf.move_to_line_start(&wi);
+        buf.backspace(&wi);
+
+        assert_eq!(buf.lines, ["only"]);
+        assert_eq!((buf.buffer_row, buf.buffer_col), (0, 0));
+    }
+
+    #[test]
+    fn the_cursor_counts_chars_not_bytes() {
+        let wi = window(20, 3);
+        let mut buf = buffer(&["héllo wörld"]);
+        buf.move_to_line_end(&wi);
+        assert_eq!(buf.buffer_col, 11);
+
+        buf.move_cursor_left(&wi);
+        buf.backspace(&wi);
+        type_text(&mut buf, "ł", &wi);
+        assert_eq!(buf.lines, ["héllo wörłd"]);
+        assert_eq!(buf.buffer_col, 10);
+    }
+
+    #[test]
+    fn a_cursor_left_past_the_end_is_clamped_before_typing() {
+        let wi = window(20, 3);
+        let mut buf = buffer(&["abc"]);
+        buf.buffer_col = 50;
+        type_text(&mut buf, "d", &wi);
+
+        assert_eq!(buf.lines, ["abcd"]);
+        assert_eq!(buf.buffer_col, 4);
+    }
+
+    #[test]
+    fn backspace_joining_at_the_top_of_the_window_scrolls_up() {
+        let wi = window(20, 3);
+        let mut buf = placed(numbered(100), 30, 33, 0);
+        buf.backspace(&wi);
+
+        assert_eq!(buf.lines[32], "line 33line 34");
+        assert_eq!(buf.lines.len(), 99);
+        assert_eq!((buf.buffer_row, buf.buffer_col), (32, 7));
+        assert_eq!((buf.top_line, buf.scr_row()), (29, 3));
+
+        // With no margin, right at the top edge
+        let wi = window(20, 0);
+        let mut buf = placed(numbered(100), 30, 30, 0);
+        buf.backspace(&wi);
+        assert_eq!((buf.buffer_row, buf.top_line), (29, 29));
+    }
+
+    #[test]
+    fn backspace_joining_in_the_middle_of_the_window_stays_put() {
+        let wi = window(20, 3);
+        let mut buf = placed(numbered(100), 30, 40, 0);
+        buf.backspace(&wi);
+
+        assert_eq!(buf.lines[39], "line 40line 41");
+        assert_eq!((buf.buffer_row, buf.buffer_col), (39, 7));
+        assert_eq!((buf.top_line, buf.scr_row()), (30, 9));
+    }
+
+    #[test]
+    fn backspace_joining_at_the_bottom_of_the_window_stays_put() {
+        let wi = window(20, 3);
+        let mut buf = placed(numbered(100), 30, 46, 0);
+        buf.backspace(&wi);
+
+        assert_eq!(buf.lines[45], "line 46line 47");
+        assert_eq!((buf.buffer_row, buf.buffer_col), (45, 7));
+        assert_eq!((buf.top_line, buf.scr_row()), (30, 15));
+
+        // The last line of a buffer that ends on screen
+        let mut buf = placed(numbered(30), 10, 29, 0);
+        buf.backspace(&wi);
+        assert_eq!(buf.lines.len(), 29);
+        assert_eq!((buf.buffer_row, buf.buffer_col), (28, 7));
+        assert_eq!(buf.top_line, 9);
+    }
+
+    #[test]
+    fn delete_joining_keeps_the_cursor_in_view() {
+        let wi = window(20, 3);
+        for row in [33, 40, 46] {
+            let mut buf = placed(numbered(100), 30, row, 0);
+            buf.move_to_line_end(&wi);
+            buf.delete_forward(&wi);
+
+            assert_eq!(buf.lines[row], format!("line {}line {}", row + 1, row + 2));
+            assert_eq!((buf.buffer_row, buf.buffer_col), (row, char_len(&format!("line {}", row + 1))));
+            assert_eq!(buf.top_line, 30);
+        }
+    }
+
+    #[test]
+    fn enter_up_and_down_keep_the_same_bottom_margin() {
+        let wi = window(20, 3);
+        let mut buf = numbered(40);
+        buf.goto_position(16, 0, &wi);
+        assert_eq!(buf.top_line, 0);
+
+        buf.move_to_line_end(&wi);
+        buf.insert_newline(&wi);
+        assert_eq!((buf.buffer_row, buf.top_line), (17, 1));
+        buf.insert_newline(&wi);
+        assert_eq!((buf.buffer_row, buf.top_line), (18, 2));
+        buf.move_cursor_up(&wi);
+        assert_eq!((buf.buffer_row, buf.top_line), (17, 2));
+        buf.move_cursor_down(&wi);
+        assert_eq!((buf.buffer_row, buf.top_line), (18, 2));
+        buf.move_cursor_down(&wi);
+        assert_eq!((buf.buffer_row, buf.top_line), (19, 3));
+        buf.insert_newline(&wi);
+        assert_eq!((buf.buffer_row, buf.top_line), (20, 4));
+
+        // Enter and Down scroll alike: the cursor sits as far up from the
+        // bottom either way
+        assert_eq!(buf.scr_row(), 16);
+        buf.move_cursor_down(&wi);
+        assert_eq!(buf.scr_row(), 16);
+    }
+
+    #[test]
+    fn enter_on_the_last_lines_goes_down_to_the_bottom_row() {
+        let wi = window(20, 3);
+        let mut buf = numbered(20);
+        buf.goto_position(19, 0, &wi);
+        assert_eq!(buf.top_line, 0);
+
+        buf.move_to_line_end(&wi);
+        buf.insert_newline(&wi);
+        assert_eq!((buf.buffer_row, buf.top_line, buf.scr_row()), (20, 1, 19));
+        buf.move_cursor_up(&wi);
+        buf.move_cursor_up(&wi);
+        assert_eq!((buf.buffer_row, buf.top_line), (18, 1));
+        buf.insert_newline(&wi);
+        assert_eq!((buf.buffer_row, buf.top_line), (19, 2));
+        buf.move_cursor_down(&wi);
+        buf.move_cursor_down(&wi);
+        assert_eq!((buf.buffer_row, buf.top_line, buf.scr_row()), (21, 2, 19));
+    }
+
+    #[test]
+    fn interleaved_enter_up_and_down_never_leave_the_margin() {
+        let wi = window(20, 3);
+        let mut buf = numbered(30);
+        buf.goto_position(15, 0, &wi);
+        for step in "EEDEUDDEEUUEDDDEEUDE".chars() {
+            match step {
+                'E' => buf.insert_newline(&wi),
+                'U' => buf.move_cursor_up(&wi),
+                _ => buf.move_cursor_down(&wi),
+            }
+            let below = 3.min(buf.lines.len() - 1 - buf.buffer_row);
+            let above = 3.min(buf.buffer_row);
+            assert!(buf.scr_row() + below < 20, "after {}", step);
+            assert!(buf.scr_row() >= above, "after {}", step);
+        }
+    }
+
+    #[test]
+    fn an_empty_file_loads_as_one_empty_line() {
+        let wi = window(20, 3);
+        let path = temp_file("empty.txt");
+        fs::write(&path, "").unwrap();
+        let mut buf = Buffer::new();
+        let loaded = buf.load(&path);
+        fs::remove_file(&path).unwrap();
+        loaded.unwrap();
+
+        assert_eq!(buf.lines, [""]);
+        assert_eq!((buf.buffer_row, buf.buffer_col), (0, 0));
+        type_text(&mut buf, "x", &wi);
+        assert_eq!(buf.lines, ["x"]);
+        assert_eq!(buf.buffer_col, 1);
+
+        buf.delete_line(&wi);
+        buf.backspace(&wi);
+        buf.delete_forward(&wi);
+        assert_eq!(buf.lines, [""]);
+    }
+
+    #[test]
+    fn a_file_of_one_line_break_loads_as_one_empty_line() {
+        let path = temp_file("newline.txt");
+        fs::write(&path, "\n").unwrap();
+        let mut buf = Buffer::new();
+        let loaded = buf.load(&path);
+        fs::remove_file(&path).unwrap();
+        loaded.unwrap();
+
+        assert_eq!(buf.lines, [""]);
+        assert!(buf.final_newline);
+    }
+
+    #[test]
+    fn a_file_shorter_than_the_window_never_scrolls() {
+        let wi = window(20, 5);
+        let mut buf = numbered(10);
+        for _ in 0..12 {
+            buf.move_cursor_down(&wi);
+            assert_eq!(buf.top_line, 0);
+        }
+        assert_eq!(buf.buffer_row, 9);
+
+        buf.goto_line(9, 0, &wi);
+        assert_eq!(buf.top_line, 0);
+        buf.page_down(&wi);
+        assert_eq!((buf.buffer_row, buf.top_line), (9, 0));
+    }
+
+    #[test]
+    fn a_jump_into_the_margin_scrolls_it_clear() {
+        let wi = window(20, 3);
+        let mut buf = numbered(100);
+
+        // Into the bottom margin of the first screen
+        buf.goto_position(18, 0, &wi);
+        assert_eq!((buf.top_line, buf.scr_row()), (2, 16));
+
+        // Into the top margin of the screen showing 40 on
+        buf.top_line = 40;
+        buf.goto_position(41, 0, &wi);
+        assert_eq!((buf.top_line, buf.scr_row()), (38, 3));
+
+        // Near the start of the buffer there's no context to keep above
+        buf.goto_position(1, 0, &wi);
+        assert_eq!((buf.top_line, buf.scr_row()), (0, 1));
+
+        // goto_line centers instead
+        buf.goto_line(60, 0, &wi);
+        assert_eq!((buf.top_line, buf.scr_row()), (50, 10));
+    }
+
+    #[test]
+    fn a_margin_over_half_the_window_keeps_the_cursor_centered() {
+        let wi = window(20, 50);
+        let mut buf = numbered(100);
+        buf.goto_position(50, 0, &wi);
+        assert_eq!(buf.scr_row(), 10);
+
+        for _ in 0..10 {
+            buf.move_cursor_down(&wi);
+            assert!((9..=10).contains(&buf.scr_row()));
+        }
+        for _ in 0..20 {
+            buf.move_cursor_up(&wi);
+            assert!((9..=10).contains(&buf.scr_row()));
+        }
+
+        // Until the ends of the buffer, which don't scroll past
+        buf.goto_position(0, 0, &wi);
+        assert_eq!(buf.top_line, 0);
+        buf.goto_position(99, 0, &wi);
+        assert_eq!((buf.top_line, buf.scr_row()), (80, 19));
+    }
+
+    #[test]
+    fn no_margin_lets_the_cursor_reach_the_edges() {
+        let wi = window(20, 0);
+        let mut buf = numbered(100);
+        buf.goto_position(19, 0, &wi);
+        assert_eq!((buf.top_line, buf.scr_row()), (0, 19));
+        buf.move_cursor_down(&wi);
+        assert_eq!((buf.top_line, buf.scr_row()), (1, 19));
+    }
+
+    #[test]
+    fn typing_at_the_end_of_a_long_line_scrolled_across() {
+        let wi = window(20, 3);
+        let long = "0123456789".repeat(9);
+        let mut buf = buffer(&[&long, "short"]);
+        buf.move_to_line_end(&wi);
+        assert_eq!((buf.buffer_col, buf.buffer_col_offset), (90, 11));
+
+        type_text(&mut buf, "xyz", &wi);
+        assert_eq!(buf.lines[0], format!("{}xyz", long));
+        assert_eq!((buf.buffer_col, buf.buffer_col_offset), (93, 14));
+
+        buf.backspace(&wi);
+        assert_eq!(buf.lines[0], format!("{}xy", long));
+        assert_eq!(buf.buffer_col, 92);
+
+        buf.insert_newline(&wi);
+        assert_eq!(buf.lines[0], format!("{}xy", long));
+        assert_eq!(buf.lines[1], "");
+        assert_eq!((buf.buffer_row, buf.buffer_col, buf.buffer_col_offset), (1, 0, 0));
+    }
+
+    #[test]
+    fn typing_partway_along_a_long_line_scrolled_across() {
+        let wi = window(20, 3);
+        let long = "abcdefghij".repeat(10);
+        let mut buf = buffer(&[&long]);
+        buf.move_to_line_end(&wi);
+        for _ in 0..15 {
+            buf.move_cursor_left(&wi);
+        }
+        assert_eq!((buf.buffer_col, buf.buffer_col_offset), (85, 21));
+
+        type_text(&mut buf, "|", &wi);
+        assert_eq!(&buf.lines[0][80..90], "abcde|fghi");
+        assert_eq!((buf.buffer_col, buf.buffer_col_offset), (86, 21));
+    }
+
+    #[test]
+    fn crlf_converts_to_lf_and_back() {
+        let wi = window(20, 3);
+        let path = temp_file("crlf.txt");
+        fs::write(&path, "one\r\ntwo\r\n\r\nfour\r\n").unwrap();
+        let mut buf = Buffer::new();
+        buf.persistent_undo = false;
+        buf.load(&path).unwrap();
+        assert_eq!(buf.line_ending, LineEnding::Crlf);
+        assert_eq!(buf.lines, ["one", "two", "", "four"]);
+
+        assert_eq!(buf.convert_line_endings(LineEnding::Lf, &wi), 0);
+        assert!(buf.is_modified);
+        buf.save().unwrap();
+        let lf = fs::read(&path).unwrap();
+
+        assert_eq!(buf.convert_line_endings(LineEnding::Crlf, &wi), 0);
+        buf.save().unwrap();
+        let crlf = fs::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(lf, b"one\ntwo\n\nfour\n");
+        assert_eq!(crlf, b"one\r\ntwo\r\n\r\nfour\r\n");
+    }
+
+    #[test]
+    fn converting_mixed_endings_splits_the_strays() {
+        let wi = window(20, 3);
+        let path = temp_file("mixed.txt");
+        fs::write(&path, "one\r\ntwo\nthree\r\n").unwrap();
+        let mut buf = Buffer::new();
+        buf.persistent_undo = false;
+        buf.load(&path).unwrap();
+
+        assert_eq!(buf.convert_line_endings(LineEnding::Crlf, &wi), 1);
+        assert_eq!(buf.lines, ["one", "two", "three"]);
+        buf.save().unwrap();
+        let saved = fs::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(saved, b"one\r\ntwo\r\nthree\r\n");
+    }
+
+    #[test]
+    fn enter_keeps_the_spaces_after_it_without_auto_indent() {
+        let wi = window(20, 3);
+        let mut buf = buffer(&["    foo  bar"]);
+        buf.auto_indent = false;
+        buf.goto_position(0, 7, &wi);
+        buf.insert_newline(&wi);
+
+        assert_eq!(buf.lines, ["    foo", "  bar"]);
+        assert_eq!((buf.buffer_row, buf.buffer_col), (1, 0));
+    }
+
+    #[test]
+    fn enter_trades_the_spaces_after_it_for_the_indent() {
+        let wi = window(20, 3);
+        let mut buf = buffer(&["    foo  bar"]);
+        buf.auto_indent = true;
+        buf.goto_position(0, 7, &wi);
+        buf.insert_newline(&wi);
+
+        assert_eq!(buf.lines, ["    foo", "    bar"]);
+        assert_eq!((buf.buffer_row, buf.buffer_col), (1, 4));
+    }
+
+    #[test]
+    fn paragraph_moves_stop_at_the_next_blank_line() {
+        let lines = buffer(&["a", "", "b", "", "c"]).lines;
+        assert_eq!(paragraph_boundary(&lines, 0, true), 1);
+        assert_eq!(paragraph_boundary(&lines, 1, true), 3);
+        assert_eq!(paragraph_boundary(&lines, 4, false), 3);
+        assert_eq!(paragraph_boundary(&lines, 3, false), 1);
+    }
+
+    #[test]
+    fn paragraph_moves_cross_a_run_of_blank_lines() {
+        let lines = buffer(&["one", "two", "", "  ", "three", "four", "", "five"]).lines;
+        assert_eq!(paragraph_boundary(&lines, 0, true), 2);
+        assert_eq!(paragraph_boundary(&lines, 2, true), 6);
+        assert_eq!(paragraph_boundary(&lines, 3, true), 6);
+        assert_eq!(paragraph_boundary(&lines, 5, false), 3);
+        assert_eq!(paragraph_boundary(&lines, 3, false), 0);
+    }
+
+    #[test]
+    fn paragraph_moves_stop_at_the_ends_of_the_buffer() {
+        let lines = buffer(&["a", "b", "", "c", "d"]).lines;
+        assert_eq!(paragraph_boundary(&lines, 3, true), 4);
+        assert_eq!(paragraph_boundary(&lines, 4, true), 4);
+        assert_eq!(paragraph_boundary(&lines, 1, false), 0);
+        assert_eq!(paragraph_boundary(&lines, 0, false), 0);
+        assert_eq!(paragraph_boundary(&buffer(&[""]).lines, 0, true), 0);
+        assert_eq!(paragraph_boundary(&buffer(&[""]).lines, 0, false), 0);
+    }
+
+    /// Numbers from a linear congruential generator, the same for a seed
+    struct Lcg(u64);
+
+    impl Lcg {
+        fn below(&mut self, n: usize) -> usize {
+            self.0 = self.0.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (self.0 >> 33) as usize % n.max(1)
+        }
+
+        /// A (row, col) inside `buf`
+        fn position(&mut self, buf: &Buffer) -> (usize, usize) {
+            let row = self.below(buf.lines.len());
+            (row, self.below(char_len(&buf.lines[row]) + 1))
+        }
+    }
+
+    /// Chars to type at random: plain, accented, wide, astral and combining
+    const CHARS: &[char] = &['a', 'z', ' ', '\t', 'é', '中', '漢', '😀', '🧿', '\u{301}', '\u{20DD}', '\u{200D}'];
+
+    /// One to four chars from CHARS, sometimes with a line break
+    fn random_text(rng: &mut Lcg) -> String {
+        (0..1 + rng.below(4)).map(|_| if rng.below(6) == 0 { '\n' } else { CHARS[rng.below(CHARS.len())] }).collect()
+    }
+
+    /// A movement, with or without Shift, as the editor makes it
+    fn random_move(rng: &mut Lcg, buf: &mut Buffer, wi: &WindowInfo) -> String {
+        let selecting = rng.below(3) == 0;
+        buf.select_while_moving(selecting);
+        let motion = match rng.below(12) {
+            0 => { buf.move_cursor_left(wi); "left" }
+            1 => { buf.move_cursor_right(wi); "right" }
+            2 => { buf.move_cursor_up(wi); "up" }
+            3 => { buf.move_cursor_down(wi); "down" }
+            4 => { buf.move_to_line_start(wi); "line start" }
+            5 => { buf.move_to_line_end(wi); "line end" }
+            6 => { buf.move_word(true, wi); "word forward" }
+            7 => { buf.move_word(false, wi); "word back" }
+            8 => { buf.move_paragraph(true, wi); "paragraph forward" }
+            9 => { buf.move_paragraph(false, wi); "paragraph back" }
+            10 => { buf.page_down(wi); "page down" }
+            _ => { buf.page_up(wi); "page up" }
+        };
+
+        format!("move {}{}", motion, if selecting { ", selecting" } else { "" })
+    }
+
+    #[test]
+    fn random_edits_keep_the_cursor_in_the_buffer_and_undo_completely() {
+        let rows = 10;
+        let wi = window(rows, 2);
+        for seed in 1..=40 {
+            let mut rng = Lcg(seed);
+            let mut buf = numbered(15);
+            let original = buf.lines.clone();
+            let mut steps = Vec::new();
+            // Whether the cursor is where the last undo step left it, so that
+            // undoing and redoing it comes back to the same place
+            let mut at_change = false;
+            for _ in 0..2500 {
+                let (lines, cursor) = (buf.lines.clone(), (buf.buffer_row, buf.buffer_col));
+                let step = match rng.below(16) {
+                    0 => {
+                        let (row, col) = rng.position(&buf);
+                        buf.goto_position(row, col, &wi);
+                        format!("goto_position({}, {})", row, col)
+                    }
+                    1 | 2 => {
+                        let c = CHARS[rng.below(CHARS.len())];
+                        buf.insert_char(c, &wi);
+                        format!("insert_char({:?})", c)
+                    }
+                    3 => {
+                        let text = random_text(&mut rng);
+                        buf.insert_text(&text, &wi);
+                        format!("insert_text({:?})", text)
+                    }
+                    4 => {
+                        let (a, b) = (rng.position(&buf), rng.position(&buf));
+                        let (start, end) = (a.min(b), a.max(b));
+                        let text = random_text(&mut rng);
+                        buf.replace_text(start, end, &text, &wi);
+                        format!("replace_text({:?}, {:?}, {:?})", start, end, text)
+                    }
+                    5 => {
+                        let start = rng.below(buf.lines.len());
+                        let end = start + 1 + rng.below((buf.lines.len() - start).min(3));
+                        let new: Vec<String> = (0..rng.below(4)).map(|_| random_text(&mut rng).replace('\n', "")).collect();
+                        let described = format!("replace_lines({}, {}, {:?})", start, end, new);
+                        buf.replace_lines(start, end, new, &wi);
+                        described
+                    }
+                    6 => {
+                        buf.delete_line(&wi);
+                        "delete_line()".to_string()
+                    }
+                    7 => {
+                        buf.backspace(&wi);
+                        "backspace()".to_string()
+                    }
+                    8 => {
+                        buf.delete_forward(&wi);
+                        "delete_forward()".to_string()
+                    }
+                    9 => {
+                        buf.insert_newline(&wi);
+                        "insert_newline()".to_string()
+                    }
+                    10 | 11 => random_move(&mut rng, &mut buf, &wi),
+                    12 => {
+                        buf.select_line(&wi);
+                        "select_line()".to_string()
+                    }
+                    13 | 14 => {
+                        // Redo straight after an undo puts back exactly what was there
+                        if buf.undo(&wi) {
+                            let undone = (buf.lines.clone(), (buf.buffer_row, buf.buffer_col));
+                            assert!(buf.redo(&wi), "seed {}: nothing to redo", seed);
+                            assert_eq!(buf.lines, lines, "seed {}, redo after:\n{}", seed, steps.join("\n"));
+                            if at_change {
+                                assert_eq!((buf.buffer_row, buf.buffer_col), cursor, "seed {}, redo after:\n{}", seed, steps.join("\n"));
+                            }
+                            assert!(buf.undo(&wi));
+                            assert_eq!((buf.lines.clone(), (buf.buffer_row, buf.buffer_col)), undone, "seed {}", seed);
+                        }
+                        "undo()".to_string()
+                    }
+                    _ => {
+                        buf.redo(&wi);
+                        "redo()".to_string()
+                    }
+                };
+                steps.push(step);
+
+                let moved = (buf.buffer_row, buf.buffer_col) != cursor;
+                at_change = match steps.last().map(String::as_str) {
+                    Some("undo()") => false,
+                    _ if buf.lines != lines => true,
+                    _ => at_change && !moved,
+                };
+
+                let place = || format!("seed {}, after:\n{}", seed, steps[steps.len().saturating_sub(30)..].join("\n"));
+                assert!(!buf.lines.is_empty(), "{}", place());
+                assert!(buf.buffer_row < buf.lines.len(), "{}", place());
+                assert!(buf.buffer_col <= char_len(&buf.lines[buf.buffer_row]), "{}", place());
+                assert!(buf.top_line <= buf.buffer_row, "{}", place());
+                assert!(buf.buffer_row < buf.top_line + rows as usize, "{}", place());
+                assert!(buf.buffer_col_offset <= buf.buffer_col, "{}", place());
+            }
+
+            while buf.undo(&wi) {}
+            assert_eq!(buf.lines, original, "seed {}", seed);
+            assert!(!buf.is_modified, "seed {}", seed);
+        }
+    }
+}
@@ -0,0 +1,64 @@
+// wfemto - A toy text editor
+// Written in 2025 by Dana Larose <ywg.dana@gmail.com>
+//
+// To the extent possible under law, the author(s) have dedicated all copyright
+// and related and neighboring rights to this software to the public domain
+// worldwide. This software is distributed without any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication along
+// with this software. If not,
+// see <http://creativecommons.org/publicdomain/zero/1.0/>.
+
+// The system clipboard and, on X11 and Wayland, the primary selection: the
+// text last selected anywhere, which middle-click pastes. Elsewhere there's
+// no such thing and middle-click pastes the clipboard instead.
+
+use sdl2::clipboard::ClipboardUtil;
+
+/// Whether the platform has a primary selection separate from the clipboard
+pub const HAS_PRIMARY: bool = cfg!(all(unix, not(target_os = "macos")));
+
+/// Make `text` the primary selection, where there is one
+pub fn publish_primary(clipboard: &ClipboardUtil, text: &str) {
+    // SDL can't take text with a NUL in it
+    if !HAS_PRIMARY || text.is_empty() || text.contains('\0') {
+        return;
+    }
+
+    if let Err(e) = clipboard.set_primary_selection_text(text) {
+        log_debug!("unable to set the primary selection: {}", e);
+    }
+}
+
+/// What middle-click should paste
+pub fn primary_text(clipboard: &ClipboardUtil) -> Option<String> {
+    if HAS_PRIMARY && clipboard.has_primary_selection_text() {
+        return clipboard.primary_selection_text().ok();
+    }
+
+    clipboard_text(clipboard)
+}
+
+pub fn clipboard_text(clipboard: &ClipboardUtil) -> Option<String> {
+    if !clipboard.has_clipboard_text() {
+        return None;
+    }
+
+    match clipboard.clipboard_text() {
+        Ok(text) => Some(text),
+        Err(e) => {
+            log_debug!("unable to read the clipboard: {}", e);
+            None
+        }
+    }
+}
+
+pub fn set_clipboard_text(clipboard: &ClipboardUtil, text: &str) {
+    if text.contains('\0') {
+        return;
+    }
+
+    if let Err(e) = clipboard.set_clipboard_text(text) {
+        log_warn!("unable to set the clipboard: {}", e);
+    }
+}
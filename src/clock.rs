@@ -0,0 +1,169 @@
+// wfemto - A toy text editor
+// Written in 2025 by Dana Larose <ywg.dana@gmail.com>
+//
+// To the extent possible under law, the author(s) have dedicated all copyright
+// and related and neighboring rights to this software to the public domain
+// worldwide. This software is distributed without any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication along
+// with this software. If not,
+// see <http://creativecommons.org/publicdomain/zero/1.0/>.
+
+// Just enough calendar code for log timestamps and inserting the date: break
+// the current time into fields and format it with a subset of strftime.
+// Local time needs the C library to know the zone; elsewhere it's UTC.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const MONTHS: [&str; 12] = [
+    "January", "February", "March", "April", "May", "June", "July", "August",
+    "September", "October", "November", "December",
+];
+const WEEKDAYS: [&str; 7] = [
+    "Sunday", "Monday", "Tuesday", "Wednesday", "Thursday", "Friday", "Saturday",
+];
+
+pub struct DateTime {
+    pub year: i64,
+    pub month: u32,   // 1-12
+    pub day: u32,     // 1-31
+    pub hour: u32,
+    pub minute: u32,
+    pub second: u32,
+    pub weekday: u32, // 0 is Sunday
+    pub utc_offset: i64, // Seconds east of UTC
+}
+
+pub fn now_secs() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+impl DateTime {
+    fn from_secs(secs: i64, utc_offset: i64) -> DateTime {
+        let local = secs + utc_offset;
+        let days = local.div_euclid(86400);
+        let rem = local.rem_euclid(86400) as u32;
+        let (year, month, day) = civil_from_days(days);
+
+        DateTime {
+            year,
+            month,
+            day,
+            hour: rem / 3600,
+            minute: (rem % 3600) / 60,
+            second: rem % 60,
+            weekday: (days + 4).rem_euclid(7) as u32, // 1970-01-01 was a Thursday
+            utc_offset,
+        }
+    }
+
+    pub fn now_utc() -> DateTime {
+        DateTime::from_secs(now_secs(), 0)
+    }
+
+    pub fn now_local() -> DateTime {
+        DateTime::local_at(now_secs())
+    }
+
+    /// The local time `secs` seconds after the epoch
+    pub fn local_at(secs: i64) -> DateTime {
+        DateTime::from_secs(secs, local_offset(secs))
+    }
+
+    /// Format with strftime-style directives: %Y %y %m %d %e %H %I %M %S %p
+    /// %b %B %a %A %j %z and %%. Anything else is copied through as is.
+    pub fn format(&self, fmt: &str) -> String {
+        let mut out = String::new();
+        let mut chars = fmt.chars();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                out.push(c);
+                continue;
+            }
+
+            match chars.next() {
+                Some('Y') => out.push_str(&self.year.to_string()),
+                Some('y') => out.push_str(&format!("{:02}", self.year.rem_euclid(100))),
+                Some('m') => out.push_str(&format!("{:02}", self.month)),
+                Some('d') => out.push_str(&format!("{:02}", self.day)),
+                Some('e') => out.push_str(&format!("{:2}", self.day)),
+                Some('H') => out.push_str(&format!("{:02}", self.hour)),
+                Some('I') => out.push_str(&format!("{:02}", (self.hour + 11) % 12 + 1)),
+                Some('M') => out.push_str(&format!("{:02}", self.minute)),
+                Some('S') => out.push_str(&format!("{:02}", self.second)),
+                Some('p') => out.push_str(if self.hour < 12 { "AM" } else { "PM" }),
+                Some('B') => out.push_str(MONTHS[self.month as usize - 1]),
+                Some('b') => out.push_str(&MONTHS[self.month as usize - 1][..3]),
+                Some('A') => out.push_str(WEEKDAYS[self.weekday as usize]),
+                Some('a') => out.push_str(&WEEKDAYS[self.weekday as usize][..3]),
+                Some('j') => out.push_str(&format!("{:03}", self.day_of_year())),
+                Some('z') => {
+                    let sign = if self.utc_offset < 0 { '-' } else { '+' };
+                    let mins = self.utc_offset.abs() / 60;
+                    out.push_str(&format!("{}{:02}{:02}", sign, mins / 60, mins % 60));
+                }
+                Some('%') => out.push('%'),
+                Some(other) => {
+                    out.push('%');
+                    out.push(other);
+                }
+                None => out.push('%'),
+            }
+        }
+
+        out
+    }
+
+    fn day_of_year(&self) -> i64 {
+        days_from_civil(self.year, self.month, self.day) - days_from_civil(self.year, 1, 1) + 1
+    }
+}
+
+#[cfg(unix)]
+fn local_offset(secs: i64) -> i64 {
+    let t = secs as libc::time_t;
+    // SAFETY: localtime_r only writes to the tm we hand it
+    unsafe {
+        let mut tm: libc::tm = std::mem::zeroed();
+        if libc::localtime_r(&t, &mut tm).is_null() {
+            0
+        } else {
+            tm.tm_gmtoff as i64
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn local_offset(_secs: i64) -> i64 {
+    0
+}
+
+// Howard Hinnant's days-since-epoch to y/m/d conversion
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = (if z >= 0 { z } else { z - 146096 }) / 146097;
+    let doe = (z - era * 146097) as u64;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = (if mp < 10 { mp + 3 } else { mp - 9 }) as u32;
+    let year = yoe as i64 + era * 400 + i64::from(month <= 2);
+
+    (year, month, day)
+}
+
+// ...and back again
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = (if year >= 0 { year } else { year - 399 }) / 400;
+    let yoe = (year - era * 400) as u64;
+    let mp = if month > 2 { month - 3 } else { month + 9 } as u64;
+    let doy = (153 * mp + 2) / 5 + day as u64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+
+    era * 146097 + doe as i64 - 719468
+}
@@ -0,0 +1,310 @@
+// wfemto - A toy text editor
+// Written in 2025 by Dana Larose <ywg.dana@gmail.com>
+//
+// To the extent possible under law, the author(s) have dedicated all copyright
+// and related and neighboring rights to this software to the public domain
+// worldwide. This software is distributed without any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication along
+// with this software. If not,
+// see <http://creativecommons.org/publicdomain/zero/1.0/>.
+
+// The commands keys are bound to, most of which can also be run by name from
+// the command palette. Some take an argument, typed after the name ("snippet
+// todo"). The editing and movement keys are commands too, without names, so
+// that everything done from the keyboard can be recorded in a macro.
+
+use crate::buffer::LineEnding;
+use crate::lineops::LineOp;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Motion {
+    Left,
+    Right,
+    Up,
+    Down,
+    LineStart,
+    LineEnd,
+    PageUp,
+    PageDown,
+    BufferStart,
+    BufferEnd,
+    ParagraphUp,
+    ParagraphDown,
+    WordLeft,
+    WordRight,
+}
+
+impl Motion {
+    fn describe(self) -> &'static str {
+        match self {
+            Motion::Left => "left",
+            Motion::Right => "right",
+            Motion::Up => "up",
+            Motion::Down => "down",
+            Motion::LineStart => "to line start",
+            Motion::LineEnd => "to line end",
+            Motion::PageUp => "page up",
+            Motion::PageDown => "page down",
+            Motion::BufferStart => "to start of buffer",
+            Motion::BufferEnd => "to end of buffer",
+            Motion::ParagraphUp => "to previous paragraph",
+            Motion::ParagraphDown => "to next paragraph",
+            Motion::WordLeft => "word left",
+            Motion::WordRight => "word right",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Command {
+    Insert, // The argument, as if typed
+    Newline,
+    Backspace,
+    DeleteForward,
+    Move(Motion, bool), // And whether to extend the selection
+    DeleteLine,
+    OpenLineBelow,
+    OpenLineAbove,
+    TransposeChars,
+    TransposeWords,
+    Reflow,
+    SelectLine,
+    Tab,
+    Dedent,
+    Cancel,
+    InsertMode, // For the vi-style modal keys
+    Append,
+    Palette,
+    Copy,
+    Cut,
+    KillLine,
+    KillWord,
+    Paste,
+    PasteRing,
+    Complete,
+    RecordMacro,
+    StopMacro,
+    PlayMacro,
+    Open,
+    Save,
+    SaveAs,
+    Scratch,
+    WriteSelection,
+    InsertFile,
+    CloseBuffer,
+    Quit,
+    NextBuffer,
+    PrevBuffer,
+    AlternateBuffer,
+    JumpBack,
+    JumpForward,
+    Watch,
+    Tail,
+    ColumnGuide,
+    Minimap,
+    LineNumbers,
+    Split,
+    Unsplit,
+    SwitchPane,
+    FocusPanel,
+    ClosePanel,
+    BufferList,
+    OpenCopy,
+    SaveAll,
+    CopyPath,
+    CopyPathLine,
+    GotoFile,
+    DiffUnsaved,
+    Undo,
+    Redo,
+    Format,
+    Pipe,
+    ExportHtml,
+    Counts,
+    Info,
+    Settings,
+    Reopen,
+    Help,
+    NextMarker,
+    Tag,
+    TagBack,
+    Compile,
+    NextError,
+    PrevError,
+    Grep,
+    InsertDate,
+    Snippet,
+    InsertChar,
+    Rename,
+    Lines(LineOp),
+    LineEndings(LineEnding),
+    SqueezeBlanks,
+    DeleteMatching,
+    SetIndent,
+    RepeatLast,
+    SpellSuggest,
+    SaveSession,
+    LoadSession,
+    Outline,
+}
+
+impl Command {
+    /// Whether RepeatLast can do this again: it changes the text, and
+    /// doing it again wherever the cursor is now makes sense
+    pub fn is_repeatable(self) -> bool {
+        matches!(self,
+            Command::Insert | Command::Newline | Command::Backspace | Command::DeleteForward |
+            Command::DeleteLine | Command::OpenLineBelow | Command::OpenLineAbove |
+            Command::TransposeChars | Command::TransposeWords | Command::Reflow | Command::Tab |
+            Command::Dedent | Command::Cut | Command::KillLine | Command::KillWord | Command::Paste |
+            Command::InsertFile | Command::Format | Command::Pipe | Command::InsertDate |
+            Command::Snippet | Command::InsertChar | Command::Rename | Command::Lines(_) |
+            Command::LineEndings(_) | Command::SqueezeBlanks | Command::DeleteMatching |
+            Command::SetIndent)
+    }
+}
+
+pub struct CommandInfo {
+    pub name: &'static str,
+    pub command: Command,
+}
+
+pub static COMMANDS: &[CommandInfo] = &[
+    CommandInfo { name: "open", command: Command::Open },
+    CommandInfo { name: "save", command: Command::Save },
+    CommandInfo { name: "save-as", command: Command::SaveAs },
+    CommandInfo { name: "scratch", command: Command::Scratch },
+    CommandInfo { name: "write-selection", command: Command::WriteSelection },
+    CommandInfo { name: "insert-file", command: Command::InsertFile },
+    CommandInfo { name: "close", command: Command::CloseBuffer },
+    CommandInfo { name: "quit", command: Command::Quit },
+    CommandInfo { name: "next-buffer", command: Command::NextBuffer },
+    CommandInfo { name: "prev-buffer", command: Command::PrevBuffer },
+    CommandInfo { name: "alternate-buffer", command: Command::AlternateBuffer },
+    CommandInfo { name: "jump-back", command: Command::JumpBack },
+    CommandInfo { name: "jump-forward", command: Command::JumpForward },
+    CommandInfo { name: "watch", command: Command::Watch },
+    CommandInfo { name: "tail", command: Command::Tail },
+    CommandInfo { name: "column-guide", command: Command::ColumnGuide },
+    CommandInfo { name: "minimap", command: Command::Minimap },
+    CommandInfo { name: "line-numbers", command: Command::LineNumbers },
+    CommandInfo { name: "split", command: Command::Split },
+    CommandInfo { name: "unsplit", command: Command::Unsplit },
+    CommandInfo { name: "switch-pane", command: Command::SwitchPane },
+    CommandInfo { name: "focus-panel", command: Command::FocusPanel },
+    CommandInfo { name: "close-panel", command: Command::ClosePanel },
+    CommandInfo { name: "buffer-list", command: Command::BufferList },
+    CommandInfo { name: "open-copy", command: Command::OpenCopy },
+    CommandInfo { name: "save-all", command: Command::SaveAll },
+    CommandInfo { name: "save-session", command: Command::SaveSession },
+    CommandInfo { name: "load-session", command: Command::LoadSession },
+    CommandInfo { name: "copy-path", command: Command::CopyPath },
+    CommandInfo { name: "copy-path-line", command: Command::CopyPathLine },
+    CommandInfo { name: "goto-file", command: Command::GotoFile },
+    CommandInfo { name: "diff-unsaved", command: Command::DiffUnsaved },
+    CommandInfo { name: "outline", command: Command::Outline },
+    CommandInfo { name: "undo", command: Command::Undo },
+    CommandInfo { name: "redo", command: Command::Redo },
+    CommandInfo { name: "format", command: Command::Format },
+    CommandInfo { name: "pipe", command: Command::Pipe },
+    CommandInfo { name: "export-html", command: Command::ExportHtml },
+    CommandInfo { name: "copy", command: Command::Copy },
+    CommandInfo { name: "cut", command: Command::Cut },
+    CommandInfo { name: "kill-line", command: Command::KillLine },
+    CommandInfo { name: "kill-word", command: Command::KillWord },
+    CommandInfo { name: "paste", command: Command::Paste },
+    CommandInfo { name: "paste-ring", command: Command::PasteRing },
+    CommandInfo { name: "complete", command: Command::Complete },
+    CommandInfo { name: "record-macro", command: Command::RecordMacro },
+    CommandInfo { name: "stop-macro", command: Command::StopMacro },
+    CommandInfo { name: "play-macro", command: Command::PlayMacro },
+    CommandInfo { name: "repeat-last", command: Command::RepeatLast },
+    CommandInfo { name: "count", command: Command::Counts },
+    CommandInfo { name: "spell", command: Command::SpellSuggest },
+    CommandInfo { name: "info", command: Command::Info },
+    CommandInfo { name: "settings", command: Command::Settings },
+    CommandInfo { name: "reopen", command: Command::Reopen },
+    CommandInfo { name: "help", command: Command::Help },
+    CommandInfo { name: "next-marker", command: Command::NextMarker },
+    CommandInfo { name: "tag", command: Command::Tag },
+    CommandInfo { name: "back", command: Command::TagBack },
+    CommandInfo { name: "compile", command: Command::Compile },
+    CommandInfo { name: "next-error", command: Command::NextError },
+    CommandInfo { name: "prev-error", command: Command::PrevError },
+    CommandInfo { name: "grep", command: Command::Grep },
+    CommandInfo { name: "insert-date", command: Command::InsertDate },
+    CommandInfo { name: "insert", command: Command::Insert },
+    CommandInfo { name: "snippet", command: Command::Snippet },
+    CommandInfo { name: "insert-char", command: Command::InsertChar },
+    CommandInfo { name: "rename", command: Command::Rename },
+    CommandInfo { name: "sort", command: Command::Lines(LineOp::Sort) },
+    CommandInfo { name: "sort-desc", command: Command::Lines(LineOp::SortDesc) },
+    CommandInfo { name: "sort-nocase", command: Command::Lines(LineOp::SortNoCase) },
+    CommandInfo { name: "sort-nocase-desc", command: Command::Lines(LineOp::SortNoCaseDesc) },
+    CommandInfo { name: "reverse", command: Command::Lines(LineOp::Reverse) },
+    CommandInfo { name: "dedup", command: Command::Lines(LineOp::Dedup) },
+    CommandInfo { name: "delete-line", command: Command::DeleteLine },
+    CommandInfo { name: "open-line-below", command: Command::OpenLineBelow },
+    CommandInfo { name: "open-line-above", command: Command::OpenLineAbove },
+    CommandInfo { name: "transpose-chars", command: Command::TransposeChars },
+    CommandInfo { name: "transpose-words", command: Command::TransposeWords },
+    CommandInfo { name: "reflow", command: Command::Reflow },
+    CommandInfo { name: "select-line", command: Command::SelectLine },
+    CommandInfo { name: "squeeze-blanks", command: Command::SqueezeBlanks },
+    CommandInfo { name: "delete-matching", command: Command::DeleteMatching },
+    CommandInfo { name: "indent", command: Command::SetIndent },
+    CommandInfo { name: "convert-lf", command: Command::LineEndings(LineEnding::Lf) },
+    CommandInfo { name: "convert-crlf", command: Command::LineEndings(LineEnding::Crlf) },
+];
+
+/// What to call `command` in the list of keys: its name in the palette or,
+/// for the editing keys that don't have one, a few words
+pub fn describe(command: Command) -> String {
+    if let Some(info) = COMMANDS.iter().find(|c| c.command == command) {
+        return info.name.to_string();
+    }
+
+    let text = match command {
+        Command::Move(motion, false) => return format!("move {}", motion.describe()),
+        Command::Move(motion, true) => return format!("select {}", motion.describe()),
+        Command::Newline => "new line",
+        Command::Backspace => "delete back",
+        Command::DeleteForward => "delete",
+        Command::Tab => "indent",
+        Command::Dedent => "dedent",
+        Command::Cancel => "cancel",
+        Command::Palette => "command palette",
+        Command::InsertMode => "insert mode",
+        Command::Append => "insert after cursor",
+        other => return format!("{:?}", other).to_lowercase(),
+    };
+
+    text.to_string()
+}
+
+/// Palette command names that `text` could be the start of
+pub fn completions(text: &str) -> Vec<String> {
+    COMMANDS.iter().map(|c| c.name).filter(|name| name.starts_with(text)).map(str::to_string).collect()
+}
+
+/// Split palette input into a command and the rest of the line. The name
+/// can be abbreviated to any prefix that only one command starts with.
+pub fn parse(input: &str) -> Result<(Command, &str), String> {
+    let input = input.trim();
+    let (name, arg) = input.split_once(char::is_whitespace).unwrap_or((input, ""));
+
+    if let Some(info) = COMMANDS.iter().find(|c| c.name == name) {
+        return Ok((info.command, arg.trim()));
+    }
+
+    let matches: Vec<&CommandInfo> = COMMANDS.iter().filter(|c| c.name.starts_with(name)).collect();
+    match matches.as_slice() {
+        [info] => Ok((info.command, arg.trim())),
+        [] => Err(format!("Unknown command '{}'", name)),
+        _ => {
+            let names: Vec<&str> = matches.iter().map(|c| c.name).collect();
+            Err(format!("'{}' could be {}", name, names.join(", ")))
+        }
+    }
+}
@@ -0,0 +1,82 @@
+// wfemto - A toy text editor
+// Written in 2025 by Dana Larose <ywg.dana@gmail.com>
+//
+// To the extent possible under law, the author(s) have dedicated all copyright
+// and related and neighboring rights to this software to the public domain
+// worldwide. This software is distributed without any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication along
+// with this software. If not,
+// see <http://creativecommons.org/publicdomain/zero/1.0/>.
+
+// Building from inside the editor: working out what to run, and picking the
+// file:line:col locations out of what the compiler said so the editor can
+// step through them. Both rustc's "  --> src/main.rs:3:5" and gcc's
+// "main.c:3:5: error: ..." forms are understood.
+
+use std::path::{Path, PathBuf};
+
+/// A place in a source file mentioned by the build output
+#[derive(Clone, Debug, PartialEq)]
+pub struct Location {
+    pub file: PathBuf,
+    pub line: usize, // 1-based, as compilers print them
+    pub col: usize,  // Also 1-based; 1 if the compiler didn't say
+    pub output_row: usize, // Where in the output it was mentioned
+}
+
+/// The command to build with and the directory to run it in. A configured
+/// command runs in the project root if there's a Cargo.toml above the file
+/// and otherwise beside the file; without one, cargo projects get
+/// `cargo build` and anything else gets nothing.
+pub fn build_command(file: &Path, configured: Option<&str>) -> Option<(String, PathBuf)> {
+    let file_dir = std::path::absolute(file).ok()?.parent()?.to_path_buf();
+    let cargo_root = file_dir.ancestors().find(|d| d.join("Cargo.toml").is_file()).map(Path::to_path_buf);
+
+    match (configured, cargo_root) {
+        (Some(command), root) => Some((command.to_string(), root.unwrap_or(file_dir))),
+        (None, Some(root)) => Some((String::from("cargo build"), root)),
+        (None, None) => None,
+    }
+}
+
+/// Every location mentioned in the output, in order, with relative paths
+/// taken to be relative to `dir` (where the build ran)
+pub fn parse_locations(output: &[String], dir: &Path) -> Vec<Location> {
+    let mut found = Vec::new();
+
+    for (row, line) in output.iter().enumerate() {
+        let text = line.trim_start();
+        let text = text.strip_prefix("--> ").or_else(|| text.strip_prefix("::: ")).unwrap_or(text);
+        let Some((file, line_no, col)) = parse_location(text) else {
+            continue;
+        };
+
+        let file = Path::new(file);
+        found.push(Location {
+            file: if file.is_relative() { dir.join(file) } else { file.to_path_buf() },
+            line: line_no,
+            col,
+            output_row: row,
+        });
+    }
+
+    found
+}
+
+/// "path:line:col..." or "path:line: ..." at the start of `text`
+fn parse_location(text: &str) -> Option<(&str, usize, usize)> {
+    let mut parts = text.splitn(4, ':');
+    let file = parts.next()?;
+    if file.is_empty() || file.contains(char::is_whitespace) {
+        return None;
+    }
+
+    let line = parts.next()?.parse::<usize>().ok().filter(|&n| n > 0)?;
+    let col = parts.next()
+        .and_then(|c| c.trim_end().parse::<usize>().ok())
+        .filter(|&n| n > 0)
+        .unwrap_or(1);
+
+    Some((file, line, col))
+}
@@ -0,0 +1,140 @@
+// wfemto - A toy text editor
+// Written in 2025 by Dana Larose <ywg.dana@gmail.com>
+//
+// To the extent possible under law, the author(s) have dedicated all copyright
+// and related and neighboring rights to this software to the public domain
+// worldwide. This software is distributed without any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication along
+// with this software. If not,
+// see <http://creativecommons.org/publicdomain/zero/1.0/>.
+
+// Word completion from the words already in the buffer, like Vim's Ctrl+N.
+// Candidates nearest the cursor come first, on the theory that the word you
+// want is the one you typed a moment ago. Also file names, for Tab in the
+// prompts that ask for one.
+
+use crate::text::is_word_char;
+
+/// Up to this many candidates are offered
+pub const MAX_CANDIDATES: usize = 50;
+
+/// The paths `text` could be the start of, in order, directories with a /
+/// after them. Hidden files only turn up once a . has been typed.
+pub fn paths(text: &str) -> Vec<String> {
+    let (dir, name) = match text.rfind('/') {
+        Some(i) => text.split_at(i + 1),
+        None => ("", text),
+    };
+    let Ok(entries) = std::fs::read_dir(if dir.is_empty() { "." } else { dir }) else {
+        return Vec::new();
+    };
+
+    let mut found: Vec<String> = entries.filter_map(Result::ok).filter_map(|entry| {
+        let file = entry.file_name().into_string().ok()?;
+        if !file.starts_with(name) || (file.starts_with('.') && !name.starts_with('.')) {
+            return None;
+        }
+        let slash = if entry.path().is_dir() { "/" } else { "" };
+        Some(format!("{}{}{}", dir, file, slash))
+    }).collect();
+    found.sort();
+    found.truncate(MAX_CANDIDATES);
+
+    found
+}
+
+/// The word being typed at (row, col) and the other words in the buffer that
+/// start with it. Returns the column the word starts at and the candidates,
+/// closest first. An empty prefix gets no candidates.
+pub fn candidates(lines: &[String], row: usize, col: usize) -> (usize, Vec<String>) {
+    let chars: Vec<char> = lines[row].chars().collect();
+    let col = col.min(chars.len());
+    let start = (0..col).rev()
+        .take_while(|&i| is_word_char(chars[i]))
+        .last()
+        .unwrap_or(col);
+    if start == col {
+        return (col, Vec::new());
+    }
+    let prefix: String = chars[start..col].iter().collect();
+
+    // (distance in lines, distance in columns, word)
+    let mut found: Vec<(usize, usize, String)> = Vec::new();
+    for (r, line) in lines.iter().enumerate() {
+        for (c, word) in words(line) {
+            if !word.starts_with(&prefix) || word == prefix || (r == row && c == start) {
+                continue;
+            }
+            found.push((r.abs_diff(row), c.abs_diff(start), word.to_string()));
+        }
+    }
+    found.sort();
+
+    let mut seen = std::collections::HashSet::new();
+    let words = found.into_iter()
+        .map(|(_, _, word)| word)
+        .filter(|word| seen.insert(word.clone()))
+        .take(MAX_CANDIDATES)
+        .collect();
+
+    (start, words)
+}
+
+/// The words in a line with the column each one starts at
+fn words(line: &str) -> Vec<(usize, &str)> {
+    let mut out = Vec::new();
+    let mut start: Option<(usize, usize)> = None; // (column, byte offset)
+
+    for (col, (i, c)) in line.char_indices().enumerate() {
+        match (start, is_word_char(c)) {
+            (None, true) => start = Some((col, i)),
+            (Some((word_col, from)), false) => {
+                out.push((word_col, &line[from..i]));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some((word_col, from)) = start {
+        out.push((word_col, &line[from..]));
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paths_in_a_directory() {
+        let dir = std::env::temp_dir().join(format!("wfemto-{}-paths", std::process::id()));
+        std::fs::create_dir_all(dir.join("alps")).unwrap();
+        std::fs::create_dir_all(dir.join(".config")).unwrap();
+        for file in ["alpha.txt", "beta.rs", ".hidden"] {
+            std::fs::write(dir.join(file), "").unwrap();
+        }
+        for n in 0..60 {
+            std::fs::write(dir.join("alps").join(format!("f{:02}", n)), "").unwrap();
+        }
+
+        let d = format!("{}/", dir.display());
+        let found = |text: &str| paths(&format!("{}{}", d, text));
+        let names = |names: &[&str]| -> Vec<String> { names.iter().map(|n| format!("{}{}", d, n)).collect() };
+        let (all, al, dot, hidden, none, missing, many) = (
+            found(""), found("al"), found("."), found(".h"), found("gamma"), found("missing/x"), found("alps/f"),
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        // Directories get their slash, and hidden files wait for a .
+        assert_eq!(all, names(&["alpha.txt", "alps/", "beta.rs"]));
+        assert_eq!(al, names(&["alpha.txt", "alps/"]));
+        assert_eq!(dot, names(&[".config/", ".hidden"]));
+        assert_eq!(hidden, names(&[".hidden"]));
+        assert!(none.is_empty());
+        assert!(missing.is_empty());
+        assert_eq!(many.len(), MAX_CANDIDATES);
+        assert_eq!(many[0], format!("{}alps/f00", d));
+    }
+}
@@ -0,0 +1,378 @@
+// wfemto - A toy text editor
+// Written in 2025 by Dana Larose <ywg.dana@gmail.com>
+//
+// To the extent possible under law, the author(s) have dedicated all copyright
+// and related and neighboring rights to this software to the public domain
+// worldwide. This software is distributed without any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication along
+// with this software. If not,
+// see <http://creativecommons.org/publicdomain/zero/1.0/>.
+
+// User settings, read from $XDG_CONFIG_HOME/wfemto/config (or
+// ~/.config/wfemto/config). The format is the usual INI-ish one:
+//
+//     # comment
+//     scroll_margin = 3
+//     tab_width = 8
+//     indent = spaces 2
+//     auto_indent = false
+//     log_level = debug
+//     formatter.py = black -q -
+//     date_format = %a %d %b %Y
+//     snippet.todo = TODO($date): $0
+//     todo_markers = NOTE BUG
+//     compile_command = make -k
+//     ignore_case = true
+//     paste_indent = true
+//     paste_confirm_lines = 5000
+//     paste_confirm_bytes = 500000
+//     keys = emacs
+//     modal = true
+//     persistent_undo = false
+//     highlight_word = false
+//     status_format = %f%m  %l:%c  %p%%%=%e %n
+//     status_bar = top
+//     status_bg = #303030
+//     status_fg = #e0e0e0
+//     column_guide = true
+//     line_numbers = relative
+//     minimap = true
+//     trim_trailing_whitespace = true
+//     fill_column = 80
+//     spell_check = false
+//     word_chars = -
+//     margin = 0
+//     vsync = false
+//     smooth_scroll = true
+//
+// Settings for one kind of file go in a section named for its extension, or
+// its whole name if it has none, after the global ones. These can set
+// tab_width, indent, auto_indent, trim_trailing_whitespace, fill_column,
+// auto_wrap, which breaks lines typed past the fill column, and word_chars,
+// chars besides letters, digits and _ that words are made of:
+//
+//     [filetype.md]
+//     indent = spaces 2
+//     trim_trailing_whitespace = false
+//     auto_wrap = true
+//
+//     [filetype.css]
+//     word_chars = -
+//
+//     [filetype.COMMIT_EDITMSG]
+//     fill_column = 72
+//     auto_wrap = true
+//
+// A missing file just means defaults; bad lines are logged and skipped.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::PathBuf;
+
+use crate::gutter::LineNumbers;
+use crate::indent::Indent;
+use crate::keymap::Profile;
+use crate::log::Level;
+use crate::statusline::StatusFormat;
+use crate::theme::Rgb;
+
+pub struct Config {
+    pub scroll_margin: usize,
+    pub tab_width: usize,
+    pub indent: Indent, // For files that don't make their own style obvious
+    pub auto_indent: bool,
+    pub log_level: Option<Level>,
+    pub formatters: HashMap<String, String>, // File extension -> command
+    pub date_format: String,
+    pub snippets: BTreeMap<String, String>,
+    pub todo_markers: Vec<String>, // Words picked out in comments
+    pub compile_command: Option<String>, // None means cargo build for cargo projects
+    pub ignore_case: bool, // When searching
+    pub paste_indent: bool, // Convert pasted indentation to the buffer's style
+    pub paste_confirm_lines: usize, // Ask before pasting this many lines or more (0 never asks)...
+    pub paste_confirm_bytes: usize, // ...or this many bytes
+    pub keys: Profile,
+    pub modal: bool, // vi-style Normal and Insert modes
+    pub persistent_undo: bool, // Keep undo history between sessions
+    pub highlight_word: bool, // Other occurrences of the word under the cursor
+    pub status_format: StatusFormat, // See statusline.rs
+    pub status_top: bool, // Status bar above the text rather than below
+    pub status_bg: Option<Rgb>, // Override the theme's status bar colours
+    pub status_fg: Option<Rgb>,
+    pub column_guide: bool, // Shade the cursor's column
+    pub line_numbers: LineNumbers,
+    pub minimap: bool, // The whole buffer in miniature down the right
+    pub trim_trailing_whitespace: bool, // On save
+    pub fill_column: usize, // Width reflowed paragraphs are filled to
+    pub auto_wrap: bool,    // Break lines typed past fill_column
+    pub word_chars: String, // Punctuation that counts as part of a word
+    pub spell_check: bool, // Underline unknown words in prose, comments and strings
+    pub margin_left: u32, // Pixels of blank space beside the text, before HiDPI scaling
+    pub margin_top: u32,  // ...and above it
+    pub smooth_scroll: bool, // PageUp and PageDown glide rather than jump
+    pub vsync: bool, // Pace drawing to the display's refresh rather than a timer
+    pub filetypes: HashMap<String, FileSettings>, // File extension -> its section
+}
+
+/// A [filetype.ext] section. Anything it doesn't set comes from the global
+/// settings.
+#[derive(Clone, Default)]
+pub struct FileSettings {
+    pub tab_width: Option<usize>,
+    pub indent: Option<Indent>,
+    pub auto_indent: Option<bool>,
+    pub trim_trailing_whitespace: Option<bool>,
+    pub fill_column: Option<usize>,
+    pub auto_wrap: Option<bool>,
+    pub word_chars: Option<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            scroll_margin: 5,
+            tab_width: 4,
+            indent: Indent::Spaces(4),
+            auto_indent: true,
+            log_level: None,
+            formatters: HashMap::from([
+                (String::from("rs"), String::from("rustfmt --emit stdout")),
+            ]),
+            date_format: String::from("%Y-%m-%d %H:%M"),
+            snippets: BTreeMap::new(),
+            todo_markers: ["TODO", "FIXME", "XXX", "HACK"].map(String::from).to_vec(),
+            compile_command: None,
+            ignore_case: false,
+            paste_indent: false,
+            paste_confirm_lines: 10_000,
+            paste_confirm_bytes: 1_000_000,
+            keys: Profile::Default,
+            modal: false,
+            persistent_undo: true,
+            highlight_word: true,
+            status_format: StatusFormat::default(),
+            status_top: false,
+            status_bg: None,
+            status_fg: None,
+            column_guide: false,
+            line_numbers: LineNumbers::Off,
+            minimap: false,
+            trim_trailing_whitespace: false,
+            fill_column: 72,
+            auto_wrap: false,
+            word_chars: String::new(),
+            spell_check: true,
+            margin_left: 10,
+            margin_top: 10,
+            smooth_scroll: false,
+            vsync: true,
+            filetypes: HashMap::new(),
+        }
+    }
+}
+
+pub fn config_dir() -> Option<PathBuf> {
+    match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => Some(PathBuf::from(dir).join("wfemto")),
+        _ => std::env::var_os("HOME")
+            .filter(|h| !h.is_empty())
+            .map(|h| PathBuf::from(h).join(".config").join("wfemto")),
+    }
+}
+
+pub fn config_path() -> Option<PathBuf> {
+    config_dir().map(|d| d.join("config"))
+}
+
+impl Config {
+    pub fn load() -> Config {
+        let Some(path) = config_path() else {
+            return Config::default();
+        };
+
+        match fs::read_to_string(&path) {
+            Ok(text) => {
+                log_info!("reading config from {}", path.display());
+                Config::parse(&text)
+            }
+            Err(_) => Config::default(),
+        }
+    }
+
+    pub fn parse(text: &str) -> Config {
+        let mut config = Config::default();
+        let mut section: Option<String> = None; // Extension of the [filetype.ext] being read
+        let mut skipping = false; // In a section that isn't one of those
+
+        for (n, raw) in text.lines().enumerate() {
+            let line = raw.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+                continue;
+            }
+
+            if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                section = name.trim().strip_prefix("filetype.").map(str::to_string);
+                skipping = section.is_none();
+                if skipping {
+                    log_warn!("config line {}: unknown section [{}]", n + 1, name);
+                }
+                continue;
+            }
+            if skipping {
+                continue;
+            }
+
+            let Some((key, value)) = line.split_once('=') else {
+                log_warn!("config line {}: expected key = value", n + 1);
+                continue;
+            };
+
+            let set = match &section {
+                Some(ext) => config.set_for_filetype(ext, key.trim(), value.trim()),
+                None => config.set(key.trim(), value.trim()),
+            };
+            if let Err(e) = set {
+                log_warn!("config line {}: {}", n + 1, e);
+            }
+        }
+
+        config
+    }
+
+    fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "scroll_margin" => self.scroll_margin = parse_usize(value)?,
+            "tab_width" => match parse_usize(value)? {
+                0 => return Err(String::from("tab_width must be at least 1")),
+                n => self.tab_width = n,
+            },
+            "indent" => self.indent = Indent::parse(value)?,
+            "auto_indent" => self.auto_indent = parse_bool(value)?,
+            "ignore_case" => self.ignore_case = parse_bool(value)?,
+            "paste_indent" => self.paste_indent = parse_bool(value)?,
+            "paste_confirm_lines" => self.paste_confirm_lines = parse_usize(value)?,
+            "paste_confirm_bytes" => self.paste_confirm_bytes = parse_usize(value)?,
+            "keys" => self.keys = Profile::parse(value)?,
+            "modal" => self.modal = parse_bool(value)?,
+            "persistent_undo" => self.persistent_undo = parse_bool(value)?,
+            "highlight_word" => self.highlight_word = parse_bool(value)?,
+            "status_format" => self.status_format = StatusFormat::parse(value),
+            "status_bar" => match value {
+                "top" => self.status_top = true,
+                "bottom" => self.status_top = false,
+                _ => return Err(format!("status_bar should be top or bottom, not '{}'", value)),
+            },
+            "status_bg" => self.status_bg = Some(Rgb::parse(value)?),
+            "status_fg" => self.status_fg = Some(Rgb::parse(value)?),
+            "column_guide" => self.column_guide = parse_bool(value)?,
+            "line_numbers" => self.line_numbers = LineNumbers::parse(value)?,
+            "minimap" => self.minimap = parse_bool(value)?,
+            "trim_trailing_whitespace" => self.trim_trailing_whitespace = parse_bool(value)?,
+            "fill_column" => self.fill_column = parse_fill_column(value)?,
+            "auto_wrap" => self.auto_wrap = parse_bool(value)?,
+            "word_chars" => self.word_chars = parse_word_chars(value),
+            "spell_check" => self.spell_check = parse_bool(value)?,
+            "margin" => {
+                let margin = parse_margin(value)?;
+                (self.margin_left, self.margin_top) = (margin, margin);
+            }
+            "margin_left" => self.margin_left = parse_margin(value)?,
+            "margin_top" => self.margin_top = parse_margin(value)?,
+            "smooth_scroll" => self.smooth_scroll = parse_bool(value)?,
+            "vsync" => self.vsync = parse_bool(value)?,
+            "log_level" => {
+                self.log_level = Some(Level::from_name(value)
+                    .ok_or_else(|| format!("unknown log level '{}'", value))?);
+            }
+            "date_format" => self.date_format = value.to_string(),
+            "compile_command" if value.is_empty() => self.compile_command = None,
+            "compile_command" => self.compile_command = Some(value.to_string()),
+            // Adds to the defaults rather than replacing them
+            "todo_markers" => {
+                for word in value.split([',', ' ']).filter(|w| !w.is_empty()) {
+                    if !self.todo_markers.iter().any(|m| m == word) {
+                        self.todo_markers.push(word.to_string());
+                    }
+                }
+            }
+            _ if key.starts_with("snippet.") => {
+                let name = key.trim_start_matches("snippet.").to_string();
+                self.snippets.insert(name, value.to_string());
+            }
+            _ if key.starts_with("formatter.") => {
+                let ext = key.trim_start_matches("formatter.").to_string();
+                if value.is_empty() {
+                    self.formatters.remove(&ext);
+                } else {
+                    self.formatters.insert(ext, value.to_string());
+                }
+            }
+            _ => return Err(format!("unknown setting '{}'", key)),
+        }
+
+        Ok(())
+    }
+
+    fn set_for_filetype(&mut self, ext: &str, key: &str, value: &str) -> Result<(), String> {
+        let settings = self.filetypes.entry(ext.to_string()).or_default();
+        match key {
+            "tab_width" => match parse_usize(value)? {
+                0 => return Err(String::from("tab_width must be at least 1")),
+                n => settings.tab_width = Some(n),
+            },
+            "indent" => settings.indent = Some(Indent::parse(value)?),
+            "auto_indent" => settings.auto_indent = Some(parse_bool(value)?),
+            "trim_trailing_whitespace" => settings.trim_trailing_whitespace = Some(parse_bool(value)?),
+            "fill_column" => settings.fill_column = Some(parse_fill_column(value)?),
+            "auto_wrap" => settings.auto_wrap = Some(parse_bool(value)?),
+            "word_chars" => settings.word_chars = Some(parse_word_chars(value)),
+            _ => return Err(format!("'{}' can't be set per filetype", key)),
+        }
+
+        Ok(())
+    }
+
+    /// The [filetype.ext] section for files named like `filename`
+    pub fn filetype(&self, filename: &str) -> Option<&FileSettings> {
+        self.filetypes.get(filetype_key(filename)?)
+    }
+}
+
+/// What a [filetype.x] section for `filename` is named: its extension, or
+/// for a file without one, like COMMIT_EDITMSG, its whole name
+pub fn filetype_key(filename: &str) -> Option<&str> {
+    let path = std::path::Path::new(filename);
+    path.extension().or_else(|| path.file_name())?.to_str()
+}
+
+fn parse_usize(value: &str) -> Result<usize, String> {
+    value.parse().map_err(|_| format!("expected a number, got '{}'", value))
+}
+
+/// The chars in `value`, spaces aside
+fn parse_word_chars(value: &str) -> String {
+    value.chars().filter(|c| !c.is_whitespace()).collect()
+}
+
+fn parse_fill_column(value: &str) -> Result<usize, String> {
+    match parse_usize(value)? {
+        0 => Err(String::from("fill_column must be at least 1")),
+        n => Ok(n),
+    }
+}
+
+fn parse_margin(value: &str) -> Result<u32, String> {
+    match parse_usize(value)? {
+        n if n > 100 => Err(format!("a margin of {} pixels is more than 100", n)),
+        n => Ok(n as u32),
+    }
+}
+
+fn parse_bool(value: &str) -> Result<bool, String> {
+    match value.to_lowercase().as_str() {
+        "true" | "yes" | "on" | "1" => Ok(true),
+        "false" | "no" | "off" | "0" => Ok(false),
+        _ => Err(format!("expected true or false, got '{}'", value)),
+    }
+}
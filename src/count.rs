@@ -0,0 +1,65 @@
+// wfemto - A toy text editor
+// Written in 2025 by Dana Larose <ywg.dana@gmail.com>
+//
+// To the extent possible under law, the author(s) have dedicated all copyright
+// and related and neighboring rights to this software to the public domain
+// worldwide. This software is distributed without any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication along
+// with this software. If not,
+// see <http://creativecommons.org/publicdomain/zero/1.0/>.
+
+// Line, word, char and byte counts, like wc. Lines and bytes are cheap to
+// get exactly, but counting words and chars in a huge buffer would stall the
+// window, so past COUNT_LIMIT bytes those two are scaled up from what was
+// counted and reported as estimates.
+
+const COUNT_LIMIT: usize = 4 * 1024 * 1024;
+
+pub struct Counts {
+    pub lines: usize,
+    pub words: usize,
+    pub chars: usize,
+    pub bytes: usize,
+    pub estimated: bool,
+}
+
+/// Count text held as lines, each of which is followed by a line break
+/// except (if `last_newline` is false) the final one.
+pub fn count<'a>(lines: impl ExactSizeIterator<Item = &'a str> + Clone, last_newline: bool) -> Counts {
+    let line_count = lines.len();
+    let breaks = if last_newline { line_count } else { line_count.saturating_sub(1) };
+    let bytes = lines.clone().map(str::len).sum::<usize>() + breaks;
+
+    let mut words = 0;
+    let mut chars = 0;
+    let mut scanned = 0;
+    for line in lines {
+        if scanned > COUNT_LIMIT {
+            break;
+        }
+        words += line.split_whitespace().count();
+        chars += line.chars().count() + 1;
+        scanned += line.len() + 1;
+    }
+
+    let estimated = scanned < bytes;
+    if !estimated {
+        // Every line was counted with a break after it
+        chars -= line_count - breaks;
+    } else {
+        let scale = bytes as f64 / scanned as f64;
+        words = (words as f64 * scale) as usize;
+        chars = (chars as f64 * scale) as usize;
+    }
+
+    Counts { lines: line_count, words, chars, bytes, estimated }
+}
+
+impl Counts {
+    pub fn describe(&self) -> String {
+        let approx = if self.estimated { "~" } else { "" };
+        format!("{} lines, {}{} words, {}{} chars, {} bytes",
+            self.lines, approx, self.words, approx, self.chars, self.bytes)
+    }
+}
@@ -0,0 +1,428 @@
+// wfemto - A toy text editor
+// Written in 2025 by Dana Larose <ywg.dana@gmail.com>
+//
+// To the extent possible under law, the author(s) have dedicated all copyright
+// and related and neighboring rights to this software to the public domain
+// worldwide. This software is distributed without any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication along
+// with this software. If not,
+// see <http://creativecommons.org/publicdomain/zero/1.0/>.
+
+// EditorConfig (https://editorconfig.org) support: find the .editorconfig
+// files above a file, parse them and work out which properties apply to it.
+// Only the properties wfemto can do something with are kept; anything else
+// is quietly ignored, as the spec asks.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum IndentStyle {
+    Tab,
+    Space,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EndOfLine {
+    Lf,
+    Crlf,
+    Cr,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Properties {
+    pub indent_style: Option<IndentStyle>,
+    pub indent_size: Option<usize>, // None with indent_size = tab means use tab_width
+    pub tab_width: Option<usize>,
+    pub end_of_line: Option<EndOfLine>,
+    pub trim_trailing_whitespace: Option<bool>,
+    pub insert_final_newline: Option<bool>,
+    pub charset: Option<String>,
+}
+
+pub struct Section {
+    pub pattern: String,
+    pub pairs: Vec<(String, String)>,
+}
+
+pub struct ConfigFile {
+    pub root: bool,
+    pub sections: Vec<Section>,
+}
+
+/// Parse the text of an .editorconfig file. Keys are lowercased, as are the
+/// values of the properties the spec says are case-insensitive.
+pub fn parse(text: &str) -> ConfigFile {
+    let mut file = ConfigFile { root: false, sections: Vec::new() };
+
+    for raw in text.lines() {
+        let line = raw.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+
+        if let Some(pattern) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            file.sections.push(Section { pattern: pattern.to_string(), pairs: Vec::new() });
+            continue;
+        }
+
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        let key = key.trim().to_lowercase();
+        let value = value.trim().to_string();
+
+        match file.sections.last_mut() {
+            Some(section) => section.pairs.push((key, value)),
+            None if key == "root" => file.root = value.eq_ignore_ascii_case("true"),
+            None => {}
+        }
+    }
+
+    file
+}
+
+impl Properties {
+    fn set(&mut self, key: &str, value: &str) {
+        let value = value.to_lowercase();
+        let number = value.parse::<usize>().ok().filter(|&n| n > 0);
+        let flag = match value.as_str() {
+            "true" => Some(true),
+            "false" => Some(false),
+            _ => None,
+        };
+
+        // "unset" resets a property to the editor's default
+        let unset = value == "unset";
+        match key {
+            "indent_style" => self.indent_style = match value.as_str() {
+                "tab" => Some(IndentStyle::Tab),
+                "space" => Some(IndentStyle::Space),
+                _ => None,
+            },
+            "indent_size" => self.indent_size = if value == "tab" { None } else { number },
+            "tab_width" => self.tab_width = number,
+            "end_of_line" => self.end_of_line = match value.as_str() {
+                "lf" => Some(EndOfLine::Lf),
+                "crlf" => Some(EndOfLine::Crlf),
+                "cr" => Some(EndOfLine::Cr),
+                _ => None,
+            },
+            "trim_trailing_whitespace" => self.trim_trailing_whitespace = flag,
+            "insert_final_newline" => self.insert_final_newline = flag,
+            "charset" => self.charset = if unset { None } else { Some(value) },
+            _ => {}
+        }
+    }
+}
+
+/// The properties for `path` from one parsed file living in `dir`
+fn apply_file(props: &mut Properties, file: &ConfigFile, dir: &Path, path: &Path) {
+    let Ok(relative) = path.strip_prefix(dir) else {
+        return;
+    };
+    let relative = relative.to_string_lossy().replace('\\', "/");
+
+    for section in &file.sections {
+        if glob_matches(&section.pattern, &relative) {
+            for (key, value) in &section.pairs {
+                props.set(key, value);
+            }
+        }
+    }
+}
+
+/// The properties that apply to the file at `path`, from every .editorconfig
+/// between it and the filesystem root (or the first one marked root = true).
+/// Closer files win.
+pub fn properties_for(path: &Path) -> Properties {
+    let path = absolute(path);
+    let mut found: Vec<(PathBuf, ConfigFile)> = Vec::new();
+
+    let mut dir = path.parent();
+    while let Some(d) = dir {
+        if let Ok(text) = fs::read_to_string(d.join(".editorconfig")) {
+            let file = parse(&text);
+            let root = file.root;
+            found.push((d.to_path_buf(), file));
+            if root {
+                break;
+            }
+        }
+        dir = d.parent();
+    }
+
+    let mut props = Properties::default();
+    for (dir, file) in found.iter().rev() {
+        apply_file(&mut props, file, dir, &path);
+    }
+
+    props
+}
+
+fn absolute(path: &Path) -> PathBuf {
+    if path.is_absolute() {
+        return path.to_path_buf();
+    }
+
+    std::env::current_dir().map(|cwd| cwd.join(path)).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Match an EditorConfig section glob against a path relative to the
+/// directory of the .editorconfig. A pattern with no slash in it matches the
+/// file name at any depth; one with a slash is anchored to that directory.
+pub fn glob_matches(pattern: &str, path: &str) -> bool {
+    let pattern = if pattern.contains('/') {
+        pattern.strip_prefix('/').unwrap_or(pattern).to_string()
+    } else {
+        format!("**/{}", pattern)
+    };
+
+    let path: Vec<char> = path.chars().collect();
+    expand_braces(&pattern).iter().any(|alt| {
+        let alt: Vec<char> = alt.chars().collect();
+        matches(&alt, &path)
+    })
+}
+
+/// Expand {a,b,c} alternatives and {1..3} number ranges into a list of plain
+/// globs. Braces that don't form either are left as literal text.
+fn expand_braces(pattern: &str) -> Vec<String> {
+    let Some(open) = pattern.find('{') else {
+        return vec![pattern.to_string()];
+    };
+    let Some(len) = matching_brace(&pattern[open..]) else {
+        return vec![pattern.to_string()];
+    };
+
+    let head = &pattern[..open];
+    let body = &pattern[open + 1..open + len];
+    let tail = &pattern[open + len + 1..];
+
+    let choices: Vec<String> = if let Some((lo, hi)) = body.split_once("..")
+        && let (Ok(lo), Ok(hi)) = (lo.parse::<i64>(), hi.parse::<i64>())
+        && hi.abs_diff(lo) < 1000
+    {
+        (lo.min(hi)..=lo.max(hi)).map(|n| n.to_string()).collect()
+    } else if body.contains(',') {
+        split_top_level(body)
+    } else {
+        // Not expanded again, or it would be found again forever
+        return expand_braces(tail).into_iter().map(|rest| format!("{}{{{}}}{}", head, body, rest)).collect();
+    };
+
+    let mut out = Vec::new();
+    for choice in choices {
+        for rest in expand_braces(tail) {
+            for expanded in expand_braces(&format!("{}{}", choice, rest)) {
+                out.push(format!("{}{}", head, expanded));
+            }
+        }
+    }
+
+    out
+}
+
+/// Byte offset of the } closing the { at the start of `s`
+fn matching_brace(s: &str) -> Option<usize> {
+    let mut depth = 0;
+    for (i, c) in s.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    None
+}
+
+/// Split on commas that aren't inside nested braces
+fn split_top_level(body: &str) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut depth = 0;
+    let mut current = String::new();
+    for c in body.chars() {
+        match c {
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(std::mem::take(&mut current));
+                continue;
+            }
+            _ => {}
+        }
+        current.push(c);
+    }
+    parts.push(current);
+
+    parts
+}
+
+fn matches(p: &[char], s: &[char]) -> bool {
+    match p.first() {
+        None => s.is_empty(),
+        Some('*') if p.get(1) == Some(&'*') => {
+            // **/ can also match no directories at all
+            if p.get(2) == Some(&'/') && matches(&p[3..], s) {
+                return true;
+            }
+            (0..=s.len()).any(|i| matches(&p[2..], &s[i..]))
+        }
+        Some('*') => {
+            for i in 0..=s.len() {
+                if matches(&p[1..], &s[i..]) {
+                    return true;
+                }
+                if i < s.len() && s[i] == '/' {
+                    break;
+                }
+            }
+            false
+        }
+        Some('?') => !s.is_empty() && s[0] != '/' && matches(&p[1..], &s[1..]),
+        Some('[') => match class_end(p) {
+            Some(end) => {
+                !s.is_empty() && s[0] != '/' && in_class(&p[1..end], s[0]) && matches(&p[end + 1..], &s[1..])
+            }
+            None => s.first() == Some(&'[') && matches(&p[1..], &s[1..]),
+        },
+        Some('\\') if p.len() > 1 => s.first() == Some(&p[1]) && matches(&p[2..], &s[1..]),
+        Some(c) => s.first() == Some(c) && matches(&p[1..], &s[1..]),
+    }
+}
+
+/// Index of the ] ending a [character class] that starts at p[0]
+fn class_end(p: &[char]) -> Option<usize> {
+    let first = if p.get(1) == Some(&'!') { 2 } else { 1 };
+    // A ] straight after the [ (or [!) is part of the class
+    (first + 1..p.len()).find(|&i| p[i] == ']')
+}
+
+fn in_class(class: &[char], c: char) -> bool {
+    let (negated, class) = match class.first() {
+        Some('!') => (true, &class[1..]),
+        _ => (false, class),
+    };
+
+    let mut found = false;
+    let mut i = 0;
+    while i < class.len() {
+        if i + 2 < class.len() && class[i + 1] == '-' {
+            found |= class[i] <= c && c <= class[i + 2];
+            i += 3;
+        } else {
+            found |= class[i] == c;
+            i += 1;
+        }
+    }
+
+    found != negated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn props(text: &str, path: &str) -> Properties {
+        let mut props = Properties::default();
+        apply_file(&mut props, &parse(text), Path::new("/project"), &Path::new("/project").join(path));
+        props
+    }
+
+    #[test]
+    fn parse_reads_root_sections_and_pairs() {
+        let file = parse("# comment\nROOT = True\n\n[*.rs]\n; another\nIndent_Style = space\n  indent_size=4  \n[Makefile]\nindent_style = tab\n");
+        assert!(file.root);
+        assert_eq!(file.sections.len(), 2);
+        assert_eq!(file.sections[0].pattern, "*.rs");
+        assert_eq!(file.sections[0].pairs, [
+            (String::from("indent_style"), String::from("space")),
+            (String::from("indent_size"), String::from("4")),
+        ]);
+        assert_eq!(file.sections[1].pattern, "Makefile");
+    }
+
+    #[test]
+    fn root_only_counts_before_the_first_section() {
+        assert!(!parse("[*]\nroot = true\n").root);
+        assert!(!parse("root = false\n").root);
+    }
+
+    #[test]
+    fn globs_without_a_slash_match_at_any_depth() {
+        assert!(glob_matches("*.rs", "main.rs"));
+        assert!(glob_matches("*.rs", "src/deep/main.rs"));
+        assert!(!glob_matches("*.rs", "main.rsx"));
+        assert!(glob_matches("Makefile", "sub/Makefile"));
+        assert!(glob_matches("*", "anything/at/all"));
+    }
+
+    #[test]
+    fn globs_with_a_slash_are_anchored() {
+        assert!(glob_matches("src/*.rs", "src/main.rs"));
+        assert!(!glob_matches("src/*.rs", "lib/src/main.rs"));
+        assert!(!glob_matches("src/*.rs", "src/deep/main.rs"));
+        assert!(glob_matches("/docs/**", "docs/a/b.md"));
+        assert!(glob_matches("src/**/*.rs", "src/main.rs"));
+        assert!(glob_matches("src/**/*.rs", "src/a/b/main.rs"));
+    }
+
+    #[test]
+    fn globs_expand_braces_ranges_and_classes() {
+        assert!(glob_matches("*.{js,ts}", "app.ts"));
+        assert!(!glob_matches("*.{js,ts}", "app.rs"));
+        assert!(glob_matches("{a,{b,c}}.txt", "c.txt"));
+        assert!(glob_matches("file{1..3}.txt", "file2.txt"));
+        assert!(!glob_matches("file{1..3}.txt", "file4.txt"));
+        assert!(glob_matches("[abc].md", "b.md"));
+        assert!(!glob_matches("[!abc].md", "b.md"));
+        assert!(glob_matches("[a-c]?.md", "cx.md"));
+        assert!(glob_matches("{single}.md", "{single}.md"));
+        assert!(glob_matches("\\*.md", "*.md"));
+        assert!(!glob_matches("\\*.md", "a.md"));
+    }
+
+    #[test]
+    fn later_sections_win() {
+        let text = "[*]\nindent_style = space\nindent_size = 2\n[*.py]\nindent_size = 4\n[*.txt]\nindent_size = 8\n";
+        let py = props(text, "app/main.py");
+        assert_eq!(py.indent_style, Some(IndentStyle::Space));
+        assert_eq!(py.indent_size, Some(4));
+        assert_eq!(props(text, "notes.md").indent_size, Some(2));
+    }
+
+    #[test]
+    fn values_are_read_case_insensitively_and_can_be_unset() {
+        let text = "[*]\nindent_style = TAB\nend_of_line = CRLF\ntrim_trailing_whitespace = True\ncharset = UTF-8\nindent_size = tab\n[*.txt]\ncharset = unset\nfoo = bar\n";
+        let rs = props(text, "main.rs");
+        assert_eq!(rs.indent_style, Some(IndentStyle::Tab));
+        assert_eq!(rs.end_of_line, Some(EndOfLine::Crlf));
+        assert_eq!(rs.trim_trailing_whitespace, Some(true));
+        assert_eq!(rs.charset.as_deref(), Some("utf-8"));
+        assert_eq!(rs.indent_size, None);
+        assert_eq!(props(text, "a.txt").charset, None);
+    }
+
+    #[test]
+    fn closer_files_win_up_to_a_root() {
+        let top = std::env::temp_dir().join(format!("wfemto-editorconfig-{}", std::process::id()));
+        let inner = top.join("inner");
+        fs::create_dir_all(&inner).unwrap();
+        fs::write(top.join(".editorconfig"), "[*]\nindent_size = 8\ntab_width = 3\n").unwrap();
+        fs::write(inner.join(".editorconfig"), "[*]\nindent_size = 2\n").unwrap();
+        let nested = properties_for(&inner.join("file.txt"));
+        fs::write(inner.join(".editorconfig"), "root = true\n[*]\nindent_size = 2\n").unwrap();
+        let rooted = properties_for(&inner.join("file.txt"));
+        fs::remove_dir_all(&top).unwrap();
+
+        assert_eq!((nested.indent_size, nested.tab_width), (Some(2), Some(3)));
+        assert_eq!((rooted.indent_size, rooted.tab_width), (Some(2), None));
+    }
+}
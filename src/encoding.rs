@@ -0,0 +1,104 @@
+// wfemto - A toy text editor
+// Written in 2025 by Dana Larose <ywg.dana@gmail.com>
+//
+// To the extent possible under law, the author(s) have dedicated all copyright
+// and related and neighboring rights to this software to the public domain
+// worldwide. This software is distributed without any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication along
+// with this software. If not,
+// see <http://creativecommons.org/publicdomain/zero/1.0/>.
+
+
+// The few text encodings besides UTF-8 a file can be read and written in.
+// Files are assumed to be UTF-8; reopening one with a different encoding is
+// for when that's wrong.
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Encoding {
+    Utf8,
+    Latin1,
+    Windows1252,
+    Utf16Le,
+    Utf16Be,
+}
+
+/// Windows-1252 for bytes 0x80 to 0x9F, where it differs from Latin-1. The
+/// five bytes it leaves undefined map to the C1 controls, as browsers do.
+const CP1252_HIGH: [char; 32] = [
+    '\u{20AC}', '\u{0081}', '\u{201A}', '\u{0192}', '\u{201E}', '\u{2026}', '\u{2020}', '\u{2021}',
+    '\u{02C6}', '\u{2030}', '\u{0160}', '\u{2039}', '\u{0152}', '\u{008D}', '\u{017D}', '\u{008F}',
+    '\u{0090}', '\u{2018}', '\u{2019}', '\u{201C}', '\u{201D}', '\u{2022}', '\u{2013}', '\u{2014}',
+    '\u{02DC}', '\u{2122}', '\u{0161}', '\u{203A}', '\u{0153}', '\u{009D}', '\u{017E}', '\u{0178}',
+];
+
+pub const NAMES: &str = "utf-8, latin-1, windows-1252, utf-16le, utf-16be";
+
+impl Encoding {
+    pub fn parse(name: &str) -> Result<Encoding, String> {
+        match name.trim().to_lowercase().replace('_', "-").as_str() {
+            "utf-8" | "utf8" => Ok(Encoding::Utf8),
+            "latin-1" | "latin1" | "iso-8859-1" => Ok(Encoding::Latin1),
+            "windows-1252" | "cp1252" => Ok(Encoding::Windows1252),
+            "utf-16le" | "utf-16" => Ok(Encoding::Utf16Le),
+            "utf-16be" => Ok(Encoding::Utf16Be),
+            _ => Err(format!("Unknown encoding '{}'; try {}", name.trim(), NAMES)),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Encoding::Utf8 => "UTF-8",
+            Encoding::Latin1 => "Latin-1",
+            Encoding::Windows1252 => "Windows-1252",
+            Encoding::Utf16Le => "UTF-16LE",
+            Encoding::Utf16Be => "UTF-16BE",
+        }
+    }
+
+    pub fn decode(self, bytes: Vec<u8>) -> Result<String, String> {
+        match self {
+            Encoding::Utf8 => String::from_utf8(bytes)
+                .map_err(|e| format!("not valid UTF-8 (at byte {})", e.utf8_error().valid_up_to())),
+            Encoding::Latin1 => Ok(bytes.iter().map(|&b| char::from(b)).collect()),
+            Encoding::Windows1252 => Ok(bytes.iter().map(|&b| match b {
+                0x80..=0x9F => CP1252_HIGH[(b - 0x80) as usize],
+                _ => char::from(b),
+            }).collect()),
+            Encoding::Utf16Le | Encoding::Utf16Be => {
+                if !bytes.len().is_multiple_of(2) {
+                    return Err(format!("an odd number of bytes can't be {}", self.name()));
+                }
+                let units = bytes.chunks_exact(2).map(|pair| match self {
+                    Encoding::Utf16Le => u16::from_le_bytes([pair[0], pair[1]]),
+                    _ => u16::from_be_bytes([pair[0], pair[1]]),
+                });
+                char::decode_utf16(units)
+                    .collect::<Result<String, _>>()
+                    .map_err(|e| format!("not valid {} (unpaired surrogate {:04X})", self.name(), e.unpaired_surrogate()))
+            }
+        }
+    }
+
+    /// The bytes for `text`, or the first char that can't be written in
+    /// this encoding
+    pub fn encode(self, text: &str) -> Result<Vec<u8>, char> {
+        match self {
+            Encoding::Utf8 => Ok(text.as_bytes().to_vec()),
+            Encoding::Latin1 => text.chars()
+                .map(|c| u8::try_from(u32::from(c)).map_err(|_| c))
+                .collect(),
+            Encoding::Windows1252 => text.chars()
+                .map(|c| match CP1252_HIGH.iter().position(|&h| h == c) {
+                    Some(i) => Ok(0x80 + i as u8),
+                    None => match u8::try_from(u32::from(c)) {
+                        Ok(b) if !(0x80..=0x9F).contains(&b) => Ok(b),
+                        _ => Err(c),
+                    },
+                })
+                .collect(),
+            Encoding::Utf16Le => Ok(text.encode_utf16().flat_map(u16::to_le_bytes).collect()),
+            Encoding::Utf16Be => Ok(text.encode_utf16().flat_map(u16::to_be_bytes).collect()),
+        }
+    }
+}
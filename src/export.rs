@@ -0,0 +1,86 @@
+// wfemto - A toy text editor
+// Written in 2025 by Dana Larose <ywg.dana@gmail.com>
+//
+// To the extent possible under law, the author(s) have dedicated all copyright
+// and related and neighboring rights to this software to the public domain
+// worldwide. This software is distributed without any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication along
+// with this software. If not,
+// see <http://creativecommons.org/publicdomain/zero/1.0/>.
+
+// Writing a buffer out as a standalone HTML page that looks like it does in
+// the editor: same highlighting, same colours, tabs expanded the same way.
+
+use std::fs;
+
+use crate::buffer::Buffer;
+use crate::highlight::{self, State, TokenKind};
+use crate::text::expand_tabs;
+use crate::theme::Theme;
+
+pub fn escape_html(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '&' => out.push_str("&amp;"),
+            '"' => out.push_str("&quot;"),
+            _ => out.push(c),
+        }
+    }
+
+    out
+}
+
+pub fn to_html(buf: &Buffer, theme: &Theme) -> String {
+    let mut html = String::new();
+    html.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+    html.push_str(&format!("<title>{}</title>\n</head>\n", escape_html(&buf.filename)));
+    html.push_str(&format!("<body style=\"background: {}\">\n", theme.background.hex()));
+    html.push_str(&format!(
+        "<pre style=\"color: {}; background: {}; font-family: 'DejaVu Sans Mono', monospace\">",
+        theme.foreground.hex(), theme.background.hex()));
+
+    let mut state = State::Normal;
+    for line in &buf.lines {
+        let spans = match buf.language {
+            Some(lang) => {
+                let (spans, next) = highlight::highlight_line(line, lang, state);
+                state = next;
+                spans
+            }
+            None => vec![highlight::Span {
+                start: 0,
+                end: usize::MAX,
+                kind: TokenKind::Text,
+            }],
+        };
+
+        for span in spans {
+            let text = escape_html(&expand_tabs(line, span.start, span.end, buf.tab_width));
+            if span.kind == TokenKind::Text {
+                html.push_str(&text);
+            } else {
+                html.push_str(&format!("<span style=\"color: {}\">{}</span>",
+                    theme.token_colour(span.kind).hex(), text));
+            }
+        }
+        html.push('\n');
+    }
+
+    html.push_str("</pre>\n</body>\n</html>\n");
+
+    html
+}
+
+/// Write the buffer as HTML next to its file (foo.rs becomes foo.rs.html)
+/// and return the path written.
+pub fn export_html(buf: &Buffer, theme: &Theme) -> Result<String, String> {
+    let path = format!("{}.html", buf.filename);
+    fs::write(&path, to_html(buf, theme)).map_err(|e| e.to_string())?;
+    log_info!("exported {} to {}", buf.filename, path);
+
+    Ok(path)
+}
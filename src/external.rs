@@ -0,0 +1,173 @@
+// wfemto - A toy text editor
+// Written in 2025 by Dana Larose <ywg.dana@gmail.com>
+//
+// To the extent possible under law, the author(s) have dedicated all copyright
+// and related and neighboring rights to this software to the public domain
+// worldwide. This software is distributed without any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication along
+// with this software. If not,
+// see <http://creativecommons.org/publicdomain/zero/1.0/>.
+
+// Running text through an external command (a formatter, `sort`, ...) without
+// freezing the window. The command runs under `sh -c` on a worker thread and
+// the main loop polls for the result each frame. A command that takes too
+// long is killed.
+
+use std::io::{Read, Write};
+use std::path::Path;
+use std::process::{Command, ExitStatus, Stdio};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+pub const FILTER_TIMEOUT: Duration = Duration::from_secs(10);
+pub const BUILD_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// What a command left behind once it exited
+pub struct Finished {
+    pub stdout: String,
+    pub stderr: String,
+    pub status: ExitStatus,
+}
+
+pub struct Filter {
+    pub command: String,
+    pub started: Instant,
+    rx: Receiver<Result<Finished, String>>,
+}
+
+impl Filter {
+    /// Start the shell command `command` with `input` on its stdin
+    pub fn spawn(command: &str, input: String, timeout: Duration) -> Filter {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c").arg(command);
+        log_info!("running filter: {}", command);
+
+        Filter::start(cmd, command.to_string(), input, timeout)
+    }
+
+    /// Run a program directly (no shell, so arguments need no quoting) in
+    /// the directory `dir`, with nothing on its stdin
+    pub fn spawn_program(program: &str, args: &[&str], dir: &Path, timeout: Duration) -> Filter {
+        let mut cmd = Command::new(program);
+        cmd.args(args).current_dir(dir);
+        let label = format!("{} {}", program, args.join(" "));
+        log_debug!("running {} in {}", label, dir.display());
+
+        Filter::start(cmd, label, String::new(), timeout)
+    }
+
+    /// Run a shell command in `dir` for its output, as a build is. stderr is
+    /// sent to stdout so the two stay interleaved as they were written.
+    pub fn spawn_build(command: &str, dir: &Path, timeout: Duration) -> Filter {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c").arg(format!("exec 2>&1\n{}", command)).current_dir(dir);
+        log_info!("running {} in {}", command, dir.display());
+
+        Filter::start(cmd, command.to_string(), String::new(), timeout)
+    }
+
+    fn start(cmd: Command, label: String, input: String, timeout: Duration) -> Filter {
+        let (tx, rx) = mpsc::channel();
+        let thread_label = label.clone();
+        thread::spawn(move || {
+            let _ = tx.send(run(cmd, &thread_label, input, timeout));
+        });
+
+        Filter {
+            command: label,
+            started: Instant::now(),
+            rx,
+        }
+    }
+
+    /// The command's stdout once it has exited successfully, or why it
+    /// didn't. None while it's still running.
+    pub fn poll(&self) -> Option<Result<String, String>> {
+        let finished = match self.poll_finished()? {
+            Ok(finished) => finished,
+            Err(e) => return Some(Err(e)),
+        };
+
+        if finished.status.success() {
+            return Some(Ok(finished.stdout));
+        }
+        Some(match finished.stderr.lines().map(str::trim).find(|l| !l.is_empty()) {
+            Some(first) => Err(first.to_string()),
+            None => Err(format!("exited with {}", finished.status)),
+        })
+    }
+
+    /// Everything the command wrote and how it exited, whether or not it
+    /// succeeded. An error means it couldn't be run or was killed.
+    pub fn poll_finished(&self) -> Option<Result<Finished, String>> {
+        match self.rx.try_recv() {
+            Ok(result) => Some(result),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(Err(String::from("filter thread died"))),
+        }
+    }
+}
+
+fn run(mut cmd: Command, command: &str, input: String, timeout: Duration) -> Result<Finished, String> {
+    let mut child = cmd
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| e.to_string())?;
+
+    // Feed stdin and drain stdout/stderr on their own threads so a command
+    // that writes a lot before reading everything can't deadlock with us.
+    let mut stdin = child.stdin.take().ok_or("no stdin")?;
+    thread::spawn(move || {
+        let _ = stdin.write_all(input.as_bytes());
+    });
+
+    let mut stdout = child.stdout.take().ok_or("no stdout")?;
+    let out_reader = thread::spawn(move || {
+        let mut bytes = Vec::new();
+        let _ = stdout.read_to_end(&mut bytes);
+        bytes
+    });
+
+    let mut stderr = child.stderr.take().ok_or("no stderr")?;
+    let err_reader = thread::spawn(move || {
+        let mut bytes = Vec::new();
+        let _ = stderr.read_to_end(&mut bytes);
+        bytes
+    });
+
+    let start = Instant::now();
+    let status = loop {
+        match child.try_wait().map_err(|e| e.to_string())? {
+            Some(status) => break status,
+            None if start.elapsed() > timeout => {
+                let _ = child.kill();
+                let _ = child.wait();
+                log_warn!("killed '{}' after {:?}", command, timeout);
+                return Err(format!("timed out after {:?}", timeout));
+            }
+            None => thread::sleep(Duration::from_millis(10)),
+        }
+    };
+
+    let out = out_reader.join().unwrap_or_default();
+    let err = err_reader.join().unwrap_or_default();
+
+    Ok(Finished {
+        stdout: String::from_utf8_lossy(&out).into_owned(),
+        stderr: String::from_utf8_lossy(&err).into_owned(),
+        status,
+    })
+}
+
+/// Split command output into buffer lines. The final newline ends the last
+/// line rather than starting a new empty one, to match how files are read.
+pub fn output_lines(output: &str) -> Vec<String> {
+    let output = output.strip_suffix('\n').unwrap_or(output);
+    output.split('\n')
+        .map(|l| l.strip_suffix('\r').unwrap_or(l).to_string())
+        .collect()
+}
@@ -0,0 +1,97 @@
+// wfemto - A toy text editor
+// Written in 2025 by Dana Larose <ywg.dana@gmail.com>
+//
+// To the extent possible under law, the author(s) have dedicated all copyright
+// and related and neighboring rights to this software to the public domain
+// worldwide. This software is distributed without any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication along
+// with this software. If not,
+// see <http://creativecommons.org/publicdomain/zero/1.0/>.
+
+// Gutter markers for lines that differ from the last commit. The diff comes
+// from `git diff -U0 HEAD` on the file as saved, run in the background; if
+// that fails for any reason (no git, not a repo, untracked file) the buffer
+// just doesn't get markers.
+
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum GitMark {
+    #[default]
+    Unchanged,
+    Added,
+    Modified,
+    Deleted, // lines were removed just above this one
+}
+
+/// The directory to run git in and the arguments for diffing `path`
+pub fn diff_command(path: &Path) -> Option<(PathBuf, Vec<String>)> {
+    let path = std::path::absolute(path).ok()?;
+    let dir = path.parent()?.to_path_buf();
+    let name = path.file_name()?.to_string_lossy().to_string();
+    let args = ["diff", "--no-color", "--no-ext-diff", "-U0", "HEAD", "--", &name];
+
+    Some((dir, args.iter().map(|a| a.to_string()).collect()))
+}
+
+/// Turn the output of `git diff -U0` into one mark per line. There's an extra
+/// entry past the last line for text deleted from the end of the file.
+pub fn parse(diff: &str, line_count: usize) -> Vec<GitMark> {
+    let mut marks = vec![GitMark::Unchanged; line_count + 1];
+
+    for line in diff.lines() {
+        let Some((old_count, new_start, new_count)) = parse_hunk_header(line) else {
+            continue;
+        };
+
+        // A deletion's hunk starts at the line before the gap
+        if new_count == 0 {
+            if let Some(mark) = marks.get_mut(new_start) {
+                *mark = GitMark::Deleted;
+            }
+            continue;
+        }
+
+        let first = new_start.saturating_sub(1);
+        for (i, row) in (first..first + new_count).enumerate() {
+            if let Some(mark) = marks.get_mut(row) {
+                *mark = if i < old_count { GitMark::Modified } else { GitMark::Added };
+            }
+        }
+    }
+
+    marks
+}
+
+/// "@@ -12,3 +12,4 @@ fn main()" -> (3, 12, 4). A missing count means 1.
+fn parse_hunk_header(line: &str) -> Option<(usize, usize, usize)> {
+    let rest = line.strip_prefix("@@ -")?;
+    let (old, rest) = rest.split_once(" +")?;
+    let (new, _) = rest.split_once(" @@")?;
+
+    let range = |r: &str| -> Option<(usize, usize)> {
+        match r.split_once(',') {
+            Some((start, count)) => Some((start.parse().ok()?, count.parse().ok()?)),
+            None => Some((r.parse().ok()?, 1)),
+        }
+    };
+    let (_, old_count) = range(old)?;
+    let (new_start, new_count) = range(new)?;
+
+    Some((old_count, new_start, new_count))
+}
+
+/// Keep the marks lined up when `old_len` lines at `row` are replaced by
+/// `new_len` lines. Lines that weren't there before count as added.
+pub fn splice(marks: &mut Vec<GitMark>, row: usize, old_len: usize, new_len: usize) {
+    if marks.is_empty() || row > marks.len() {
+        return;
+    }
+
+    let end = (row + old_len).min(marks.len());
+    let kept = old_len.min(new_len);
+    let mut replacement: Vec<GitMark> = marks[row..end].iter().take(kept).copied().collect();
+    replacement.resize(new_len, GitMark::Added);
+    marks.splice(row..end, replacement);
+}
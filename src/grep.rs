@@ -0,0 +1,191 @@
+// wfemto - A toy text editor
+// Written in 2025 by Dana Larose <ywg.dana@gmail.com>
+//
+// To the extent possible under law, the author(s) have dedicated all copyright
+// and related and neighboring rights to this software to the public domain
+// worldwide. This software is distributed without any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication along
+// with this software. If not,
+// see <http://creativecommons.org/publicdomain/zero/1.0/>.
+
+// Find in files: search every text file under a directory on a worker
+// thread, sending back "path:line: text" results a file at a time so they
+// can be shown while the search carries on. Version control directories,
+// build output and binary files are skipped.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+use std::time::Instant;
+
+use crate::search::Query;
+
+/// Directories never worth searching
+const SKIP_DIRS: &[&str] = &[".git", ".hg", ".svn", "target", "node_modules"];
+
+/// Matched lines longer than this are cut short in the results
+const MAX_RESULT_CHARS: usize = 200;
+
+/// Files with a NUL in their first this many bytes are taken to be binary
+const BINARY_SNIFF: usize = 8192;
+
+pub struct Grep {
+    pub pattern: String,
+    pub root: PathBuf,
+    pub started: Instant,
+    pub found: usize,
+    rx: Receiver<Vec<String>>,
+    cancel: Arc<AtomicBool>,
+}
+
+pub enum Progress {
+    Running(Vec<String>), // Results since the last poll
+    Done(Vec<String>),    // The last of them
+}
+
+impl Grep {
+    pub fn spawn(pattern: &str, root: &Path, ignore_case: bool) -> Grep {
+        let (tx, rx) = mpsc::channel();
+        let cancel = Arc::new(AtomicBool::new(false));
+
+        let needles = vec![pattern.to_string()];
+        let dir = root.to_path_buf();
+        let stop = Arc::clone(&cancel);
+        thread::spawn(move || {
+            let query = Query { needles: &needles, whole_word: false, ignore_case };
+            let mut pending = vec![dir.clone()];
+            while let Some(next) = pending.pop() {
+                if stop.load(Ordering::Relaxed) {
+                    return;
+                }
+                if next.is_dir() {
+                    pending.extend(dir_entries(&next).into_iter().rev());
+                } else if let Some(results) = search_file(&next, &dir, &query)
+                    && !results.is_empty()
+                    && tx.send(results).is_err()
+                {
+                    return;
+                }
+            }
+        });
+
+        log_info!("searching {} for '{}'", root.display(), pattern);
+
+        Grep {
+            pattern: pattern.to_string(),
+            root: root.to_path_buf(),
+            started: Instant::now(),
+            found: 0,
+            rx,
+            cancel,
+        }
+    }
+
+    /// Pick up whatever the worker has found so far
+    pub fn poll(&mut self) -> Progress {
+        let mut results = Vec::new();
+        loop {
+            match self.rx.try_recv() {
+                Ok(batch) => results.extend(batch),
+                Err(TryRecvError::Empty) => {
+                    self.found += results.len();
+                    return Progress::Running(results);
+                }
+                Err(TryRecvError::Disconnected) => {
+                    self.found += results.len();
+                    return Progress::Done(results);
+                }
+            }
+        }
+    }
+
+    pub fn cancel(&self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+}
+
+/// The entries of `dir` worth looking at, sorted so results come out in a
+/// predictable order. Symlinks aren't followed, so there are no loops.
+fn dir_entries(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+
+    let mut paths: Vec<PathBuf> = entries.flatten()
+        .filter(|e| e.file_type().is_ok_and(|t| t.is_file() || t.is_dir()))
+        .filter(|e| !SKIP_DIRS.iter().any(|skip| e.file_name() == *skip))
+        .map(|e| e.path())
+        .collect();
+    paths.sort();
+
+    paths
+}
+
+/// The result lines for one file, or None if it isn't text
+fn search_file(path: &Path, root: &Path, query: &Query) -> Option<Vec<String>> {
+    let bytes = fs::read(path).ok()?;
+    if bytes[..bytes.len().min(BINARY_SNIFF)].contains(&0) {
+        return None;
+    }
+    let text = String::from_utf8(bytes).ok()?;
+
+    let name = path.strip_prefix(root).unwrap_or(path).display().to_string();
+    let results = text.lines()
+        .enumerate()
+        .filter(|(_, line)| query.is_match(line))
+        .map(|(i, line)| {
+            let mut shown: String = line.chars().take(MAX_RESULT_CHARS).collect();
+            if shown.len() < line.len() {
+                shown.push('…');
+            }
+            format!("{}:{}: {}", name, i + 1, shown.trim_end_matches('\r'))
+        })
+        .collect();
+
+    Some(results)
+}
+
+/// The file and (1-based) line a result line points at. Results are
+/// "name:N: text", so the name runs up to the first :N: and may have colons
+/// of its own.
+pub fn parse_result(line: &str) -> Option<(&str, usize)> {
+    line.match_indices(':').find_map(|(i, _)| {
+        let rest = &line[i + 1..];
+        let digits = rest.len() - rest.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+        let number = rest[..digits].parse::<usize>().ok().filter(|&n| n > 0)?;
+        rest[digits..].starts_with(": ").then_some((&line[..i], number)).filter(|_| i > 0)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn results_give_their_file_and_line() {
+        assert_eq!(parse_result("src/main.rs:12: fn main() {"), Some(("src/main.rs", 12)));
+        assert_eq!(parse_result("notes.txt:3: "), Some(("notes.txt", 3)));
+        assert_eq!(parse_result("a.rs:7: let s = \"x:9: y\";"), Some(("a.rs", 7)));
+    }
+
+    #[test]
+    fn file_names_can_have_colons() {
+        assert_eq!(parse_result("C:/work/a.rs:4: x"), Some(("C:/work/a.rs", 4)));
+        assert_eq!(parse_result("12:30 notes.txt:5: lunch"), Some(("12:30 notes.txt", 5)));
+    }
+
+    #[test]
+    fn other_lines_are_not_results() {
+        assert_eq!(parse_result("3 matches for ':0: ' in /tmp"), None);
+        assert_eq!(parse_result("Searching /tmp for 'a:0: b'"), None);
+        assert_eq!(parse_result("a.rs:0: x"), None);
+        assert_eq!(parse_result(":4: x"), None);
+        assert_eq!(parse_result("a.rs:4:x"), None);
+        assert_eq!(parse_result("a.rs:x: y"), None);
+        assert_eq!(parse_result(""), None);
+    }
+}
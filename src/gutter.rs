@@ -0,0 +1,75 @@
+// wfemto - A toy text editor
+// Written in 2025 by Dana Larose <ywg.dana@gmail.com>
+//
+// To the extent possible under law, the author(s) have dedicated all copyright
+// and related and neighboring rights to this software to the public domain
+// worldwide. This software is distributed without any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication along
+// with this software. If not,
+// see <http://creativecommons.org/publicdomain/zero/1.0/>.
+
+
+// Line numbers down the left of the text. Relative numbers count lines away
+// from the cursor, which is what a repeat count before a movement wants.
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LineNumbers {
+    Off,
+    Absolute,
+    Relative,
+    Both, // Relative, but the cursor's line shows its own number
+}
+
+impl LineNumbers {
+    pub fn parse(s: &str) -> Result<LineNumbers, String> {
+        match s {
+            "off" | "false" => Ok(LineNumbers::Off),
+            "absolute" | "on" | "true" => Ok(LineNumbers::Absolute),
+            "relative" => Ok(LineNumbers::Relative),
+            "both" => Ok(LineNumbers::Both),
+            _ => Err(format!("line numbers should be off, absolute, relative or both, not '{}'", s)),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            LineNumbers::Off => "off",
+            LineNumbers::Absolute => "absolute",
+            LineNumbers::Relative => "relative",
+            LineNumbers::Both => "both",
+        }
+    }
+
+    /// The next one along, for the command that cycles through them
+    pub fn next(self) -> LineNumbers {
+        match self {
+            LineNumbers::Off => LineNumbers::Absolute,
+            LineNumbers::Absolute => LineNumbers::Relative,
+            LineNumbers::Relative => LineNumbers::Both,
+            LineNumbers::Both => LineNumbers::Off,
+        }
+    }
+
+    /// Columns the gutter takes for a buffer `line_count` lines long shown
+    /// `rows` at a time, including the gap before the text
+    pub fn width(self, line_count: usize, rows: usize) -> usize {
+        let digits = |n: usize| n.max(1).to_string().len();
+        match self {
+            LineNumbers::Off => 0,
+            LineNumbers::Absolute => digits(line_count) + 1,
+            LineNumbers::Relative => digits(rows) + 1,
+            LineNumbers::Both => digits(line_count).max(digits(rows)) + 1,
+        }
+    }
+
+    /// What the gutter shows beside `row` with the cursor on `cursor_row`
+    pub fn label(self, row: usize, cursor_row: usize) -> String {
+        match self {
+            LineNumbers::Off => String::new(),
+            LineNumbers::Absolute => (row + 1).to_string(),
+            LineNumbers::Both if row == cursor_row => (row + 1).to_string(),
+            LineNumbers::Relative | LineNumbers::Both => row.abs_diff(cursor_row).to_string(),
+        }
+    }
+}
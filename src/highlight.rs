@@ -0,0 +1,384 @@
+// wfemto - A toy text editor
+// Written in 2025 by Dana Larose <ywg.dana@gmail.com>
+//
+// To the extent possible under law, the author(s) have dedicated all copyright
+// and related and neighboring rights to this software to the public domain
+// worldwide. This software is distributed without any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication along
+// with this software. If not,
+// see <http://creativecommons.org/publicdomain/zero/1.0/>.
+
+// A deliberately simple syntax highlighter: keywords, types, numbers, strings
+// and comments, driven by a small table of languages. It knows nothing about
+// SDL; it just splits a line into spans, so the screen and the HTML export
+// colour text exactly the same way.
+//
+// The only thing carried from one line to the next is whether it ended
+// inside a block comment, a string or a Markdown code block, so a buffer can
+// cache that state per line and re-highlight any visible line on its own.
+
+use crate::outline;
+use crate::search::Query;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TokenKind {
+    Text,
+    Keyword,
+    Type,
+    Number,
+    String,
+    Comment,
+    Marker, // TODO and friends
+}
+
+/// Columns `start..end` of a line (in chars) and what they are
+#[derive(Clone, Copy, Debug)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub kind: TokenKind,
+}
+
+/// Where a line starts: normally, or partway through a construct that
+/// began on an earlier line.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum State {
+    #[default]
+    Normal,
+    BlockComment,
+    Str(char),
+    Fence, // Inside a Markdown code block
+}
+
+pub struct Language {
+    pub name: &'static str,
+    pub extensions: &'static [&'static str],
+    pub keywords: &'static [&'static str],
+    pub types: &'static [&'static str],
+    pub line_comment: Option<&'static str>,
+    pub block_comment: Option<(&'static str, &'static str)>,
+    pub quotes: &'static [char],
+    pub char_literals: bool, // 'x' is a char, but a lone ' isn't a string (Rust lifetimes)
+    pub multiline_strings: bool,
+    pub markdown: bool,   // # headings and ``` code blocks take up whole lines
+}
+
+pub static LANGUAGES: &[Language] = &[
+    Language {
+        name: "Rust",
+        extensions: &["rs"],
+        keywords: &[
+            "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else",
+            "enum", "extern", "false", "fn", "for", "if", "impl", "in", "let", "loop",
+            "match", "mod", "move", "mut", "pub", "ref", "return", "self", "Self",
+            "static", "struct", "super", "trait", "true", "type", "unsafe", "use",
+            "where", "while",
+        ],
+        types: &[
+            "bool", "char", "f32", "f64", "i8", "i16", "i32", "i64", "i128", "isize",
+            "u8", "u16", "u32", "u64", "u128", "usize", "str", "String", "Vec",
+            "Option", "Result", "Box", "Some", "None", "Ok", "Err",
+        ],
+        line_comment: Some("//"),
+        block_comment: Some(("/*", "*/")),
+        quotes: &['"'],
+        char_literals: true,
+        multiline_strings: true,
+        markdown: false,
+    },
+    Language {
+        name: "C",
+        extensions: &["c", "h", "cc", "cpp", "cxx", "hpp", "hh"],
+        keywords: &[
+            "break", "case", "class", "const", "continue", "default", "delete", "do",
+            "else", "enum", "extern", "for", "goto", "if", "inline", "namespace", "new",
+            "private", "protected", "public", "return", "sizeof", "static", "struct",
+            "switch", "template", "typedef", "union", "using", "virtual", "volatile",
+            "while", "true", "false", "nullptr", "NULL",
+        ],
+        types: &[
+            "auto", "bool", "char", "double", "float", "int", "long", "short", "signed",
+            "unsigned", "void", "size_t", "int8_t", "int16_t", "int32_t", "int64_t",
+            "uint8_t", "uint16_t", "uint32_t", "uint64_t",
+        ],
+        line_comment: Some("//"),
+        block_comment: Some(("/*", "*/")),
+        quotes: &['"'],
+        char_literals: true,
+        multiline_strings: false,
+        markdown: false,
+    },
+    Language {
+        name: "Python",
+        extensions: &["py"],
+        keywords: &[
+            "and", "as", "assert", "async", "await", "break", "class", "continue",
+            "def", "del", "elif", "else", "except", "False", "finally", "for", "from",
+            "global", "if", "import", "in", "is", "lambda", "None", "nonlocal", "not",
+            "or", "pass", "raise", "return", "True", "try", "while", "with", "yield",
+        ],
+        types: &["int", "float", "str", "bytes", "list", "dict", "set", "tuple", "bool"],
+        line_comment: Some("#"),
+        block_comment: None,
+        quotes: &['"', '\''],
+        char_literals: false,
+        multiline_strings: false,
+        markdown: false,
+    },
+    Language {
+        name: "Shell",
+        extensions: &["sh", "bash"],
+        keywords: &[
+            "case", "do", "done", "elif", "else", "esac", "export", "fi", "for",
+            "function", "if", "in", "local", "return", "then", "until", "while",
+        ],
+        types: &[],
+        line_comment: Some("#"),
+        block_comment: None,
+        quotes: &['"', '\''],
+        char_literals: false,
+        multiline_strings: true,
+        markdown: false,
+    },
+    Language {
+        name: "Config",
+        extensions: &["toml", "ini", "cfg", "conf", "yaml", "yml"],
+        keywords: &["true", "false"],
+        types: &[],
+        line_comment: Some("#"),
+        block_comment: None,
+        quotes: &['"', '\''],
+        char_literals: false,
+        multiline_strings: false,
+        markdown: false,
+    },
+    Language {
+        name: "Markdown",
+        extensions: &["md", "markdown"],
+        keywords: &[],
+        types: &[],
+        line_comment: None,
+        block_comment: Some(("<!--", "-->")),
+        quotes: &[],
+        char_literals: false,
+        multiline_strings: false,
+        markdown: true,
+    },
+];
+
+/// Pick a language from a filename's extension
+pub fn language_for(filename: &str) -> Option<&'static Language> {
+    let ext = std::path::Path::new(filename).extension()?.to_str()?;
+    LANGUAGES.iter().find(|lang| lang.extensions.contains(&ext))
+}
+
+fn starts_at(chars: &[char], i: usize, pat: &str) -> bool {
+    (i..).zip(pat.chars()).all(|(j, p)| chars.get(j) == Some(&p))
+}
+
+/// Index just past the closing quote (or the end of the line if there isn't
+/// one), skipping backslash escapes.
+fn end_of_string(chars: &[char], mut i: usize, quote: char) -> (usize, bool) {
+    while i < chars.len() {
+        if chars[i] == '\\' {
+            i += 2;
+        } else if chars[i] == quote {
+            return (i + 1, true);
+        } else {
+            i += 1;
+        }
+    }
+
+    (chars.len(), false)
+}
+
+/// Length of a char literal starting at i ('x' or '\n'), if there is one
+fn char_literal_len(chars: &[char], i: usize) -> Option<usize> {
+    if chars.get(i + 1) == Some(&'\\') {
+        (i + 3..(i + 12).min(chars.len()))
+            .find(|&j| chars[j] == '\'')
+            .map(|j| j + 1 - i)
+    } else if chars.get(i + 2) == Some(&'\'') {
+        Some(3)
+    } else {
+        None
+    }
+}
+
+/// Split a line into spans, given the state the previous line ended in.
+/// Returns the spans (covering the whole line, in order) and the state the
+/// next line starts in.
+pub fn highlight_line(line: &str, lang: &Language, state: State) -> (Vec<Span>, State) {
+    if lang.markdown
+        && let Some(whole) = markdown_line(line, state)
+    {
+        return whole;
+    }
+
+    let chars: Vec<char> = line.chars().collect();
+    let mut spans: Vec<Span> = Vec::new();
+    let mut push = |start: usize, end: usize, kind: TokenKind| {
+        if start >= end {
+            return;
+        }
+        match spans.last_mut() {
+            Some(last) if last.kind == kind && last.end == start => last.end = end,
+            _ => spans.push(Span { start, end, kind }),
+        }
+    };
+
+    let mut state = state;
+    let mut i = 0;
+    while i < chars.len() {
+        match state {
+            State::BlockComment => {
+                let close = lang.block_comment.map(|(_, close)| close).unwrap_or("");
+                let end = (i..chars.len())
+                    .find(|&j| starts_at(&chars, j, close))
+                    .map(|j| j + close.chars().count());
+                match end {
+                    Some(end) => {
+                        push(i, end, TokenKind::Comment);
+                        state = State::Normal;
+                        i = end;
+                    }
+                    None => {
+                        push(i, chars.len(), TokenKind::Comment);
+                        i = chars.len();
+                    }
+                }
+                continue;
+            }
+            State::Str(quote) => {
+                let (end, closed) = end_of_string(&chars, i, quote);
+                push(i, end, TokenKind::String);
+                if closed || !lang.multiline_strings {
+                    state = State::Normal;
+                }
+                i = end;
+                continue;
+            }
+            State::Normal | State::Fence => {}
+        }
+
+        let c = chars[i];
+        if let Some(marker) = lang.line_comment
+            && starts_at(&chars, i, marker)
+        {
+            push(i, chars.len(), TokenKind::Comment);
+            break;
+        }
+
+        if let Some((open, _)) = lang.block_comment
+            && starts_at(&chars, i, open)
+        {
+            let len = open.chars().count();
+            push(i, i + len, TokenKind::Comment);
+            state = State::BlockComment;
+            i += len;
+            continue;
+        }
+
+        if lang.quotes.contains(&c) {
+            push(i, i + 1, TokenKind::String);
+            state = State::Str(c);
+            i += 1;
+            continue;
+        }
+
+        if lang.char_literals && c == '\'' {
+            if let Some(len) = char_literal_len(&chars, i) {
+                push(i, i + len, TokenKind::String);
+                i += len;
+            } else {
+                push(i, i + 1, TokenKind::Text);
+                i += 1;
+            }
+            continue;
+        }
+
+        if c.is_alphanumeric() || c == '_' {
+            let start = i;
+            let number = c.is_ascii_digit();
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_'
+                || (number && chars[i] == '.' && chars.get(i + 1).is_some_and(|d| d.is_ascii_digit())))
+            {
+                i += 1;
+            }
+            let word: String = chars[start..i].iter().collect();
+            let kind = if number {
+                TokenKind::Number
+            } else if lang.keywords.contains(&word.as_str()) {
+                TokenKind::Keyword
+            } else if lang.types.contains(&word.as_str()) {
+                TokenKind::Type
+            } else {
+                TokenKind::Text
+            };
+            push(start, i, kind);
+            continue;
+        }
+
+        push(i, i + 1, TokenKind::Text);
+        i += 1;
+    }
+
+    // A string that runs off the end of a line only carries on to the next
+    // line in languages that allow it.
+    if let State::Str(_) = state
+        && !lang.multiline_strings
+    {
+        state = State::Normal;
+    }
+
+    (spans, state)
+}
+
+/// A Markdown line that's all one thing: a heading, a code fence or a line
+/// of code inside one
+fn markdown_line(line: &str, state: State) -> Option<(Vec<Span>, State)> {
+    let fence = outline::is_fence(line);
+    let (kind, next) = match state {
+        State::Fence if fence => (TokenKind::String, State::Normal),
+        State::Fence => (TokenKind::String, State::Fence),
+        State::Normal if fence => (TokenKind::String, State::Fence),
+        State::Normal if outline::heading(line).is_some() => (TokenKind::Keyword, State::Normal),
+        _ => return None,
+    };
+    let end = line.chars().count();
+    let spans = if end > 0 { vec![Span { start: 0, end, kind }] } else { Vec::new() };
+
+    Some((spans, next))
+}
+
+/// Pick out the TODO-style markers inside spans of kind `within` (comments,
+/// or plain text when there's no highlighting)
+pub fn with_markers(line: &str, spans: Vec<Span>, markers: &Query, within: TokenKind) -> Vec<Span> {
+    let chars: Vec<char> = line.chars().collect();
+    let mut out = Vec::with_capacity(spans.len());
+
+    for span in spans {
+        if span.kind != within {
+            out.push(span);
+            continue;
+        }
+
+        let end = span.end.min(chars.len());
+        let mut pos = span.start;
+        while let Some((col, len)) = markers.find_in_line(&chars, pos)
+            && col + len <= end
+        {
+            if col > pos {
+                out.push(Span { start: pos, end: col, kind: span.kind });
+            }
+            out.push(Span { start: col, end: col + len, kind: TokenKind::Marker });
+            pos = col + len;
+        }
+        if pos < span.end {
+            out.push(Span { start: pos, end: span.end, kind: span.kind });
+        }
+    }
+
+    out
+}
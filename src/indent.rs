@@ -0,0 +1,136 @@
+// wfemto - A toy text editor
+// Written in 2025 by Dana Larose <ywg.dana@gmail.com>
+//
+// To the extent possible under law, the author(s) have dedicated all copyright
+// and related and neighboring rights to this software to the public domain
+// worldwide. This software is distributed without any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication along
+// with this software. If not,
+// see <http://creativecommons.org/publicdomain/zero/1.0/>.
+
+// How a buffer is indented, and guessing that from its contents so that Tab
+// in a file indented with two spaces inserts two spaces.
+
+use crate::text::display_col;
+
+const DETECT_LINES: usize = 500;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Indent {
+    Tabs,
+    Spaces(usize),
+}
+
+impl Indent {
+    /// "tabs", "spaces 2" or just "2"
+    pub fn parse(text: &str) -> Result<Indent, String> {
+        let text = text.trim().to_lowercase();
+        if text == "tab" || text == "tabs" {
+            return Ok(Indent::Tabs);
+        }
+
+        let width = text.trim_start_matches("spaces").trim();
+        match width.parse::<usize>() {
+            Ok(n) if (1..=16).contains(&n) => Ok(Indent::Spaces(n)),
+            _ => Err(format!("expected 'tabs' or a width from 1 to 16, got '{}'", text)),
+        }
+    }
+
+    pub fn describe(self) -> String {
+        match self {
+            Indent::Tabs => String::from("Tabs"),
+            Indent::Spaces(n) => format!("Spaces: {}", n),
+        }
+    }
+
+    /// What pressing Tab inserts with the cursor at screen column `x`: a tab,
+    /// or enough spaces to reach the next indent stop.
+    pub fn fill_from(self, x: usize) -> String {
+        match self {
+            Indent::Tabs => String::from("\t"),
+            Indent::Spaces(n) => " ".repeat(n - x % n),
+        }
+    }
+
+    /// One level of indentation
+    pub fn unit(self) -> String {
+        self.fill_from(0)
+    }
+}
+
+/// The leading whitespace of a line
+pub fn leading(line: &str) -> &str {
+    let end = line.len() - line.trim_start_matches([' ', '\t']).len();
+    &line[..end]
+}
+
+/// `line` with its leading whitespace redone in the style `indent`. Going to
+/// spaces each tab becomes one level; going to tabs each `tab_width` columns
+/// become a tab, with any odd spaces left over.
+pub fn convert_leading(line: &str, indent: Indent, tab_width: usize) -> String {
+    let lead = leading(line);
+    let new_lead = match indent {
+        Indent::Spaces(n) => lead.chars().map(|c| if c == '\t' { " ".repeat(n) } else { String::from(" ") }).collect(),
+        Indent::Tabs => {
+            let tab_width = tab_width.max(1);
+            let width = display_col(lead, usize::MAX, tab_width);
+            "\t".repeat(width / tab_width) + &" ".repeat(width % tab_width)
+        }
+    };
+
+    new_lead + &line[lead.len()..]
+}
+
+/// Guess the indentation from the start of a file. Lines indented with tabs
+/// are counted against lines indented with spaces; for spaces the width is
+/// the most common step in indentation between one line and the next.
+/// Returns None if there's nothing indented to go on.
+pub fn detect(lines: &[String]) -> Option<Indent> {
+    let mut tab_lines = 0;
+    let mut space_lines = 0;
+    let mut steps = [0usize; 9];
+    let mut previous = 0;
+
+    for line in lines.iter().take(DETECT_LINES) {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let lead = leading(line);
+        if lead.starts_with('\t') {
+            tab_lines += 1;
+            continue;
+        }
+
+        // The " * " inside C-style block comments isn't real indentation
+        let width = lead.len();
+        if line[width..].starts_with('*') {
+            continue;
+        }
+
+        if width > 0 {
+            space_lines += 1;
+        }
+        let step = width.abs_diff(previous);
+        if (1..steps.len()).contains(&step) {
+            steps[step] += 1;
+        }
+        previous = width;
+    }
+
+    if tab_lines == 0 && space_lines == 0 {
+        return None;
+    }
+    if tab_lines > space_lines {
+        return Some(Indent::Tabs);
+    }
+
+    // max_by_key picks the last of equals, so ties go to the wider indent
+    let (width, count) = steps.iter().enumerate().max_by_key(|&(_, count)| *count)?;
+    if *count == 0 {
+        return None;
+    }
+
+    Some(Indent::Spaces(width))
+}
@@ -0,0 +1,99 @@
+// wfemto - A toy text editor
+// Written in 2025 by Dana Larose <ywg.dana@gmail.com>
+//
+// To the extent possible under law, the author(s) have dedicated all copyright
+// and related and neighboring rights to this software to the public domain
+// worldwide. This software is distributed without any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication along
+// with this software. If not,
+// see <http://creativecommons.org/publicdomain/zero/1.0/>.
+
+// Where the cursor was before each big jump in a buffer (to a tag, the next
+// error, the end of the file...), so Alt+Left can retrace them and Alt+Right
+// go forward again, like a browser's back and forward buttons.
+
+/// How many jumps a buffer remembers
+const MAX_JUMPS: usize = 100;
+
+#[derive(Clone, Copy)]
+pub struct Jump {
+    pub row: usize,
+    pub col: usize,
+    pub top_line: usize,
+}
+
+impl Jump {
+    fn same_place(&self, other: &Jump) -> bool {
+        self.row == other.row && self.col == other.col
+    }
+}
+
+#[derive(Default)]
+pub struct JumpList {
+    entries: Vec<Jump>,
+    index: usize, // Where back() goes from; entries.len() when not retracing
+}
+
+impl JumpList {
+    pub fn new() -> Self {
+        JumpList::default()
+    }
+
+    /// Remember `from`, the place being jumped away from. Jumping somewhere
+    /// new after going back forgets the jumps that were ahead.
+    pub fn push(&mut self, from: Jump) {
+        self.entries.truncate(self.index);
+        if !self.entries.last().is_some_and(|j| j.same_place(&from)) {
+            self.entries.push(from);
+        }
+        if self.entries.len() > MAX_JUMPS {
+            self.entries.remove(0);
+        }
+        self.index = self.entries.len();
+    }
+
+    /// Where to go back to from `here`
+    pub fn back(&mut self, here: Jump) -> Option<Jump> {
+        // Leaving the newest end, so keep it for forward() to come back to
+        if self.index == self.entries.len() {
+            if !self.entries.last().is_some_and(|j| j.same_place(&here)) {
+                self.entries.push(here);
+            }
+            self.index = self.entries.len() - 1;
+        }
+        if self.index == 0 {
+            return None;
+        }
+
+        self.index -= 1;
+        Some(self.entries[self.index])
+    }
+
+    pub fn forward(&mut self) -> Option<Jump> {
+        if self.index + 1 >= self.entries.len() {
+            return None;
+        }
+
+        self.index += 1;
+        Some(self.entries[self.index])
+    }
+
+    /// Keep the jumps pointing at the same text when `old_len` lines at `row`
+    /// are replaced by `new_len` lines. Jumps into lines that went away land
+    /// on the last line of what replaced them.
+    pub fn splice(&mut self, row: usize, old_len: usize, new_len: usize) {
+        for jump in &mut self.entries {
+            if jump.row >= row + old_len {
+                jump.row = jump.row + new_len - old_len;
+            } else if jump.row >= row + new_len {
+                jump.row = (row + new_len).saturating_sub(1).max(row);
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.index = 0;
+    }
+}
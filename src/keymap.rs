@@ -0,0 +1,335 @@
+// wfemto - A toy text editor
+// Written in 2025 by Dana Larose <ywg.dana@gmail.com>
+//
+// To the extent possible under law, the author(s) have dedicated all copyright
+// and related and neighboring rights to this software to the public domain
+// worldwide. This software is distributed without any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication along
+// with this software. If not,
+// see <http://creativecommons.org/publicdomain/zero/1.0/>.
+
+// Which command each key runs while editing a buffer. Prompts and popups
+// handle their own keys before these are looked at.
+//
+// The bindings come in profiles, picked in the config file. A profile only
+// lists the keys it changes; anything it doesn't mention falls through to
+// the defaults.
+//
+// A binding can take two keys, like Ctrl+K then S: the first only waits for
+// the second. A profile that binds the first key on its own hides the
+// defaults that start with it.
+
+use sdl2::keyboard::{Keycode, Mod};
+
+use crate::commands::{Command, Motion};
+use crate::lineops::LineOp;
+
+/// The modifiers held with a key. Only left Alt counts: right Alt is AltGr
+/// on a lot of layouts.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Mods {
+    pub ctrl: bool,
+    pub shift: bool,
+    pub alt: bool,
+}
+
+const NONE: Mods = Mods { ctrl: false, shift: false, alt: false };
+const CTRL: Mods = Mods { ctrl: true, shift: false, alt: false };
+const SHIFT: Mods = Mods { ctrl: false, shift: true, alt: false };
+const CTRL_SHIFT: Mods = Mods { ctrl: true, shift: true, alt: false };
+const ALT: Mods = Mods { ctrl: false, shift: false, alt: true };
+const CTRL_ALT: Mods = Mods { ctrl: true, shift: false, alt: true };
+
+impl Mods {
+    pub fn from_keymod(keymod: Mod) -> Mods {
+        Mods {
+            ctrl: keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD),
+            shift: keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD),
+            alt: keymod.intersects(Mod::LALTMOD),
+        }
+    }
+}
+
+pub struct Binding {
+    pub prefix: Option<(Keycode, Mods)>, // The key typed first, for a two key binding
+    pub key: Keycode,
+    pub mods: Mods,
+    pub command: Command,
+}
+
+const fn bind(key: Keycode, mods: Mods, command: Command) -> Binding {
+    Binding { prefix: None, key, mods, command }
+}
+
+/// A binding for `key` typed after `first`
+const fn chord(first: Keycode, first_mods: Mods, key: Keycode, mods: Mods, command: Command) -> Binding {
+    Binding { prefix: Some((first, first_mods)), key, mods, command }
+}
+
+/// The first key of a two key command, typed and waiting on the second
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Prefix {
+    Chord(Keycode, Mods),
+    Normal(char),
+}
+
+impl Prefix {
+    /// How it shows in the status bar while it waits, like "Ctrl+K -"
+    pub fn describe(self) -> String {
+        match self {
+            Prefix::Chord(key, mods) => format!("{} -", key_name(key, mods)),
+            Prefix::Normal(c) => format!("{} -", c),
+        }
+    }
+}
+
+pub static DEFAULT_KEYS: &[Binding] = &[
+    bind(Keycode::Return, NONE, Command::Newline),
+    bind(Keycode::Return, CTRL, Command::OpenLineBelow),
+    bind(Keycode::Return, CTRL_SHIFT, Command::OpenLineAbove),
+    bind(Keycode::Backspace, NONE, Command::Backspace),
+    bind(Keycode::Delete, NONE, Command::DeleteForward),
+    bind(Keycode::Left, NONE, Command::Move(Motion::Left, false)),
+    bind(Keycode::Left, SHIFT, Command::Move(Motion::Left, true)),
+    bind(Keycode::Right, NONE, Command::Move(Motion::Right, false)),
+    bind(Keycode::Right, SHIFT, Command::Move(Motion::Right, true)),
+    bind(Keycode::Left, CTRL, Command::Move(Motion::WordLeft, false)),
+    bind(Keycode::Left, CTRL_SHIFT, Command::Move(Motion::WordLeft, true)),
+    bind(Keycode::Right, CTRL, Command::Move(Motion::WordRight, false)),
+    bind(Keycode::Right, CTRL_SHIFT, Command::Move(Motion::WordRight, true)),
+    bind(Keycode::Backspace, CTRL, Command::KillWord),
+    bind(Keycode::Up, NONE, Command::Move(Motion::Up, false)),
+    bind(Keycode::Up, SHIFT, Command::Move(Motion::Up, true)),
+    bind(Keycode::Down, NONE, Command::Move(Motion::Down, false)),
+    bind(Keycode::Down, SHIFT, Command::Move(Motion::Down, true)),
+    bind(Keycode::Up, CTRL, Command::Move(Motion::ParagraphUp, false)),
+    bind(Keycode::Up, CTRL_SHIFT, Command::Move(Motion::ParagraphUp, true)),
+    bind(Keycode::Down, CTRL, Command::Move(Motion::ParagraphDown, false)),
+    bind(Keycode::Down, CTRL_SHIFT, Command::Move(Motion::ParagraphDown, true)),
+    bind(Keycode::Home, NONE, Command::Move(Motion::LineStart, false)),
+    bind(Keycode::Home, SHIFT, Command::Move(Motion::LineStart, true)),
+    bind(Keycode::End, NONE, Command::Move(Motion::LineEnd, false)),
+    bind(Keycode::End, SHIFT, Command::Move(Motion::LineEnd, true)),
+    bind(Keycode::PageUp, NONE, Command::Move(Motion::PageUp, false)),
+    bind(Keycode::PageUp, SHIFT, Command::Move(Motion::PageUp, true)),
+    bind(Keycode::PageDown, NONE, Command::Move(Motion::PageDown, false)),
+    bind(Keycode::PageDown, SHIFT, Command::Move(Motion::PageDown, true)),
+    bind(Keycode::Tab, NONE, Command::Tab),
+    bind(Keycode::Tab, SHIFT, Command::Dedent),
+    bind(Keycode::Escape, NONE, Command::Cancel),
+    bind(Keycode::Q, CTRL, Command::Quit),
+    bind(Keycode::S, CTRL, Command::Save),
+    bind(Keycode::S, CTRL_SHIFT, Command::Split),
+    bind(Keycode::S, CTRL_ALT, Command::SaveAll),
+    bind(Keycode::O, CTRL_SHIFT, Command::GotoFile),
+    bind(Keycode::W, CTRL, Command::CloseBuffer),
+    bind(Keycode::W, CTRL_SHIFT, Command::Unsplit),
+    bind(Keycode::F6, NONE, Command::SwitchPane),
+    bind(Keycode::F6, SHIFT, Command::FocusPanel),
+    bind(Keycode::Z, CTRL, Command::Undo),
+    bind(Keycode::Z, CTRL_SHIFT, Command::Redo),
+    bind(Keycode::Y, CTRL, Command::Redo),
+    bind(Keycode::I, CTRL_SHIFT, Command::Format),
+    bind(Keycode::L, CTRL_SHIFT, Command::SelectLine),
+    bind(Keycode::K, CTRL_SHIFT, Command::DeleteLine),
+    bind(Keycode::T, ALT, Command::TransposeWords),
+    bind(Keycode::Q, ALT, Command::Reflow),
+    bind(Keycode::Equals, ALT, Command::Counts),
+    bind(Keycode::S, ALT, Command::SpellSuggest),
+    bind(Keycode::C, CTRL, Command::Copy),
+    bind(Keycode::X, CTRL, Command::Cut),
+    bind(Keycode::V, CTRL, Command::Paste),
+    bind(Keycode::V, CTRL_SHIFT, Command::PasteRing),
+    bind(Keycode::G, CTRL, Command::Info),
+    bind(Keycode::F1, NONE, Command::Help),
+    bind(Keycode::Space, CTRL, Command::Complete),
+    bind(Keycode::RightBracket, CTRL, Command::Tag),
+    bind(Keycode::T, CTRL, Command::TagBack),
+    bind(Keycode::F4, NONE, Command::NextError),
+    bind(Keycode::F4, SHIFT, Command::PrevError),
+    bind(Keycode::F8, NONE, Command::NextMarker),
+    bind(Keycode::E, CTRL_SHIFT, Command::ExportHtml),
+    bind(Keycode::Tab, CTRL, Command::NextBuffer),
+    bind(Keycode::Tab, CTRL_SHIFT, Command::PrevBuffer),
+    bind(Keycode::Num6, CTRL, Command::AlternateBuffer),
+    bind(Keycode::Left, ALT, Command::JumpBack),
+    bind(Keycode::Right, ALT, Command::JumpForward),
+    bind(Keycode::O, CTRL, Command::Open),
+    bind(Keycode::X, ALT, Command::Palette),
+    bind(Keycode::U, CTRL_SHIFT, Command::InsertChar),
+    bind(Keycode::F, CTRL_SHIFT, Command::Grep),
+    bind(Keycode::R, CTRL_SHIFT, Command::Rename),
+    bind(Keycode::Backslash, CTRL, Command::Pipe),
+    bind(Keycode::Num9, CTRL_SHIFT, Command::RecordMacro),
+    bind(Keycode::Num0, CTRL_SHIFT, Command::StopMacro),
+    bind(Keycode::P, CTRL_SHIFT, Command::PlayMacro),
+    bind(Keycode::Period, CTRL, Command::RepeatLast),
+    chord(Keycode::K, CTRL, Keycode::S, NONE, Command::Lines(LineOp::Sort)),
+    chord(Keycode::K, CTRL, Keycode::R, NONE, Command::Lines(LineOp::Reverse)),
+    chord(Keycode::K, CTRL, Keycode::D, NONE, Command::Lines(LineOp::Dedup)),
+    chord(Keycode::K, CTRL, Keycode::B, NONE, Command::BufferList),
+    chord(Keycode::K, CTRL, Keycode::L, NONE, Command::LineNumbers),
+    chord(Keycode::K, CTRL, Keycode::M, NONE, Command::Minimap),
+    chord(Keycode::K, CTRL, Keycode::O, NONE, Command::Outline),
+];
+
+/// What a character typed in vi-style Normal mode does. `d` and `g` start a
+/// two key command instead, looked up with normal_pair().
+pub fn normal_command(c: char) -> Option<Command> {
+    match c {
+        'h' => Some(Command::Move(Motion::Left, false)),
+        'j' => Some(Command::Move(Motion::Down, false)),
+        'k' => Some(Command::Move(Motion::Up, false)),
+        'l' => Some(Command::Move(Motion::Right, false)),
+        '0' => Some(Command::Move(Motion::LineStart, false)),
+        '$' => Some(Command::Move(Motion::LineEnd, false)),
+        'G' => Some(Command::Move(Motion::BufferEnd, false)),
+        '{' => Some(Command::Move(Motion::ParagraphUp, false)),
+        '}' => Some(Command::Move(Motion::ParagraphDown, false)),
+        'w' => Some(Command::Move(Motion::WordRight, false)),
+        'b' => Some(Command::Move(Motion::WordLeft, false)),
+        'x' => Some(Command::DeleteForward),
+        'i' => Some(Command::InsertMode),
+        'a' => Some(Command::Append),
+        '.' => Some(Command::RepeatLast),
+        _ => None,
+    }
+}
+
+/// Whether `c` starts a two key Normal mode command
+pub fn is_normal_prefix(c: char) -> bool {
+    matches!(c, 'd' | 'g')
+}
+
+pub fn normal_pair(first: char, second: char) -> Option<Command> {
+    match (first, second) {
+        ('d', 'd') => Some(Command::DeleteLine),
+        ('g', 'g') => Some(Command::Move(Motion::BufferStart, false)),
+        ('g', 'f') => Some(Command::GotoFile),
+        _ => None,
+    }
+}
+
+/// Whether `key` is only a modifier, which doesn't finish a two key command
+/// on its own
+pub fn is_modifier(key: Keycode) -> bool {
+    matches!(key,
+        Keycode::LCtrl | Keycode::RCtrl | Keycode::LShift | Keycode::RShift |
+        Keycode::LAlt | Keycode::RAlt | Keycode::LGui | Keycode::RGui)
+}
+
+/// The number on a digit key
+pub fn digit(key: Keycode) -> Option<usize> {
+    let digits = [
+        Keycode::Num0, Keycode::Num1, Keycode::Num2, Keycode::Num3, Keycode::Num4,
+        Keycode::Num5, Keycode::Num6, Keycode::Num7, Keycode::Num8, Keycode::Num9,
+    ];
+    digits.iter().position(|&k| k == key)
+}
+
+/// Emacs-style movement and killing, at the cost of Ctrl+W closing the buffer
+pub static EMACS_KEYS: &[Binding] = &[
+    bind(Keycode::A, CTRL, Command::Move(Motion::LineStart, false)),
+    bind(Keycode::E, CTRL, Command::Move(Motion::LineEnd, false)),
+    bind(Keycode::F, CTRL, Command::Move(Motion::Right, false)),
+    bind(Keycode::B, CTRL, Command::Move(Motion::Left, false)),
+    bind(Keycode::N, CTRL, Command::Move(Motion::Down, false)),
+    bind(Keycode::P, CTRL, Command::Move(Motion::Up, false)),
+    bind(Keycode::K, CTRL, Command::KillLine),
+    bind(Keycode::W, CTRL, Command::KillWord),
+    bind(Keycode::T, CTRL, Command::TransposeChars),
+];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Profile {
+    Default,
+    Emacs,
+}
+
+impl Profile {
+    pub fn parse(name: &str) -> Result<Profile, String> {
+        match name.trim().to_lowercase().as_str() {
+            "default" => Ok(Profile::Default),
+            "emacs" => Ok(Profile::Emacs),
+            _ => Err(format!("unknown key profile '{}'", name)),
+        }
+    }
+
+    /// The bindings this profile has instead of the defaults
+    fn overrides(self) -> &'static [Binding] {
+        match self {
+            Profile::Default => &[],
+            Profile::Emacs => EMACS_KEYS,
+        }
+    }
+
+    /// Every binding in effect: the overrides, then the defaults they leave
+    fn bindings(self) -> impl Iterator<Item = &'static Binding> {
+        let overrides = self.overrides();
+        let hidden = move |d: &Binding| overrides.iter().any(|o| {
+            (o.prefix == d.prefix && o.key == d.key && o.mods == d.mods)
+                || (o.prefix.is_none() && d.prefix == Some((o.key, o.mods)))
+        });
+        overrides.iter().chain(DEFAULT_KEYS.iter().filter(move |d| !hidden(d)))
+    }
+}
+
+/// Every key that does something in `profile`, as ("Ctrl+S", command),
+/// in the order they're listed here
+pub fn active_bindings(profile: Profile, modal: bool) -> Vec<(String, Command)> {
+    let mut found: Vec<(String, Command)> = profile.bindings()
+        .map(|b| match b.prefix {
+            Some((key, mods)) => (format!("{} {}", key_name(key, mods), key_name(b.key, b.mods)), b.command),
+            None => (key_name(b.key, b.mods), b.command),
+        })
+        .collect();
+
+    if modal {
+        for c in "hjkl0$G{}wbxia.".chars() {
+            found.extend(normal_command(c).map(|command| (format!("{} (Normal)", c), command)));
+        }
+        for pair in ["dd", "gg", "gf"] {
+            let mut chars = pair.chars();
+            let (first, second) = (chars.next().unwrap(), chars.next().unwrap());
+            found.extend(normal_pair(first, second).map(|command| (format!("{} (Normal)", pair), command)));
+        }
+    }
+
+    found
+}
+
+pub fn key_name(key: Keycode, mods: Mods) -> String {
+    let mut name = String::new();
+    if mods.ctrl {
+        name.push_str("Ctrl+");
+    }
+    if mods.alt {
+        name.push_str("Alt+");
+    }
+    if mods.shift {
+        name.push_str("Shift+");
+    }
+    name.push_str(&key.name());
+
+    name
+}
+
+/// The command bound to `key` with exactly `mods` held
+pub fn lookup(profile: Profile, key: Keycode, mods: Mods) -> Option<Command> {
+    profile.bindings()
+        .find(|b| b.prefix.is_none() && b.key == key && b.mods == mods)
+        .map(|b| b.command)
+}
+
+/// Whether `key` with `mods` held starts a two key command
+pub fn is_prefix(profile: Profile, key: Keycode, mods: Mods) -> bool {
+    profile.bindings().any(|b| b.prefix == Some((key, mods)))
+}
+
+/// The command bound to `key` typed after `first`
+pub fn lookup_chord(profile: Profile, first: (Keycode, Mods), key: Keycode, mods: Mods) -> Option<Command> {
+    profile.bindings()
+        .find(|b| b.prefix == Some(first) && b.key == key && b.mods == mods)
+        .map(|b| b.command)
+}
@@ -0,0 +1,141 @@
+// wfemto - A toy text editor
+// Written in 2025 by Dana Larose <ywg.dana@gmail.com>
+//
+// To the extent possible under law, the author(s) have dedicated all copyright
+// and related and neighboring rights to this software to the public domain
+// worldwide. This software is distributed without any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication along
+// with this software. If not,
+// see <http://creativecommons.org/publicdomain/zero/1.0/>.
+
+
+// Line-by-line differences between two versions of a file, from the longest
+// common subsequence of their lines. Lines the two share at the start and end
+// are set aside first, so the table only covers the part that changed; if
+// even that's too big to compare line by line, the whole middle counts as
+// removed and re-added.
+
+/// Most cells the LCS table may have
+const MAX_CELLS: usize = 4_000_000;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Diff {
+    Same(usize, usize), // Index in old, index in new
+    Removed(usize),     // Index in old
+    Added(usize),       // Index in new
+}
+
+/// How to turn `old` into `new`, in order
+pub fn diff(old: &[String], new: &[String]) -> Vec<Diff> {
+    let head = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let tail = old[head..].iter().rev().zip(new[head..].iter().rev()).take_while(|(a, b)| a == b).count();
+    let (old_mid, new_mid) = (&old[head..old.len() - tail], &new[head..new.len() - tail]);
+
+    let mut out: Vec<Diff> = (0..head).map(|i| Diff::Same(i, i)).collect();
+    if old_mid.len().saturating_mul(new_mid.len()) > MAX_CELLS {
+        out.extend((head..old.len() - tail).map(Diff::Removed));
+        out.extend((head..new.len() - tail).map(Diff::Added));
+    } else {
+        out.extend(lcs(old_mid, new_mid).into_iter().map(|d| match d {
+            Diff::Same(a, b) => Diff::Same(head + a, head + b),
+            Diff::Removed(a) => Diff::Removed(head + a),
+            Diff::Added(b) => Diff::Added(head + b),
+        }));
+    }
+    out.extend((0..tail).map(|i| Diff::Same(old.len() - tail + i, new.len() - tail + i)));
+
+    out
+}
+
+/// Lines added and removed
+pub fn counts(diff: &[Diff]) -> (usize, usize) {
+    let added = diff.iter().filter(|d| matches!(d, Diff::Added(_))).count();
+    let removed = diff.iter().filter(|d| matches!(d, Diff::Removed(_))).count();
+    (added, removed)
+}
+
+fn lcs(old: &[String], new: &[String]) -> Vec<Diff> {
+    // lengths[i][j] is the LCS of old[i..] and new[j..]
+    let width = new.len() + 1;
+    let mut lengths = vec![0u32; (old.len() + 1) * width];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lengths[i * width + j] = if old[i] == new[j] {
+                lengths[(i + 1) * width + j + 1] + 1
+            } else {
+                lengths[(i + 1) * width + j].max(lengths[i * width + j + 1])
+            };
+        }
+    }
+
+    // Removals go before additions where there's a choice, as diff does
+    let mut out = Vec::with_capacity(old.len().max(new.len()));
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            out.push(Diff::Same(i, j));
+            i += 1;
+            j += 1;
+        } else if j == new.len() || (i < old.len() && lengths[(i + 1) * width + j] >= lengths[i * width + j + 1]) {
+            out.push(Diff::Removed(i));
+            i += 1;
+        } else {
+            out.push(Diff::Added(j));
+            j += 1;
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(text: &[&str]) -> Vec<String> {
+        text.iter().map(|line| line.to_string()).collect()
+    }
+
+    #[test]
+    fn identical_lines_are_all_the_same() {
+        let text = lines(&["a", "b", "c"]);
+        assert_eq!(diff(&text, &text), [Diff::Same(0, 0), Diff::Same(1, 1), Diff::Same(2, 2)]);
+        assert_eq!(diff(&[], &[]), []);
+    }
+
+    #[test]
+    fn one_side_empty() {
+        let text = lines(&["a", "b"]);
+        assert_eq!(diff(&[], &text), [Diff::Added(0), Diff::Added(1)]);
+        assert_eq!(diff(&text, &[]), [Diff::Removed(0), Diff::Removed(1)]);
+    }
+
+    #[test]
+    fn shared_head_and_tail_around_a_change() {
+        let changes = diff(&lines(&["a", "b", "c", "d"]), &lines(&["a", "x", "d"]));
+        assert_eq!(changes, [Diff::Same(0, 0), Diff::Removed(1), Diff::Removed(2), Diff::Added(1), Diff::Same(3, 2)]);
+        assert_eq!(counts(&changes), (1, 2));
+    }
+
+    #[test]
+    fn lines_in_common_are_found_between_changes() {
+        let changes = diff(&lines(&["a", "b", "c"]), &lines(&["b", "c", "d"]));
+        assert_eq!(changes, [Diff::Removed(0), Diff::Same(1, 0), Diff::Same(2, 1), Diff::Added(2)]);
+    }
+
+    #[test]
+    fn too_big_a_middle_is_removed_and_readded() {
+        // 2001 x 2001 lines between the shared ends is over MAX_CELLS
+        let side = |name: &str| -> Vec<String> {
+            let middle = (0..2001).map(|n| if n == 1000 { "common".to_string() } else { format!("{}{}", name, n) });
+            std::iter::once("head".to_string()).chain(middle).chain(std::iter::once("tail".to_string())).collect()
+        };
+        let changes = diff(&side("old"), &side("new"));
+
+        assert_eq!(changes.first(), Some(&Diff::Same(0, 0)));
+        assert_eq!(changes.last(), Some(&Diff::Same(2002, 2002)));
+        assert_eq!(counts(&changes), (2001, 2001));
+        assert_eq!(changes.iter().filter(|d| matches!(d, Diff::Same(..))).count(), 2);
+    }
+}
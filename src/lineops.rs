@@ -0,0 +1,64 @@
+// wfemto - A toy text editor
+// Written in 2025 by Dana Larose <ywg.dana@gmail.com>
+//
+// To the extent possible under law, the author(s) have dedicated all copyright
+// and related and neighboring rights to this software to the public domain
+// worldwide. This software is distributed without any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication along
+// with this software. If not,
+// see <http://creativecommons.org/publicdomain/zero/1.0/>.
+
+// Whole-line transformations for the sort/reverse/dedup commands. Each one
+// returns the new lines and how many lines it changed, for the status bar.
+// Plus working out which lines squeezing blank lines leaves.
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LineOp {
+    Sort,
+    SortDesc,
+    SortNoCase,
+    SortNoCaseDesc,
+    Reverse,
+    Dedup,
+}
+
+pub fn apply(op: LineOp, lines: &[String]) -> (Vec<String>, usize) {
+    let mut out = lines.to_vec();
+    match op {
+        // All of these sorts are stable, so equal lines keep their order
+        LineOp::Sort => out.sort(),
+        LineOp::SortDesc => out.sort_by(|a, b| b.cmp(a)),
+        LineOp::SortNoCase => out.sort_by_cached_key(|l| l.to_lowercase()),
+        LineOp::SortNoCaseDesc => out.sort_by_cached_key(|l| std::cmp::Reverse(l.to_lowercase())),
+        LineOp::Reverse => out.reverse(),
+        LineOp::Dedup => {
+            out.dedup();
+            let removed = lines.len() - out.len();
+            return (out, removed);
+        }
+    }
+
+    let moved = lines.iter().zip(&out).filter(|(a, b)| a != b).count();
+
+    (out, moved)
+}
+
+/// Which of `lines` are left after squeezing each run of blank (or only
+/// whitespace) lines down to one
+pub fn squeeze_blanks(lines: &[String]) -> Vec<bool> {
+    let blank = |line: &String| line.trim().is_empty();
+    lines.iter().enumerate()
+        .map(|(i, line)| i == 0 || !blank(line) || !blank(&lines[i - 1]))
+        .collect()
+}
+
+/// The status bar report for running `op` over `total` lines
+pub fn describe(op: LineOp, total: usize, affected: usize) -> String {
+    let plural = |n: usize| if n == 1 { "" } else { "s" };
+    match op {
+        LineOp::Dedup => format!("Removed {} duplicate line{}", affected, plural(affected)),
+        LineOp::Reverse => format!("Reversed {} line{}", total, plural(total)),
+        _ => format!("Sorted {} line{}, {} moved", total, plural(total), affected),
+    }
+}
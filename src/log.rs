@@ -0,0 +1,211 @@
+// wfemto - A toy text editor
+// Written in 2025 by Dana Larose <ywg.dana@gmail.com>
+//
+// To the extent possible under law, the author(s) have dedicated all copyright
+// and related and neighboring rights to this software to the public domain
+// worldwide. This software is distributed without any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication along
+// with this software. If not,
+// see <http://creativecommons.org/publicdomain/zero/1.0/>.
+
+// A small logging facility. wfemto is a GUI app so println! output mostly goes
+// nowhere; instead we append timestamped lines to a per-user log file.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use crate::clock::DateTime;
+
+pub const LOG_LEVEL_ENV: &str = "WFEMTO_LOG";
+
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+pub enum Level {
+    Error = 0,
+    Warn = 1,
+    Info = 2,
+    Debug = 3,
+}
+
+impl Level {
+    pub fn from_name(name: &str) -> Option<Level> {
+        match name.trim().to_lowercase().as_str() {
+            "error" => Some(Level::Error),
+            "warn" | "warning" => Some(Level::Warn),
+            "info" => Some(Level::Info),
+            "debug" => Some(Level::Debug),
+            _ => None,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Level::Error => "ERROR",
+            Level::Warn => "WARN",
+            Level::Info => "INFO",
+            Level::Debug => "DEBUG",
+        }
+    }
+}
+
+/// Anything that can receive formatted log lines. The editor uses a
+/// FileLogger; tests can install their own to capture output.
+pub trait Logger: Send {
+    fn write_line(&mut self, line: &str);
+}
+
+pub struct FileLogger {
+    file: File,
+}
+
+impl FileLogger {
+    pub fn open(path: &PathBuf) -> Result<Self, String> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        }
+
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| e.to_string())?;
+
+        Ok(FileLogger { file })
+    }
+}
+
+impl Logger for FileLogger {
+    fn write_line(&mut self, line: &str) {
+        // If the log can't be written there's nowhere sensible to report it
+        let _ = writeln!(self.file, "{}", line);
+    }
+}
+
+static LOGGER: Mutex<Option<Box<dyn Logger>>> = Mutex::new(None);
+static MAX_LEVEL: AtomicU8 = AtomicU8::new(Level::Info as u8);
+
+/// Where the log file lives: $XDG_STATE_HOME/wfemto/wfemto.log, falling back
+/// to ~/.local/state and finally the system temp dir.
+pub fn log_path() -> PathBuf {
+    let base = match std::env::var_os("XDG_STATE_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => match std::env::var_os("HOME") {
+            Some(home) if !home.is_empty() => PathBuf::from(home).join(".local").join("state"),
+            _ => std::env::temp_dir(),
+        },
+    };
+
+    base.join("wfemto").join("wfemto.log")
+}
+
+/// The level requested via the WFEMTO_LOG env var, if it's set to something
+/// we recognize.
+pub fn level_from_env() -> Option<Level> {
+    std::env::var(LOG_LEVEL_ENV).ok().and_then(|s| Level::from_name(&s))
+}
+
+pub fn set_logger(logger: Box<dyn Logger>) {
+    if let Ok(mut l) = LOGGER.lock() {
+        *l = Some(logger);
+    }
+}
+
+pub fn set_level(level: Level) {
+    MAX_LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+pub fn enabled(level: Level) -> bool {
+    level as u8 <= MAX_LEVEL.load(Ordering::Relaxed)
+}
+
+/// Install the file logger at the default path. Failing to open the log is
+/// not fatal; we just run without one.
+pub fn init() {
+    set_level(level_from_env().unwrap_or(Level::Info));
+
+    let path = log_path();
+    match FileLogger::open(&path) {
+        Ok(logger) => set_logger(Box::new(logger)),
+        Err(e) => eprintln!("wfemto: unable to open log file {}: {}", path.display(), e),
+    }
+}
+
+pub fn log(level: Level, msg: &str) {
+    if !enabled(level) {
+        return;
+    }
+
+    if let Ok(mut l) = LOGGER.lock()
+        && let Some(logger) = l.as_mut()
+    {
+        logger.write_line(&format!("{} {:<5} {}", timestamp(), level.label(), msg));
+    }
+}
+
+/// Current UTC time formatted as "YYYY-MM-DD HH:MM:SS"
+pub fn timestamp() -> String {
+    DateTime::now_utc().format("%Y-%m-%d %H:%M:%S")
+}
+
+macro_rules! log_error {
+    ($($arg:tt)*) => { $crate::log::log($crate::log::Level::Error, &format!($($arg)*)) };
+}
+
+macro_rules! log_warn {
+    ($($arg:tt)*) => { $crate::log::log($crate::log::Level::Warn, &format!($($arg)*)) };
+}
+
+macro_rules! log_info {
+    ($($arg:tt)*) => { $crate::log::log($crate::log::Level::Info, &format!($($arg)*)) };
+}
+
+macro_rules! log_debug {
+    ($($arg:tt)*) => { $crate::log::log($crate::log::Level::Debug, &format!($($arg)*)) };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    /// Keeps every line logged, for looking at afterwards
+    struct Capture(Arc<Mutex<Vec<String>>>);
+
+    impl Logger for Capture {
+        fn write_line(&mut self, line: &str) {
+            self.0.lock().unwrap().push(line.to_string());
+        }
+    }
+
+    #[test]
+    fn lines_below_the_level_are_dropped() {
+        let lines = Arc::new(Mutex::new(Vec::new()));
+        set_logger(Box::new(Capture(Arc::clone(&lines))));
+        set_level(Level::Warn);
+        log_error!("log test {}", 1);
+        log_warn!("log test {}", 2);
+        log_info!("log test {}", 3);
+        log_debug!("log test {}", 4);
+        set_level(Level::Info);
+
+        // Other tests log too, so only this one's lines count
+        let lines: Vec<String> = lines.lock().unwrap().iter().filter(|l| l.contains("log test")).cloned().collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(&lines[0][19..], " ERROR log test 1");
+        assert_eq!(&lines[1][19..], " WARN  log test 2");
+
+        // Led by the time, as 2025-06-01 12:34:56
+        let stamp: Vec<char> = lines[0][..19].chars().collect();
+        for (i, c) in stamp.iter().enumerate() {
+            match i {
+                4 | 7 => assert_eq!(*c, '-'),
+                10 => assert_eq!(*c, ' '),
+                13 | 16 => assert_eq!(*c, ':'),
+                _ => assert!(c.is_ascii_digit(), "{}", lines[0]),
+            }
+        }
+    }
+}
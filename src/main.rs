@@ -11,563 +11,4819 @@
 
 extern crate sdl2;
 
-use std::cmp;
-use std::fs::File;
-use std::io::{BufRead, BufReader};
+#[macro_use]
+mod log;
+mod args;
+mod buffer;
+mod clipboard;
+mod clock;
+mod commands;
+mod compile;
+mod complete;
+mod config;
+mod count;
+mod editorconfig;
+mod encoding;
+mod export;
+mod external;
+mod gitdiff;
+mod grep;
+mod gutter;
+mod highlight;
+mod indent;
+mod jumps;
+mod keymap;
+mod linediff;
+mod lineops;
+mod minimap;
+mod occurrences;
+mod outline;
+mod panel;
+mod paste;
+mod pathref;
+mod prompt;
+mod reflow;
+mod ring;
+#[cfg(test)]
+mod scenario;
+mod search;
+mod session;
+mod snippet;
+mod spell;
+mod statusline;
+mod swap;
+mod tags;
+mod text;
+mod theme;
+mod undo;
+mod undofile;
+mod unicode;
+
+use std::collections::HashMap;
+use std::borrow::Cow;
+use std::fs;
+use std::path::{Path, PathBuf};
 use std::time::Duration;
 
-use sdl2::event::Event;
-use sdl2::keyboard::Keycode;
-use sdl2::pixels::Color;
-use sdl2::rect::Rect;
-use sdl2::render::Canvas;
-use sdl2::ttf::Font;
-use sdl2::video::Window;
+use sdl2::clipboard::ClipboardUtil;
+use sdl2::event::{Event, WindowEvent};
+use sdl2::keyboard::{Keycode, Mod};
+use sdl2::mouse::MouseButton;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::Canvas;
+use sdl2::ttf::Font;
+use sdl2::video::Window;
+
+use args::{Args, Invocation};
+use buffer::{Buffer, View};
+use clock::DateTime;
+use commands::{Command, Motion};
+use config::Config;
+use external::Filter;
+use gitdiff::GitMark;
+use gutter::LineNumbers;
+use keymap::{Mods, Prefix};
+use highlight::{Span, TokenKind};
+use prompt::Prompt;
+use occurrences::Occurrences;
+use panel::Panel;
+use search::{Match, Query};
+use session::{Session, SessionFile};
+use text::{char_len, expand_tabs};
+use theme::{Rgb, Theme};
+
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+const EDITOR_COLS: u32 = 80;
+const EDITOR_ROWS: u32 = 32;
+const FONT_SIZE: u16 = 14;
+const FONT_PATH: &str = "DejaVuSansMono.ttf";
+
+// Exit statuses, so a script can tell why wfemto didn't start. An error once
+// it's running is the 1 main() returning Err gives.
+const EXIT_USAGE: i32 = 2;
+const EXIT_NO_DISPLAY: i32 = 3;
+const EXIT_NO_FONT: i32 = 4;
+
+const TRUNCATED_MARKER: &str = "\u{2192}"; // In the last column of a line that goes on past it
+const ELLIPSIS: &str = "\u{2026}"; // Marks text cut off to fit the window
+const FRAME_INTERVAL: Duration = Duration::from_millis(16); // ~60 FPS, when there's no vsync to wait on
+
+const OPEN_FILE_LABEL: &str = "Open file: ";
+const PIPE_LABEL: &str = "Pipe through: ";
+const PALETTE_LABEL: &str = "Command: ";
+const GREP_LABEL: &str = "Find in files: ";
+const CHAR_LABEL: &str = "Insert character: ";
+const RENAME_LABEL: &str = "Rename to: ";
+const SAVE_AS_LABEL: &str = "Save as: ";
+const INSERT_FILE_LABEL: &str = "Insert file: ";
+const DELETE_MATCHING_LABEL: &str = "Delete lines containing: ";
+const REOPEN_LABEL: &str = "Reopen with encoding: ";
+
+#[derive(PartialEq)]
+enum EditorMode {
+    Edit,
+    OpenFile,
+    PipeCommand,
+    Palette,
+    Grep,
+    InsertChar,
+    Rename,
+    SaveAs,
+    InsertFile,
+    DeleteMatching,
+    Reopen,
+    ConfirmQuit,
+    ConfirmClose,
+    ConfirmRecover,
+    ConfirmOverwrite,
+    ConfirmPaste,
+}
+
+#[derive(Clone, Copy)]
+pub struct WindowInfo {
+    pub rows: u32,
+    pub cols: u32,
+    pub char_width: u32,
+    pub char_height: u32,    
+    pub scroll_margin: usize, // Lines of context kept around the cursor
+    pub text_top: i32,        // Pixel rows where the text area...
+    pub status_y: i32,        // ...the status bar...
+    pub prompt_y: i32,        // ...and the prompt line (the status bar's, if it has none) start
+    pub margin_left: i32,     // Blank space left of the text (and right of it)...
+    pub margin_top: i32,      // ...and above it, in drawing pixels
+    pub text_left: i32,       // Where the text starts, after the margin and any line numbers
+    pub scale: f32,           // Drawing pixels per window unit, 2 on most HiDPI screens
+}
+
+impl WindowInfo {
+    /// Mouse positions arrive in window units; everything is drawn in pixels
+    fn to_pixels(self, x: i32, y: i32) -> (i32, i32) {
+        ((x as f32 * self.scale) as i32, (y as f32 * self.scale) as i32)
+    }
+
+    /// The whole window's width in pixels, which the text may not use all of
+    fn full_width(self) -> u32 {
+        EDITOR_COLS * self.char_width + (self.margin_left as u32 * 2)
+    }
+
+    fn to_window_units(self, rect: Rect) -> Rect {
+        let unscale = |v: i32| (v as f32 / self.scale) as i32;
+        Rect::new(unscale(rect.x()), unscale(rect.y()),
+            (rect.width() as f32 / self.scale) as u32, (rect.height() as f32 / self.scale) as u32)
+    }
+}
+
+/// The pane that doesn't have focus when the window is split. The focused
+/// pane always shows the current buffer using that buffer's own cursor.
+struct Pane {
+    buffer: usize,
+    view: View,
+}
+
+/// What a command's output replaces
+enum FilterTarget {
+    Format,
+    Buffer,
+    // The selection, and whether the text sent to the command ended in a
+    // newline. If it didn't, the newline most commands add to their last line
+    // of output is dropped so the text doesn't gain a line at every run.
+    Selection((usize, usize), (usize, usize), bool),
+}
+
+/// An external command working on a buffer in the background. The result is
+/// only applied if the buffer hasn't been touched since the command started.
+struct FilterJob {
+    filter: Filter,
+    buffer_id: u64,
+    generation: u64,
+    target: FilterTarget,
+}
+
+/// A small list drawn over the text for picking one of a few things
+struct ListPopup {
+    items: Vec<String>,
+    selected: usize,
+}
+
+/// Largest repeat count Alt+digits can build up
+const MAX_REPEAT: usize = 9999;
+
+/// How long the first key of a two key command waits for the second
+const PENDING_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Most commands the palette remembers
+const PALETTE_HISTORY: usize = 100;
+
+/// Most items a popup list shows at once
+const POPUP_ROWS: usize = 8;
+
+/// How long the pointer rests on a cut off line before it's shown whole...
+const HOVER_DELAY: Duration = Duration::from_millis(500);
+/// ...in a box at most this many columns wide...
+const TOOLTIP_COLS: u32 = 80;
+/// ...and this many rows high
+const TOOLTIP_ROWS: usize = 12;
+
+impl ListPopup {
+    fn new(items: Vec<String>) -> Self {
+        ListPopup { items, selected: 0 }
+    }
+
+    /// Up/Down move the selection, wrapping at either end. Returns false for
+    /// any other key.
+    fn navigate(&mut self, keycode: Keycode) -> bool {
+        let count = self.items.len();
+        match keycode {
+            Keycode::Up => self.selected = (self.selected + count - 1) % count,
+            Keycode::Down => self.selected = (self.selected + 1) % count,
+            _ => return false,
+        }
+
+        true
+    }
+}
+
+/// The list of words offered by Ctrl+Space. It's dismissed by any key it
+/// doesn't handle itself.
+struct Completion {
+    row: usize,
+    start: usize,  // Column the word being completed starts at
+    prefix: String,
+    list: ListPopup,
+}
+
+/// Several definitions of a tag to choose between
+struct TagPicker {
+    tags: Vec<tags::Tag>,
+    list: ListPopup,
+}
+
+/// What an unknown word could be instead, with adding it to the user's
+/// word list as the last choice
+struct SpellPicker {
+    buffer_id: u64,
+    generation: u64, // The word's place is only good while this is the buffer's
+    row: usize,
+    start: usize, // Columns of the word
+    end: usize,
+    word: String,
+    list: ListPopup,
+}
+
+/// The buffers with unsaved changes listed when quitting, each with whether
+/// it's to be saved first, and the panel the list took the place of
+struct QuitList {
+    items: Vec<(usize, bool)>, // Buffer index, save it
+    hidden: Option<Panel>,
+}
+
+/// Where the last paste put its text, so Ctrl+Shift+V straight afterwards
+/// can swap it for an older copy
+struct Paste {
+    buffer_id: u64,
+    generation: u64,
+    start: (usize, usize),
+    end: (usize, usize),
+    index: usize, // In the clipboard ring
+}
+
+/// Name of the scratch buffer build output goes in
+const COMPILE_BUFFER: &str = "*compile*";
+
+/// A build running in the background
+struct Build {
+    filter: Filter,
+    dir: std::path::PathBuf, // Where it runs; paths in its output are relative to this
+}
+
+/// Name of the scratch buffer find-in-files results go in
+const GREP_BUFFER: &str = "*grep*";
+
+/// Name of the buffer for jotting things down in. It has no file behind it
+/// and never asks to be saved; saving it asks where to.
+const SCRATCH_BUFFER: &str = "*scratch*";
+
+/// A selection as (buffer id, generation, (start, end)), to tell when it
+/// changes
+type SelectionKey = (u64, u64, ((usize, usize), (usize, usize)));
+
+/// How long a selection made with the keyboard has to stay the same before
+/// it becomes the primary selection
+const PRIMARY_DELAY: Duration = Duration::from_millis(200);
+
+/// How often dragging past the top or bottom of a pane scrolls it
+const DRAG_SCROLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// How long a page movement takes to scroll into place, with smooth_scroll
+const SCROLL_TIME: Duration = Duration::from_millis(100);
+
+/// A page movement's scroll, drawn over SCROLL_TIME rather than all at once
+struct ScrollAnim {
+    buffer: u64, // Id of the buffer scrolled
+    from: f32,   // Where the view was drawn, in lines
+    to: usize,   // The top line it's heading for
+    start: std::time::Instant,
+}
+
+/// How often watched files are checked for changes
+const WATCH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// `git diff` running for a buffer, to refresh its gutter markers
+struct GitJob {
+    filter: Filter,
+    buffer_id: u64,
+    generation: u64,
+}
+
+/// Everything that isn't one buffer's own. A buffer keeps its text, undo
+/// history, selection, scroll position, jumps, modified flag and settings;
+/// the prompt, clipboard ring, macros, build errors and so on are shared by
+/// all of them. Anything here about a single buffer, like a paste that can
+/// still be cycled or a running filter, carries that buffer's id.
+struct TextEditor {
+    buffers: Vec<Buffer>,
+    current: usize,
+    split: Option<Pane>,
+    focus_top: bool, // With a split, whether the focused pane is the upper one
+    cursor_visible: bool,
+    last_cursor_blink: std::time::Instant,
+    mode: EditorMode,
+    prompt: Prompt,  // Buffer for command/filename input
+    saved_view: Option<View>, // Where we were before a prompt opened
+    message: Option<String>, // Feedback shown in the status bar until the next key
+    config: Config,
+    theme: Theme,
+    job: Option<FilterJob>,
+    git_jobs: Vec<GitJob>,
+    completion: Option<Completion>,
+    tag_picker: Option<TagPicker>,
+    spell: Option<spell::Checker>, // Loaded the first time it's needed
+    spell_picker: Option<SpellPicker>,
+    tag_stack: Vec<(u64, usize, usize)>, // (buffer id, row, col) jumped from
+    build: Option<Build>,
+    errors: Vec<compile::Location>, // From the last build
+    error_index: Option<usize>,     // The one F4 last went to
+    grep: Option<grep::Grep>,
+    grep_dir: std::path::PathBuf, // Paths in the grep results are relative to this
+    composition: String, // Text an input method is partway through composing
+    info: Option<Vec<String>>, // Shown over the text until the next key
+    dragging: bool, // Selecting with the left mouse button held down...
+    drag_pointer: Option<(i32, i32)>, // ...with the pointer last here, in pixels
+    last_drag_scroll: std::time::Instant,
+    scroll_anim: Option<ScrollAnim>,
+    selection_seen: Option<(SelectionKey, std::time::Instant)>, // And since when, for the primary selection
+    published: Option<SelectionKey>, // Last made the primary selection
+    ring: ring::ClipRing,
+    ring_picker: Option<ListPopup>,
+    last_paste: Option<Paste>,
+    clipboard: Option<ClipboardUtil>, // Only None until SDL is up
+    recording: Option<Vec<(Command, String)>>, // The macro being recorded
+    last_macro: Vec<(Command, String)>,
+    last_change: Option<(Command, String, usize)>, // What RepeatLast does again, and how many times over
+    typing: bool, // The last command run was typing, so more of it adds to last_change
+    palette_history: Vec<String>, // What's been run from the palette, oldest first
+    session_name: Option<String>, // Session last saved or loaded, saved again on quitting
+    repeat: Option<usize>, // Count typed with Alt+digits for the next command
+    normal: bool, // With modal editing on, whether typing runs commands
+    pending: Option<(keymap::Prefix, std::time::Instant)>, // First key of a two key command, and when it was typed
+    chord_typed: bool, // The last key finished a two key command, so the text it types is dropped
+    active_id: u64, // Buffer that was current last frame...
+    previous_id: Option<u64>, // ...and the one before it, for AlternateBuffer
+    swapped: HashMap<u64, u64>, // Buffer id -> generation its swap file holds
+    last_swap: std::time::Instant,
+    recovery: Option<swap::Swap>, // Found for the current buffer, waiting on y/n
+    overwrite: Option<String>, // Existing file save-as is waiting on y/n to write over
+    held_paste: Option<(String, Option<usize>)>, // Paste too big to make without a y, and the ring entry it's from
+    paste_due: bool, // The held paste was said yes to, and goes in once the frame saying so is up
+    saving_selection: bool, // The save-as prompt is for writing out just the selection
+    last_watch: std::time::Instant,
+    occurrences: Occurrences,
+    rename_skips_literals: bool, // Leave strings and comments alone when renaming
+    help: Option<usize>, // Page of the key list being shown
+    column_guide: bool,
+    line_numbers: LineNumbers,
+    minimap: bool,
+    minimap_grab: Option<i32>, // Dragging the minimap's box, held this far below its top
+    panel: Option<Panel>, // Results across the bottom of the text area
+    panel_focused: bool,
+    quit_list: Option<QuitList>, // While confirming a quit
+    splash: bool, // Showing the title until the first key that does something
+}
+
+impl TextEditor {
+    fn new(config: Config) -> Self {
+        let defaults = Theme::default();
+        let theme = Theme {
+            status_bg: config.status_bg.unwrap_or(defaults.status_bg),
+            status_fg: config.status_fg.unwrap_or(defaults.status_fg),
+            ..defaults
+        };
+        let mut editor = TextEditor {
+            buffers: Vec::new(),
+            current: 0,
+            split: None,
+            focus_top: true,
+            cursor_visible: true,
+            last_cursor_blink: std::time::Instant::now(),
+            mode: EditorMode::Edit,
+            prompt: Prompt::new(),
+            saved_view: None,
+            message: None,
+            config,
+            theme,
+            job: None,
+            git_jobs: Vec::new(),
+            completion: None,
+            tag_picker: None,
+            spell: None,
+            spell_picker: None,
+            tag_stack: Vec::new(),
+            build: None,
+            errors: Vec::new(),
+            error_index: None,
+            grep: None,
+            grep_dir: std::path::PathBuf::new(),
+            composition: String::new(),
+            info: None,
+            dragging: false,
+            drag_pointer: None,
+            last_drag_scroll: std::time::Instant::now(),
+            scroll_anim: None,
+            selection_seen: None,
+            published: None,
+            ring: ring::ClipRing::new(),
+            ring_picker: None,
+            last_paste: None,
+            clipboard: None,
+            recording: None,
+            last_macro: Vec::new(),
+            last_change: None,
+            typing: false,
+            palette_history: Vec::new(),
+            session_name: None,
+            repeat: None,
+            normal: false,
+            pending: None,
+            chord_typed: false,
+            active_id: 0,
+            previous_id: None,
+            swapped: HashMap::new(),
+            last_swap: std::time::Instant::now(),
+            recovery: None,
+            overwrite: None,
+            held_paste: None,
+            paste_due: false,
+            saving_selection: false,
+            last_watch: std::time::Instant::now(),
+            occurrences: Occurrences::new(),
+            rename_skips_literals: false,
+            help: None,
+            column_guide: false,
+            line_numbers: LineNumbers::Off,
+            minimap: false,
+            minimap_grab: None,
+            panel: None,
+            panel_focused: false,
+            quit_list: None,
+            splash: true,
+        };
+        editor.normal = editor.config.modal;
+        editor.column_guide = editor.config.column_guide;
+        editor.line_numbers = editor.config.line_numbers;
+        editor.minimap = editor.config.minimap;
+        editor.buffers.push(editor.scratch_buffer());
+        editor.active_id = editor.buf().id;
+
+        editor
+    }
+
+    /// An empty buffer with the user's default settings
+    fn new_buffer(&self) -> Buffer {
+        let mut buffer = Buffer::new();
+        apply_settings(&self.config, &mut buffer, "");
+        buffer.persistent_undo = self.config.persistent_undo;
+
+        buffer
+    }
+
+    fn scratch_buffer(&self) -> Buffer {
+        let mut buffer = self.new_buffer();
+        buffer.set_filename(SCRATCH_BUFFER);
+
+        buffer
+    }
+
+    /// Switch to the scratch buffer, making one if there isn't one
+    fn goto_scratch(&mut self) {
+        match self.buffers.iter().position(|b| b.filename == SCRATCH_BUFFER) {
+            Some(index) => self.current = index,
+            None => {
+                self.buffers.push(self.scratch_buffer());
+                self.current = self.buffers.len() - 1;
+            }
+        }
+    }
+
+    fn buf(&self) -> &Buffer {
+        &self.buffers[self.current]
+    }
+
+    fn buf_mut(&mut self) -> &mut Buffer {
+        &mut self.buffers[self.current]
+    }
+
+    /// The text shown before what's being typed, if the mode is a prompt
+    fn prompt_label(&self) -> Option<&'static str> {
+        match self.mode {
+            EditorMode::OpenFile => Some(OPEN_FILE_LABEL),
+            EditorMode::PipeCommand => Some(PIPE_LABEL),
+            EditorMode::Palette => Some(PALETTE_LABEL),
+            EditorMode::Grep => Some(GREP_LABEL),
+            EditorMode::InsertChar => Some(CHAR_LABEL),
+            EditorMode::Rename => Some(RENAME_LABEL),
+            EditorMode::SaveAs => Some(SAVE_AS_LABEL),
+            EditorMode::InsertFile => Some(INSERT_FILE_LABEL),
+            EditorMode::DeleteMatching => Some(DELETE_MATCHING_LABEL),
+            EditorMode::Reopen => Some(REOPEN_LABEL),
+            _ => None,
+        }
+    }
+
+    fn in_prompt(&self) -> bool {
+        self.prompt_label().is_some()
+    }
+
+    /// Switch to a prompt, remembering exactly where the cursor and viewport
+    /// were so that leaving the prompt can put them back.
+    fn begin_prompt(&mut self, mode: EditorMode) {
+        self.prompt.clear();
+        self.prompt.completer = match mode {
+            EditorMode::OpenFile | EditorMode::SaveAs | EditorMode::InsertFile => Some(complete::paths),
+            EditorMode::Palette => Some(commands::completions),
+            _ => None,
+        };
+        self.mode = mode;
+        self.saving_selection = false;
+        self.saved_view = Some(self.buf().view());
+    }
+
+    /// Leave a prompt, however it ended (accepted, cancelled or failed),
+    /// restoring the position saved when it opened.
+    fn end_prompt(&mut self) {
+        self.mode = EditorMode::Edit;
+        if let Some(view) = self.saved_view.take() {
+            self.buf_mut().set_view(view);
+        }
+    }
+
+    /// Load a file into a new buffer and switch to it. On failure the
+    /// current buffer is left alone and the error goes to the status bar.
+    fn open_file(&mut self, filename: &str) -> bool {
+        let mut buffer = self.new_buffer();
+        apply_settings(&self.config, &mut buffer, filename);
+        match buffer.load(filename) {
+            Ok(()) => {
+                self.buffers.push(buffer);
+                self.current = self.buffers.len() - 1;
+                self.refresh_git_marks();
+                self.check_swap();
+                true
+            }
+            Err(e) => {
+                log_warn!("unable to open {}: {}", filename, e);
+                self.message = Some(format!("Unable to open {}: {}", filename, e));
+                false
+            }
+        }
+    }
+
+    /// Returns false if the buffer wasn't saved, either because that failed
+    /// or because it's the scratch buffer and a name is being asked for
+    fn save_current(&mut self) -> bool {
+        if self.buf().filename == SCRATCH_BUFFER {
+            self.begin_prompt(EditorMode::SaveAs);
+            return false;
+        }
+
+        match self.buf_mut().save() {
+            Ok(()) => {
+                self.message = Some(format!("Saved {}", self.buf().filename));
+                self.refresh_git_marks();
+                self.drop_swap(self.current);
+                true
+            }
+            Err(e) => {
+                log_error!("unable to save {}: {}", self.buf().filename, e);
+                self.message = Some(format!("Unable to save: {}", e));
+                false
+            }
+        }
+    }
+
+    /// Put the current file's absolute path on the clipboard, and with
+    /// `with_line` the cursor's line number after it, like path:12
+    fn copy_path(&mut self, with_line: bool) {
+        let buf = self.buf();
+        if buf.filename.is_empty() || buf.filename.starts_with('*') {
+            self.message = Some(String::from("This buffer has no file"));
+            return;
+        }
+
+        let path = match std::path::absolute(&buf.filename) {
+            Ok(path) => path.to_string_lossy().into_owned(),
+            Err(e) => {
+                self.message = Some(format!("Unable to work out the path of {}: {}", buf.filename, e));
+                return;
+            }
+        };
+        let text = if with_line { format!("{}:{}", path, buf.buffer_row + 1) } else { path };
+        if let Some(clipboard) = &self.clipboard {
+            clipboard::set_clipboard_text(clipboard, &text);
+        }
+        self.message = Some(format!("Copied {}", text));
+    }
+
+    /// Save every modified buffer that has a file to go to. Any that can't be
+    /// saved stay modified and are listed in the panel with the reason.
+    fn save_all(&mut self, window_info: &WindowInfo) {
+        let current = self.current;
+        let (mut saved, mut scratch) = (0, false);
+        let mut failed = Vec::new();
+        for index in 0..self.buffers.len() {
+            let buf = &mut self.buffers[index];
+            if !buf.is_modified {
+                continue;
+            }
+            if buf.filename == SCRATCH_BUFFER {
+                scratch = true;
+                continue;
+            }
+            if buf.filename.is_empty() || buf.filename.starts_with('*') {
+                continue;
+            }
+
+            match buf.save() {
+                Ok(()) => {
+                    saved += 1;
+                    self.current = index;
+                    self.refresh_git_marks();
+                    self.drop_swap(index);
+                }
+                Err(e) => {
+                    log_error!("unable to save {}: {}", buf.filename, e);
+                    failed.push(format!("{:>2}  {}: {}", index + 1, buf.filename, e));
+                }
+            }
+        }
+        self.current = current;
+
+        let plural = |n: usize| if n == 1 { "" } else { "s" };
+        let mut message = match saved {
+            0 if failed.is_empty() && !scratch => String::from("No unsaved buffers"),
+            0 => String::from("Saved nothing"),
+            n => format!("Saved {} buffer{}", n, plural(n)),
+        };
+        if !failed.is_empty() {
+            message.push_str(&format!(", {} failed", failed.len()));
+        }
+        if scratch {
+            message.push_str(", scratch buffer skipped (no file name)");
+        }
+        self.message = Some(message);
+
+        if !failed.is_empty() {
+            let title = format!("Unable to save {} buffer{}", failed.len(), plural(failed.len()));
+            let panel = Panel::new(&title, failed, |editor, index, _| {
+                let number = editor.panel.as_ref()
+                    .and_then(|panel| panel.lines[index].split_whitespace().next()?.parse::<usize>().ok());
+                if let Some(number) = number.filter(|&n| n >= 1 && n <= editor.buffers.len()) {
+                    editor.current = number - 1;
+                    editor.panel_focused = false;
+                }
+            });
+            self.open_panel(panel, window_info);
+        }
+    }
+
+    /// Save the current buffer under a new name, which it keeps from then
+    /// on. Writing over some other file that already exists has to be
+    /// confirmed first.
+    fn save_as(&mut self, filename: &str) {
+        self.saving_selection = false;
+        if filename != self.buf().filename && Path::new(filename).exists() {
+            self.overwrite = Some(filename.to_string());
+            self.mode = EditorMode::ConfirmOverwrite;
+            return;
+        }
+
+        self.save_as_confirmed(filename);
+    }
+
+    /// Write just the selected text to a file, leaving the buffer and its
+    /// name alone. Like save-as, an existing file is only written over once
+    /// that's confirmed.
+    fn write_selection(&mut self, filename: &str) {
+        if self.buf().selection().is_none() {
+            self.message = Some(String::from("Nothing selected"));
+            return;
+        }
+
+        if Path::new(filename).exists() {
+            self.overwrite = Some(filename.to_string());
+            self.saving_selection = true;
+            self.mode = EditorMode::ConfirmOverwrite;
+            return;
+        }
+
+        self.write_selection_confirmed(filename);
+    }
+
+    fn write_selection_confirmed(&mut self, filename: &str) {
+        let buf = self.buf();
+        let Some((start, end)) = buf.selection() else {
+            return;
+        };
+
+        let text = buf.text_between(start, end);
+        let count = text.lines().count();
+        let text = text.replace('\n', buf.line_ending.as_str());
+        match fs::write(filename, text) {
+            Ok(()) => {
+                let plural = if count == 1 { "" } else { "s" };
+                self.message = Some(format!("Wrote {} line{} to {}", count, plural, filename));
+            }
+            Err(e) => {
+                log_error!("unable to write the selection to {}: {}", filename, e);
+                self.message = Some(format!("Unable to write {}: {}", filename, e));
+            }
+        }
+    }
+
+    fn insert_file(&mut self, filename: &str, window_info: &WindowInfo) {
+        if self.buf().read_only {
+            self.message = Some(format!("{} is read-only", self.buf().filename));
+            return;
+        }
+
+        match self.buf_mut().insert_file(filename, window_info) {
+            Ok(0) => self.message = Some(format!("{} is empty", filename)),
+            Ok(count) => {
+                let plural = if count == 1 { "" } else { "s" };
+                self.message = Some(format!("Inserted {} line{} from {}", count, plural, filename));
+            }
+            Err(e) => {
+                log_warn!("unable to insert {}: {}", filename, e);
+                self.message = Some(format!("Unable to insert {}: {}", filename, e));
+            }
+        }
+    }
+
+    fn save_as_confirmed(&mut self, filename: &str) {
+        // The swap file goes with the old name
+        self.drop_swap(self.current);
+        let old = std::mem::take(&mut self.buf_mut().filename);
+        self.buf_mut().set_filename(filename);
+        if !self.save_current() {
+            self.buf_mut().set_filename(&old);
+            return;
+        }
+
+        let buf = &mut self.buffers[self.current];
+        apply_settings(&self.config, buf, filename);
+        buf.apply_editorconfig();
+    }
+
+    /// Read the current buffer's file again as `name` encoding, for when it
+    /// isn't the UTF-8 it was taken to be
+    fn reopen(&mut self, name: &str, window_info: &WindowInfo) {
+        let encoding = match encoding::Encoding::parse(name) {
+            Ok(encoding) => encoding,
+            Err(e) => {
+                self.message = Some(e);
+                return;
+            }
+        };
+        let buf = self.buf_mut();
+        if buf.filename.is_empty() || !Path::new(&buf.filename).is_file() {
+            self.message = Some(String::from("Only files on disk can be reopened"));
+            return;
+        }
+        if buf.is_modified {
+            self.message = Some(format!("{} has unsaved changes", buf.filename));
+            return;
+        }
+
+        match buf.reopen_as(encoding, window_info) {
+            Ok(()) => self.message = Some(format!("Reopened {} as {}", buf.filename, encoding.name())),
+            Err(e) => {
+                log_warn!("unable to reopen {} as {}: {}", buf.filename, encoding.name(), e);
+                self.message = Some(format!("Unable to read {} as {}: {}", buf.filename, encoding.name(), e));
+            }
+        }
+    }
+
+    /// List the settings the current buffer ended up with and where each
+    /// came from
+    fn show_settings(&mut self) {
+        let buf = self.buf();
+        let filetype = self.config.filetype(&buf.filename).cloned().unwrap_or_default();
+        let props = editorconfig::properties_for(Path::new(&buf.filename));
+        let defaults = Config::default();
+        let section = format!("[filetype.{}]", config::filetype_key(&buf.filename).unwrap_or_default());
+        let from = |editorconfig: bool, in_filetype: bool, configured: bool| -> String {
+            if editorconfig {
+                String::from("editorconfig")
+            } else if in_filetype {
+                section.clone()
+            } else if configured {
+                String::from("config")
+            } else {
+                String::from("default")
+            }
+        };
+        let on_off = |b: bool| if b { "on" } else { "off" };
+
+        let indent_from = match buf.indent_from {
+            "config" if filetype.indent.is_some() => section.clone(),
+            "config" if self.config.indent == defaults.indent => String::from("default"),
+            other => other.to_string(),
+        };
+        let tab_from = from(props.tab_width.is_some(), filetype.tab_width.is_some(),
+            self.config.tab_width != defaults.tab_width);
+        let auto_from = from(false, filetype.auto_indent.is_some(), self.config.auto_indent != defaults.auto_indent);
+        let trim_from = from(props.trim_trailing_whitespace.is_some(), filetype.trim_trailing_whitespace.is_some(),
+            self.config.trim_trailing_whitespace != defaults.trim_trailing_whitespace);
+        let fill_from = from(false, filetype.fill_column.is_some(), self.config.fill_column != defaults.fill_column);
+        let wrap_from = from(false, filetype.auto_wrap.is_some(), self.config.auto_wrap != defaults.auto_wrap);
+        let word_from = from(false, filetype.word_chars.is_some(), self.config.word_chars != defaults.word_chars);
+
+        self.info = Some(vec![
+            format!("Settings for {}", buf.filename),
+            format!("Indent: {} ({})", buf.indent.describe(), indent_from),
+            format!("Tab width: {} ({})", buf.tab_width, tab_from),
+            format!("Auto-indent: {} ({})", on_off(buf.auto_indent), auto_from),
+            format!("Trim trailing whitespace: {} ({})", on_off(buf.trim_trailing_whitespace), trim_from),
+            format!("Fill column: {} ({})", buf.fill_column, fill_from),
+            format!("Auto-wrap: {} ({})", on_off(buf.auto_wrap), wrap_from),
+            format!("Extra word chars: {} ({})", if buf.word_chars.is_empty() { "none" } else { &buf.word_chars }, word_from),
+        ]);
+    }
+
+    /// Offer to recover the current buffer's file from a swap file left by a
+    /// wfemto that didn't exit cleanly
+    fn check_swap(&mut self) {
+        let filename = &self.buf().filename;
+        let Some(found) = swap::read(filename) else {
+            return;
+        };
+        if found.is_live() {
+            self.message = Some(format!("{} is also open in another wfemto (pid {})", filename, found.pid));
+            return;
+        }
+
+        log_info!("found a swap file for {} from pid {}", filename, found.pid);
+        self.recovery = Some(found);
+        self.mode = EditorMode::ConfirmRecover;
+    }
+
+    /// Put the text from the swap file into the buffer, as an unsaved change
+    fn recover(&mut self, window_info: &WindowInfo) {
+        let Some(found) = self.recovery.take() else {
+            return;
+        };
+
+        let buf = self.buf_mut();
+        let count = buf.lines.len();
+        buf.replace_lines(0, count, found.lines, window_info);
+        self.message = Some(String::from("Recovered unsaved changes"));
+    }
+
+    fn discard_recovery(&mut self) {
+        self.recovery = None;
+        swap::remove(&self.buf().filename);
+    }
+
+    /// Every few seconds, copy buffers with unsaved changes that have changed
+    /// since last time to their swap files, and remove the swap files of
+    /// ones that no longer have unsaved changes
+    fn write_swaps(&mut self) {
+        if self.last_swap.elapsed() < swap::INTERVAL {
+            return;
+        }
+        self.last_swap = std::time::Instant::now();
+
+        for buf in &self.buffers {
+            if !swap::wanted(&buf.filename) || buf.read_only {
+                continue;
+            }
+            if buf.is_modified && self.swapped.get(&buf.id) != Some(&buf.generation) {
+                match swap::write(&buf.filename, &buf.lines) {
+                    Ok(()) => _ = self.swapped.insert(buf.id, buf.generation),
+                    Err(e) => log_warn!("unable to write swap file for {}: {}", buf.filename, e),
+                }
+            } else if !buf.is_modified && self.swapped.remove(&buf.id).is_some() {
+                swap::remove(&buf.filename);
+            }
+        }
+    }
+
+    fn toggle_watch(&mut self) {
+        let buf = self.buf_mut();
+        if buf.filename.is_empty() || !Path::new(&buf.filename).is_file() {
+            self.message = Some(String::from("Only files on disk can be watched"));
+            return;
+        }
+
+        buf.watch = !buf.watch;
+        buf.tail &= buf.watch;
+        let state = if buf.watch { "Watching" } else { "Stopped watching" };
+        self.message = Some(format!("{} {}", state, buf.filename));
+    }
+
+    /// Follow the end of the file as it grows, like tail -f. Running it again
+    /// goes back to the end if following was paused, otherwise stops.
+    fn toggle_tail(&mut self, window_info: &WindowInfo) {
+        let buf = self.buf_mut();
+        if buf.filename.is_empty() || !Path::new(&buf.filename).is_file() {
+            self.message = Some(String::from("Only files on disk can be followed"));
+            return;
+        }
+
+        if buf.following() {
+            buf.tail = false;
+            buf.watch = false;
+            self.message = Some(format!("Stopped following {}", buf.filename));
+            return;
+        }
+
+        buf.tail = true;
+        buf.watch = true;
+        buf.anchor = None;
+        buf.goto_position(buf.lines.len() - 1, 0, window_info);
+        self.message = Some(format!("Following {}", buf.filename));
+    }
+
+    /// Once a second, reload watched buffers whose files have changed, or for
+    /// tailed ones read what's been added. One with unsaved changes isn't
+    /// touched; there's just a warning.
+    fn poll_watched(&mut self, window_info: &WindowInfo) {
+        if self.last_watch.elapsed() < WATCH_INTERVAL {
+            return;
+        }
+        self.last_watch = std::time::Instant::now();
+
+        for buf in self.buffers.iter_mut().filter(|b| b.watch && b.changed_on_disk()) {
+            if buf.is_modified {
+                self.message = Some(format!("{} changed on disk, but has unsaved changes here", buf.filename));
+                // Warn once per change rather than every second
+                buf.disk_mtime = fs::metadata(&buf.filename).and_then(|m| m.modified()).ok();
+                continue;
+            }
+            let read = if buf.tail { buf.read_appended(window_info) } else { buf.reload(window_info) };
+            if let Err(e) = read {
+                log_warn!("unable to reload {}: {}", buf.filename, e);
+                self.message = Some(format!("Unable to reload {}: {}", buf.filename, e));
+                buf.watch = false;
+                buf.tail = false;
+            }
+        }
+    }
+
+    /// The focused buffer's top line as drawn, which is part way between two
+    /// lines while a page movement is scrolling
+    fn scroll_position(&mut self) -> f32 {
+        let (id, top) = (self.buf().id, self.buf().top_line);
+        let Some(anim) = &self.scroll_anim else {
+            return top as f32;
+        };
+
+        // Anything else that scrolls the buffer, or leaves it, ends the animation
+        let t = anim.start.elapsed().as_secs_f32() / SCROLL_TIME.as_secs_f32();
+        if anim.buffer != id || anim.to != top || t >= 1.0 {
+            self.scroll_anim = None;
+            return top as f32;
+        }
+
+        let eased = 1.0 - (1.0 - t).powi(3);
+        anim.from + (top as f32 - anim.from) * eased
+    }
+
+    /// Scroll from where the view was drawn at `from` to the buffer's top line
+    /// gradually
+    fn animate_scroll(&mut self, from: f32) {
+        let buf = self.buf();
+        if buf.top_line as f32 != from {
+            self.scroll_anim = Some(ScrollAnim {
+                buffer: buf.id,
+                from,
+                to: buf.top_line,
+                start: std::time::Instant::now(),
+            });
+        }
+    }
+
+    fn selection_key(&self) -> Option<SelectionKey> {
+        let buf = self.buf();
+        buf.selection().map(|range| (buf.id, buf.generation, range))
+    }
+
+    /// Make the selection the primary selection once it's stopped changing,
+    /// so selecting with Shift+movement doesn't set it on every keystroke
+    fn publish_selection(&mut self) {
+        if !clipboard::HAS_PRIMARY || self.dragging {
+            return;
+        }
+        let Some(key) = self.selection_key() else {
+            self.selection_seen = None;
+            return;
+        };
+
+        match self.selection_seen {
+            Some((seen, since)) if seen == key => {
+                if since.elapsed() >= PRIMARY_DELAY && self.published != Some(key) {
+                    let (_, _, (start, end)) = key;
+                    if let Some(clipboard) = &self.clipboard {
+                        clipboard::publish_primary(clipboard, &self.buf().text_between(start, end));
+                    }
+                    self.published = Some(key);
+                }
+            }
+            _ => self.selection_seen = Some((key, std::time::Instant::now())),
+        }
+    }
+
+    /// Remove the swap file of the buffer at `index`, if it has one
+    fn drop_swap(&mut self, index: usize) {
+        let buf = &self.buffers[index];
+        if self.swapped.remove(&buf.id).is_some() {
+            swap::remove(&buf.filename);
+        }
+    }
+
+    fn export_current(&mut self) {
+        match export::export_html(self.buf(), &self.theme) {
+            Ok(path) => self.message = Some(format!("Exported to {}", path)),
+            Err(e) => {
+                log_error!("unable to export {}: {}", self.buf().filename, e);
+                self.message = Some(format!("Unable to export: {}", e));
+            }
+        }
+    }
+
+    /// Start the formatter configured for the current file's extension
+    fn format_current(&mut self) {
+        if self.job.is_some() {
+            self.message = Some(String::from("Already running a command"));
+            return;
+        }
+
+        let buf = self.buf();
+        let ext = Path::new(&buf.filename).extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or("");
+        let Some(command) = self.config.formatters.get(ext) else {
+            self.message = Some(format!("No formatter configured for .{} files", ext));
+            return;
+        };
+
+        let mut input = buf.lines.join("\n");
+        input.push('\n');
+        self.job = Some(FilterJob {
+            filter: Filter::spawn(command, input, external::FILTER_TIMEOUT),
+            buffer_id: buf.id,
+            generation: buf.generation,
+            target: FilterTarget::Format,
+        });
+    }
+
+    /// Send the selection (or the whole buffer) through a shell command and
+    /// replace it with what comes out.
+    fn pipe_through(&mut self, command: &str) {
+        if command.trim().is_empty() {
+            return;
+        }
+        if self.job.is_some() {
+            self.message = Some(String::from("Already running a command"));
+            return;
+        }
+        if self.buf().read_only {
+            self.message = Some(format!("{} is read-only", self.buf().filename));
+            return;
+        }
+
+        let buf = self.buf();
+        let (input, target) = match buf.selection() {
+            Some((start, end)) => {
+                let text = buf.text_between(start, end);
+                let newline = text.ends_with('\n');
+                (text, FilterTarget::Selection(start, end, newline))
+            }
+            None => {
+                let mut text = buf.lines.join("\n");
+                text.push('\n');
+                (text, FilterTarget::Buffer)
+            }
+        };
+
+        self.job = Some(FilterJob {
+            filter: Filter::spawn(command, input, external::FILTER_TIMEOUT),
+            buffer_id: buf.id,
+            generation: buf.generation,
+            target,
+        });
+    }
+
+    /// Check on a running command and apply its output if it has finished
+    fn poll_job(&mut self, window_info: &WindowInfo) {
+        let Some(result) = self.job.as_ref().and_then(|job| job.filter.poll()) else {
+            return;
+        };
+        let job = self.job.take().unwrap();
+        let command = job.filter.command.split_whitespace().next().unwrap_or("").to_string();
+
+        let output = match result {
+            Ok(output) => output,
+            Err(e) => {
+                log_warn!("{} failed: {}", job.filter.command, e);
+                self.message = Some(format!("{}: {}", command, e));
+                return;
+            }
+        };
+
+        let Some(index) = self.buffers.iter().position(|b| b.id == job.buffer_id) else {
+            return;
+        };
+        if self.buffers[index].generation != job.generation {
+            self.message = Some(format!("Buffer changed while {} was running; not applied", command));
+            return;
+        }
+
+        // The buffer may be in the unfocused pane, but the two panes are
+        // within a line of each other in height, which is close enough for
+        // scrolling the cursor into view.
+        let view_info = self.view_info(window_info);
+        let buf = &mut self.buffers[index];
+        let generation = buf.generation;
+        match job.target {
+            FilterTarget::Format | FilterTarget::Buffer => {
+                let end = buf.lines.len();
+                buf.replace_lines(0, end, external::output_lines(&output), &view_info);
+            }
+            FilterTarget::Selection(start, end, newline) => {
+                let text = match output.strip_suffix('\n') {
+                    Some(trimmed) if !newline => trimmed,
+                    _ => output.as_str(),
+                };
+                buf.replace_text(start, end, text, &view_info);
+            }
+        }
+
+        self.message = Some(match job.target {
+            _ if buf.generation == generation => format!("{}: no changes", command),
+            FilterTarget::Format => format!("Formatted with {}", command),
+            _ => format!("Replaced with output of {}", command),
+        });
+    }
+
+    /// Diff the current buffer's file against HEAD in the background. Called
+    /// when the file is loaded or saved, so what's on disk is the buffer.
+    fn refresh_git_marks(&mut self) {
+        let buf = self.buf();
+        let Some((dir, args)) = gitdiff::diff_command(Path::new(&buf.filename)) else {
+            return;
+        };
+
+        let (id, generation) = (buf.id, buf.generation);
+        self.git_jobs.retain(|job| job.buffer_id != id);
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        self.git_jobs.push(GitJob {
+            filter: Filter::spawn_program("git", &args, &dir, external::FILTER_TIMEOUT),
+            buffer_id: id,
+            generation,
+        });
+    }
+
+    /// Pick up finished diffs. Any failure just means no markers, and a diff
+    /// of a buffer that's been edited since is stale and dropped.
+    fn poll_git_jobs(&mut self) {
+        let mut i = 0;
+        while i < self.git_jobs.len() {
+            let Some(result) = self.git_jobs[i].filter.poll() else {
+                i += 1;
+                continue;
+            };
+            let job = self.git_jobs.remove(i);
+
+            let Some(buf) = self.buffers.iter_mut().find(|b| b.id == job.buffer_id) else {
+                continue;
+            };
+            match result {
+                Ok(diff) if buf.generation == job.generation => {
+                    buf.git_marks = gitdiff::parse(&diff, buf.lines.len());
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    log_debug!("no git markers for {}: {}", buf.filename, e);
+                    buf.git_marks.clear();
+                }
+            }
+        }
+    }
+
+    /// Report counts for the selection, or the whole buffer if there isn't one
+    fn show_counts(&mut self) {
+        let buf = self.buf();
+        self.message = Some(match buf.selection() {
+            Some((start, end)) => {
+                let text = buf.text_between(start, end);
+                let body = text.strip_suffix('\n');
+                let lines: Vec<&str> = body.unwrap_or(&text).split('\n').collect();
+                format!("Selection: {}", count::count(lines.iter().copied(), body.is_some()).describe())
+            }
+            None => count::count(buf.lines.iter().map(String::as_str), true).describe(),
+        });
+    }
+
+    /// Describe the current file and where the cursor is in it
+    fn show_info(&mut self) {
+        let buf = self.buf_mut();
+        let (row, col) = (buf.buffer_row, buf.buffer_col);
+        let (char_offset, byte_offset) = buf.offset_of(row, col);
+        let last = buf.lines.len() - 1;
+        let (chars, mut bytes) = buf.offset_of(last, usize::MAX);
+        if buf.final_newline && !(last == 0 && buf.lines[0].is_empty()) {
+            bytes += buf.line_ending.as_str().len();
+        }
+
+        let path = std::path::absolute(&buf.filename)
+            .map(|p| p.display().to_string())
+            .unwrap_or_else(|_| buf.filename.clone());
+        let bom = if buf.bom { " with BOM" } else { "" };
+        self.info = Some(vec![
+            path,
+            format!("{} bytes, {} lines, {} chars", bytes, buf.lines.len(), chars),
+            format!("{}{}, {} line endings", buf.encoding.name(), bom, buf.line_ending.name()),
+            format!("Line {}, column {}", row + 1, col + 1),
+            format!("Offset {} chars, {} bytes", char_offset, byte_offset),
+            String::from(if buf.is_modified { "Modified" } else { "Unmodified" }),
+        ]);
+    }
+
+    fn insert_date(&mut self, window_info: &WindowInfo) {
+        let date = DateTime::now_local().format(&self.config.date_format);
+        self.buf_mut().insert_text(&date, window_info);
+    }
+
+    fn insert_snippet(&mut self, name: &str, window_info: &WindowInfo) {
+        let Some(template) = self.config.snippets.get(name) else {
+            let names: Vec<&str> = self.config.snippets.keys().map(String::as_str).collect();
+            self.message = Some(if names.is_empty() {
+                String::from("No snippets configured")
+            } else {
+                format!("Snippets: {}", names.join(", "))
+            });
+            return;
+        };
+
+        let date = DateTime::now_local().format(&self.config.date_format);
+        let expansion = snippet::expand(template, &date);
+        let buf = self.buf_mut();
+        let start = buf.insert_text(&expansion.text, window_info);
+        if let Some(offset) = expansion.cursor {
+            let (row, col) = snippet::position_after(start, &expansion.text, offset);
+            buf.goto_position(row, col, window_info);
+        }
+    }
+
+    /// Insert the character named by a codepoint or name. A bad one is
+    /// reported and returns false.
+    fn insert_codepoint(&mut self, input: &str, window_info: &WindowInfo) -> bool {
+        match unicode::parse(input) {
+            Ok(c) => {
+                self.buf_mut().insert_char(c, window_info);
+                true
+            }
+            Err(e) => {
+                self.message = Some(e);
+                false
+            }
+        }
+    }
+
+    /// Complete the word before the cursor: straight away if there's only one
+    /// way to, otherwise by offering a list
+    fn start_completion(&mut self, window_info: &WindowInfo) {
+        let buf = self.buf();
+        if buf.read_only || buf.anchor.is_some() {
+            return;
+        }
+
+        let (row, col) = (buf.buffer_row, buf.buffer_col);
+        let (start, candidates) = complete::candidates(&buf.lines, row, col);
+        let prefix = text::col_slice(&buf.lines[row], start, col).to_string();
+        let completion = Completion { row, start, prefix, list: ListPopup::new(candidates) };
+        match completion.list.items.len() {
+            0 => self.message = Some(String::from("No completions")),
+            1 => self.accept_completion(completion, window_info),
+            _ => self.completion = Some(completion),
+        }
+    }
+
+    fn accept_completion(&mut self, completion: Completion, window_info: &WindowInfo) {
+        let word = &completion.list.items[completion.list.selected];
+        self.buf_mut().insert_text(&word[completion.prefix.len()..], window_info);
+    }
+
+    /// Keys for the completion list. Returns false for keys it doesn't use,
+    /// which also close it.
+    fn completion_key(&mut self, keycode: Keycode, window_info: &WindowInfo) -> bool {
+        let Some(completion) = self.completion.as_mut() else {
+            return false;
+        };
+
+        if completion.list.navigate(keycode) {
+            return true;
+        }
+        match keycode {
+            Keycode::Return | Keycode::Tab => {
+                let completion = self.completion.take().unwrap();
+                self.accept_completion(completion, window_info);
+            }
+            Keycode::Escape => self.completion = None,
+            _ => {
+                self.completion = None;
+                return false;
+            }
+        }
+
+        true
+    }
+
+    /// Copy the selection into the clipboard ring and onto the system
+    /// clipboard, and for a cut delete it as well
+    fn copy_selection(&mut self, cut: bool, window_info: &WindowInfo) {
+        let Some(text) = self.buf().selected_text() else {
+            self.message = Some(String::from("Nothing selected"));
+            return;
+        };
+
+        self.ring.push(&text);
+        if let Some(clipboard) = &self.clipboard {
+            clipboard::set_clipboard_text(clipboard, &text);
+        }
+        if !cut {
+            return;
+        }
+        if self.buf().read_only {
+            self.message = Some(format!("{} is read-only; copied instead", self.buf().filename));
+            return;
+        }
+        self.buf_mut().delete_selection(window_info);
+    }
+
+    /// Cut from the cursor to the end of the line, or at the end of the line
+    /// cut the line break
+    fn kill_line(&mut self, window_info: &WindowInfo) {
+        let buf = self.buf_mut();
+        let (row, col) = (buf.buffer_row, buf.buffer_col);
+        let end = if col < char_len(&buf.lines[row]) {
+            (row, char_len(&buf.lines[row]))
+        } else if row + 1 < buf.lines.len() {
+            (row + 1, 0)
+        } else {
+            return;
+        };
+
+        buf.anchor = Some(end);
+        self.copy_selection(true, window_info);
+    }
+
+    /// Fill the paragraph the cursor is in, or every paragraph in the
+    /// selected lines, to the buffer's fill column
+    fn reflow(&mut self, window_info: &WindowInfo) {
+        let buf = self.buf();
+        if buf.read_only {
+            self.message = Some(format!("{} is read-only", buf.filename));
+            return;
+        }
+
+        let rows = if buf.selection().is_some() {
+            Some(buf.selected_rows())
+        } else {
+            reflow::paragraph(&buf.lines, buf.buffer_row)
+        };
+        let Some((start, end)) = rows else {
+            self.message = Some(String::from("Not in a paragraph"));
+            return;
+        };
+
+        let cursor = Some((buf.buffer_row, buf.buffer_col))
+            .filter(|&(row, _)| (start..end).contains(&row))
+            .map(|(row, col)| (row - start, col));
+        let (lines, moved) = reflow::reflow(&buf.lines[start..end], buf.fill_column, cursor);
+        let buf = self.buf_mut();
+        buf.replace_lines(start, end, lines, window_info);
+        if let Some((row, col)) = moved {
+            buf.goto_position(start + row, col, window_info);
+        }
+    }
+
+    /// Delete the cursor's line, keeping it in the clipboard and the ring
+    fn delete_line(&mut self, window_info: &WindowInfo) {
+        let buf = self.buf();
+        if buf.read_only {
+            self.message = Some(format!("{} is read-only", buf.filename));
+            return;
+        }
+        // Nothing to cut, and the ring and clipboard shouldn't fill up with it
+        if buf.lines == [""] {
+            return;
+        }
+
+        let text = format!("{}\n", buf.lines[buf.buffer_row.min(buf.lines.len() - 1)]);
+        self.ring.push(&text);
+        if let Some(clipboard) = &self.clipboard {
+            clipboard::set_clipboard_text(clipboard, &text);
+        }
+        self.buf_mut().delete_line(window_info);
+    }
+
+    /// Cut the selection, or if there isn't one the word before the cursor
+    fn kill_word(&mut self, window_info: &WindowInfo) {
+        let buf = self.buf_mut();
+        if buf.selection().is_none() {
+            let start = text::prev_word_start(&buf.lines[buf.buffer_row], buf.buffer_col, &buf.word_chars);
+            buf.anchor = Some((buf.buffer_row, start));
+        }
+        self.copy_selection(true, window_info);
+    }
+
+    /// Paste from the system clipboard. Text copied in another program joins
+    /// the ring as the newest entry.
+    fn paste(&mut self, window_info: &WindowInfo) {
+        if let Some(text) = self.clipboard.as_ref().and_then(clipboard::clipboard_text) {
+            self.ring.push(&text);
+        }
+        self.paste_entry(0, window_info);
+    }
+
+    /// Paste entry `index` of the clipboard ring at the cursor
+    fn paste_entry(&mut self, index: usize, window_info: &WindowInfo) {
+        let Some(text) = self.ring.get(index).map(String::from) else {
+            return;
+        };
+        if !self.hold_big_paste(&text, Some(index)) {
+            self.paste_text(&text, Some(index), window_info);
+        }
+    }
+
+    /// Paste `text`, remembering which ring entry it came from (if it did)
+    /// so that PasteRing straight after can swap it for the next
+    fn paste_text(&mut self, text: &str, index: Option<usize>, window_info: &WindowInfo) {
+        let Some(start) = self.insert_pasted(text, window_info) else {
+            return;
+        };
+        let Some(index) = index else {
+            return;
+        };
+
+        let buf = self.buf();
+        self.last_paste = Some(Paste {
+            buffer_id: buf.id,
+            generation: buf.generation,
+            start,
+            end: (buf.buffer_row, buf.buffer_col),
+            index,
+        });
+    }
+
+    /// Hold back a paste big enough that it was likely a mistake, and ask
+    /// about it. Returns false, to go ahead, for anything smaller.
+    fn hold_big_paste(&mut self, text: &str, index: Option<usize>) -> bool {
+        let (max_lines, max_bytes) = (self.config.paste_confirm_lines, self.config.paste_confirm_bytes);
+        let lines = text.lines().count();
+        let big = (max_lines > 0 && lines >= max_lines) || (max_bytes > 0 && text.len() >= max_bytes);
+        if !big || self.buf().read_only {
+            return false;
+        }
+
+        self.held_paste = Some((text.to_string(), index));
+        self.mode = EditorMode::ConfirmPaste;
+        true
+    }
+
+    /// Answer the question about a big paste. A yes only says it's pasting
+    /// for now; the paste itself waits for finish_paste(), after a frame, so
+    /// there's something on screen while it goes in.
+    fn confirm_paste(&mut self, paste: bool) {
+        self.mode = EditorMode::Edit;
+        if paste && let Some((text, _)) = &self.held_paste {
+            self.message = Some(format!("Pasting {} lines…", text.lines().count()));
+            self.paste_due = true;
+        } else if self.held_paste.take().is_some() {
+            self.message = Some(String::from("Paste cancelled"));
+        }
+    }
+
+    /// Make the big paste that was said yes to, if there is one
+    fn finish_paste(&mut self, window_info: &WindowInfo) {
+        if !std::mem::take(&mut self.paste_due) {
+            return;
+        }
+        if let Some((text, index)) = self.held_paste.take() {
+            self.paste_text(&text, index, &self.view_info(window_info));
+        }
+    }
+
+    /// Insert pasted text, tidied up for the buffer, as one undoable step.
+    /// Returns where it starts, or None if the buffer can't be changed.
+    fn insert_pasted(&mut self, text: &str, window_info: &WindowInfo) -> Option<(usize, usize)> {
+        if self.buf().read_only {
+            self.message = Some(format!("{} is read-only", self.buf().filename));
+            return None;
+        }
+
+        let indent = self.config.paste_indent.then_some(self.buf().indent);
+        let text = paste::normalize(text, indent, self.buf().tab_width);
+        let lines = text.split('\n').count();
+        if lines > 1 {
+            self.message = Some(format!("Pasted {} lines", lines));
+        }
+
+        Some(self.buf_mut().insert_text(&text, window_info))
+    }
+
+    /// Straight after a paste, swap the pasted text for the next older entry
+    /// in the ring. Otherwise list the ring to pick from.
+    fn paste_from_ring(&mut self, window_info: &WindowInfo) {
+        let count = self.ring.len();
+        let buf = &mut self.buffers[self.current];
+        if let Some(paste) = self.last_paste.take()
+            && paste.buffer_id == buf.id
+            && paste.generation == buf.generation
+            && paste.end == (buf.buffer_row, buf.buffer_col)
+        {
+            let index = (paste.index + 1) % count;
+            let indent = self.config.paste_indent.then_some(buf.indent);
+            let text = paste::normalize(self.ring.get(index).unwrap_or(""), indent, buf.tab_width);
+            buf.replace_text(paste.start, paste.end, &text, window_info);
+            buf.anchor = None;
+            self.last_paste = Some(Paste {
+                generation: buf.generation,
+                end: (buf.buffer_row, buf.buffer_col),
+                index,
+                ..paste
+            });
+            self.message = Some(format!("Clipboard ring {} of {}", index + 1, count));
+            return;
+        }
+
+        if self.ring.is_empty() {
+            self.message = Some(String::from("Nothing has been copied"));
+            return;
+        }
+        self.ring_picker = Some(ListPopup::new(self.ring.summaries()));
+    }
+
+    /// Keys for the clipboard ring list, which takes every key while it's
+    /// open. Typed digits are picked up by ring_picker_text().
+    fn ring_picker_key(&mut self, keycode: Keycode, window_info: &WindowInfo) -> bool {
+        let Some(picker) = self.ring_picker.as_mut() else {
+            return false;
+        };
+
+        if picker.navigate(keycode) {
+            return true;
+        }
+        match keycode {
+            Keycode::Return | Keycode::Tab => {
+                let picker = self.ring_picker.take().unwrap();
+                self.paste_entry(picker.selected, window_info);
+            }
+            Keycode::Escape => self.ring_picker = None,
+            _ => {}
+        }
+
+        true
+    }
+
+    /// A digit typed with the clipboard ring list open pastes that entry
+    fn ring_picker_text(&mut self, text: &str, window_info: &WindowInfo) {
+        let Some(digit) = text.chars().next().and_then(|c| c.to_digit(10)) else {
+            return;
+        };
+        let index = (digit as usize + 9) % 10;
+        if index < self.ring.len() {
+            self.ring_picker = None;
+            self.paste_entry(index, window_info);
+        }
+    }
+
+    /// Switch to the buffer holding `path`, or open it if there isn't one
+    fn visit_file(&mut self, path: &Path) -> bool {
+        match self.buffer_for(path) {
+            Some(index) => {
+                self.current = index;
+                true
+            }
+            None => self.open_file(&path.to_string_lossy()),
+        }
+    }
+
+    /// The buffer that already has `path` open, however it was named: the
+    /// paths are compared with symlinks and . and .. resolved
+    fn buffer_for(&self, path: &Path) -> Option<usize> {
+        let target = fs::canonicalize(path).ok()?;
+        self.buffers.iter().position(|b| fs::canonicalize(&b.filename).is_ok_and(|p| p == target))
+    }
+
+    /// Open `filename` in a buffer of its own even if it's open already, to
+    /// compare with the other. Without a name, the current buffer's file.
+    fn open_copy(&mut self, filename: &str) {
+        let filename = if filename.is_empty() { self.buf().filename.clone() } else { filename.to_string() };
+        let copy = self.buffer_for(Path::new(&filename)).is_some();
+        if self.open_file(&filename) {
+            self.buf_mut().copy = copy;
+            if copy {
+                self.message = Some(format!("Opened another copy of {}", filename));
+            }
+        }
+    }
+
+    /// Keep the files open, where each is scrolled to and which is in front
+    /// as session `name`, which is then saved again on quitting. Buffers
+    /// without a file behind them are left out.
+    fn save_session(&mut self, name: &str) {
+        if name.is_empty() {
+            self.message = Some(String::from("save-session needs a name"));
+            return;
+        }
+
+        let mut session = Session::default();
+        for (index, buf) in self.buffers.iter().enumerate() {
+            if buf.filename == SCRATCH_BUFFER || !Path::new(&buf.filename).is_file() {
+                continue;
+            }
+            if index == self.current {
+                session.current = session.files.len();
+            }
+            let path = fs::canonicalize(&buf.filename).map_or(buf.filename.clone(), |p| p.to_string_lossy().into_owned());
+            session.files.push(SessionFile {
+                path,
+                row: buf.buffer_row,
+                col: buf.buffer_col,
+                top_line: buf.top_line,
+                col_offset: buf.buffer_col_offset,
+            });
+        }
+
+        let count = session.files.len();
+        self.message = Some(match session::save(name, &session) {
+            Ok(()) => {
+                self.session_name = Some(name.to_string());
+                format!("Saved session {} ({} file{})", name, count, if count == 1 { "" } else { "s" })
+            }
+            Err(e) => e,
+        });
+    }
+
+    /// Open the files of session `name` where they were left. Files that
+    /// have gone since are skipped and listed. Returns whether any opened.
+    fn load_session(&mut self, name: &str) -> bool {
+        if name.is_empty() {
+            self.message = Some(String::from("load-session needs a name"));
+            return false;
+        }
+        let session = match session::load(name) {
+            Ok(session) => session,
+            Err(e) => {
+                self.message = Some(e);
+                return false;
+            }
+        };
+
+        let mut front = None;
+        let mut missing = Vec::new();
+        for (i, file) in session.files.iter().enumerate() {
+            if !Path::new(&file.path).is_file() || !self.visit_file(Path::new(&file.path)) {
+                missing.push(file.path.as_str());
+                continue;
+            }
+            self.buf_mut().set_view(View {
+                buffer_row: file.row,
+                buffer_col: file.col,
+                desired_col: file.col,
+                top_line: file.top_line,
+                buffer_col_offset: file.col_offset,
+            });
+            if i == session.current || front.is_none() {
+                front = Some(self.current);
+            }
+        }
+
+        let opened = session.files.len() - missing.len();
+        if let Some(index) = front {
+            self.current = index;
+        }
+        self.session_name = Some(name.to_string());
+        let mut message = format!("Loaded session {} ({} file{})", name, opened, if opened == 1 { "" } else { "s" });
+        if !missing.is_empty() {
+            message.push_str(&format!(", skipped missing {}", missing.join(", ")));
+        }
+        self.message = Some(message);
+
+        opened > 0
+    }
+
+    /// Start building the project the current file belongs to
+    fn compile(&mut self) {
+        if self.build.is_some() {
+            self.message = Some(String::from("Already compiling"));
+            return;
+        }
+
+        let configured = self.config.compile_command.as_deref();
+        let Some((command, dir)) = compile::build_command(Path::new(&self.buf().filename), configured) else {
+            self.message = Some(String::from("No compile_command configured"));
+            return;
+        };
+
+        self.build = Some(Build {
+            filter: Filter::spawn_build(&command, &dir, external::BUILD_TIMEOUT),
+            dir,
+        });
+    }
+
+    /// When the build finishes, put its output in the compile buffer and
+    /// collect the locations it mentions. A failed build brings the output
+    /// into view.
+    fn poll_build(&mut self, window_info: &WindowInfo) {
+        let Some(result) = self.build.as_ref().and_then(|build| build.filter.poll_finished()) else {
+            return;
+        };
+        let build = self.build.take().unwrap();
+        let command = &build.filter.command;
+
+        let finished = match result {
+            Ok(finished) => finished,
+            Err(e) => {
+                log_warn!("{} failed: {}", command, e);
+                self.message = Some(format!("{}: {}", command, e));
+                return;
+            }
+        };
+
+        let mut output = self.new_buffer();
+        output.lines = external::output_lines(&finished.stdout);
+        output.set_filename(COMPILE_BUFFER);
+        output.read_only = true;
+        self.errors = compile::parse_locations(&output.lines, &build.dir);
+        self.error_index = None;
+
+        let index = match self.buffers.iter().position(|b| b.filename == COMPILE_BUFFER) {
+            Some(index) => {
+                self.buffers[index] = output;
+                index
+            }
+            None => {
+                self.buffers.push(output);
+                self.buffers.len() - 1
+            }
+        };
+        if !finished.status.success() {
+            self.current = index;
+            self.buf_mut().goto_line(0, 0, window_info);
+        }
+
+        let plural = if self.errors.len() == 1 { "" } else { "s" };
+        log_info!("{} finished: {}", command, finished.status);
+        self.message = Some(format!("{}: {}, {} location{}", command, finished.status, self.errors.len(), plural));
+    }
+
+    /// Go to the next (or previous) location from the last build, opening
+    /// its file if need be
+    fn step_error(&mut self, forward: bool, window_info: &WindowInfo) {
+        let count = self.errors.len();
+        if count == 0 {
+            self.message = Some(String::from("No errors"));
+            return;
+        }
+
+        let index = match (self.error_index, forward) {
+            (None, true) => 0,
+            (None, false) => count - 1,
+            (Some(i), true) => (i + 1) % count,
+            (Some(i), false) => (i + count - 1) % count,
+        };
+        self.error_index = Some(index);
+        let location = self.errors[index].clone();
+
+        // Keep the output buffer's cursor on the message being visited
+        if let Some(output) = self.buffers.iter_mut().find(|b| b.filename == COMPILE_BUFFER) {
+            output.goto_line(location.output_row, 0, window_info);
+        }
+
+        if !self.visit_file(&location.file) {
+            return;
+        }
+        self.buf_mut().mark_jump();
+        self.buf_mut().goto_line(location.line - 1, location.col - 1, window_info);
+        self.message = Some(format!("Location {} of {}", index + 1, count));
+    }
+
+    /// Search the files under the current file's directory for `pattern`,
+    /// with the results streaming into the grep buffer
+    fn start_grep(&mut self, pattern: &str, window_info: &WindowInfo) {
+        if pattern.is_empty() {
+            return;
+        }
+        if let Some(running) = self.grep.take() {
+            running.cancel();
+        }
+
+        let dir = std::path::absolute(&self.buf().filename).ok()
+            .and_then(|p| p.parent().map(Path::to_path_buf))
+            .unwrap_or_default();
+        let search = grep::Grep::spawn(pattern, &dir, self.config.ignore_case);
+
+        let mut output = self.new_buffer();
+        output.lines = vec![format!("Searching {} for '{}'", dir.display(), pattern)];
+        output.set_filename(GREP_BUFFER);
+        output.read_only = true;
+        match self.buffers.iter().position(|b| b.filename == GREP_BUFFER) {
+            Some(index) => {
+                self.buffers[index] = output;
+                self.current = index;
+            }
+            None => {
+                self.buffers.push(output);
+                self.current = self.buffers.len() - 1;
+            }
+        }
+        self.buf_mut().goto_line(0, 0, window_info);
+
+        self.grep_dir = dir;
+        self.grep = Some(search);
+    }
+
+    /// Add whatever the search has found since last time to the grep buffer.
+    /// Closing the buffer stops the search.
+    fn poll_grep(&mut self) {
+        let Some(search) = self.grep.as_mut() else {
+            return;
+        };
+        let Some(output) = self.buffers.iter_mut().find(|b| b.filename == GREP_BUFFER) else {
+            self.cancel_grep();
+            return;
+        };
+
+        let (results, done) = match search.poll() {
+            grep::Progress::Running(results) => (results, false),
+            grep::Progress::Done(results) => (results, true),
+        };
+        output.lines.extend(results);
+        if !done {
+            return;
+        }
+
+        let plural = if search.found == 1 { "" } else { "es" };
+        let summary = format!("{} match{} for '{}' in {}", search.found, plural, search.pattern, search.root.display());
+        log_info!("grep finished in {:?}: {}", search.started.elapsed(), summary);
+        output.lines[0] = summary;
+        self.message = Some(format!("{} match{}", search.found, plural));
+        self.grep = None;
+    }
+
+    fn cancel_grep(&mut self) {
+        if let Some(search) = self.grep.take() {
+            search.cancel();
+            self.message = Some(format!("Search cancelled after {} matches", search.found));
+        }
+    }
+
+    /// Open the file and line the grep result under the cursor points at
+    fn open_grep_result(&mut self, window_info: &WindowInfo) {
+        let buf = self.buf();
+        // The first line is what was searched for, not a result
+        if buf.buffer_row == 0 {
+            return;
+        }
+        let Some((file, line)) = grep::parse_result(&buf.lines[buf.buffer_row]) else {
+            return;
+        };
+
+        let path = self.grep_dir.join(file);
+        if self.visit_file(&path) {
+            self.buf_mut().mark_jump();
+            self.buf_mut().goto_line(line - 1, 0, window_info);
+        }
+    }
+
+    /// Open the file named under the cursor, at the line and column after
+    /// the name if it has them. A relative name is looked for beside the
+    /// current file first, then from the working directory.
+    fn goto_file(&mut self, window_info: &WindowInfo) {
+        let buf = self.buf();
+        let Some(target) = pathref::path_at(&buf.lines[buf.buffer_row], buf.buffer_col) else {
+            self.message = Some(String::from("No file name under the cursor"));
+            return;
+        };
+
+        let path = Path::new(&target.path);
+        let places = match Path::new(&buf.filename).parent() {
+            _ if path.is_absolute() => vec![path.to_path_buf()],
+            Some(dir) => vec![dir.join(path), path.to_path_buf()],
+            None => vec![path.to_path_buf()],
+        };
+        let Some(found) = places.into_iter().find(|p| p.is_file()) else {
+            self.message = Some(format!("Can't find a file called '{}'", target.path));
+            return;
+        };
+
+        if self.visit_file(&found) {
+            let buf = self.buf_mut();
+            buf.mark_jump();
+            if let Some(line) = target.line {
+                let col = target.col.unwrap_or(1);
+                buf.goto_line(line.saturating_sub(1), col.saturating_sub(1), window_info);
+            }
+        }
+    }
+
+    /// Look up `name` (or the word under the cursor) in the nearest tags file
+    /// and go to its definition, asking which one if there are several
+    fn jump_to_tag(&mut self, name: &str, window_info: &WindowInfo) {
+        let buf = self.buf();
+        let name = match name {
+            "" => {
+                let line = &buf.lines[buf.buffer_row];
+                match text::word_at(line, buf.buffer_col) {
+                    Some((start, end)) => text::col_slice(line, start, end).to_string(),
+                    None => {
+                        self.message = Some(String::from("No word under the cursor"));
+                        return;
+                    }
+                }
+            }
+            name => name.to_string(),
+        };
+
+        let dir = std::path::absolute(&buf.filename).ok()
+            .and_then(|p| p.parent().map(Path::to_path_buf))
+            .unwrap_or_default();
+        let Some(tags_file) = tags::find_tags_file(&dir) else {
+            self.message = Some(String::from("No tags file found"));
+            return;
+        };
+
+        match tags::lookup(&tags_file, &name) {
+            Ok(found) if found.is_empty() => self.message = Some(format!("No tag '{}'", name)),
+            Ok(mut found) if found.len() == 1 => self.goto_tag(found.remove(0), window_info),
+            Ok(found) => {
+                let items = found.iter().map(tags::Tag::describe).collect();
+                self.tag_picker = Some(TagPicker { tags: found, list: ListPopup::new(items) });
+            }
+            Err(e) => {
+                log_warn!("unable to read {}: {}", tags_file.display(), e);
+                self.message = Some(format!("Unable to read {}: {}", tags_file.display(), e));
+            }
+        }
+    }
+
+    /// Open the tag's file (or switch to it, if it's already open) and move
+    /// to the definition, remembering where we were for tag_back()
+    fn goto_tag(&mut self, tag: tags::Tag, window_info: &WindowInfo) {
+        let from = (self.buf().id, self.buf().buffer_row, self.buf().buffer_col);
+        if !self.visit_file(&tag.file) {
+            return;
+        }
+        self.tag_stack.push(from);
+
+        let buf = self.buf_mut();
+        let Some(row) = tag.address.locate(&buf.lines) else {
+            self.message = Some(format!("Couldn't find '{}' in {}", tag.name, tag.file.display()));
+            return;
+        };
+        let col = buf.lines[row].find(&tag.name)
+            .map(|i| text::char_len(&buf.lines[row][..i]))
+            .unwrap_or(0);
+        buf.mark_jump();
+        buf.goto_position(row, col, window_info);
+    }
+
+    /// Go back to where the last tag jump started
+    fn tag_back(&mut self, window_info: &WindowInfo) {
+        let Some((id, row, col)) = self.tag_stack.pop() else {
+            self.message = Some(String::from("Tag stack is empty"));
+            return;
+        };
+        let Some(index) = self.buffers.iter().position(|b| b.id == id) else {
+            self.message = Some(String::from("That buffer has been closed"));
+            return;
+        };
+
+        self.current = index;
+        self.buf_mut().goto_position(row, col, window_info);
+    }
+
+    /// Keys for the tag picker, which takes every key while it's open
+    fn tag_picker_key(&mut self, keycode: Keycode, window_info: &WindowInfo) -> bool {
+        let Some(picker) = self.tag_picker.as_mut() else {
+            return false;
+        };
+
+        if picker.list.navigate(keycode) {
+            return true;
+        }
+        match keycode {
+            Keycode::Return | Keycode::Tab => {
+                let mut picker = self.tag_picker.take().unwrap();
+                let tag = picker.tags.swap_remove(picker.list.selected);
+                self.goto_tag(tag, window_info);
+            }
+            Keycode::Escape => self.tag_picker = None,
+            _ => {}
+        }
+
+        true
+    }
+
+    fn spell_checker(&mut self) -> &mut spell::Checker {
+        self.spell.get_or_insert_with(|| {
+            let places = bundled_places(spell::WORDS_PATH);
+            spell::Checker::load(places.iter().find(|p| p.is_file()).map(PathBuf::as_path))
+        })
+    }
+
+    /// The unknown words in `rows` lines of buffer `index` from `top`: all
+    /// of them in prose, only those in comments and strings in code
+    fn misspelled(&mut self, index: usize, top: usize, rows: usize) -> Vec<Match> {
+        let buf = &self.buffers[index];
+        let prose = spell::is_prose(&buf.filename);
+        if !self.config.spell_check || (!prose && buf.language.is_none()) {
+            return Vec::new();
+        }
+
+        self.spell_checker();
+        let Some(checker) = self.spell.as_mut() else {
+            return Vec::new();
+        };
+        let buf = &self.buffers[index];
+        let mut found = Vec::new();
+        for row in top..(top + rows).min(buf.lines.len()) {
+            let line = &buf.lines[row];
+            let unknown = checker.unknown_words(line);
+            if unknown.is_empty() {
+                continue;
+            }
+
+            let checked: Vec<(usize, usize)> = match buf.language.filter(|_| !prose) {
+                Some(lang) => highlight::highlight_line(line, lang, buf.hl_state(row)).0.iter()
+                    .filter(|span| matches!(span.kind, TokenKind::Comment | TokenKind::String))
+                    .map(|span| (span.start, span.end))
+                    .collect(),
+                None => vec![(0, usize::MAX)],
+            };
+            found.extend(unknown.iter()
+                .filter(|&&(start, end)| checked.iter().any(|&(from, to)| from <= start && end <= to))
+                .map(|&(start, end)| Match { row, col: start, len: end - start }));
+        }
+
+        found
+    }
+
+    /// Offer other spellings for the word at the cursor, or to add it to the
+    /// user's word list
+    fn spell_suggest(&mut self) {
+        let buf = self.buf();
+        let (row, col) = (buf.buffer_row, buf.buffer_col);
+        let Some((start, end)) = spell::word_at(&buf.lines[row], col) else {
+            self.message = Some(String::from("No word at the cursor"));
+            return;
+        };
+        let word = text::col_slice(&buf.lines[row], start, end).to_string();
+
+        let checker = self.spell_checker();
+        if checker.is_empty() {
+            self.message = Some(format!("No word list found; put a {} beside the font", spell::WORDS_PATH));
+            return;
+        }
+        if checker.is_known(&word) {
+            self.message = Some(format!("'{}' is spelled right", word));
+            return;
+        }
+
+        let mut items = checker.suggest(&word);
+        items.push(format!("Add '{}' to your words", word));
+        let (buffer_id, generation) = (self.buf().id, self.buf().generation);
+        self.spell_picker = Some(SpellPicker { buffer_id, generation, row, start, end, word, list: ListPopup::new(items) });
+    }
+
+    /// Close the list of spellings if the text it was offered for has
+    /// changed or gone out of sight
+    fn drop_stale_spell_picker(&mut self) {
+        let buf = self.buf();
+        if self.spell_picker.as_ref().is_some_and(|p| p.buffer_id != buf.id || p.generation != buf.generation) {
+            self.spell_picker = None;
+        }
+    }
+
+    /// Keys for the list of spellings, which takes every key while it's open
+    fn spell_picker_key(&mut self, keycode: Keycode, window_info: &WindowInfo) -> bool {
+        self.drop_stale_spell_picker();
+        let Some(picker) = self.spell_picker.as_mut() else {
+            return false;
+        };
+
+        if picker.list.navigate(keycode) {
+            return true;
+        }
+        match keycode {
+            Keycode::Return | Keycode::Tab => {
+                let picker = self.spell_picker.take().unwrap();
+                if picker.list.selected + 1 == picker.list.items.len() {
+                    let added = self.spell_checker().add(&picker.word);
+                    self.message = Some(match added {
+                        Ok(()) => format!("Added '{}' to your words", picker.word),
+                        Err(e) => format!("Couldn't add '{}': {}", picker.word, e),
+                    });
+                } else {
+                    let spelling = &picker.list.items[picker.list.selected];
+                    let buf = self.buf_mut();
+                    let row = picker.row.min(buf.lines.len() - 1);
+                    let len = text::char_len(&buf.lines[row]);
+                    let (start, end) = (picker.start.min(len), picker.end.min(len));
+                    buf.replace_text((row, start), (row, end), spelling, window_info);
+                    buf.anchor = None;
+                }
+            }
+            Keycode::Escape => self.spell_picker = None,
+            _ => {}
+        }
+
+        true
+    }
+
+    /// Select the next TODO-style marker after the cursor, wrapping around
+    fn next_marker(&mut self, window_info: &WindowInfo) {
+        let query = Query { needles: &self.config.todo_markers, whole_word: true, ignore_case: false };
+        let buf = &self.buffers[self.current];
+        let Some(found) = query.find_next(&buf.lines, (buf.buffer_row, buf.buffer_col + 1)) else {
+            self.message = Some(String::from("No markers found"));
+            return;
+        };
+
+        let buf = self.buf_mut();
+        buf.mark_jump();
+        buf.goto_position(found.row, found.col + found.len, window_info);
+        buf.anchor = Some((found.row, found.col));
+    }
+
+    /// The key list, one "key  command" line per binding, split into pages
+    /// that fit in `rows` with room for the box's border and a footer
+    fn help_pages(&self, rows: u32) -> Vec<Vec<String>> {
+        let bindings = keymap::active_bindings(self.config.keys, self.config.modal);
+        let width = bindings.iter().map(|(key, _)| char_len(key)).max().unwrap_or(0);
+        let lines: Vec<String> = bindings.iter()
+            .map(|(key, command)| format!("{:<width$}  {}", key, commands::describe(*command)))
+            .collect();
+
+        let per_page = (rows as usize).saturating_sub(4).max(1);
+        lines.chunks(per_page).map(<[String]>::to_vec).collect()
+    }
+
+    /// Keys for the help box, which takes every key while it's open
+    fn help_key(&mut self, keycode: Keycode, rows: u32) -> bool {
+        let Some(page) = self.help else {
+            return false;
+        };
+
+        let pages = self.help_pages(rows).len();
+        match keycode {
+            Keycode::Escape | Keycode::F1 => self.help = None,
+            Keycode::PageDown | Keycode::Down | Keycode::Space if page + 1 < pages => self.help = Some(page + 1),
+            Keycode::PageUp | Keycode::Up if page > 0 => self.help = Some(page - 1),
+            _ => {}
+        }
+
+        true
+    }
+
+    /// The word under the cursor, as (start, end) columns and its text
+    fn word_under_cursor(&self) -> Option<(usize, usize, String)> {
+        let buf = self.buf();
+        let line = &buf.lines[buf.buffer_row];
+        let (start, end) = text::word_at(line, buf.buffer_col)?;
+        Some((start, end, text::col_slice(line, start, end).to_string()))
+    }
+
+    /// Ask what to rename the word under the cursor to, starting from the
+    /// word itself
+    fn begin_rename(&mut self) {
+        let Some((_, _, word)) = self.word_under_cursor() else {
+            self.message = Some(String::from("No word under the cursor"));
+            return;
+        };
+
+        self.begin_prompt(EditorMode::Rename);
+        word.chars().for_each(|c| self.prompt.insert(c));
+    }
+
+    /// Replace every whole-word occurrence of the word under the cursor with
+    /// `new_name`, as one undoable step. The cursor stays on the renamed word.
+    fn rename_word(&mut self, new_name: &str, window_info: &WindowInfo) {
+        let Some((start, end, old_name)) = self.word_under_cursor() else {
+            self.message = Some(String::from("No word under the cursor"));
+            return;
+        };
+        if self.buf().read_only {
+            self.message = Some(format!("{} is read-only", self.buf().filename));
+            return;
+        }
+
+        let skip_literals = self.rename_skips_literals && self.buf().language.is_some();
+        if skip_literals {
+            let count = self.buf().lines.len();
+            self.buf_mut().update_highlight(count);
+        }
+
+        let buf = self.buf();
+        let needles = [old_name.clone()];
+        let query = Query { needles: &needles, whole_word: true, ignore_case: false };
+        let in_literal = |row: usize, col: usize| {
+            let Some(lang) = buf.language.filter(|_| skip_literals) else {
+                return false;
+            };
+            highlight::highlight_line(&buf.lines[row], lang, buf.hl_state(row)).0.iter()
+                .any(|s| s.start <= col && col < s.end && matches!(s.kind, TokenKind::String | TokenKind::Comment))
+        };
+
+        let mut renamed: Vec<(usize, String)> = Vec::new();
+        let mut count = 0;
+        let mut cursor_shift = 0;
+        for row in 0..buf.lines.len() {
+            let line = &buf.lines[row];
+            let found: Vec<Match> = query.find_all(&buf.lines, row..row + 1).into_iter()
+                .filter(|m| !in_literal(row, m.col))
+                .collect();
+            if found.is_empty() {
+                continue;
+            }
+
+            let mut new_line = String::new();
+            let mut col = 0;
+            for m in &found {
+                new_line.push_str(text::col_slice(line, col, m.col));
+                new_line.push_str(new_name);
+                col = m.col + m.len;
+                if row == buf.buffer_row && m.col < start {
+                    cursor_shift += 1;
+                }
+            }
+            new_line.push_str(text::col_slice(line, col, char_len(line)));
+            count += found.len();
+            renamed.push((row, new_line));
+        }
+
+        let Some((&(first, _), &(last, _))) = renamed.first().zip(renamed.last()) else {
+            self.message = Some(format!("No occurrences of '{}' to rename", old_name));
+            return;
+        };
+
+        // Lines in between that had nothing to rename are carried over as is
+        let mut new_lines: Vec<String> = buf.lines[first..=last].to_vec();
+        for (row, line) in renamed {
+            new_lines[row - first] = line;
+        }
+
+        let new_len = char_len(new_name);
+        let offset = (buf.buffer_col - start).min(new_len);
+        let row = buf.buffer_row;
+        let col = start + cursor_shift * new_len - cursor_shift * (end - start) + offset;
+        let buf = self.buf_mut();
+        buf.replace_lines(first, last + 1, new_lines, window_info);
+        buf.goto_position(row, col, window_info);
+
+        let plural = if count == 1 { "" } else { "s" };
+        self.message = Some(format!("Renamed {} occurrence{} of '{}'", count, plural, old_name));
+    }
+
+    /// Sort/reverse/dedup the selected lines (or all of them), leaving the
+    /// result selected
+    fn transform_lines(&mut self, op: lineops::LineOp, window_info: &WindowInfo) {
+        let buf = self.buf_mut();
+        if buf.read_only {
+            self.message = Some(format!("{} is read-only", buf.filename));
+            return;
+        }
+
+        let (start, end) = buf.selected_rows();
+        let (lines, affected) = lineops::apply(op, &buf.lines[start..end]);
+        let new_end = start + lines.len();
+        buf.replace_lines(start, end, lines, window_info);
+        buf.select_rows(start, new_end, window_info);
+        self.message = Some(lineops::describe(op, end - start, affected));
+    }
+
+    /// Collapse runs of blank lines in the selection, or the whole buffer,
+    /// down to one
+    fn squeeze_blanks(&mut self, window_info: &WindowInfo) {
+        let buf = self.buf_mut();
+        if buf.read_only {
+            self.message = Some(format!("{} is read-only", buf.filename));
+            return;
+        }
+
+        let (start, end) = buf.selected_rows();
+        let keep = lineops::squeeze_blanks(&buf.lines[start..end]);
+        let removed = buf.retain_rows(start, end, &keep, window_info);
+        let plural = if removed == 1 { "" } else { "s" };
+        self.message = Some(format!("Removed {} blank line{}", removed, plural));
+    }
+
+    /// Delete every line in the selection, or the whole buffer, that
+    /// `pattern` appears in
+    fn delete_matching(&mut self, pattern: &str, window_info: &WindowInfo) {
+        let needles = vec![pattern.to_string()];
+        let query = Query { needles: &needles, whole_word: false, ignore_case: self.config.ignore_case };
+        let buf = self.buf_mut();
+        if buf.read_only {
+            self.message = Some(format!("{} is read-only", buf.filename));
+            return;
+        }
+
+        let (start, end) = buf.selected_rows();
+        let keep: Vec<bool> = buf.lines[start..end].iter().map(|line| !query.is_match(line)).collect();
+        let removed = buf.retain_rows(start, end, &keep, window_info);
+        let plural = if removed == 1 { "" } else { "s" };
+        self.message = Some(format!("Deleted {} line{} containing '{}'", removed, plural, pattern));
+    }
+
+    /// Run the command a prompt was opened for with what was typed into it.
+    /// Returns true if the command was to quit and it's fine to do so.
+    fn accept_prompt(&mut self, window_info: &WindowInfo) -> bool {
+        let text = self.prompt.text.clone();
+        let mode = std::mem::replace(&mut self.mode, EditorMode::Edit);
+        self.end_prompt();
+
+        let command = match mode {
+            EditorMode::OpenFile => Command::Open,
+            EditorMode::PipeCommand => Command::Pipe,
+            EditorMode::Grep => Command::Grep,
+            EditorMode::InsertChar => Command::InsertChar,
+            EditorMode::Rename => Command::Rename,
+            EditorMode::SaveAs if std::mem::take(&mut self.saving_selection) => Command::WriteSelection,
+            EditorMode::SaveAs => Command::SaveAs,
+            EditorMode::InsertFile => Command::InsertFile,
+            EditorMode::DeleteMatching => Command::DeleteMatching,
+            EditorMode::Reopen => Command::Reopen,
+            EditorMode::Palette => match commands::parse(&text) {
+                Ok((command, arg)) => {
+                    self.palette_history.retain(|entry| *entry != text);
+                    self.palette_history.push(text.clone());
+                    if self.palette_history.len() > PALETTE_HISTORY {
+                        self.palette_history.remove(0);
+                    }
+                    return self.execute(command, arg, window_info);
+                }
+                Err(e) => {
+                    self.message = Some(e);
+                    return false;
+                }
+            },
+            _ => return false,
+        };
+
+        !text.is_empty() && self.execute(command, &text, window_info)
+    }
+
+    /// Run a command, adding it to the macro being recorded if there is one.
+    /// A command that opens a prompt is recorded once the prompt has been
+    /// answered, with the answer as its argument, so playing it back doesn't
+    /// stop to ask again. The same goes for remembering it for RepeatLast.
+    fn execute(&mut self, command: Command, arg: &str, window_info: &WindowInfo) -> bool {
+        let quit = self.run_command(command, arg, window_info);
+
+        let macro_control = matches!(command, Command::RecordMacro | Command::StopMacro | Command::PlayMacro);
+        let prompting = self.in_prompt();
+        if command.is_repeatable() && !prompting {
+            match &mut self.last_change {
+                Some((Command::Insert, typed, _)) if command == Command::Insert && self.typing => typed.push_str(arg),
+                last => *last = Some((command, arg.to_string(), 1)),
+            }
+        }
+        self.typing = command == Command::Insert;
+
+        if let Some(recording) = &mut self.recording
+            && !macro_control
+            && !prompting
+        {
+            // Typing is kept as one step rather than one per keystroke
+            match recording.last_mut() {
+                Some((Command::Insert, typed)) if command == Command::Insert => typed.push_str(arg),
+                _ => recording.push((command, arg.to_string())),
+            }
+        }
+
+        quit
+    }
+
+    /// Run a command `times` times over. Typing is repeated as one insert,
+    /// and a command that opens a prompt only runs once.
+    fn execute_times(&mut self, command: Command, arg: &str, times: usize, window_info: &WindowInfo) -> bool {
+        if command == Command::Insert {
+            return self.execute(command, &arg.repeat(times), window_info);
+        }
+
+        for _ in 0..times {
+            if self.execute(command, arg, window_info) {
+                return true;
+            }
+            if self.in_prompt() {
+                break;
+            }
+        }
+        let prompting = self.in_prompt();
+        if let Some((last, _, count)) = &mut self.last_change
+            && *last == command
+            && !prompting
+        {
+            *count = times;
+        }
+
+        false
+    }
+
+    /// Do the last change again at the cursor: the same command, with the
+    /// same answer to its prompt, as many times over as it was done
+    fn repeat_last(&mut self, window_info: &WindowInfo) -> bool {
+        let Some((command, arg, times)) = self.last_change.clone() else {
+            self.message = Some(String::from("Nothing to repeat"));
+            return false;
+        };
+
+        let arg = if command == Command::Insert { arg.repeat(times) } else { arg };
+        let times = if command == Command::Insert { 1 } else { times };
+        for _ in 0..times {
+            if self.run_command(command, &arg, window_info) {
+                return true;
+            }
+        }
+
+        false
+    }
+
+    /// A key pressed while editing, with whatever it does for the mode and
+    /// any popup or panel that's up. Returns true if it quits.
+    fn handle_key(&mut self, keycode: Keycode, mods: Mods, window_info: &WindowInfo) -> bool {
+        // Keys typed before the paste went in come after it
+        self.finish_paste(window_info);
+        self.message = None;
+        self.chord_typed = false;
+        // A click earlier in the same batch of events may have changed panes
+        self.track_buffer_switch();
+
+        // The info box goes away on any key, and Escape does no more than that
+        if self.info.take().is_some() && keycode == Keycode::Escape {
+            return false;
+        }
+
+        if self.help_key(keycode, window_info.rows) {
+            return false;
+        }
+
+        let view_info = self.view_info(window_info);
+
+        // The y/n prompts swallow every other key
+        if self.mode == EditorMode::ConfirmQuit {
+            return self.quit_key(keycode);
+        }
+
+        if self.mode == EditorMode::ConfirmOverwrite {
+            let filename = self.overwrite.take().unwrap_or_default();
+            match keycode {
+                Keycode::Y => {
+                    self.mode = EditorMode::Edit;
+                    if std::mem::take(&mut self.saving_selection) {
+                        self.write_selection_confirmed(&filename);
+                    } else {
+                        self.save_as_confirmed(&filename);
+                    }
+                }
+                Keycode::N | Keycode::Escape => self.mode = EditorMode::Edit,
+                _ => self.overwrite = Some(filename),
+            }
+            return false;
+        }
+
+        if self.mode == EditorMode::ConfirmPaste {
+            match keycode {
+                Keycode::Y => self.confirm_paste(true),
+                Keycode::N | Keycode::Escape => self.confirm_paste(false),
+                _ => {}
+            }
+            return false;
+        }
+
+        if self.mode == EditorMode::ConfirmRecover {
+            match keycode {
+                Keycode::Y => self.recover(&view_info),
+                Keycode::N | Keycode::Escape => self.discard_recovery(),
+                _ => return false,
+            }
+            self.mode = EditorMode::Edit;
+            return false;
+        }
+
+        if self.mode == EditorMode::ConfirmClose {
+            match keycode {
+                Keycode::Y => {
+                    self.save_current();
+                    if !self.buf().is_modified {
+                        self.close_current();
+                    }
+                    self.mode = EditorMode::Edit;
+                }
+                Keycode::N => {
+                    self.close_current();
+                    self.mode = EditorMode::Edit;
+                }
+                Keycode::Escape => self.mode = EditorMode::Edit,
+                _ => {}
+            }
+            return false;
+        }
+
+        if self.mode == EditorMode::Edit && self.completion_key(keycode, &view_info) {
+            return false;
+        }
+        if self.mode == EditorMode::Edit && self.tag_picker_key(keycode, &view_info) {
+            return false;
+        }
+        if self.mode == EditorMode::Edit && self.ring_picker_key(keycode, &view_info) {
+            return false;
+        }
+        if self.mode == EditorMode::Edit && self.spell_picker_key(keycode, &view_info) {
+            return false;
+        }
+        if self.mode == EditorMode::Edit && self.panel_key(keycode, mods, window_info) {
+            return false;
+        }
+
+        if self.in_prompt() {
+            match keycode {
+                Keycode::Return => {
+                    // A codepoint that doesn't parse leaves the prompt open to fix it
+                    if self.mode == EditorMode::InsertChar
+                        && let Err(e) = unicode::parse(&self.prompt.text)
+                    {
+                        self.message = Some(e);
+                        return false;
+                    }
+
+                    let before = (self.buf().id, self.buf().generation);
+                    if self.accept_prompt(window_info) {
+                        return true;
+                    }
+                    if (self.buf().id, self.buf().generation) != before {
+                        self.splash = false;
+                    }
+                }
+                Keycode::Escape => self.end_prompt(),
+                Keycode::Tab if self.mode == EditorMode::Rename => {
+                    self.rename_skips_literals = !self.rename_skips_literals;
+                }
+                Keycode::Up if self.mode == EditorMode::Palette => {
+                    self.prompt.recall(&self.palette_history, true);
+                }
+                Keycode::Down if self.mode == EditorMode::Palette => {
+                    self.prompt.recall(&self.palette_history, false);
+                }
+                Keycode::Tab => {
+                    let candidates = self.prompt.complete();
+                    if candidates.len() > 1 {
+                        self.message = Some(candidates.join("  "));
+                    }
+                }
+                // Emacs keys work in prompts whatever the key profile, since
+                // nothing else uses them there
+                Keycode::A if mods.ctrl => self.prompt.home(),
+                Keycode::E if mods.ctrl => self.prompt.end(),
+                Keycode::F if mods.ctrl => self.prompt.right(),
+                Keycode::B if mods.ctrl => self.prompt.left(),
+                Keycode::K if mods.ctrl => self.prompt.kill_to_end(),
+                Keycode::W if mods.ctrl => self.prompt.delete_word_back(),
+                Keycode::Backspace => self.prompt.backspace(),
+                Keycode::Delete => self.prompt.delete(),
+                Keycode::Left => self.prompt.left(),
+                Keycode::Right => self.prompt.right(),
+                Keycode::Home => self.prompt.home(),
+                Keycode::End => self.prompt.end(),
+                _ => {}
+            }
+            return false;
+        }
+
+        // Alt+digits build up a count for the next command. Cancel isn't
+        // repeated; it may be what throws the count away.
+        if let Some(digit) = keymap::digit(keycode)
+            && mods == (Mods { alt: true, ..Mods::default() })
+        {
+            self.add_repeat_digit(digit);
+            return false;
+        }
+
+        // The second key of a two key command, or the first. Another modifier
+        // on its own is still waiting for its key.
+        if let Some((Prefix::Chord(first, first_mods), _)) = self.pending
+            && !keymap::is_modifier(keycode)
+        {
+            return self.chord_key((first, first_mods), keycode, mods, window_info);
+        }
+        if keymap::is_prefix(self.config.keys, keycode, mods) {
+            self.pending = Some((Prefix::Chord(keycode, mods), std::time::Instant::now()));
+            return false;
+        }
+
+        let Some(command) = keymap::lookup(self.config.keys, keycode, mods) else {
+            return false;
+        };
+        let before = (self.buf().id, self.buf().generation);
+        let times = if command == Command::Cancel { 1 } else { self.repeat.take().unwrap_or(1) };
+        if self.execute_times(command, "", times, window_info) {
+            return true;
+        }
+
+        // Only keys that do something to the buffer dismiss the splash;
+        // brushing a modifier or opening a prompt doesn't.
+        if matches!(command, Command::Move(..)) || (self.buf().id, self.buf().generation) != before {
+            self.splash = false;
+        }
+
+        false
+    }
+
+    /// Text typed while editing, after any key that produced it has been
+    /// through handle_key()
+    fn handle_text(&mut self, text: &str, window_info: &WindowInfo) {
+        self.finish_paste(window_info);
+        if self.help.is_some() || std::mem::take(&mut self.chord_typed) || self.spell_picker.is_some()
+            || (self.panel_focused && self.mode == EditorMode::Edit)
+        {
+            return;
+        }
+        if self.ring_picker.is_some() {
+            self.ring_picker_text(text, &self.view_info(window_info));
+            return;
+        }
+
+        self.completion = None;
+        self.composition.clear();
+        if self.in_prompt() {
+            text.chars().for_each(|c| self.prompt.insert(c));
+        } else if self.mode == EditorMode::Edit && self.normal {
+            let times = self.repeat.take().unwrap_or(1);
+            text.chars().for_each(|c| self.normal_key(c, times, window_info));
+        } else if self.mode == EditorMode::Edit {
+            self.splash = false;
+            let times = self.repeat.take().unwrap_or(1);
+            self.execute_times(Command::Insert, text, times, window_info);
+        }
+    }
+
+    /// A character typed in Normal mode
+    fn normal_key(&mut self, c: char, times: usize, window_info: &WindowInfo) {
+        let command = match self.pending.take() {
+            Some((Prefix::Normal(first), _)) => keymap::normal_pair(first, c),
+            _ if keymap::is_normal_prefix(c) => {
+                self.pending = Some((Prefix::Normal(c), std::time::Instant::now()));
+                return;
+            }
+            _ => keymap::normal_command(c),
+        };
+
+        if let Some(command) = command {
+            self.execute_times(command, "", times, window_info);
+        }
+    }
+
+    /// Escape puts away the most local thing in progress, one per press: the
+    /// selection, then the highlighted occurrences, then a repeat count and
+    /// the first key of a Normal mode pair. With none of those left it does
+    /// nothing, so a running grep is only stopped by closing its buffer.
+    /// Prompts, popups and the panel see Escape before it gets here. In
+    /// modal mode it also always goes back to Normal mode, as vi's does.
+    fn cancel(&mut self) {
+        self.normal = self.config.modal;
+
+        let buf = self.buf_mut();
+        if buf.selection().is_some() {
+            buf.anchor = None;
+            return;
+        }
+        buf.anchor = None;
+
+        if self.occurrences.dismiss() {
+            return;
+        }
+        self.repeat = None;
+        self.pending = None;
+    }
+
+    /// The key typed after the first of a two key binding. Escape gives up
+    /// on the binding. Returns true if the command run wants to quit.
+    fn chord_key(&mut self, first: (Keycode, Mods), keycode: Keycode, mods: Mods, window_info: &WindowInfo) -> bool {
+        self.pending = None;
+        self.chord_typed = true;
+        if keycode == Keycode::Escape {
+            return false;
+        }
+
+        let Some(command) = keymap::lookup_chord(self.config.keys, first, keycode, mods) else {
+            let (first_key, first_mods) = first;
+            let keys = format!("{} {}", keymap::key_name(first_key, first_mods), keymap::key_name(keycode, mods));
+            self.message = Some(format!("{} isn't bound", keys));
+            return false;
+        };
+        let times = self.repeat.take().unwrap_or(1);
+        self.execute_times(command, "", times, window_info)
+    }
+
+    /// Give up on the first key of a two key command after it's waited too
+    /// long for the second
+    fn expire_pending(&mut self) {
+        if self.pending.is_some_and(|(_, since)| since.elapsed() >= PENDING_TIMEOUT) {
+            self.pending = None;
+        }
+    }
+
+    /// Add a digit to the repeat count for the next command
+    fn add_repeat_digit(&mut self, digit: usize) {
+        let count = self.repeat.unwrap_or(0) * 10 + digit;
+        self.repeat = Some(count.min(MAX_REPEAT));
+    }
+
+    fn record_macro(&mut self) {
+        self.recording = Some(Vec::new());
+        self.message = Some(String::from("Recording macro"));
+    }
+
+    fn stop_macro(&mut self) {
+        let Some(recorded) = self.recording.take() else {
+            self.message = Some(String::from("Not recording"));
+            return;
+        };
+
+        let plural = if recorded.len() == 1 { "" } else { "s" };
+        self.message = Some(format!("Recorded {} step{}", recorded.len(), plural));
+        self.last_macro = recorded;
+    }
+
+    /// Replay the last macro `arg` times (once if it's empty), undoable in
+    /// one go in the buffer it starts in. Returns true if a step was to
+    /// quit and it's fine to do so.
+    fn play_macro(&mut self, arg: &str, window_info: &WindowInfo) -> bool {
+        let times = match arg {
+            "" => 1,
+            n => match n.parse::<usize>() {
+                Ok(n) => n,
+                Err(_) => {
+                    self.message = Some(format!("Expected a number of times, got '{}'", n));
+                    return false;
+                }
+            },
+        };
+        if self.recording.is_some() {
+            self.message = Some(String::from("Can't play a macro while recording one"));
+            return false;
+        }
+        if self.last_macro.is_empty() {
+            self.message = Some(String::from("No macro recorded"));
+            return false;
+        }
+
+        let steps = self.last_macro.clone();
+        let id = self.buf().id;
+        self.buf_mut().begin_undo_group();
+        let mut quit = false;
+        'replay: for _ in 0..times {
+            for (command, arg) in &steps {
+                if self.run_command(*command, arg, window_info) {
+                    quit = true;
+                    break 'replay;
+                }
+            }
+        }
+        if let Some(buf) = self.buffers.iter_mut().find(|b| b.id == id) {
+            buf.end_undo_group();
+        }
+
+        quit
+    }
+
+    fn run_command(&mut self, command: Command, arg: &str, window_info: &WindowInfo) -> bool {
+        let view_info = self.view_info(window_info);
+        match command {
+            Command::Insert => {
+                let buf = self.buf_mut();
+                arg.chars().for_each(|c| buf.insert_char(c, &view_info));
+            }
+            Command::Newline if self.buf().filename == GREP_BUFFER => self.open_grep_result(&view_info),
+            Command::Newline => self.buf_mut().insert_newline(&view_info),
+            Command::Backspace => self.buf_mut().backspace(&view_info),
+            Command::DeleteForward => self.buf_mut().delete_forward(&view_info),
+            Command::Move(motion, select) => {
+                let drawn_top = self.scroll_position();
+
+                // Shift+movement extends the selection, plain movement drops it
+                let buf = self.buf_mut();
+                buf.select_while_moving(select);
+                match motion {
+                    Motion::Left => buf.move_cursor_left(&view_info),
+                    Motion::Right => buf.move_cursor_right(&view_info),
+                    Motion::Up => buf.move_cursor_up(&view_info),
+                    Motion::Down => buf.move_cursor_down(&view_info),
+                    Motion::LineStart => buf.move_to_line_start(&view_info),
+                    // In a tailed buffer that's stopped following, End catches up
+                    Motion::LineEnd if buf.tail && !buf.following() => {
+                        buf.goto_position(buf.lines.len() - 1, 0, &view_info);
+                    }
+                    Motion::LineEnd => buf.move_to_line_end(&view_info),
+                    Motion::PageUp => buf.page_up(&view_info),
+                    Motion::PageDown => buf.page_down(&view_info),
+                    Motion::ParagraphUp => buf.move_paragraph(false, &view_info),
+                    Motion::ParagraphDown => buf.move_paragraph(true, &view_info),
+                    Motion::WordLeft => buf.move_word(false, &view_info),
+                    Motion::WordRight => buf.move_word(true, &view_info),
+                    Motion::BufferStart => {
+                        buf.mark_jump();
+                        buf.goto_position(0, 0, &view_info);
+                    }
+                    Motion::BufferEnd => {
+                        buf.mark_jump();
+                        buf.goto_position(buf.lines.len() - 1, 0, &view_info);
+                    }
+                }
+
+                // Moving the cursor a line or two scrolls at once; paging glides
+                if self.config.smooth_scroll && matches!(motion, Motion::PageUp | Motion::PageDown) {
+                    self.animate_scroll(drawn_top);
+                }
+            }
+            Command::DeleteLine => self.delete_line(&view_info),
+            Command::OpenLineBelow => self.buf_mut().open_line(false, &view_info),
+            Command::OpenLineAbove => self.buf_mut().open_line(true, &view_info),
+            Command::Reflow => self.reflow(&view_info),
+            Command::TransposeChars | Command::TransposeWords => {
+                let words = command == Command::TransposeWords;
+                if let Err(e) = self.buf_mut().transpose(words, &view_info) {
+                    self.message = Some(e);
+                }
+            }
+            Command::SelectLine => self.buf_mut().select_line(&view_info),
+            Command::InsertMode => self.normal = false,
+            Command::Append => {
+                self.buf_mut().move_cursor_right(&view_info);
+                self.normal = false;
+            }
+            Command::Tab => self.buf_mut().insert_tab(&view_info),
+            Command::Dedent => self.buf_mut().dedent_block(&view_info),
+            Command::Cancel => self.cancel(),
+            Command::Palette => self.begin_prompt(EditorMode::Palette),
+            Command::Copy => self.copy_selection(false, &view_info),
+            Command::Cut => self.copy_selection(true, &view_info),
+            Command::KillLine => self.kill_line(&view_info),
+            Command::KillWord => self.kill_word(&view_info),
+            Command::Paste => self.paste(&view_info),
+            Command::PasteRing => self.paste_from_ring(&view_info),
+            Command::Complete => self.start_completion(&view_info),
+            Command::RecordMacro => self.record_macro(),
+            Command::StopMacro => self.stop_macro(),
+            Command::PlayMacro => return self.play_macro(arg, window_info),
+            Command::RepeatLast => return self.repeat_last(window_info),
+            Command::Open if arg.is_empty() => self.begin_prompt(EditorMode::OpenFile),
+            Command::Open => match self.buffer_for(Path::new(arg)) {
+                Some(index) => {
+                    self.current = index;
+                    self.message = Some(format!("{} is already open, switched to it", arg));
+                }
+                None => _ = self.open_file(arg),
+            },
+            Command::OpenCopy => self.open_copy(arg),
+            Command::Save => _ = self.save_current(),
+            Command::SaveAll => self.save_all(window_info),
+            Command::CopyPath => self.copy_path(false),
+            Command::CopyPathLine => self.copy_path(true),
+            Command::GotoFile => self.goto_file(&view_info),
+            Command::SaveAs if arg.is_empty() => self.begin_prompt(EditorMode::SaveAs),
+            Command::SaveAs => self.save_as(arg),
+            Command::Scratch => self.goto_scratch(),
+            Command::WriteSelection if self.buf().selection().is_none() => {
+                self.message = Some(String::from("Nothing selected"));
+            }
+            Command::WriteSelection if arg.is_empty() => {
+                self.begin_prompt(EditorMode::SaveAs);
+                self.saving_selection = true;
+            }
+            Command::WriteSelection => self.write_selection(arg),
+            Command::InsertFile if arg.is_empty() => self.begin_prompt(EditorMode::InsertFile),
+            Command::InsertFile => self.insert_file(arg, &view_info),
+            Command::SqueezeBlanks => self.squeeze_blanks(&view_info),
+            Command::DeleteMatching if arg.is_empty() => self.begin_prompt(EditorMode::DeleteMatching),
+            Command::DeleteMatching => self.delete_matching(arg, &view_info),
+            Command::LineEndings(_) if self.buf().read_only => {
+                self.message = Some(format!("{} is read-only", self.buf().filename));
+            }
+            Command::LineEndings(ending) => {
+                let mixed = self.buf_mut().convert_line_endings(ending, &view_info);
+                self.message = Some(match mixed {
+                    0 => format!("Line endings are now {}", ending.name()),
+                    n => format!("Line endings are now {}, {} mixed ones normalized", ending.name(), n),
+                });
+            }
+            Command::CloseBuffer => self.request_close(),
+            Command::Quit => return self.request_quit(&view_info),
+            Command::NextBuffer => self.next_buffer(),
+            Command::PrevBuffer => self.prev_buffer(),
+            Command::AlternateBuffer => self.alternate_buffer(),
+            Command::Watch => self.toggle_watch(),
+            Command::Tail => self.toggle_tail(&view_info),
+            Command::ColumnGuide => self.column_guide = !self.column_guide,
+            Command::Minimap => self.minimap = !self.minimap,
+            Command::LineNumbers if arg.is_empty() => {
+                self.line_numbers = self.line_numbers.next();
+                self.message = Some(format!("Line numbers: {}", self.line_numbers.name()));
+            }
+            Command::LineNumbers => match LineNumbers::parse(arg) {
+                Ok(numbers) => self.line_numbers = numbers,
+                Err(e) => self.message = Some(e),
+            },
+            Command::JumpBack => {
+                if !self.buf_mut().jump_back(&view_info) {
+                    self.message = Some(String::from("No earlier jumps"));
+                }
+            }
+            Command::JumpForward => {
+                if !self.buf_mut().jump_forward(&view_info) {
+                    self.message = Some(String::from("No later jumps"));
+                }
+            }
+            Command::Split => self.split_window(window_info),
+            Command::Unsplit => self.unsplit_window(),
+            Command::SwitchPane => self.switch_pane(window_info),
+            Command::FocusPanel if self.panel.is_some() => self.panel_focused = !self.panel_focused,
+            Command::FocusPanel => self.message = Some(String::from("No panel open")),
+            Command::ClosePanel => self.close_panel(),
+            Command::BufferList => self.buffer_list(window_info),
+            Command::DiffUnsaved => self.diff_unsaved(window_info),
+            Command::Outline => self.outline(window_info),
+            Command::Undo => {
+                self.buf_mut().undo(&view_info);
+            }
+            Command::Redo => {
+                self.buf_mut().redo(&view_info);
+            }
+            Command::Format => self.format_current(),
+            Command::Pipe if arg.is_empty() => self.begin_prompt(EditorMode::PipeCommand),
+            Command::Pipe => self.pipe_through(arg),
+            Command::ExportHtml => self.export_current(),
+            Command::Counts => self.show_counts(),
+            Command::SpellSuggest => self.spell_suggest(),
+            Command::SaveSession => self.save_session(arg),
+            Command::LoadSession => {
+                self.load_session(arg);
+            }
+            Command::Info => self.show_info(),
+            Command::Settings => self.show_settings(),
+            Command::Reopen if arg.is_empty() => self.begin_prompt(EditorMode::Reopen),
+            Command::Reopen => self.reopen(arg, &view_info),
+            Command::Help => self.help = Some(0),
+            Command::NextMarker => self.next_marker(&view_info),
+            Command::Tag => self.jump_to_tag(arg, &view_info),
+            Command::TagBack => self.tag_back(&view_info),
+            Command::Compile => self.compile(),
+            Command::NextError => self.step_error(true, &view_info),
+            Command::PrevError => self.step_error(false, &view_info),
+            Command::Grep if arg.is_empty() => self.begin_prompt(EditorMode::Grep),
+            Command::Grep => self.start_grep(arg, &view_info),
+            Command::InsertDate => self.insert_date(&view_info),
+            Command::Snippet => self.insert_snippet(arg, &view_info),
+            Command::InsertChar if arg.is_empty() => self.begin_prompt(EditorMode::InsertChar),
+            Command::InsertChar => {
+                self.insert_codepoint(arg, &view_info);
+            }
+            Command::Rename if arg.is_empty() => self.begin_rename(),
+            Command::Rename => self.rename_word(arg, &view_info),
+            Command::Lines(op) => self.transform_lines(op, &view_info),
+            Command::SetIndent if arg.is_empty() => {
+                self.message = Some(format!("Indenting with {}", self.buf().indent.describe()));
+            }
+            Command::SetIndent => match indent::Indent::parse(arg) {
+                Ok(indent) => {
+                    self.buf_mut().indent = indent;
+                    self.buf_mut().indent_locked = true;
+                    self.buf_mut().indent_from = "set this session";
+                    self.message = Some(format!("Indenting with {}", indent.describe()));
+                }
+                Err(e) => self.message = Some(e),
+            },
+        }
+
+        false
+    }
+
+    /// The buffer row shown at text row `scr_row` of the window, moving the
+    /// focus to the other pane if that's where it is
+    fn focus_row(&mut self, scr_row: u32, window_info: &WindowInfo) -> usize {
+        let (focused_rows, other_rows) = self.pane_rows(window_info);
+        let focused_first_row = if self.focus_top { 0 } else { other_rows };
+        if scr_row < focused_first_row || scr_row >= focused_first_row + focused_rows {
+            self.switch_pane(window_info);
+        }
+
+        let first_row = if self.focus_top { 0 } else { self.pane_rows(window_info).1 };
+        let row = self.buf().top_line + (scr_row - first_row) as usize;
+        row.min(self.buf().lines.len() - 1)
+    }
+
+    fn next_buffer(&mut self) {
+        self.current = (self.current + 1) % self.buffers.len();
+    }
+
+    fn prev_buffer(&mut self) {
+        self.current = (self.current + self.buffers.len() - 1) % self.buffers.len();
+    }
+
+    /// Go back to the buffer that was current before this one
+    fn alternate_buffer(&mut self) {
+        let previous = self.previous_id.and_then(|id| self.buffers.iter().position(|b| b.id == id));
+        let Some(index) = previous else {
+            self.message = Some(String::from("No other buffer to switch to"));
+            return;
+        };
+
+        self.current = index;
+        let name = Path::new(&self.buf().filename).file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_else(|| self.buf().filename.clone());
+        self.message = Some(format!("→ {}", name));
+    }
+
+    /// Notice when the current buffer has changed, however it happened, so
+    /// AlternateBuffer knows where to go back to. A completion list was for
+    /// a spot in the old buffer, so it goes.
+    fn track_buffer_switch(&mut self) {
+        let id = self.buf().id;
+        if id != self.active_id {
+            self.previous_id = Some(self.active_id);
+            self.active_id = id;
+            self.completion = None;
+            self.tag_picker = None;
+            self.spell_picker = None;
+        }
+    }
+
+    fn dirty_count(&self) -> usize {
+        self.buffers.iter().filter(|b| b.is_modified && b.filename != SCRATCH_BUFFER).count()
+    }
+
+    /// Returns true if it's fine to quit right away. Otherwise the buffers
+    /// with unsaved changes are listed in the panel, to choose which to save
+    /// before quitting.
+    fn request_quit(&mut self, window_info: &WindowInfo) -> bool {
+        let items: Vec<(usize, bool)> = self.buffers.iter().enumerate()
+            .filter(|(_, b)| b.is_modified && b.filename != SCRATCH_BUFFER)
+            .map(|(i, _)| (i, true))
+            .collect();
+        if items.is_empty() {
+            return true;
+        }
+        if self.mode == EditorMode::ConfirmQuit {
+            return false;
+        }
+
+        self.quit_list = Some(QuitList { items, hidden: self.panel.take() });
+        let panel = Panel::new("Unsaved buffers", self.quit_lines(), |_, _, _| {});
+        self.open_panel(panel, window_info);
+        self.mode = EditorMode::ConfirmQuit;
+        false
+    }
+
+    fn quit_lines(&self) -> Vec<String> {
+        let items = self.quit_list.as_ref().map_or(&[][..], |list| &list.items);
+        items.iter().map(|&(index, save)| {
+            let mark = if save { 'x' } else { ' ' };
+            format!("[{}] {:>2}  {}", mark, index + 1, self.buffers[index].filename)
+        }).collect()
+    }
+
+    /// Keys for the list of unsaved buffers while confirming a quit. Returns
+    /// true when it's time to quit.
+    fn quit_key(&mut self, keycode: Keycode) -> bool {
+        let (Some(list), Some(panel)) = (self.quit_list.as_mut(), self.panel.as_mut()) else {
+            return true;
+        };
+
+        match keycode {
+            Keycode::Space => {
+                if let Some(item) = list.items.get_mut(panel.selected) {
+                    item.1 = !item.1;
+                }
+                let lines = self.quit_lines();
+                if let Some(panel) = self.panel.as_mut() {
+                    panel.lines = lines;
+                }
+            }
+            Keycode::S => return self.save_for_quit(),
+            Keycode::D | Keycode::Y => return true,
+            Keycode::N | Keycode::Escape => self.cancel_quit(),
+            other => _ = panel.navigate(other),
+        }
+
+        false
+    }
+
+    /// Save the buffers ticked in the quit list. The first that fails stops
+    /// the quit, with the reason in the status bar.
+    fn save_for_quit(&mut self) -> bool {
+        let items = self.quit_list.as_ref().map(|list| list.items.clone()).unwrap_or_default();
+        for (index, _) in items.into_iter().filter(|&(_, save)| save) {
+            if let Err(e) = self.buffers[index].save() {
+                log_error!("unable to save {}: {}", self.buffers[index].filename, e);
+                self.cancel_quit();
+                self.message = Some(format!("Unable to save {}: {}. Not quitting.", self.buffers[index].filename, e));
+                return false;
+            }
+            self.drop_swap(index);
+        }
+
+        true
+    }
+
+    fn cancel_quit(&mut self) {
+        if let Some(list) = self.quit_list.take() {
+            self.panel = list.hidden;
+        }
+        self.panel_focused = false;
+        self.mode = EditorMode::Edit;
+    }
+
+    fn request_close(&mut self) {
+        if self.buf().is_modified && self.buf().filename != SCRATCH_BUFFER {
+            self.mode = EditorMode::ConfirmClose;
+        } else {
+            self.close_current();
+        }
+    }
+
+    fn close_current(&mut self) {
+        let closed = self.current;
+        self.buf().save_history();
+        self.drop_swap(closed);
+        self.buffers.remove(closed);
+        if self.buffers.is_empty() {
+            self.buffers.push(self.scratch_buffer());
+        }
+        self.current = self.current.min(self.buffers.len() - 1);
+
+        // Keep the other pane pointed at a buffer that still exists
+        let current = self.current;
+        if let Some(pane) = &mut self.split {
+            if pane.buffer == closed {
+                pane.buffer = current;
+                pane.view = self.buffers[current].view();
+            } else if pane.buffer > closed {
+                pane.buffer -= 1;
+            }
+        }
+    }
+
+    /// Where the text area, the status bar and the prompt line go. The
+    /// status bar is at the bottom or, if the config says so, the top. While
+    /// a prompt is open it gets a line of its own beside the status bar,
+    /// taken from the text area, so the status stays in view. An open panel
+    /// takes rows from the bottom of the text area too.
+    fn layout(&self, window_info: &WindowInfo) -> WindowInfo {
+        let bar_rows = if self.in_prompt() { 2 } else { 1 };
+        let text_rows = EDITOR_ROWS + 1 - bar_rows;
+        let rows = text_rows - self.panel.as_ref().map_or(0, Panel::rows);
+        let char_height = window_info.char_height as i32;
+        let (text_top, status_y) = if self.config.status_top {
+            (window_info.margin_top + bar_rows as i32 * char_height, 0)
+        } else {
+            (window_info.margin_top, window_info.margin_top + text_rows as i32 * char_height)
+        };
+        let prompt_y = status_y + (bar_rows as i32 - 1) * char_height;
+
+        let gutter = self.gutter_cols(rows as usize) as u32;
+        let minimap = if self.minimap { minimap::COLS } else { 0 };
+        let cols = EDITOR_COLS - gutter - minimap;
+        let text_left = window_info.margin_left + (gutter * window_info.char_width) as i32;
+
+        WindowInfo { rows, cols, text_top, text_left, status_y, prompt_y, ..*window_info }
+    }
+
+    /// Where the minimap goes, right of the text, if it's on
+    fn minimap_area(&self, window_info: &WindowInfo) -> Option<Rect> {
+        if !self.minimap {
+            return None;
+        }
+
+        let x = window_info.text_left + (window_info.cols * window_info.char_width) as i32;
+        Some(Rect::new(x, window_info.text_top, minimap::COLS * window_info.char_width,
+            window_info.rows * window_info.char_height))
+    }
+
+    /// Scroll the focused pane so the minimap's box has its top `y` pixels
+    /// down the minimap
+    fn minimap_scroll(&mut self, y: i32, window_info: &WindowInfo) {
+        let Some(area) = self.minimap_area(window_info) else {
+            return;
+        };
+        let view_info = self.view_info(window_info);
+        let buf = self.buf_mut();
+        let top = minimap::line_at(buf.lines.len(), area.height(), y - area.y());
+        buf.scroll_to(top, &view_info);
+    }
+
+    /// Columns taken by line numbers, enough for whichever buffer on screen
+    /// is longest
+    fn gutter_cols(&self, rows: usize) -> usize {
+        let other = self.split.as_ref().map_or(0, |pane| self.buffers[pane.buffer].lines.len());
+        self.line_numbers.width(self.buf().lines.len().max(other), rows)
+    }
+
+    /// Rows available to the focused pane and to the other one. Without a
+    /// split the focused pane gets the whole text area.
+    fn pane_rows(&self, window_info: &WindowInfo) -> (u32, u32) {
+        if self.split.is_none() {
+            return (window_info.rows, 0);
+        }
+
+        let top = window_info.rows / 2;
+        let bottom = window_info.rows - top;
+        if self.focus_top { (top, bottom) } else { (bottom, top) }
+    }
+
+    /// The pixel rows the focused pane's text covers, top inclusive and
+    /// bottom exclusive
+    fn pane_span(&self, window_info: &WindowInfo) -> (i32, i32) {
+        let (rows, other_rows) = self.pane_rows(window_info);
+        let first_row = if self.focus_top { 0 } else { other_rows };
+        let top = window_info.text_top + (first_row * window_info.char_height) as i32;
+        (top, top + (rows * window_info.char_height) as i32)
+    }
+
+    /// Window info describing just the focused pane, which is what cursor
+    /// movement and scrolling need to work with.
+    fn view_info(&self, window_info: &WindowInfo) -> WindowInfo {
+        let (rows, _) = self.pane_rows(window_info);
+        WindowInfo { rows, ..*window_info }
+    }
+
+    /// Split the text area in two, both panes starting on the current
+    /// buffer at the current position.
+    fn split_window(&mut self, window_info: &WindowInfo) {
+        if self.split.is_some() {
+            return;
+        }
+
+        self.split = Some(Pane { buffer: self.current, view: self.buf().view() });
+        self.focus_top = true;
+
+        let view_info = self.view_info(window_info);
+        self.buf_mut().ensure_cursor_visible(&view_info, view_info.scroll_margin);
+    }
+
+    fn unsplit_window(&mut self) {
+        self.split = None;
+        self.focus_top = true;
+    }
+
+    /// Show `panel` under the text, in place of any panel already open, and
+    /// give it the keys
+    fn open_panel(&mut self, panel: Panel, window_info: &WindowInfo) {
+        self.panel = Some(panel);
+        self.panel_focused = true;
+
+        // The text area just got shorter
+        let view_info = self.view_info(&self.layout(window_info));
+        self.buf_mut().ensure_cursor_visible(&view_info, view_info.scroll_margin);
+    }
+
+    fn close_panel(&mut self) {
+        self.panel = None;
+        self.panel_focused = false;
+    }
+
+    /// Keys for the panel while it has the focus. It takes every key but
+    /// those with Ctrl or Alt, so commands still work from it.
+    fn panel_key(&mut self, keycode: Keycode, mods: Mods, window_info: &WindowInfo) -> bool {
+        let Some(panel) = self.panel.as_mut().filter(|_| self.panel_focused) else {
+            return false;
+        };
+        if mods.ctrl || mods.alt {
+            return false;
+        }
+
+        if panel.navigate(keycode) {
+            return true;
+        }
+        match keycode {
+            Keycode::Return if !panel.lines.is_empty() => {
+                let (action, row) = (panel.on_enter, panel.selected);
+                action(self, row, window_info);
+            }
+            Keycode::Tab | Keycode::F6 => self.panel_focused = false,
+            Keycode::Escape => self.close_panel(),
+            _ => {}
+        }
+
+        true
+    }
+
+    /// List the open buffers in the panel; Enter switches to one
+    fn buffer_list(&mut self, window_info: &WindowInfo) {
+        let lines = self.buffers.iter().enumerate().map(|(i, buf)| {
+            let here = if i == self.current { '>' } else { ' ' };
+            let modified = if buf.is_modified { " [+]" } else { "" };
+            let copy = if buf.copy { " [copy]" } else { "" };
+            format!("{} {:>2}  {}{}{}", here, i + 1, buf.filename, modified, copy)
+        }).collect();
+        let mut panel = Panel::new("Buffers", lines, |editor, row, _| {
+            if row < editor.buffers.len() {
+                editor.current = row;
+                editor.close_panel();
+            }
+        });
+        panel.select(self.current);
+        self.open_panel(panel, window_info);
+    }
+
+    /// List how the buffer differs from its file on disk in the panel, each
+    /// line under the number of the buffer line it's at; Enter goes there
+    fn diff_unsaved(&mut self, window_info: &WindowInfo) {
+        let buf = self.buf();
+        if buf.filename.is_empty() || buf.filename.starts_with('*') {
+            self.message = Some(String::from("Only file buffers can be diffed"));
+            return;
+        }
+        let disk = match buf.disk_lines() {
+            Ok(disk) => disk,
+            Err(e) => {
+                self.message = Some(format!("Unable to read {}: {}", buf.filename, e));
+                return;
+            }
+        };
+
+        let old = disk.as_deref().unwrap_or_default();
+        let changes = linediff::diff(old, &buf.lines);
+        let (added, removed) = linediff::counts(&changes);
+        if added + removed == 0 {
+            self.message = Some(format!("No unsaved changes to {}", buf.filename));
+            return;
+        }
+
+        let mut lines = Vec::new();
+        let mut row = 0; // Where the next line of the buffer is
+        for change in changes {
+            match change {
+                linediff::Diff::Same(_, new) => row = new + 1,
+                linediff::Diff::Removed(i) => lines.push(format!("{:>5} - {}", row + 1, old[i])),
+                linediff::Diff::Added(i) => {
+                    lines.push(format!("{:>5} + {}", i + 1, buf.lines[i]));
+                    row = i + 1;
+                }
+            }
+        }
+
+        let missing = if disk.is_none() { ", not on disk" } else { "" };
+        let title = format!("Unsaved changes to {}: +{} -{}{}", buf.filename, added, removed, missing);
+        let panel = Panel::new(&title, lines, |editor, index, window_info| {
+            let Some(panel) = &editor.panel else {
+                return;
+            };
+            let row = panel.lines[index].split_whitespace().next().and_then(|n| n.parse::<usize>().ok());
+            if let Some(row) = row {
+                let view_info = editor.view_info(window_info);
+                let buf = editor.buf_mut();
+                buf.goto_position((row - 1).min(buf.lines.len() - 1), 0, &view_info);
+                editor.panel_focused = false;
+            }
+        });
+        self.open_panel(panel, window_info);
+    }
+
+    /// List the headings of a Markdown buffer in the panel, indented by
+    /// level, with the one the cursor is under selected. Enter jumps to a
+    /// heading; Escape leaves the cursor where it was.
+    fn outline(&mut self, window_info: &WindowInfo) {
+        let buf = self.buf_mut();
+        if !buf.has_outline() {
+            self.message = Some(String::from("Only Markdown files have an outline"));
+            return;
+        }
+        buf.update_outline();
+        if buf.outline().is_empty() {
+            self.message = Some(String::from("No headings"));
+            return;
+        }
+
+        let lines = buf.outline().iter()
+            .map(|h| format!("{:>5}  {}{}", h.row + 1, "  ".repeat(h.level - 1), h.title))
+            .collect();
+        let current = buf.outline().partition_point(|h| h.row <= buf.buffer_row).saturating_sub(1);
+        let title = format!("Outline of {}", buf.filename);
+        let mut panel = Panel::new(&title, lines, |editor, index, window_info| {
+            let Some(panel) = &editor.panel else {
+                return;
+            };
+            let row = panel.lines[index].split_whitespace().next().and_then(|n| n.parse::<usize>().ok());
+            if let Some(row) = row {
+                let view_info = editor.view_info(window_info);
+                let buf = editor.buf_mut();
+                buf.mark_jump();
+                buf.goto_position((row - 1).min(buf.lines.len() - 1), 0, &view_info);
+                editor.close_panel();
+            }
+        });
+        panel.select(current);
+        self.open_panel(panel, window_info);
+    }
+
+    fn switch_pane(&mut self, window_info: &WindowInfo) {
+        let Some(other) = self.split.take() else {
+            return;
+        };
+
+        self.split = Some(Pane { buffer: self.current, view: self.buf().view() });
+        self.current = other.buffer;
+        self.buf_mut().set_view(other.view);
+        self.focus_top = !self.focus_top;
+
+        let view_info = self.view_info(window_info);
+        self.buf_mut().ensure_cursor_visible(&view_info, view_info.scroll_margin);
+    }
+}
+
+fn sdl_colour(c: Rgb) -> Color {
+    Color::RGB(c.0, c.1, c.2)
+}
+
+/// Drawn in place of a character the font has no glyph for
+const MISSING_GLYPH: char = '\u{25A1}';
+
+/// `text` with anything the font can't draw swapped for MISSING_GLYPH, so
+/// that it's both visible and measured the same wherever it's drawn. SDL_ttf
+/// can only be asked about the first 65536 codepoints; past those counts as
+/// missing.
+fn with_placeholders<'a>(font: &Font, text: &'a str) -> Cow<'a, str> {
+    text::replace_missing(text, MISSING_GLYPH, |c| {
+        c.is_ascii() || (u32::from(c) <= 0xFFFF && font.find_glyph(c).is_some())
+    })
+}
+
+/// How wide `text` is drawn, in pixels
+fn text_width(font: &Font, text: &str) -> u32 {
+    font.size_of(&with_placeholders(font, text)).unwrap_or((0, 0)).0
+}
+
+/// Draw `text` at (x, y). Text that can't be drawn is logged and left out
+/// rather than stopping the rest of the screen being drawn.
+fn render_text(
+    canvas: &mut Canvas<Window>,
+    font: &Font,
+    text: &str,
+    x: i32,
+    y: i32,
+    colour: Color,
+) -> Result<(), String> {
+    
+    if text.is_empty() {
+        return Ok(());
+    }
+    
+    let surface = match font.render(&with_placeholders(font, text)).blended(colour) {
+        Ok(surface) => surface,
+        Err(e) => {
+            log_debug!("unable to render {:?}: {}", text, e);
+            return Ok(());
+        }
+    };
+    
+    let texture_creator = canvas.texture_creator();
+    let texture = texture_creator
+        .create_texture_from_surface(&surface)
+        .map_err(|e| e.to_string())?;
+    
+    let target = Rect::new(x, y, surface.width(), surface.height());
+    canvas.copy(&texture, None, Some(target))?;
+    
+    Ok(())
+}
+
+/// Draw the lines of a buffer visible in a pane occupying `rows` text rows
+/// starting at `first_row`, moved up `shift` pixels while a scroll is part
+/// way through a line. The buffer's highlighter states need to be up to date
+/// for those lines. Returns the lines cut off at the right, as (y, row).
+#[allow(clippy::too_many_arguments)]
+fn draw_pane(
+    canvas: &mut Canvas<Window>,
+    font: &Font,
+    theme: &Theme,
+    markers: &Query,
+    buf: &Buffer,
+    view: &View,
+    selection: Option<((usize, usize), (usize, usize))>,
+    occurrences: &[Match],
+    misspelled: &[Match],
+    numbers: LineNumbers,
+    first_row: u32,
+    rows: u32,
+    shift: i32,
+    window_info: &WindowInfo,
+) -> Result<Vec<(i32, usize)>, String> {
+    let lines = &buf.lines;
+    let offset = view.buffer_col_offset;
+    let width_of = |line: &str, from: usize, to: usize| {
+        text_width(font, &expand_tabs(line, from, to, buf.tab_width))
+    };
+
+    // A view saved for an unfocused pane may be out of date if the buffer was
+    // edited from the other pane, so don't trust it to be in range.
+    let buffer_start = view.top_line.min(lines.len());
+    // A shifted pane shows part of one more line at the bottom
+    let shown_rows = if shift > 0 { rows + 1 } else { rows };
+    let buffer_end = (buffer_start + shown_rows as usize).min(lines.len());
+
+    // Nothing goes past the right of the text area, nor, while shifted, out
+    // of the pane
+    let pane_top = window_info.text_top + (first_row * window_info.char_height) as i32;
+    let text_right = window_info.text_left + (window_info.cols * window_info.char_width) as i32;
+    canvas.set_clip_rect(Rect::new(0, pane_top, text_right as u32, rows * window_info.char_height));
+
+    let mut truncated = Vec::new();
+    for (scr_row, line) in lines[buffer_start..buffer_end].iter().enumerate() {
+        let row = buffer_start + scr_row;
+        let y = pane_top - shift + (scr_row as i32 * window_info.char_height as i32);
+
+        // Not the occurrence the cursor is in; it's plain where the cursor is
+        let others = occurrences.iter().filter(|m| m.row == row && m.col + m.len > offset)
+            .filter(|m| row != buf.buffer_row || buf.buffer_col < m.col || buf.buffer_col > m.col + m.len);
+        for m in others {
+            let from = m.col.max(offset);
+            let x_from = width_of(line, offset, from);
+            let width = width_of(line, from, m.col + m.len);
+            canvas.set_draw_color(sdl_colour(theme.occurrence));
+            canvas.fill_rect(Rect::new(window_info.text_left + x_from as i32, y, width.max(1), window_info.char_height))
+                .map_err(|e| e.to_string())?;
+        }
+
+        // Whole lines are shaded right across, so they look different
+        let line_rows = selection.and(buf.line_selection());
+        if let Some((start, end)) = line_rows
+            && (start..end).contains(&row)
+        {
+            canvas.set_draw_color(sdl_colour(theme.selection));
+            canvas.fill_rect(Rect::new(window_info.text_left, y, window_info.cols * window_info.char_width,
+                window_info.char_height)).map_err(|e| e.to_string())?;
+        } else if let Some(((start_row, start_col), (end_row, end_col))) = selection.filter(|_| line_rows.is_none())
+            && row >= start_row && row <= end_row
+        {
+            let from = if row == start_row { start_col } else { 0 };
+            let to = if row == end_row { end_col } else { char_len(line) };
+            let from = from.max(offset);
+            let to = to.max(offset);
+
+            let x_from = width_of(line, offset, from);
+            let mut width = width_of(line, from, to);
+            if row != end_row {
+                // Show that the line break is selected too
+                width += window_info.char_width;
+            }
+
+            if width > 0 {
+                canvas.set_draw_color(sdl_colour(theme.selection));
+                canvas.fill_rect(Rect::new(window_info.text_left + x_from as i32, y, width, window_info.char_height))
+                    .map_err(|e| e.to_string())?;
+            }
+        }
+
+        draw_git_mark(canvas, theme, buf.git_marks.get(row).copied(), y, window_info)?;
+
+        if numbers != LineNumbers::Off {
+            let label = numbers.label(row, view.buffer_row);
+            let label_width = text_width(font, &label) as i32;
+            let x = window_info.text_left - window_info.char_width as i32 - label_width;
+            let colour = if row == view.buffer_row { theme.foreground } else { theme.line_number };
+            render_text(canvas, font, &label, x, y, sdl_colour(colour))?;
+        }
+
+        let spans = match buf.language {
+            Some(lang) => {
+                let spans = highlight::highlight_line(line, lang, buf.hl_state(row)).0;
+                highlight::with_markers(line, spans, markers, TokenKind::Comment)
+            }
+            None => {
+                let spans = vec![Span { start: 0, end: usize::MAX, kind: TokenKind::Text }];
+                highlight::with_markers(line, spans, markers, TokenKind::Text)
+            }
+        };
+
+        // Apply horizontal scrolling offset to all lines
+        let mut x = window_info.text_left;
+        for span in spans.iter().filter(|span| span.end > offset) {
+            let text = expand_tabs(line, span.start.max(offset), span.end, buf.tab_width);
+            render_text(canvas, font, &text, x, y, sdl_colour(theme.token_colour(span.kind)))?;
+            x += text_width(font, &text) as i32;
+        }
+
+        // Control characters go over the top in reverse video, so a ^L can't
+        // be mistaken for a ^ followed by an L
+        for (col, c) in line.chars().enumerate().skip(offset) {
+            let Some(shown) = text::caret_notation(c) else {
+                continue;
+            };
+            let shown: String = shown.iter().collect();
+            let x = window_info.text_left + width_of(line, offset, col) as i32;
+            let width = text_width(font, &shown);
+            canvas.set_draw_color(sdl_colour(theme.foreground));
+            canvas.fill_rect(Rect::new(x, y, width, window_info.char_height)).map_err(|e| e.to_string())?;
+            render_text(canvas, font, &shown, x, y, sdl_colour(theme.background))?;
+        }
+
+        // Unknown words are underlined
+        for m in misspelled.iter().filter(|m| m.row == row && m.col + m.len > offset) {
+            let from = m.col.max(offset);
+            let x_from = window_info.text_left + width_of(line, offset, from) as i32;
+            let thickness = (window_info.char_height / 12).max(1);
+            canvas.set_draw_color(sdl_colour(theme.misspelled));
+            canvas.fill_rect(Rect::new(x_from, y + (window_info.char_height - thickness) as i32,
+                width_of(line, from, m.col + m.len).max(1), thickness)).map_err(|e| e.to_string())?;
+        }
+
+        // A line that runs off the right ends in a marker in the last column
+        if x > text_right {
+            let marker_x = text_right - window_info.char_width as i32;
+            canvas.set_draw_color(sdl_colour(theme.background));
+            canvas.fill_rect(Rect::new(marker_x, y, window_info.char_width, window_info.char_height))
+                .map_err(|e| e.to_string())?;
+            render_text(canvas, font, TRUNCATED_MARKER, marker_x, y, sdl_colour(theme.line_number))?;
+            if y < pane_top + (rows * window_info.char_height) as i32 {
+                truncated.push((y, row));
+            }
+        }
+    }
+    canvas.set_clip_rect(None);
+
+    // Text deleted from the end of the file is marked below the last line
+    if buffer_end == lines.len() && buffer_end - buffer_start < rows as usize {
+        let y = pane_top - shift + ((buffer_end - buffer_start) as i32 * window_info.char_height as i32);
+        draw_git_mark(canvas, theme, buf.git_marks.get(buffer_end).copied(), y, window_info)?;
+    }
+
+    Ok(truncated)
+}
+
+/// Where the cursor's column starts on screen, allowing for tabs and
+/// horizontal scrolling. Width is measured with the font since char_width
+/// times the column count comes out slightly off.
+fn cursor_x(font: &Font, buf: &Buffer, window_info: &WindowInfo) -> i32 {
+    let line = &buf.lines[buf.buffer_row];
+    let before = expand_tabs(line, buf.buffer_col_offset, buf.buffer_col, buf.tab_width);
+    window_info.text_left + text_width(font, &before) as i32
+}
+
+/// How wide the char under the cursor is drawn, or a cell's width at the
+/// end of the line
+fn cursor_width(font: &Font, buf: &Buffer, window_info: &WindowInfo) -> u32 {
+    let line = &buf.lines[buf.buffer_row];
+    if buf.buffer_col >= char_len(line) {
+        return window_info.char_width;
+    }
+
+    let shown = expand_tabs(line, buf.buffer_col, buf.buffer_col + 1, buf.tab_width);
+    match text_width(font, &shown) {
+        0 => window_info.char_width,
+        width => width,
+    }
+}
+
+/// The buffer position under pixel (x, y), or None if it isn't over the
+/// text. A click in the unfocused pane focuses it.
+fn click_position(
+    editor: &mut TextEditor,
+    font: &Font,
+    x: i32,
+    y: i32,
+    window_info: &WindowInfo,
+) -> Option<(usize, usize)> {
+    let scr_row = u32::try_from(y - window_info.text_top).ok()? / window_info.char_height;
+    if scr_row >= window_info.rows {
+        return None;
+    }
+
+    let row = editor.focus_row(scr_row, window_info);
+    let buf = editor.buf();
+    let line = &buf.lines[row];
+    let offset = buf.buffer_col_offset;
+    let x = (x - window_info.text_left).max(0) as u32;
+
+    // The first column whose char reaches past x by more than half its width
+    let width_to = |col: usize| text_width(font, &expand_tabs(line, offset, col, buf.tab_width));
+    let mut left = 0;
+    for col in offset..char_len(line) {
+        let right = width_to(col + 1);
+        if x < (left + right) / 2 {
+            return Some((row, col));
+        }
+        left = right;
+    }
+
+    Some((row, char_len(line).max(offset)))
+}
+
+/// While a drag selection has the pointer above or below the focused pane,
+/// keep scrolling that way and extending the selection, a line at a time
+/// plus one more for each line's height the pointer is past the edge.
+/// Checked every frame since the pointer can be held still out there.
+fn drag_scroll(editor: &mut TextEditor, font: &Font, window_info: &WindowInfo) {
+    let Some((x, y)) = editor.drag_pointer else {
+        return;
+    };
+    if !editor.dragging || editor.last_drag_scroll.elapsed() < DRAG_SCROLL_INTERVAL {
+        return;
+    }
+
+    let (top, bottom) = editor.pane_span(window_info);
+    let char_height = window_info.char_height as i32;
+    let (edge, lines) = if y < top {
+        (top, -(1 + (top - y) / char_height))
+    } else if y >= bottom {
+        (bottom - 1, 1 + (y - bottom) / char_height)
+    } else {
+        return;
+    };
+    editor.last_drag_scroll = std::time::Instant::now();
 
-const EDITOR_COLS: u32 = 80;
-const EDITOR_ROWS: u32 = 32;
-const FONT_SIZE: u16 = 14;
-const MARGIN_LEFT: i32 = 10;
-const MARGIN_TOP: i32 = 10;
+    let Some((row, col)) = click_position(editor, font, x, edge, window_info) else {
+        return;
+    };
+    let view_info = editor.view_info(window_info);
+    let buf = editor.buf_mut();
+    let target = row.saturating_add_signed(lines as isize).min(buf.lines.len() - 1);
+    buf.goto_position(target, col, &view_info);
+}
 
-const OPEN_FILE_MARGIN: usize = 11;
+/// A bar in the left margin for an added or modified line, or a small
+/// triangle on the boundary above a line where lines were deleted
+fn draw_git_mark(
+    canvas: &mut Canvas<Window>,
+    theme: &Theme,
+    mark: Option<GitMark>,
+    y: i32,
+    window_info: &WindowInfo,
+) -> Result<(), String> {
+    // With the margin narrowed there's nowhere to put them
+    if window_info.margin_left < 6 {
+        return Ok(());
+    }
 
-#[derive(PartialEq)]
-enum EditorMode {
-    Edit,
-    OpenFile
+    match mark {
+        Some(GitMark::Added) | Some(GitMark::Modified) => {
+            let colour = if mark == Some(GitMark::Added) { theme.git_added } else { theme.git_modified };
+            canvas.set_draw_color(sdl_colour(colour));
+            canvas.fill_rect(Rect::new(3, y, 3, window_info.char_height)).map_err(|e| e.to_string())
+        }
+        Some(GitMark::Deleted) => {
+            canvas.set_draw_color(sdl_colour(theme.git_deleted));
+            for dy in -4i32..=4 {
+                let width = 5 - dy.unsigned_abs();
+                canvas.fill_rect(Rect::new(2, y + dy, width, 1)).map_err(|e| e.to_string())?;
+            }
+            Ok(())
+        }
+        _ => Ok(()),
+    }
 }
 
-struct WindowInfo {
+/// A popup list just below buffer position `at` (or above it if there's no
+/// room below)
+#[allow(clippy::too_many_arguments)]
+fn draw_popup(
+    canvas: &mut Canvas<Window>,
+    font: &Font,
+    theme: &Theme,
+    buf: &Buffer,
+    list: &ListPopup,
+    at: (usize, usize),
+    first_row: u32,
     rows: u32,
-    cols: u32,
-    char_width: u32,
-    char_height: u32,    
-}
+    window_info: &WindowInfo,
+) -> Result<(), String> {
+    let shown = list.items.len().min(POPUP_ROWS);
+    // Scroll the list just enough to keep the selection in it
+    let first = (list.selected + 1).saturating_sub(shown);
+    let items = &list.items[first..first + shown];
 
-struct TextEditor {
-    lines: Vec<String>,
-    scr_col: usize,
-    scr_row: usize,
-    buffer_col: usize,
-    buffer_row: usize,
-    buffer_col_offset: usize,  // Horizontal scroll offset
-    prev_cursor_x: usize,
-    prev_cursor_y: usize,
-    filename: String,
-    is_modified: bool,
-    cursor_visible: bool,
-    last_cursor_blink: std::time::Instant,
-    mode: EditorMode,
-    input_buffer: String,  // Buffer for command/filename input
+    let (row, col) = at;
+    let line = &buf.lines[row];
+    let offset = buf.buffer_col_offset.min(col);
+    let x = window_info.text_left + text_width(font, &expand_tabs(line, offset, col, buf.tab_width)) as i32;
+    let width = items.iter()
+        .map(|item| text_width(font, item))
+        .max()
+        .unwrap_or(0) + window_info.char_width;
+
+    let scr_row = first_row + row.saturating_sub(buf.top_line) as u32;
+    let below = scr_row + 1 + shown as u32 <= first_row + rows;
+    let top_row = if below { scr_row + 1 } else { scr_row.saturating_sub(shown as u32) };
+    let y = window_info.text_top + (top_row * window_info.char_height) as i32;
+
+    draw_overlay(canvas, font, theme, items, list.selected.checked_sub(first), (x, y), width, window_info)
 }
 
-impl TextEditor {
-    fn new() -> Self {
-        TextEditor {
-            lines: vec![String::new()],
-            scr_col: 0,
-            scr_row: 0,
-            prev_cursor_x: 0,
-            prev_cursor_y: 0,
-            buffer_col: 0,
-            buffer_row: 0,
-            buffer_col_offset: 0,
-            filename: String::from("filename.txt"),
-            is_modified: false,
-            cursor_visible: true,
-            last_cursor_blink: std::time::Instant::now(),
-            mode: EditorMode::Edit,
-            input_buffer: String::new(),
+/// Lines of text on a box `width` wide with its top left at `at`, in the
+/// status bar's background over whatever is there, with `selected` shaded
+#[allow(clippy::too_many_arguments)]
+fn draw_overlay(
+    canvas: &mut Canvas<Window>,
+    font: &Font,
+    theme: &Theme,
+    lines: &[String],
+    selected: Option<usize>,
+    (x, y): (i32, i32),
+    width: u32,
+    window_info: &WindowInfo,
+) -> Result<(), String> {
+    let char_height = window_info.char_height;
+    canvas.set_draw_color(sdl_colour(theme.status_bg));
+    canvas.fill_rect(Rect::new(x, y, width, lines.len() as u32 * char_height)).map_err(|e| e.to_string())?;
+    for (i, line) in lines.iter().enumerate() {
+        let line_y = y + (i as u32 * char_height) as i32;
+        if selected == Some(i) {
+            canvas.set_draw_color(sdl_colour(theme.selection));
+            canvas.fill_rect(Rect::new(x, line_y, width, char_height)).map_err(|e| e.to_string())?;
         }
+        let padding = (window_info.char_width / 2) as i32;
+        render_text(canvas, font, line, x + padding, line_y, sdl_colour(theme.foreground))?;
     }
 
-    fn insert_char(&mut self, c: char, window_info: &WindowInfo) {
-        if self.mode == EditorMode::OpenFile {
-            let pos = self.scr_col - OPEN_FILE_MARGIN;
-            self.input_buffer.insert(pos, c);
-            self.scr_col += 1;
-        } else {
-            let line = &mut self.lines[self.buffer_row];
-            line.insert(self.buffer_col, c);
-            self.buffer_col += 1;
+    Ok(())
+}
 
-            // Adjust horizontal scrolling
-            self.scr_col = self.buffer_col - self.buffer_col_offset;
-            if self.scr_col >= window_info.cols as usize {
-                self.buffer_col_offset += 1;
-                self.scr_col = window_info.cols as usize - 1;
-            }
+/// The whole of a line cut off at the right, wrapped in a box by the pointer
+/// at (x, y) on the row at `row_y`: below the row if there's room, else
+/// above it
+#[allow(clippy::too_many_arguments)]
+fn draw_tooltip(
+    canvas: &mut Canvas<Window>,
+    font: &Font,
+    theme: &Theme,
+    buf: &Buffer,
+    row: usize,
+    (x, row_y): (i32, i32),
+    window_info: &WindowInfo,
+) -> Result<(), String> {
+    let char_width = window_info.char_width;
+    let char_height = window_info.char_height as i32;
+    let (full_width, bottom) = (window_info.full_width() as i32, window_info.status_y);
 
-            self.is_modified = true;
-        }
+    let cols = (full_width as u32 / char_width).saturating_sub(2).clamp(1, TOOLTIP_COLS);
+    let text = expand_tabs(&buf.lines[row], 0, usize::MAX, buf.tab_width);
+    let mut lines = reflow::wrap(&text, cols as usize);
+    if lines.len() > TOOLTIP_ROWS {
+        lines.truncate(TOOLTIP_ROWS);
+        let last: String = lines[TOOLTIP_ROWS - 1].chars().take(cols as usize - 1).collect();
+        lines[TOOLTIP_ROWS - 1] = last + ELLIPSIS;
     }
 
-    fn backspace(&mut self, window_info: &WindowInfo) {
-        if self.buffer_col > 0 {
-            let line = &mut self.lines[self.buffer_row];
-            line.remove(self.buffer_col - 1);
-            self.buffer_col -= 1;
+    let width = lines.iter().map(|line| text_width(font, line)).max().unwrap_or(0) + char_width;
+    let height = lines.len() as i32 * char_height;
+    let x = x.min(full_width - width as i32).max(0);
+    let y = if row_y + char_height + height <= bottom { row_y + char_height } else { (row_y - height).max(0) };
 
-            // Adjust horizontal scrolling
-            if self.buffer_col < self.buffer_col_offset {
-                self.buffer_col_offset = self.buffer_col;
-            }
-            self.scr_col = self.buffer_col - self.buffer_col_offset;
+    draw_overlay(canvas, font, theme, &lines, None, (x, y), width, window_info)
+}
+
+/// The panel in the rows under the text area: its title in the status bar's
+/// colours, then as many of its lines as fit
+fn draw_panel(
+    canvas: &mut Canvas<Window>,
+    font: &Font,
+    theme: &Theme,
+    panel: &Panel,
+    focused: bool,
+    window_info: &WindowInfo,
+) -> Result<(), String> {
+    let char_height = window_info.char_height as i32;
+    let top = window_info.text_top + window_info.rows as i32 * char_height;
+    let width = window_info.full_width();
 
-            self.is_modified = true;
-        } else if self.buffer_row > 0 {
-            let current_line = self.lines.remove(self.buffer_row);
-            self.buffer_row -= 1;
-            self.buffer_col = self.lines[self.buffer_row].len();
-            self.buffer_col_offset = 0;
-            self.scr_col = self.buffer_col.min(window_info.cols as usize - 1);
+    canvas.set_draw_color(sdl_colour(theme.status_bg));
+    canvas.fill_rect(Rect::new(0, top, width, window_info.char_height)).map_err(|e| e.to_string())?;
+    let hint = if focused { "Enter to open, Tab back to the text, Esc to close" } else { "Shift+F6 to use" };
+    let title = format!("{}  ({})", panel.title, hint);
+    render_text(canvas, font, &title, window_info.margin_left, top, sdl_colour(theme.status_fg))?;
 
-            self.lines[self.buffer_row].push_str(&current_line);
-            self.is_modified = true;
+    let shown = panel.lines.iter().enumerate().skip(panel.top).take(panel.visible());
+    for (i, (index, line)) in shown.enumerate() {
+        let y = top + (i as i32 + 1) * char_height;
+        if index == panel.selected {
+            let colour = if focused { theme.selection } else { theme.occurrence };
+            canvas.set_draw_color(sdl_colour(colour));
+            canvas.fill_rect(Rect::new(0, y, width, window_info.char_height)).map_err(|e| e.to_string())?;
         }
+        render_text(canvas, font, &expand_tabs(line, 0, usize::MAX, 8), window_info.margin_left, y,
+            sdl_colour(theme.foreground))?;
     }
 
-    fn backspace_buffer(&mut self, offset: usize) {
-        if self.input_buffer.is_empty() || self.scr_col == offset {
-            return;
-        }
+    Ok(())
+}
 
-        let buffer_pos = self.scr_col - offset - 1;
-        if buffer_pos <= self.input_buffer.len() {
-            self.input_buffer.remove(buffer_pos );
-            self.scr_col -= 1;
-        }
+/// A box of text in the middle of the text area
+fn draw_info_box(
+    canvas: &mut Canvas<Window>,
+    font: &Font,
+    theme: &Theme,
+    lines: &[String],
+    window_info: &WindowInfo,
+) -> Result<(), String> {
+    let char_height = window_info.char_height;
+    let padding = window_info.char_width;
+    let width = lines.iter()
+        .map(|line| text_width(font, line))
+        .max()
+        .unwrap_or(0) + padding * 2;
+    let height = (lines.len() as u32 + 1) * char_height;
+
+    let area_width = window_info.cols * window_info.char_width;
+    let area_height = window_info.rows * char_height;
+    let x = window_info.text_left + (area_width.saturating_sub(width) / 2) as i32;
+    let y = window_info.text_top + (area_height.saturating_sub(height) / 2) as i32;
+
+    canvas.set_draw_color(sdl_colour(theme.status_bg));
+    canvas.fill_rect(Rect::new(x, y, width, height)).map_err(|e| e.to_string())?;
+    for (i, line) in lines.iter().enumerate() {
+        let line_y = y + (char_height / 2 + i as u32 * char_height) as i32;
+        render_text(canvas, font, line, x + padding as i32, line_y, sdl_colour(theme.foreground))?;
     }
 
-    fn insert_newline(&mut self) {
-        let current_line = &mut self.lines[self.buffer_row];
+    Ok(())
+}
 
-        // Split line at cursor
-        let rest_of_line = current_line[self.buffer_col..].to_string();
+/// Title and a few key hints, centered in the text area until the user starts
+/// working on something.
+fn draw_splash(
+    canvas: &mut Canvas<Window>,
+    font: &Font,
+    theme: &Theme,
+    width: u32,
+    height: u32,
+) -> Result<(), String> {
+    let title = format!("wfemto {} -- a toy text editor", VERSION);
+    let lines = [
+        title.as_str(),
+        "",
+        "Ctrl+O  open file",
+        "Ctrl+S  save",
+        "Ctrl+Q  quit",
+    ];
 
-        self.lines[self.buffer_row].truncate(self.buffer_col);
+    let line_height = font.height() as u32;
+    let block_height = line_height * lines.len() as u32;
+    let top = (height.saturating_sub(block_height) / 2) as i32;
 
-        self.buffer_row += 1;
-        self.scr_row = cmp::min(self.scr_row + 1, EDITOR_ROWS as usize - 1);
-        self.lines.insert(self.buffer_row, rest_of_line);
-        self.buffer_col = 0;
-        self.buffer_col_offset = 0;
-        self.scr_col = 0;
-        self.is_modified = true;
+    for (i, line) in lines.iter().enumerate() {
+        let w = text_width(font, line);
+        let colour = if i == 0 { theme.foreground } else { theme.status_fg };
+        render_text(
+            canvas,
+            font,
+            line,
+            (width.saturating_sub(w) / 2) as i32,
+            top + (i as u32 * line_height) as i32,
+            sdl_colour(colour))?;
     }
 
-    fn move_cursor_left(&mut self, window_info: &WindowInfo) {
-        if self.mode == EditorMode::OpenFile {
-            if self.scr_col - OPEN_FILE_MARGIN > 0 {
-                self.scr_col -= 1;
-            }
+    Ok(())
+}
 
-            return;
-        }
+/// Draw the status bar, and the prompt line if there is one. Returns where
+/// the file name ended up across the bar, for clicking on.
+fn draw_status_bar(
+    canvas: &mut Canvas<Window>,
+    font: &Font,
+    editor: &TextEditor,
+    window_info: &WindowInfo
+) -> Result<Option<(i32, i32)>, String> {
+    let buf = editor.buf();
+    // The status with `name` standing in for the buffer's file name, so a
+    // long one can be shortened
+    let status_for = |name: &str| match editor.mode {
+        EditorMode::ConfirmQuit => {
+            let dirty = editor.dirty_count();
+            let noun = if dirty == 1 { "buffer has" } else { "buffers have" };
+            let keys = "Space: save or not, s: save ticked and quit, d: discard all and quit, Esc: cancel";
+            (format!("{} {} unsaved changes. {}", dirty, noun, keys), String::new())
+        },
+        EditorMode::ConfirmClose => {
+            (format!("Save changes to {} before closing? (y/n, Esc to cancel)", name), String::new())
+        },
+        EditorMode::ConfirmOverwrite => {
+            let overwrite = editor.overwrite.as_deref().unwrap_or("");
+            (format!("{} already exists. Overwrite it? (y/n)", overwrite), String::new())
+        },
+        EditorMode::ConfirmPaste => {
+            let text = editor.held_paste.as_ref().map_or("", |(text, _)| text.as_str());
+            let size = text.len() as f64 / 1_000_000.0;
+            (format!("Paste {} lines ({:.1} MB)? (y/n)", text.lines().count(), size), String::new())
+        },
+        EditorMode::ConfirmRecover => {
+            let saved = editor.recovery.as_ref().map(|r| r.saved).unwrap_or(0);
+            let when = DateTime::local_at(saved).format("%Y-%m-%d %H:%M");
+            (format!("{} has unsaved changes from {}. Recover them? (y/n)", name, when), String::new())
+        },
+        _ => editor.config.status_format.render(|field| match field {
+            'f' => name.to_string(),
+            _ => status_field(editor, field),
+        }),
+    };
+    let (mut status, right_side) = status_for(&buf.filename);
 
-        if self.buffer_col > 0 {
-            self.buffer_col -= 1;
+    let width = window_info.full_width();
+    let room = width.saturating_sub(2 * window_info.margin_left as u32);
+    canvas.set_draw_color(sdl_colour(editor.theme.status_bg));
+    canvas.fill_rect(Rect::new(0, window_info.status_y, width, window_info.char_height)).map_err(|e| e.to_string())?;
 
-            // Adjust horizontal scrolling
-            if self.buffer_col < self.buffer_col_offset {
-                self.buffer_col_offset = self.buffer_col;
-            }
-            self.scr_col = self.buffer_col - self.buffer_col_offset;
-        } else if self.buffer_row > 0 {
-            self.buffer_row -= 1;
-            self.buffer_col = self.lines[self.buffer_row].len();
+    let spinner = |filter: &Filter, verb: &str| {
+        let spinner = ['|', '/', '-', '\\'];
+        let frame = (filter.started.elapsed().as_millis() / 100) as usize % spinner.len();
+        format!("{} {} {}", verb, filter.command, spinner[frame])
+    };
+    let running = match (&editor.job, &editor.build, &editor.grep) {
+        (Some(job), _, _) => Some(spinner(&job.filter, "Running")),
+        (None, Some(build), _) => Some(spinner(&build.filter, "Building with")),
+        (None, None, Some(search)) => Some(format!("{} matches for '{}' so far (Esc to stop)", search.found, search.pattern)),
+        (None, None, None) => None,
+    };
+    // Messages take the place of the right side of the format, and get as
+    // much of the bar as they need; the left side has what's left
+    let right_side = Some(&right_side).filter(|r| !r.is_empty());
+    let pending = editor.pending.map(|(prefix, _)| prefix.describe());
+    let msg = pending.as_ref().or(running.as_ref()).or(editor.message.as_ref()).or(right_side)
+        .map(|msg| fit_text(font, msg, room, false));
+    let msg_width = msg.as_ref().map_or(0, |msg| text_width(font, msg));
+    let left_room = if msg.is_some() { room.saturating_sub(msg_width + window_info.char_width) } else { room };
 
-            // Reset horizontal scroll or adjust if line is long
-            if self.buffer_col < window_info.cols as usize {
-                self.buffer_col_offset = 0;
-                self.scr_col = self.buffer_col;
-            } else {
-                self.buffer_col_offset = self.buffer_col - (window_info.cols as usize - 1);
-                self.scr_col = window_info.cols as usize - 1;
-            }
-        }
+    // Too long, the file name loses its start first, but never its last part
+    let status_width = text_width(font, &status);
+    let mut name = buf.filename.clone();
+    if status_width > left_room {
+        let basename = Path::new(&buf.filename).file_name().map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let keep = text_width(font, &buf.filename).saturating_sub(status_width - left_room)
+            .max(text_width(font, &format!("{}{}", ELLIPSIS, basename)));
+        name = fit_text(font, &buf.filename, keep, true);
+        status = status_for(&name).0;
     }
-    
-    fn move_cursor_right(&mut self, window_info: &WindowInfo) {
-        if self.mode == EditorMode::OpenFile {
-            if self.scr_col < self.input_buffer.len() + OPEN_FILE_MARGIN {
-                self.scr_col += 1;
-            }
-            return;
-        }
+    let status = fit_text(font, &status, left_room, false);
+    render_text(canvas, font, &status, window_info.margin_left, window_info.status_y, sdl_colour(editor.theme.status_fg))?;
 
-        if self.buffer_col < self.lines[self.buffer_row].len() {
-            self.buffer_col += 1;
+    let name_span = status.find(&name).filter(|_| editor.mode == EditorMode::Edit && !name.is_empty()).map(|at| {
+        let from = window_info.margin_left + text_width(font, &status[..at]) as i32;
+        let to = from + text_width(font, &name) as i32;
+        (from, to.min(window_info.margin_left + left_room as i32))
+    });
 
-            // Adjust horizontal scrolling
-            self.scr_col = self.buffer_col - self.buffer_col_offset;
-            if self.scr_col >= window_info.cols as usize {
-                self.buffer_col_offset += 1;
-                self.scr_col = window_info.cols as usize - 1;
-            }
-        } else if self.buffer_row < self.lines.len() - 1 {
-            self.buffer_row += 1;
-            self.buffer_col = 0;
-            self.buffer_col_offset = 0;
-            self.scr_col = 0;
-        }
+    if let Some(label) = editor.prompt_label() {
+        let (prompt, _) = prompt_line(font, editor, label, room);
+        canvas.set_draw_color(sdl_colour(editor.theme.background));
+        canvas.fill_rect(Rect::new(0, window_info.prompt_y, width, window_info.char_height)).map_err(|e| e.to_string())?;
+        render_text(canvas, font, &prompt, window_info.margin_left, window_info.prompt_y, sdl_colour(editor.theme.foreground))?;
     }
 
-    fn move_cursor_up(&mut self, window_info: &WindowInfo) {
-        if self.buffer_row > 0 {
-            self.buffer_row -= 1;
+    if let Some(msg) = msg {
+        let right = window_info.full_width() as i32 - window_info.margin_left;
+        render_text(
+            canvas,
+            font,
+            &msg,
+            right - msg_width as i32, window_info.status_y, sdl_colour(editor.theme.status_fg))?;
+    }
 
-            if self.buffer_col > self.lines[self.buffer_row].len() {
-                self.buffer_col = self.lines[self.buffer_row].len();
-            }
+    Ok(name_span)
+}
 
-            // Adjust horizontal scrolling
-            if self.buffer_col < self.buffer_col_offset {
-                self.buffer_col_offset = self.buffer_col;
-            } else if self.buffer_col >= self.buffer_col_offset + window_info.cols as usize {
-                self.buffer_col_offset = self.buffer_col - (window_info.cols as usize - 1);
-            }
-            self.scr_col = self.buffer_col - self.buffer_col_offset;
-        }
+/// The prompt line as it's drawn in `width` pixels, and the part of it
+/// before the cursor. Text scrolled or cut off either end gives way to an
+/// ellipsis.
+fn prompt_line(font: &Font, editor: &TextEditor, label: &str, width: u32) -> (String, String) {
+    let (shown, before_cursor) = editor.prompt.shown();
+    let lead = if shown.len() < editor.prompt.text.len() { ELLIPSIS } else { "" };
+    let mut line = format!("{}{}{}", label, lead, shown);
+    if editor.mode == EditorMode::Rename {
+        let literals = if editor.rename_skips_literals { "skipped" } else { "included" };
+        line.push_str(&format!("   (strings and comments {}, Tab to change)", literals));
+    }
 
-        if self.scr_row > 0 && !(self.scr_row == 5 && self.buffer_row > 5) {
-            self.scr_row -= 1;
-        }
+    (fit_text(font, &line, width, false), format!("{}{}{}", label, lead, before_cursor))
+}
+
+/// As much of `text` as fits in `width` pixels, with an ellipsis in place of
+/// the rest: cut off the end, or with `from_start` off the start
+fn fit_text(font: &Font, text: &str, width: u32, from_start: bool) -> String {
+    if text_width(font, text) <= width {
+        return text.to_string();
     }
-    
-    fn move_cursor_down(&mut self, window_info: &WindowInfo) {
-        if self.buffer_row == self.lines.len() - 1 {
-            return
+
+    let chars: Vec<char> = text.chars().collect();
+    let keeping = |n: usize| -> String {
+        if from_start {
+            ELLIPSIS.chars().chain(chars[chars.len() - n..].iter().copied()).collect()
+        } else {
+            chars[..n].iter().copied().chain(ELLIPSIS.chars()).collect()
         }
+    };
 
-        if self.buffer_row < self.lines.len() - 1 {
-            self.buffer_row += 1;
+    // The most chars that still fit, found by halving
+    let (mut fewest, mut most) = (0, chars.len());
+    while fewest < most {
+        let n = (fewest + most).div_ceil(2);
+        if text_width(font, &keeping(n)) <= width {
+            fewest = n;
+        } else {
+            most = n - 1;
+        }
+    }
 
-            if self.buffer_col > self.lines[self.buffer_row].len() {
-                self.buffer_col = self.lines[self.buffer_row].len();
-            }
+    if text_width(font, &keeping(fewest)) <= width { keeping(fewest) } else { String::new() }
+}
 
-            // Adjust horizontal scrolling
-            if self.buffer_col < self.buffer_col_offset {
-                self.buffer_col_offset = self.buffer_col;
-            } else if self.buffer_col >= self.buffer_col_offset + window_info.cols as usize {
-                self.buffer_col_offset = self.buffer_col - (window_info.cols as usize - 1);
-            }
-            self.scr_col = self.buffer_col - self.buffer_col_offset;
+/// The text for one %-field of the status format
+fn status_field(editor: &TextEditor, field: char) -> String {
+    let buf = editor.buf();
+    match field {
+        'f' => buf.filename.clone(),
+        'm' if buf.is_modified && buf.filename != SCRATCH_BUFFER => String::from("*"),
+        'b' => (editor.current + 1).to_string(),
+        'B' => editor.buffers.len().to_string(),
+        'r' => {
+            let encoding_flag = format!(" [{}]", buf.encoding.name());
+            let flags = [
+                (buf.read_only, " [RO]"),
+                (buf.copy, " [copy]"),
+                (buf.encoding != encoding::Encoding::Utf8, encoding_flag.as_str()),
+                (buf.watch && !buf.tail, " [watch]"),
+                (buf.following(), " [tail]"),
+                (buf.tail && !buf.following(), " [tail paused]"),
+                (editor.recording.is_some(), " [REC]"),
+            ];
+            flags.iter().filter(|(on, _)| *on).map(|(_, flag)| *flag).collect()
         }
+        'M' if editor.config.modal => String::from(if editor.normal { "  NORMAL" } else { "  INSERT" }),
+        'R' => editor.repeat.map(|count| format!("  repeat: {}", count)).unwrap_or_default(),
+        'l' => (buf.buffer_row + 1).to_string(),
+        'c' => (buf.buffer_col + 1).to_string(),
+        'p' => ((buf.buffer_row + 1) * 100 / buf.lines.len()).to_string(),
+        'e' => buf.line_ending.name().to_string(),
+        'E' => buf.encoding.name().to_string(),
+        'n' => buf.language.map_or("plain text", |lang| lang.name).to_string(),
+        'i' => buf.indent.describe(),
+        'h' => outline::enclosing(buf.outline(), buf.buffer_row).map(|h| h.title.clone()).unwrap_or_default(),
+        _ => String::new(),
+    }
+}
 
-        let bm = EDITOR_ROWS as usize - 5;
-        if self.scr_row < window_info.rows as usize - 1 && !(self.scr_row == bm && self.buffer_row < self.lines.len() - 5) {
-            self.scr_row += 1;
+fn main() -> Result<(), String> {
+    let args = match args::parse(std::env::args().skip(1)) {
+        Ok(Invocation::Run(args)) => args,
+        Ok(Invocation::Help) => {
+            print!("{}", args::usage());
+            return Ok(());
+        }
+        Ok(Invocation::Version) => {
+            println!("wfemto {}", VERSION);
+            println!("log file: {}", log::log_path().display());
+            return Ok(());
+        }
+        Err(e) => {
+            eprintln!("wfemto: {}", e);
+            eprint!("{}", args::usage());
+            std::process::exit(EXIT_USAGE);
         }
+    };
+
+    log::init();
+    log_info!("wfemto {} starting", VERSION);
+
+    let config = Config::load();
+    if let Some(level) = config.log_level
+        && log::level_from_env().is_none()
+    {
+        log::set_level(level);
     }
 
-    /// Save the current file
-    fn save(&mut self) {
-        // TODO: Implement file saving
-        println!("Save file: {}", self.filename);
+    let startup = match preflight() {
+        Ok(startup) => startup,
+        Err((status, e)) => fatal(status, &e),
+    };
+
+    let result = run(args, config, startup);
+    if let Err(e) = &result {
+        log_error!("exiting with error: {}", e);
     }
 
-    fn load(&mut self, filename: &str) -> Result<(), String> {
-        let file = File::open(filename).map_err(|e| e.to_string())?;
-        let reader = BufReader::new(file);
+    result
+}
 
-        self.lines.clear();
-        for line in reader.lines() {
-            self.lines.push(line.map_err(|e| e.to_string())?);
-        }
+/// What has to be in place before there's any point opening a window
+struct Startup {
+    sdl_context: sdl2::Sdl,
+    video_subsystem: sdl2::VideoSubsystem,
+    ttf_context: sdl2::ttf::Sdl2TtfContext,
+    font_path: PathBuf,
+}
 
-        self.filename = filename.to_string();
-        self.scr_col = 0;
-        self.scr_row = 0;
-        self.buffer_col = 0;
-        self.buffer_row = 0;
-        self.buffer_col_offset = 0;
-        self.is_modified = false;
+/// Start SDL and find the font, or say which of them failed, how, and the
+/// exit status for it
+fn preflight() -> Result<Startup, (i32, String)> {
+    let no_display = |e: String| {
+        (EXIT_NO_DISPLAY, format!("Unable to start SDL: {}\nIs there a display to open a window on?", e))
+    };
+    let sdl_context = sdl2::init().map_err(no_display)?;
+    let video_subsystem = sdl_context.video().map_err(no_display)?;
+    log_info!("SDL {} initialized, video driver: {}",
+        sdl2::version::version(), video_subsystem.current_video_driver());
+
+    let ttf_context = sdl2::ttf::init()
+        .map_err(|e| (EXIT_NO_FONT, format!("Unable to start SDL_ttf: {}", e)))?;
+    let font_path = find_font()?;
+    ttf_context.load_font(&font_path, FONT_SIZE)
+        .map_err(|e| (EXIT_NO_FONT, format!("Unable to load the font {}: {}", font_path.display(), e)))?;
+
+    Ok(Startup { sdl_context, video_subsystem, ttf_context, font_path })
+}
 
-        Ok(())
+/// Where a file that comes with wfemto, like the font, can be: in the
+/// working directory or beside the executable
+fn bundled_places(name: &str) -> Vec<PathBuf> {
+    let mut places = Vec::new();
+    if let Ok(dir) = std::env::current_dir() {
+        places.push(dir.join(name));
     }
+    if let Some(dir) = std::env::current_exe().ok().and_then(|exe| exe.parent().map(Path::to_path_buf)) {
+        places.push(dir.join(name));
+    }
+    places.dedup();
+
+    places
 }
 
-fn render_text(
-    canvas: &mut Canvas<Window>,
-    font: &Font,
-    text: &str,
-    x: i32,
-    y: i32,
-    colour: Color,
-) -> Result<(), String> {
-    
-    if text.is_empty() {
-        return Ok(());
+/// The first FONT_PATH found in the working directory or beside the
+/// executable
+fn find_font() -> Result<PathBuf, (i32, String)> {
+    let places = bundled_places(FONT_PATH);
+    if let Some(found) = places.iter().find(|p| p.is_file()) {
+        return Ok(found.clone());
     }
-    
-    let surface = font
-        .render(text).blended(colour).map_err(|e| e.to_string())?;
-    
-    let texture_creator = canvas.texture_creator();
-    let texture = texture_creator
-        .create_texture_from_surface(&surface)
-        .map_err(|e| e.to_string())?;
-    
-    let target = Rect::new(x, y, surface.width(), surface.height());
-    canvas.copy(&texture, None, Some(target))?;
-    
-    Ok(())
+
+    let searched: Vec<String> = places.iter().map(|p| format!("  {}", p.display())).collect();
+    Err((EXIT_NO_FONT, format!("Unable to find the font {}. Looked for:\n{}\nPut a copy in one of those places.",
+        FONT_PATH, searched.join("\n"))))
 }
 
-fn draw_status_bar(
-    canvas: &mut Canvas<Window>, 
-    font: &Font, 
-    editor: &TextEditor, 
-    window_info: &WindowInfo
-) -> Result<(), String> {    
-    let status = match editor.mode {
-        EditorMode::Edit => { 
-            let mut status = editor.filename.clone();  
-            if editor.is_modified {
-                status.push('*');
-            }
-            status
-        },
-        EditorMode::OpenFile => {
-            let mut status = String::from("Open file: ");
-            status.push_str(&editor.input_buffer);
-            status
-        },
-    };
-    
-    let status_bar_row_pixels = window_info.rows * window_info.char_height + MARGIN_TOP as u32;
+/// Report an error that stops wfemto starting, in a message box as well when
+/// there's a display to show one on, since there may be no terminal to see
+/// stderr, and exit with `status`
+fn fatal(status: i32, message: &str) -> ! {
+    log_error!("can't start: {}", message);
+    eprintln!("wfemto: {}", message);
+    let shown = sdl2::messagebox::show_simple_message_box(
+        sdl2::messagebox::MessageBoxFlag::ERROR, "wfemto can't start", message, None);
+    if let Err(e) = shown {
+        log_debug!("no message box: {}", e);
+    }
 
-    canvas.set_draw_color(Color::RGB(217, 217, 214));
-    canvas.fill_rect(Rect::new(0, status_bar_row_pixels as i32, 
-        window_info.cols * window_info.char_width + (MARGIN_LEFT as u32 * 2), window_info.char_height)).map_err(|e| e.to_string())?;
-    canvas.set_draw_color(Color::RGB(0, 0, 0));
+    std::process::exit(status);
+}
 
-    render_text(
-        canvas,
-        font,
-        &status,
-        10, status_bar_row_pixels as i32, Color::RGB(89, 89, 88))?;
+/// Give `buffer` the settings for a file called `filename`: the global ones
+/// with its [filetype.ext] section over them. Indentation detection and
+/// EditorConfig get their say after this, when the file's loaded, and an
+/// indent set by hand this session beats the lot.
+fn apply_settings(config: &Config, buffer: &mut Buffer, filename: &str) {
+    let filetype = config.filetype(filename).cloned().unwrap_or_default();
+    buffer.tab_width = filetype.tab_width.unwrap_or(config.tab_width);
+    buffer.auto_indent = filetype.auto_indent.unwrap_or(config.auto_indent);
+    buffer.trim_trailing_whitespace = filetype.trim_trailing_whitespace.unwrap_or(config.trim_trailing_whitespace);
+    buffer.fill_column = filetype.fill_column.unwrap_or(config.fill_column);
+    buffer.word_chars = filetype.word_chars.unwrap_or_else(|| config.word_chars.clone());
+    buffer.auto_wrap = filetype.auto_wrap.unwrap_or(config.auto_wrap);
+    if !buffer.indent_locked {
+        buffer.indent = filetype.indent.unwrap_or(config.indent);
+        buffer.indent_from = "config";
+    }
+}
 
-    Ok(())
+/// Drawing pixels per window unit: 1 normally, 2 or so on a HiDPI screen
+fn display_scale(canvas: &Canvas<Window>) -> f32 {
+    let (drawable, _) = canvas.output_size().unwrap_or((1, 1));
+    let (window, _) = canvas.window().size();
+    if window == 0 { 1.0 } else { (drawable as f32 / window as f32).max(1.0) }
 }
 
-fn main() -> Result<(), String> {
-    let sdl_context = sdl2::init()?;
-    let video_subsystem = sdl_context.video()?;
+/// If the window's scale has changed (it opened on a HiDPI screen, or was
+/// dragged to a screen with a different one), the font at the new size and
+/// the metrics that go with it
+fn rescale<'ttf>(
+    ttf_context: &'ttf sdl2::ttf::Sdl2TtfContext,
+    font_path: &Path,
+    canvas: &Canvas<Window>,
+    window_info: &WindowInfo,
+    config: &Config,
+) -> Result<Option<(Font<'ttf, 'static>, WindowInfo)>, String> {
+    let scale = display_scale(canvas);
+    if (scale - window_info.scale).abs() < 0.01 {
+        return Ok(None);
+    }
 
-    let ttf_context = sdl2::ttf::init().map_err(|e| e.to_string())?;
+    let size = (FONT_SIZE as f32 * scale).round() as u16;
+    let font = ttf_context.load_font(font_path, size)?;
+    let (char_width, char_height) = font.size_of("X").map_err(|e| e.to_string())?;
+    log_info!("display scale is now {}: font at {}pt, cell size {}x{}", scale, size, char_width, char_height);
 
-    let font_path = "DejaVuSansMono.ttf";    
-    let font = ttf_context.load_font(font_path, FONT_SIZE)?;
-    
+    let margin_left = (config.margin_left as f32 * scale).round() as i32;
+    let margin_top = (config.margin_top as f32 * scale).round() as i32;
+    Ok(Some((font, WindowInfo { char_width, char_height, margin_left, margin_top, scale, ..*window_info })))
+}
+
+fn run(args: Args, config: Config, startup: Startup) -> Result<(), String> {
+    let Startup { sdl_context, video_subsystem, ttf_context, font_path } = startup;
+
+    let mut font = ttf_context.load_font(&font_path, FONT_SIZE)?;
     let (char_width, char_height) = font.size_of("X").map_err(|e| e.to_string())?;
+    log_info!("loaded font {} at {}pt, cell size {}x{}", font_path.display(), FONT_SIZE, char_width, char_height);
 
-    let window_width = EDITOR_COLS * char_width + (MARGIN_LEFT * 2) as u32;
-    let window_height = ((EDITOR_ROWS + 1) * char_height) + MARGIN_TOP as u32;
+    // Sized in window units; on a HiDPI screen there are more pixels than that
+    // to draw in, which is sorted out once the window exists
+    let window_width = EDITOR_COLS * char_width + config.margin_left * 2;
+    let window_height = ((EDITOR_ROWS + 1) * char_height) + config.margin_top;
+    log_debug!("window size {}x{}", window_width, window_height);
 
-    let window_info = WindowInfo { rows: EDITOR_ROWS, cols: EDITOR_COLS, char_width, char_height };
+    let mut window_info = WindowInfo {
+        rows: EDITOR_ROWS,
+        cols: EDITOR_COLS,
+        char_width,
+        char_height,
+        scroll_margin: config.scroll_margin,
+        text_top: config.margin_top as i32,
+        status_y: 0,
+        prompt_y: 0,
+        margin_left: config.margin_left as i32,
+        margin_top: config.margin_top as i32,
+        text_left: config.margin_left as i32,
+        scale: 1.0,
+    };
 
     let window = video_subsystem
         .window("wfemto", window_width, window_height)
         .position_centered()
+        .allow_highdpi()
         .build()
         .map_err(|e| e.to_string())?;
 
-    let mut canvas = window.into_canvas().build().map_err(|e| e.to_string())?;
+    let mut canvas_builder = window.into_canvas();
+    if config.vsync {
+        canvas_builder = canvas_builder.present_vsync();
+    }
+    let mut canvas = canvas_builder.build().map_err(|e| e.to_string())?;
+    // Asking for vsync doesn't mean getting it; without it we pace ourselves
+    let vsync_flag = sdl2::sys::SDL_RendererFlags::SDL_RENDERER_PRESENTVSYNC as u32;
+    let vsync = canvas.info().flags & vsync_flag != 0;
+    log_info!("renderer {}, vsync {}", canvas.info().name, if vsync { "on" } else { "off" });
+    let texture_creator = canvas.texture_creator();
+    let mut minimap = minimap::Minimap::new(&texture_creator);
+    if let Some(scaled) = rescale(&ttf_context, &font_path, &canvas, &window_info, &config)? {
+        (font, window_info) = scaled;
+    }
 
-    let mut editor = TextEditor::new();
+    let mut editor = TextEditor::new(config);
+    window_info = editor.layout(&window_info);
+    editor.clipboard = Some(video_subsystem.clipboard());
     let mut event_pump = sdl_context.event_pump()?;
 
-    let mut splash_title= true;
-    
-    //editor.load("src/main.rs")?;
+    let text_input = video_subsystem.text_input();
+    let mut ime_rect_set: Option<Rect> = None;
+    let mut status_name: Option<(i32, i32)> = None; // Where the file name is across the status bar
+    let mut hover: Option<((i32, i32), std::time::Instant)> = None; // Where the pointer is resting, and since when
+
+    if let Some(file) = &args.file {
+        apply_settings(&editor.config, &mut editor.buffers[editor.current], file);
+        if Path::new(file).exists() {
+            editor.buf_mut().load(file)?;
+            editor.refresh_git_marks();
+        } else {
+            log_info!("{} does not exist, starting a new file", file);
+            editor.buf_mut().set_filename(file);
+            editor.buf_mut().apply_editorconfig();
+        }
+        editor.splash = false;
+    }
+    editor.buf_mut().read_only = args.readonly;
+    if args.file.is_some() && !args.readonly {
+        editor.check_swap();
+    }
+
+    if let Some(line) = args.line {
+        let col = args.col.unwrap_or(1);
+        editor.buf_mut().goto_line(line.saturating_sub(1), col.saturating_sub(1), &window_info);
+    }
+
+    if let Some(name) = &args.session
+        && editor.load_session(name)
+    {
+        editor.splash = false;
+    }
 
     'running: loop {
+        let frame_start = std::time::Instant::now();
+        window_info = editor.layout(&window_info);
         for event in event_pump.poll_iter() {
+            // The pointer rests from when it last moved; a key, a click, the
+            // wheel or leaving the window all put an end to it
+            hover = match &event {
+                Event::MouseMotion { x, y, .. } => Some((window_info.to_pixels(*x, *y), std::time::Instant::now())),
+                Event::KeyDown { .. } | Event::MouseButtonDown { .. } | Event::MouseWheel { .. }
+                    | Event::Window { win_event: WindowEvent::Leave, .. } => None,
+                _ => hover,
+            };
+
             match event {
-                Event::Quit { .. } => break 'running,
-                Event::TextInput { text, .. } => {
-                    for c in text.chars() {
-                        editor.insert_char(c, &window_info);
+                Event::Quit { .. } if editor.request_quit(&window_info) => break 'running,
+                Event::Window { win_event: WindowEvent::DisplayChanged(_) | WindowEvent::SizeChanged(..)
+                    | WindowEvent::Moved(..), .. } => {
+                    if let Some(scaled) = rescale(&ttf_context, &font_path, &canvas, &window_info, &editor.config)? {
+                        (font, window_info) = scaled;
+                        window_info = editor.layout(&window_info);
                     }
-                    splash_title= false;
-                }
-
-                Event::KeyDown {
-                    keycode: Some(keycode),
-                    keymod,
-                    ..
-                } => {
-                    splash_title= false;
-                    // Handle special keys
-                    match keycode {
-                        Keycode::Return => if editor.mode == EditorMode::Edit {
-                            editor.insert_newline()
-                        } else {
-                            let filename = editor.input_buffer.clone();
-                            editor.load(&filename);
-                            editor.mode = EditorMode::Edit;                            
-                        },
-                        Keycode::Backspace => {
-                            if editor.mode == EditorMode::Edit {
-                                editor.backspace(&window_info);
-                            } else {
-                                editor.backspace_buffer(OPEN_FILE_MARGIN);
-                            }
-                        },
-                        Keycode::Left => editor.move_cursor_left(&window_info),
-                        Keycode::Right => editor.move_cursor_right(&window_info),
-                        Keycode::Up => {
-                            if editor.mode == EditorMode::Edit {
-                                editor.move_cursor_up(&window_info)
-                            }
-                        },
-                        Keycode::Down => {
-                            if editor.mode == EditorMode::Edit {
-                                editor.move_cursor_down(&window_info)
-                            }                            
-                        },
-                        Keycode::Q if keymod.contains(sdl2::keyboard::Mod::LCTRLMOD)
-                            || keymod.contains(sdl2::keyboard::Mod::RCTRLMOD) =>
-                        {
-                            break 'running;
+                }
+                // Alt shortcuts can still produce text; don't type it
+                Event::TextInput { .. } if sdl_context.keyboard().mod_state().intersects(Mod::LALTMOD) => {}
+                // ...and some platforms type the space of Ctrl+Space
+                Event::TextInput { ref text, .. } if text == " "
+                    && sdl_context.keyboard().mod_state().intersects(Mod::LCTRLMOD | Mod::RCTRLMOD) => {}
+                Event::TextEditing { text, .. } => editor.composition = text,
+                Event::TextInput { text, .. } => editor.handle_text(&text, &window_info),
+
+                Event::MouseButtonDown { mouse_btn: MouseButton::Left, x, y, .. } if editor.mode == EditorMode::Edit
+                    && editor.minimap_area(&window_info).is_some_and(|area| {
+                        let (x, y) = window_info.to_pixels(x, y);
+                        area.contains_point((x, y))
+                    }) => {
+                    let (_, y) = window_info.to_pixels(x, y);
+                    let Some(area) = editor.minimap_area(&window_info) else {
+                        continue;
+                    };
+                    let (rows, _) = editor.pane_rows(&window_info);
+                    let buf = editor.buf();
+                    let (box_y, box_height) = minimap::viewport(buf.lines.len(), area.height(), buf.top_line, rows as usize);
+
+                    // The box can be dragged from where it's grabbed; a click
+                    // anywhere else centres it there first
+                    let offset = y - area.y() - box_y;
+                    let grab = if (0..box_height as i32).contains(&offset) { offset } else { box_height as i32 / 2 };
+                    editor.minimap_grab = Some(grab);
+                    editor.minimap_scroll(y - grab, &window_info);
+                }
+                Event::MouseMotion { y, .. } if editor.minimap_grab.is_some() => {
+                    let (_, y) = window_info.to_pixels(0, y);
+                    let grab = editor.minimap_grab.unwrap_or(0);
+                    editor.minimap_scroll(y - grab, &window_info);
+                }
+                Event::MouseButtonUp { mouse_btn: MouseButton::Left, .. } if editor.minimap_grab.is_some() => {
+                    editor.minimap_grab = None;
+                }
+                // Clicking the file name in the status bar copies its path,
+                // with Ctrl its line too
+                Event::MouseButtonDown { mouse_btn: MouseButton::Left, x, y, .. } if editor.mode == EditorMode::Edit
+                    && status_name.is_some_and(|(from, to)| {
+                        let (x, y) = window_info.to_pixels(x, y);
+                        let bar = window_info.status_y..window_info.status_y + window_info.char_height as i32;
+                        bar.contains(&y) && (from..to).contains(&x)
+                    }) => {
+                    let ctrl = sdl_context.keyboard().mod_state().intersects(Mod::LCTRLMOD | Mod::RCTRLMOD);
+                    editor.copy_path(ctrl);
+                }
+                Event::MouseButtonDown { mouse_btn, clicks, x, y, .. } if editor.mode == EditorMode::Edit => {
+                    editor.completion = None;
+                    let (x, y) = window_info.to_pixels(x, y);
+                    let Some((row, col)) = click_position(&mut editor, &font, x, y, &window_info) else {
+                        continue;
+                    };
+                    let view_info = editor.view_info(&window_info);
+                    editor.splash = false;
+                    let buf = editor.buf_mut();
+                    buf.anchor = None;
+                    buf.goto_position(row, col, &view_info);
+
+                    match mouse_btn {
+                        // Left of the text, in the margin or among the line
+                        // numbers, selects whole lines
+                        MouseButton::Left if x < window_info.text_left => {
+                            buf.goto_position(row, 0, &view_info);
+                            buf.anchor = Some((row, 0));
+                            buf.line_anchor = buf.anchor;
+                            editor.dragging = true;
+                            editor.drag_pointer = Some((x, y));
                         }
-                        Keycode::S if keymod.contains(sdl2::keyboard::Mod::LCTRLMOD)
-                            || keymod.contains(sdl2::keyboard::Mod::RCTRLMOD) =>
-                        {
-                            editor.save();
-                        },
-                        Keycode::O if keymod.contains(sdl2::keyboard::Mod::LCTRLMOD)
-                            || keymod.contains(sdl2::keyboard::Mod::RCTRLMOD) =>
-                        {
-                            if editor.mode != EditorMode::OpenFile {
-                                editor.mode = EditorMode::OpenFile;
-                                editor.input_buffer = String::new();
-                                editor.prev_cursor_x = editor.scr_col;
-                                editor.prev_cursor_y = editor.scr_row;
-                                editor.scr_col = OPEN_FILE_MARGIN;
-                                editor.scr_row = EDITOR_ROWS as usize;
-                            }
-                        },
-                        Keycode::Home => {
-                            if editor.mode == EditorMode::Edit {
-                                editor.buffer_col = 0;
-                                editor.buffer_col_offset = 0;
-                                editor.scr_col = 0;
-                            }
-                            else {
-                                editor.scr_col = OPEN_FILE_MARGIN;
-                            }
-                        },
-                        Keycode::End => {
-                            if editor.mode == EditorMode::Edit {
-                                editor.buffer_col = editor.lines[editor.buffer_row].len();
-                                if editor.buffer_col < window_info.cols as usize {
-                                    editor.buffer_col_offset = 0;
-                                    editor.scr_col = editor.buffer_col;
-                                } else {
-                                    editor.buffer_col_offset = editor.buffer_col - (window_info.cols as usize - 1);
-                                    editor.scr_col = window_info.cols as usize - 1;
-                                }
-                            }
-                            else {
-                                editor.scr_col = editor.input_buffer.len() + OPEN_FILE_MARGIN;
+                        // A double-click selects the word, punctuation or
+                        // spaces it lands on
+                        MouseButton::Left if clicks == 2 => {
+                            let (start, end) = text::class_run(&buf.lines[row], col, &buf.word_chars);
+                            buf.line_anchor = None;
+                            buf.anchor = Some((row, start));
+                            buf.goto_position(row, end, &view_info);
+                        }
+                        MouseButton::Left => {
+                            buf.anchor = Some((row, col));
+                            buf.line_anchor = None;
+                            editor.dragging = true;
+                            editor.drag_pointer = Some((x, y));
+                        }
+                        MouseButton::Middle => {
+                            if let Some(text) = editor.clipboard.as_ref().and_then(clipboard::primary_text)
+                                && !editor.hold_big_paste(&text, None)
+                            {
+                                editor.insert_pasted(&text, &view_info);
                             }
-                        },
-                        Keycode::Escape => { 
-                            editor.mode = EditorMode::Edit;
-                            editor.scr_col = editor.prev_cursor_x;
-                            editor.scr_row = editor.prev_cursor_y;
-                        },
+                        }
                         _ => {}
                     }
                 }
+                Event::MouseMotion { mousestate, x, y, .. } if editor.dragging && mousestate.left() => {
+                    // Keep to the focused pane, however far the pointer strays.
+                    // Past its top or bottom, drag_scroll takes over.
+                    let view_info = editor.view_info(&window_info);
+                    let (top, bottom) = editor.pane_span(&window_info);
+                    let (x, y) = window_info.to_pixels(x, y);
+                    editor.drag_pointer = Some((x, y));
+                    let y = y.clamp(top, bottom - 1);
+                    if let Some((row, col)) = click_position(&mut editor, &font, x, y, &window_info) {
+                        editor.buf_mut().goto_position(row, col, &view_info);
+                    }
+                }
+                Event::MouseButtonUp { mouse_btn: MouseButton::Left, .. } if editor.dragging => {
+                    editor.dragging = false;
+                    editor.drag_pointer = None;
+                    if let Some(text) = editor.buf().selected_text()
+                        && let Some(clipboard) = &editor.clipboard
+                    {
+                        clipboard::publish_primary(clipboard, &text);
+                    }
+                    editor.published = editor.selection_key();
+                }
 
+                Event::KeyDown { keycode: Some(keycode), keymod, .. }
+                    if editor.handle_key(keycode, Mods::from_keymod(keymod), &window_info) => break 'running,
                 _ => {}
             }
         }
 
+        editor.poll_job(&window_info);
+        editor.poll_git_jobs();
+        editor.poll_build(&editor.view_info(&window_info));
+        editor.poll_grep();
+        editor.expire_pending();
+        editor.track_buffer_switch();
+        editor.drop_stale_spell_picker();
+        editor.write_swaps();
+        editor.poll_watched(&editor.view_info(&window_info));
+        drag_scroll(&mut editor, &font, &window_info);
+        editor.publish_selection();
+
         // Clear screen
-        canvas.set_draw_color(Color::RGB(255, 255, 255));
+        canvas.set_draw_color(sdl_colour(editor.theme.background));
         canvas.clear();
 
-        if editor.lines.len() > 0 && splash_title {
-            let s = String::from("wfemto 0.0.1 -- a toy text editor");
-            let col = EDITOR_COLS as i32 / 2 - s.len() as i32 / 2;
-
-            render_text(
-                &mut canvas,
-                &font,
-                &s,
-                col * window_info.char_width as i32, 
-                MARGIN_TOP + (EDITOR_ROWS as i32 / 4 * window_info.char_height as i32), 
-                Color::RGB(0, 0, 0))?;
-            canvas.present();
+        let (focused_rows, other_rows) = editor.pane_rows(&window_info);
+        let focused_first_row = if editor.focus_top { 0 } else { other_rows };
 
-            std::thread::sleep(Duration::from_millis(16)); // ~60 FPS
+        // Under the text, so it's drawn first
+        if editor.column_guide && !editor.splash {
+            let top = window_info.text_top + (focused_first_row * window_info.char_height) as i32;
+            canvas.set_draw_color(sdl_colour(editor.theme.column_guide));
+            canvas.fill_rect(Rect::new(cursor_x(&font, editor.buf(), &window_info), top, cursor_width(&font, editor.buf(), &window_info),
+                focused_rows * window_info.char_height)).map_err(|e| e.to_string())?;
+        }
 
-            continue           
-        } 
-        
-        let buffer_start = (editor.buffer_row as i32 - editor.scr_row as i32).max(0) as usize;
-        let buffer_end = (buffer_start + window_info.rows as usize).min(editor.lines.len());
+        // Mid-scroll the text is drawn from a line that may not be top_line
+        let drawn_top = editor.scroll_position();
+        let shift = (drawn_top.fract() * window_info.char_height as f32) as i32;
+        let mut focused_view = editor.buf().view();
+        focused_view.top_line = drawn_top as usize;
 
-        let mut scr_row = 0;
-        for buffer_row in buffer_start..buffer_end {
-            let line = &editor.lines[buffer_row];
+        let focused_end = editor.buf().top_line.max(focused_view.top_line) + focused_rows as usize + 1;
+        editor.buf_mut().update_highlight(focused_end);
+        editor.buf_mut().update_outline();
+        if let Some(pane) = &editor.split {
+            let end = pane.view.top_line + other_rows as usize;
+            editor.buffers[pane.buffer].update_highlight(end);
+        }
+        if editor.config.highlight_word && editor.mode == EditorMode::Edit {
+            let buf = &editor.buffers[editor.current];
+            editor.occurrences.update(buf, focused_rows as usize);
+        } else {
+            editor.occurrences.clear();
+        }
 
-            // Apply horizontal scrolling offset to all lines
-            let display_text = if editor.buffer_col_offset < line.len() {
-                &line[editor.buffer_col_offset..]
-            } else {
-                ""
+        let mut truncated = Vec::new(); // Lines cut off at the right, as (y, buffer, row)
+        if editor.splash {
+            let (width, height) = canvas.output_size()?;
+            draw_splash(&mut canvas, &font, &editor.theme, width, height - window_info.char_height)?;
+        } else {
+            let misspelled = editor.misspelled(editor.current, focused_view.top_line, focused_rows as usize + 1);
+            let other_misspelled = match &editor.split {
+                Some(pane) => editor.misspelled(pane.buffer, pane.view.top_line, other_rows as usize),
+                None => Vec::new(),
             };
+            let markers = Query { needles: &editor.config.todo_markers, whole_word: true, ignore_case: false };
+            let cut = draw_pane(&mut canvas, &font, &editor.theme, &markers, editor.buf(), &focused_view,
+                editor.buf().selection(), &editor.occurrences.matches, &misspelled, editor.line_numbers,
+                focused_first_row, focused_rows, shift, &window_info)?;
+            truncated.extend(cut.into_iter().map(|(y, row)| (y, editor.current, row)));
+
+            if let Some(pane) = &editor.split {
+                let other_first_row = if editor.focus_top { focused_rows } else { 0 };
+                let cut = draw_pane(&mut canvas, &font, &editor.theme, &markers, &editor.buffers[pane.buffer],
+                    &pane.view, None, &[], &other_misspelled, editor.line_numbers, other_first_row, other_rows, 0,
+                    &window_info)?;
+                truncated.extend(cut.into_iter().map(|(y, row)| (y, pane.buffer, row)));
 
-            render_text(
-                &mut canvas,
-                &font,
-                display_text,
-                MARGIN_LEFT,
-                MARGIN_TOP + (scr_row as i32 * window_info.char_height as i32),
-                Color::RGB(0, 0, 0))?;
-            scr_row += 1;
+                // Divider between the two panes
+                let top_rows = if editor.focus_top { focused_rows } else { other_rows };
+                let divider_y = window_info.text_top + (top_rows * window_info.char_height) as i32;
+                canvas.set_draw_color(sdl_colour(editor.theme.status_bg));
+                canvas.fill_rect(Rect::new(0, divider_y - 1, window_info.full_width(), 2))
+                    .map_err(|e| e.to_string())?;
+            }
         }
         
+        if let Some(panel) = &editor.panel {
+            draw_panel(&mut canvas, &font, &editor.theme, panel, editor.panel_focused, &window_info)?;
+        }
+
+        if let Some(area) = editor.minimap_area(&window_info)
+            && !editor.splash
+        {
+            let buf = editor.buf();
+            minimap.draw(&mut canvas, &editor.theme, buf, area, buf.top_line, focused_rows as usize)?;
+        }
+
         if editor.last_cursor_blink.elapsed() >= Duration::from_millis(500) {
             editor.cursor_visible = !editor.cursor_visible;
             editor.last_cursor_blink = std::time::Instant::now();
         }
         
-        draw_status_bar(&mut canvas, &font, &editor, &window_info)?;
+        // A prompt too long for the window scrolls to keep its cursor in view,
+        // with room for the ellipses that mark text cut off
+        if let Some(label) = editor.prompt_label() {
+            let room = window_info.full_width().saturating_sub(2 * window_info.margin_left as u32)
+                .saturating_sub(text_width(&font, label) + 2 * text_width(&font, ELLIPSIS));
+            editor.prompt.scroll(|text| text_width(&font, text) <= room);
+        }
+
+        // The splash has no status bar unless a prompt was opened over it
+        status_name = None;
+        if !editor.splash || editor.mode != EditorMode::Edit {
+            status_name = draw_status_bar(&mut canvas, &font, &editor, &window_info)?;
+        }
         
-        if editor.cursor_visible {            
-            canvas.set_draw_color(Color::RGB(128, 128, 128));
-            
+        if let Some(completion) = &editor.completion {
+            draw_popup(&mut canvas, &font, &editor.theme, editor.buf(), &completion.list,
+                (completion.row, completion.start), focused_first_row, focused_rows, &window_info)?;
+        }
+        if let Some(picker) = &editor.tag_picker {
+            let buf = editor.buf();
+            draw_popup(&mut canvas, &font, &editor.theme, buf, &picker.list,
+                (buf.buffer_row, buf.buffer_col), focused_first_row, focused_rows, &window_info)?;
+        }
+        if let Some(picker) = &editor.ring_picker {
+            let buf = editor.buf();
+            draw_popup(&mut canvas, &font, &editor.theme, buf, picker,
+                (buf.buffer_row, buf.buffer_col), focused_first_row, focused_rows, &window_info)?;
+        }
+        if let Some(picker) = &editor.spell_picker {
+            draw_popup(&mut canvas, &font, &editor.theme, editor.buf(), &picker.list,
+                (picker.row, picker.start), focused_first_row, focused_rows, &window_info)?;
+        }
+        // A line cut off at the right is shown whole once the pointer has
+        // rested on it a moment, unless something else is in the way
+        let overlaid = editor.completion.is_some() || editor.tag_picker.is_some() || editor.ring_picker.is_some()
+            || editor.spell_picker.is_some() || editor.info.is_some() || editor.help.is_some();
+        if let Some(((x, y), since)) = hover
+            && since.elapsed() >= HOVER_DELAY
+            && editor.mode == EditorMode::Edit && !editor.dragging && editor.minimap_grab.is_none() && !overlaid
+            && (window_info.text_left..window_info.text_left + (window_info.cols * window_info.char_width) as i32)
+                .contains(&x)
+            && let Some(&(row_y, buffer, row)) = truncated.iter()
+                .find(|(row_y, _, _)| (*row_y..*row_y + window_info.char_height as i32).contains(&y))
+        {
+            draw_tooltip(&mut canvas, &font, &editor.theme, &editor.buffers[buffer], row, (x, row_y), &window_info)?;
+        }
+        if let Some(info) = &editor.info {
+            draw_info_box(&mut canvas, &font, &editor.theme, info, &window_info)?;
+        }
+        if let Some(page) = editor.help {
+            let pages = editor.help_pages(window_info.rows);
+            let mut lines = pages.get(page).cloned().unwrap_or_default();
+            lines.push(String::new());
+            lines.push(format!("Page {} of {}: PageUp/PageDown for more, Esc or F1 to close", page + 1, pages.len()));
+            draw_info_box(&mut canvas, &font, &editor.theme, &lines, &window_info)?;
+        }
+
+        // Typing only goes somewhere in the buffer or a prompt; the y/n
+        // questions take keys, not text
+        let wants_text = editor.mode == EditorMode::Edit || editor.in_prompt();
+        if wants_text != text_input.is_active() {
+            if wants_text {
+                text_input.start();
+            } else {
+                text_input.stop();
+                editor.composition.clear();
+            }
+        }
+
+        let show_cursor = match editor.mode {
+            // It would have to slide along with the text
+            EditorMode::Edit => !editor.splash && editor.scroll_anim.is_none(),
+            _ => editor.in_prompt(),
+        };
+        if show_cursor {
+            let buf = editor.buf();
+
             // Calculate actual text width up to cursor position
             // NB: char_width * text was inaccurate
-            let text_width = if editor.mode == EditorMode::OpenFile {
-                let status = format!("Open file: {}", &editor.input_buffer[..editor.scr_col - OPEN_FILE_MARGIN]);
-                font.size_of(&status).unwrap_or((0, 0)).0
+            let x = if let Some(label) = editor.prompt_label() {
+                let room = window_info.full_width().saturating_sub(2 * window_info.margin_left as u32);
+                let (_, before_cursor) = prompt_line(&font, &editor, label, room);
+                window_info.margin_left + text_width(&font, &before_cursor) as i32
+            } else {
+                cursor_x(&font, buf, &window_info)
+            };
+
+            // The prompt cursor lives on the prompt line, not inside a pane
+            let y = if editor.in_prompt() {
+                window_info.prompt_y
             } else {
-                let text_before_cursor = &editor.lines[editor.buffer_row][..editor.buffer_col];
-                font.size_of(text_before_cursor).unwrap_or((0, 0)).0
+                window_info.text_top + ((focused_first_row as usize + buf.scr_row()) as i32 * window_info.char_height as i32)
             };
-                        
-            let cursor_rect = Rect::new(
-                MARGIN_LEFT + text_width as i32,
-                MARGIN_TOP + (editor.scr_row as i32 * window_info.char_height as i32),
-                2,
-                window_info.char_height,
-            );
-            canvas.fill_rect(cursor_rect).map_err(|e| e.to_string())?;
+
+            // Text an input method is still composing is drawn over the line,
+            // underlined, until it's committed
+            let mut composed_width = 0;
+            if !editor.composition.is_empty() {
+                composed_width = text_width(&font, &editor.composition);
+                canvas.set_draw_color(sdl_colour(editor.theme.background));
+                canvas.fill_rect(Rect::new(x, y, composed_width.max(1), window_info.char_height))
+                    .map_err(|e| e.to_string())?;
+                render_text(&mut canvas, &font, &editor.composition, x, y, sdl_colour(editor.theme.foreground))?;
+                canvas.set_draw_color(sdl_colour(editor.theme.foreground));
+                canvas.fill_rect(Rect::new(x, y + window_info.char_height as i32 - 2, composed_width.max(1), 1))
+                    .map_err(|e| e.to_string())?;
+            }
+
+            // So the input method's candidate window opens beside the cursor
+            let ime_rect = Rect::new(x, y, composed_width.max(2), window_info.char_height);
+            if ime_rect_set != Some(ime_rect) {
+                text_input.set_rect(window_info.to_window_units(ime_rect));
+                ime_rect_set = Some(ime_rect);
+            }
+
+            if editor.cursor_visible {
+                canvas.set_draw_color(sdl_colour(editor.theme.cursor));
+                let cursor_rect = Rect::new(x + composed_width as i32, y, 2, window_info.char_height);
+                canvas.fill_rect(cursor_rect).map_err(|e| e.to_string())?;
+            }
         }
 
         canvas.present();
+        editor.finish_paste(&window_info);
 
-        std::thread::sleep(Duration::from_millis(16)); // ~60 FPS
+        // With vsync, present() has already waited for the display
+        if !vsync {
+            std::thread::sleep(FRAME_INTERVAL.saturating_sub(frame_start.elapsed()));
+        }
+    }
+
+    if let Some(name) = editor.session_name.clone() {
+        editor.save_session(&name);
+    }
+    editor.buffers.iter().for_each(Buffer::save_history);
+    for index in 0..editor.buffers.len() {
+        editor.drop_swap(index);
     }
 
     Ok(())
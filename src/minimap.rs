@@ -0,0 +1,148 @@
+// wfemto - A toy text editor
+// Written in 2025 by Dana Larose <ywg.dana@gmail.com>
+//
+// To the extent possible under law, the author(s) have dedicated all copyright
+// and related and neighboring rights to this software to the public domain
+// worldwide. This software is distributed without any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication along
+// with this software. If not,
+// see <http://creativecommons.org/publicdomain/zero/1.0/>.
+
+// The whole buffer shrunk down the right-hand side of the window: a streak of
+// pixels for each line, as long as the line, with changed lines flagged in
+// the git colours. The streaks are drawn into a texture that's only redone
+// when the text or its marks change; the box showing which part is on screen
+// goes over it every frame.
+
+use sdl2::pixels::PixelFormatEnum;
+use sdl2::rect::Rect;
+use sdl2::render::{BlendMode, Canvas, Texture, TextureCreator};
+use sdl2::video::{Window, WindowContext};
+
+use crate::buffer::Buffer;
+use crate::gitdiff::GitMark;
+use crate::text::display_col;
+use crate::theme::{Rgb, Theme};
+
+/// Text columns the minimap takes from the right of the window
+pub const COLS: u32 = 8;
+
+/// Columns of text per pixel across
+const COLS_PER_PIXEL: usize = 2;
+
+/// Pixels down per line when the buffer is short enough to allow it
+const MAX_LINE_HEIGHT: f32 = 2.0;
+
+/// Room on the left for git marks
+const MARK_WIDTH: usize = 2;
+
+pub struct Minimap<'a> {
+    creator: &'a TextureCreator<WindowContext>,
+    texture: Option<Texture<'a>>,
+    drawn: Option<(u64, u64, u32, u32)>, // Buffer id, generation and size the texture was drawn for...
+    marks: Vec<GitMark>, // ...and the git marks it shows
+}
+
+impl<'a> Minimap<'a> {
+    pub fn new(creator: &'a TextureCreator<WindowContext>) -> Self {
+        Minimap { creator, texture: None, drawn: None, marks: Vec::new() }
+    }
+
+    /// Draw `buf` into `area`, with `rows` lines from `top_line` boxed as
+    /// being on screen
+    pub fn draw(
+        &mut self,
+        canvas: &mut Canvas<Window>,
+        theme: &Theme,
+        buf: &Buffer,
+        area: Rect,
+        top_line: usize,
+        rows: usize,
+    ) -> Result<(), String> {
+        let key = (buf.id, buf.generation, area.width(), area.height());
+        if self.drawn != Some(key) || self.marks != buf.git_marks || self.texture.is_none() {
+            let mut texture = match self.texture.take() {
+                Some(t) if t.query().width == area.width() && t.query().height == area.height() => t,
+                _ => self.creator.create_texture_streaming(PixelFormatEnum::RGB24, area.width(), area.height())
+                    .map_err(|e| e.to_string())?,
+            };
+            let pixels = streaks(theme, buf, area.width() as usize, area.height() as usize);
+            texture.update(None, &pixels, area.width() as usize * 3).map_err(|e| e.to_string())?;
+            self.texture = Some(texture);
+            self.drawn = Some(key);
+            self.marks.clone_from(&buf.git_marks);
+        }
+
+        if let Some(texture) = &self.texture {
+            canvas.copy(texture, None, Some(area))?;
+        }
+
+        let (y, height) = viewport(buf.lines.len(), area.height(), top_line, rows);
+        let Rgb(r, g, b) = theme.selection;
+        canvas.set_blend_mode(BlendMode::Blend);
+        canvas.set_draw_color(sdl2::pixels::Color::RGBA(r, g, b, 110));
+        let drawn = canvas.fill_rect(Rect::new(area.x(), area.y() + y, area.width(), height));
+        canvas.set_blend_mode(BlendMode::None);
+        drawn
+    }
+}
+
+/// Pixels down per buffer line, less than one for a long buffer
+fn line_height(line_count: usize, height: u32) -> f32 {
+    (height as f32 / line_count.max(1) as f32).min(MAX_LINE_HEIGHT)
+}
+
+/// Where the box for lines `top_line` on, `rows` of them, goes: its offset
+/// from the top of the minimap and its height
+pub fn viewport(line_count: usize, height: u32, top_line: usize, rows: usize) -> (i32, u32) {
+    let per_line = line_height(line_count, height);
+    let y = (top_line as f32 * per_line) as i32;
+    let shown = rows.min(line_count.saturating_sub(top_line).max(1));
+    (y, ((shown as f32 * per_line) as u32).max(2))
+}
+
+/// The buffer line at `y` pixels down the minimap
+pub fn line_at(line_count: usize, height: u32, y: i32) -> usize {
+    let per_line = line_height(line_count, height);
+    ((y.max(0) as f32 / per_line) as usize).min(line_count.saturating_sub(1))
+}
+
+/// The minimap as RGB24 pixels, `width` by `height`
+fn streaks(theme: &Theme, buf: &Buffer, width: usize, height: usize) -> Vec<u8> {
+    let mut pixels = vec![0; width * height * 3];
+    let mut fill = |x0: usize, x1: usize, y: usize, Rgb(r, g, b): Rgb| {
+        if y >= height {
+            return;
+        }
+        for x in x0..x1.min(width) {
+            let i = (y * width + x) * 3;
+            pixels[i..i + 3].copy_from_slice(&[r, g, b]);
+        }
+    };
+
+    for y in 0..height {
+        fill(0, width, y, theme.column_guide);
+    }
+
+    let per_line = line_height(buf.lines.len(), height as u32);
+    for (row, line) in buf.lines.iter().enumerate() {
+        let y = (row as f32 * per_line) as usize;
+        let indent = line.chars().take_while(|c| c.is_whitespace()).count();
+        let start = display_col(line, indent, buf.tab_width) / COLS_PER_PIXEL;
+        let end = display_col(line, line.chars().count(), buf.tab_width).div_ceil(COLS_PER_PIXEL);
+        if end > start {
+            fill(MARK_WIDTH + 1 + start, MARK_WIDTH + 1 + end, y, theme.comment);
+        }
+
+        let mark = match buf.git_marks.get(row) {
+            Some(GitMark::Added) => theme.git_added,
+            Some(GitMark::Modified) => theme.git_modified,
+            Some(GitMark::Deleted) => theme.git_deleted,
+            _ => continue,
+        };
+        fill(0, MARK_WIDTH, y, mark);
+    }
+
+    pixels
+}
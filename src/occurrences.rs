@@ -0,0 +1,81 @@
+// wfemto - A toy text editor
+// Written in 2025 by Dana Larose <ywg.dana@gmail.com>
+//
+// To the extent possible under law, the author(s) have dedicated all copyright
+// and related and neighboring rights to this software to the public domain
+// worldwide. This software is distributed without any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication along
+// with this software. If not,
+// see <http://creativecommons.org/publicdomain/zero/1.0/>.
+
+// Lighting up the other places the word under the cursor appears, once the
+// cursor has rested in it for a moment. Only the lines on screen are
+// searched, and only again when the word, the text or the scroll changes.
+
+use std::time::{Duration, Instant};
+
+use crate::buffer::Buffer;
+use crate::search::{Match, Query};
+use crate::text::{col_slice, word_at};
+
+/// How long the cursor has to stay in a word before it's highlighted
+pub const DELAY: Duration = Duration::from_millis(300);
+
+pub struct Occurrences {
+    word: String,
+    since: Instant, // When the cursor moved into the word
+    searched: Option<(u64, u64, usize, usize)>, // Buffer id, generation, top line and rows
+    dismissed: bool, // Escape put them away until the cursor leaves the word
+    pub matches: Vec<Match>,
+}
+
+impl Occurrences {
+    pub fn new() -> Self {
+        Occurrences { word: String::new(), since: Instant::now(), searched: None, dismissed: false, matches: Vec::new() }
+    }
+
+    /// Keep up with the cursor in `buf`, which shows `rows` lines
+    pub fn update(&mut self, buf: &Buffer, rows: usize) {
+        let line = &buf.lines[buf.buffer_row.min(buf.lines.len() - 1)];
+        let word = word_at(line, buf.buffer_col).map_or("", |(start, end)| col_slice(line, start, end));
+        if word != self.word {
+            self.word = word.to_string();
+            self.since = Instant::now();
+            self.dismissed = false;
+            self.clear();
+            return;
+        }
+        if word.is_empty() || self.dismissed || self.since.elapsed() < DELAY {
+            return;
+        }
+
+        let key = (buf.id, buf.generation, buf.top_line, rows);
+        if self.searched == Some(key) {
+            return;
+        }
+        self.searched = Some(key);
+
+        let needles = [self.word.clone()];
+        let query = Query { needles: &needles, whole_word: true, ignore_case: false };
+        let end = (buf.top_line + rows).min(buf.lines.len());
+        self.matches = query.find_all(&buf.lines, buf.top_line..end);
+    }
+
+    /// Stop highlighting until the cursor moves to another word. Returns
+    /// false if there was nothing highlighted.
+    pub fn dismiss(&mut self) -> bool {
+        if self.matches.is_empty() {
+            return false;
+        }
+
+        self.dismissed = true;
+        self.clear();
+        true
+    }
+
+    pub fn clear(&mut self) {
+        self.searched = None;
+        self.matches.clear();
+    }
+}
@@ -0,0 +1,70 @@
+// wfemto - A toy text editor
+// Written in 2025 by Dana Larose <ywg.dana@gmail.com>
+//
+// To the extent possible under law, the author(s) have dedicated all copyright
+// and related and neighboring rights to this software to the public domain
+// worldwide. This software is distributed without any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication along
+// with this software. If not,
+// see <http://creativecommons.org/publicdomain/zero/1.0/>.
+
+// The headings of a Markdown file, for the outline command to list and jump
+// between. Only ATX headings count: a line starting with one to six #s and a
+// space. A # line inside a fenced code block (``` or ~~~) is code, not a
+// heading, so the fences are tracked on the way down.
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Heading {
+    pub row: usize,
+    pub level: usize, // 1 for #, up to 6 for ######
+    pub title: String,
+}
+
+/// The level and title of `line` if it's a heading. Up to three spaces of
+/// indentation are allowed, and a closing run of #s isn't part of the title.
+pub fn heading(line: &str) -> Option<(usize, &str)> {
+    let rest = line.trim_start_matches(' ');
+    if line.len() - rest.len() > 3 {
+        return None;
+    }
+
+    let level = rest.chars().take_while(|&c| c == '#').count();
+    let rest = &rest[level..];
+    if !(1..=6).contains(&level) || !(rest.is_empty() || rest.starts_with([' ', '\t'])) {
+        return None;
+    }
+
+    let title = rest.trim();
+    let open = title.trim_end_matches('#');
+    if open.is_empty() || open.ends_with([' ', '\t']) {
+        Some((level, open.trim_end()))
+    } else {
+        Some((level, title))
+    }
+}
+
+/// Whether `line` opens or closes a fenced code block
+pub fn is_fence(line: &str) -> bool {
+    let rest = line.trim_start_matches(' ');
+    line.len() - rest.len() <= 3 && (rest.starts_with("```") || rest.starts_with("~~~"))
+}
+
+/// Add the headings and fences in `lines` from row `from` on to the ones
+/// already found above it
+pub fn scan(lines: &[String], from: usize, headings: &mut Vec<Heading>, fences: &mut Vec<usize>) {
+    for (row, line) in lines.iter().enumerate().skip(from) {
+        if is_fence(line) {
+            fences.push(row);
+        } else if fences.len().is_multiple_of(2)
+            && let Some((level, title)) = heading(line)
+        {
+            headings.push(Heading { row, level, title: title.to_string() });
+        }
+    }
+}
+
+/// The heading `row` comes under: the last one at or above it
+pub fn enclosing(headings: &[Heading], row: usize) -> Option<&Heading> {
+    headings[..headings.partition_point(|h| h.row <= row)].last()
+}
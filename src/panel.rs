@@ -0,0 +1,80 @@
+// wfemto - A toy text editor
+// Written in 2025 by Dana Larose <ywg.dana@gmail.com>
+//
+// To the extent possible under law, the author(s) have dedicated all copyright
+// and related and neighboring rights to this software to the public domain
+// worldwide. This software is distributed without any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication along
+// with this software. If not,
+// see <http://creativecommons.org/publicdomain/zero/1.0/>.
+
+// A read-only list across the bottom of the text area, for results that
+// should stay in view while the text above is worked on. It has a title row,
+// its own scroll position and a selected row; what Enter does with that row
+// is up to whatever opened it.
+
+use sdl2::keyboard::Keycode;
+
+use crate::{TextEditor, WindowInfo};
+
+/// Most rows the panel takes from the text area, title included
+const MAX_ROWS: u32 = 10;
+
+/// What Enter does: given the editor and the index of the selected line
+pub type Action = fn(&mut TextEditor, usize, &WindowInfo);
+
+pub struct Panel {
+    pub title: String,
+    pub lines: Vec<String>,
+    pub selected: usize,
+    pub top: usize, // First line shown
+    pub on_enter: Action,
+}
+
+impl Panel {
+    pub fn new(title: &str, lines: Vec<String>, on_enter: Action) -> Self {
+        Panel { title: title.to_string(), lines, selected: 0, top: 0, on_enter }
+    }
+
+    /// Text rows the panel takes, title included: enough for its lines up to
+    /// MAX_ROWS
+    pub fn rows(&self) -> u32 {
+        (self.lines.len() as u32 + 1).clamp(2, MAX_ROWS)
+    }
+
+    /// Lines shown under the title
+    pub fn visible(&self) -> usize {
+        self.rows() as usize - 1
+    }
+
+    /// Move the selection for Up, Down, PageUp, PageDown, Home and End.
+    /// Returns false for any other key.
+    pub fn navigate(&mut self, keycode: Keycode) -> bool {
+        let last = self.lines.len().saturating_sub(1);
+        let page = self.visible();
+        let index = match keycode {
+            Keycode::Up => self.selected.saturating_sub(1),
+            Keycode::Down => (self.selected + 1).min(last),
+            Keycode::PageUp => self.selected.saturating_sub(page),
+            Keycode::PageDown => (self.selected + page).min(last),
+            Keycode::Home => 0,
+            Keycode::End => last,
+            _ => return false,
+        };
+        self.select(index);
+
+        true
+    }
+
+    /// Select line `index`, scrolling to keep it in view
+    pub fn select(&mut self, index: usize) {
+        let page = self.visible();
+        self.selected = index.min(self.lines.len().saturating_sub(1));
+        if self.selected < self.top {
+            self.top = self.selected;
+        } else if self.selected >= self.top + page {
+            self.top = self.selected + 1 - page;
+        }
+    }
+}
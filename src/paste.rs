@@ -0,0 +1,31 @@
+// wfemto - A toy text editor
+// Written in 2025 by Dana Larose <ywg.dana@gmail.com>
+//
+// To the extent possible under law, the author(s) have dedicated all copyright
+// and related and neighboring rights to this software to the public domain
+// worldwide. This software is distributed without any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication along
+// with this software. If not,
+// see <http://creativecommons.org/publicdomain/zero/1.0/>.
+
+// Tidying up text pasted in from other programs before it goes into a
+// buffer: their line breaks become the buffer's (which inside the editor are
+// always \n), and optionally their indentation becomes the buffer's too.
+
+use crate::indent::{self, Indent};
+
+/// `text` ready to insert. A single trailing line break is dropped, so a
+/// line copied from a terminal doesn't leave an empty line after it.
+pub fn normalize(text: &str, indent: Option<Indent>, tab_width: usize) -> String {
+    let text = text.replace("\r\n", "\n").replace('\r', "\n");
+    let text = text.strip_suffix('\n').unwrap_or(&text);
+
+    match indent {
+        Some(indent) => text.split('\n')
+            .map(|line| indent::convert_leading(line, indent, tab_width))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        None => text.to_string(),
+    }
+}
@@ -0,0 +1,118 @@
+// wfemto - A toy text editor
+// Written in 2025 by Dana Larose <ywg.dana@gmail.com>
+//
+// To the extent possible under law, the author(s) have dedicated all copyright
+// and related and neighboring rights to this software to the public domain
+// worldwide. This software is distributed without any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication along
+// with this software. If not,
+// see <http://creativecommons.org/publicdomain/zero/1.0/>.
+
+
+// Picking out the file name the cursor is on, for opening it: in grep
+// results, compiler messages, #include lines and the like. Quotes and
+// brackets around a name aren't part of it, and a :line or :line:col after
+// it says where in the file to go.
+
+#[derive(Debug, PartialEq)]
+pub struct PathRef {
+    pub path: String,
+    pub line: Option<usize>,
+    pub col: Option<usize>,
+}
+
+/// Whether `c` can be part of a path as it's written in running text
+fn is_path_char(c: char) -> bool {
+    !c.is_whitespace() && !"\"'`<>()[]{},;|".contains(c)
+}
+
+/// The path at `col` in `line`, or just before it at the end of a name
+pub fn path_at(line: &str, col: usize) -> Option<PathRef> {
+    let chars: Vec<char> = line.chars().collect();
+    let col = col.min(chars.len());
+    let col = match chars.get(col) {
+        Some(&c) if is_path_char(c) => col,
+        _ if col > 0 && is_path_char(chars[col - 1]) => col - 1,
+        _ => return None,
+    };
+
+    let start = (0..col).rev().take_while(|&i| is_path_char(chars[i])).last().unwrap_or(col);
+    let end = (col..chars.len()).take_while(|&i| is_path_char(chars[i])).last().map_or(col, |i| i + 1);
+    let text: String = chars[start..end].iter().collect();
+
+    // The end of a sentence, or the colon before a compiler's message, but
+    // not the dots of a name like ..
+    let text = text.trim_end_matches(':');
+    let last = text.rsplit('/').next().unwrap_or(text);
+    let text = if last.chars().all(|c| c == '.') { text } else { text.trim_end_matches(['.', ':']) };
+    split_location(text)
+}
+
+/// `text` with any :line or :line:col taken off the end
+fn split_location(text: &str) -> Option<PathRef> {
+    let mut path = text;
+    let mut numbers = Vec::new();
+    while numbers.len() < 2
+        && let Some((rest, n)) = path.rsplit_once(':')
+        && !rest.is_empty()
+        && let Ok(n) = n.parse::<usize>()
+    {
+        numbers.insert(0, n);
+        path = rest;
+    }
+
+    if path.is_empty() {
+        return None;
+    }
+    Some(PathRef { path: path.to_string(), line: numbers.first().copied(), col: numbers.get(1).copied() })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn path_ref(path: &str, line: Option<usize>, col: Option<usize>) -> Option<PathRef> {
+        Some(PathRef { path: path.to_string(), line, col })
+    }
+
+    #[test]
+    fn a_location_after_the_path() {
+        assert_eq!(path_at("error at src/main.rs:12:5: oops", 12), path_ref("src/main.rs", Some(12), Some(5)));
+        assert_eq!(path_at("open ../lib/a.rs:3", 7), path_ref("../lib/a.rs", Some(3), None));
+        assert_eq!(path_at("foo.rs:12.", 0), path_ref("foo.rs", Some(12), None));
+    }
+
+    #[test]
+    fn quotes_and_brackets_are_not_part_of_it() {
+        assert_eq!(path_at("#include \"util/str.h\"", 12), path_ref("util/str.h", None, None));
+        assert_eq!(path_at("#include \"util/str.h\"", 20), path_ref("util/str.h", None, None));
+        assert_eq!(path_at("(see 'a.rs')", 6), path_ref("a.rs", None, None));
+        assert_eq!(path_at("(\"a.rs\")", 0), None);
+        assert_eq!(path_at("a  b", 2), None);
+        assert_eq!(path_at("", 0), None);
+    }
+
+    #[test]
+    fn a_sentence_ending_after_the_path() {
+        assert_eq!(path_at("see notes.txt.", 14), path_ref("notes.txt", None, None));
+        assert_eq!(path_at("see notes.txt.", 6), path_ref("notes.txt", None, None));
+    }
+
+    #[test]
+    fn dot_dot_keeps_its_dots() {
+        assert_eq!(path_at("cd ..", 5), path_ref("..", None, None));
+        assert_eq!(path_at("cd src/..", 4), path_ref("src/..", None, None));
+        assert_eq!(path_at("in ../..:", 3), path_ref("../..", None, None));
+    }
+
+    #[test]
+    fn split_location_takes_at_most_a_line_and_col() {
+        assert_eq!(split_location("a.rs"), path_ref("a.rs", None, None));
+        assert_eq!(split_location("a.rs:3"), path_ref("a.rs", Some(3), None));
+        assert_eq!(split_location("a.rs:3:4"), path_ref("a.rs", Some(3), Some(4)));
+        assert_eq!(split_location("a.rs:1:2:3"), path_ref("a.rs:1", Some(2), Some(3)));
+        assert_eq!(split_location("a.rs:x"), path_ref("a.rs:x", None, None));
+        assert_eq!(split_location(""), None);
+    }
+}
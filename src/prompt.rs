@@ -0,0 +1,277 @@
+// wfemto - A toy text editor
+// Written in 2025 by Dana Larose <ywg.dana@gmail.com>
+//
+// To the extent possible under law, the author(s) have dedicated all copyright
+// and related and neighboring rights to this software to the public domain
+// worldwide. This software is distributed without any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication along
+// with this software. If not,
+// see <http://creativecommons.org/publicdomain/zero/1.0/>.
+
+use crate::text::{byte_idx, char_len, col_slice, prev_word_start};
+
+/// Where Tab finds what a prompt's text could be completed to: given the
+/// text so far, the whole texts it might become
+pub type Completer = fn(&str) -> Vec<String>;
+
+/// A single line of text being typed into the status bar (a filename, say)
+/// along with its own cursor. The cursor is a char index into the text, so
+/// it can never point outside it or into the middle of a multibyte char.
+pub struct Prompt {
+    pub text: String,
+    cursor: usize,
+    offset: usize, // First char shown, when it's too long to show whole
+    pub completer: Option<Completer>,
+    cycle: Option<(Vec<String>, usize)>, // Candidates Tab is stepping through, and which it's on
+    recalled: Option<(usize, String)>, // History entry Up and Down are on, and the text typed before them
+}
+
+impl Prompt {
+    pub fn new() -> Self {
+        Prompt { text: String::new(), cursor: 0, offset: 0, completer: None, cycle: None, recalled: None }
+    }
+
+    /// Scroll as little as brings the cursor into view, where `fits` says
+    /// whether a stretch of the text can be shown at once
+    pub fn scroll(&mut self, fits: impl Fn(&str) -> bool) {
+        let cursor = self.cursor.min(char_len(&self.text));
+        if fits(&self.text) {
+            self.offset = 0;
+            return;
+        }
+
+        self.offset = self.offset.min(cursor);
+        while self.offset < cursor && !fits(col_slice(&self.text, self.offset, cursor)) {
+            self.offset += 1;
+        }
+    }
+
+    /// The text from the first char shown on, and the part of that before
+    /// the cursor
+    pub fn shown(&self) -> (&str, &str) {
+        let from = byte_idx(&self.text, self.offset.min(self.cursor));
+        (&self.text[from..], &self.text[from..byte_idx(&self.text, self.cursor)])
+    }
+
+    pub fn clear(&mut self) {
+        self.text.clear();
+        self.cursor = 0;
+        self.offset = 0;
+        self.completer = None;
+        self.cycle = None;
+        self.recalled = None;
+    }
+
+    /// Step through `history`, oldest first, for Up (`older`) and Down. Down
+    /// past the newest entry brings back what had been typed.
+    pub fn recall(&mut self, history: &[String], older: bool) {
+        let index = match (&self.recalled, older) {
+            (None, true) => history.len().checked_sub(1),
+            (None, false) => return,
+            (Some((index, _)), true) => Some(index.saturating_sub(1)),
+            (Some((index, _)), false) => Some(index + 1).filter(|&i| i < history.len()),
+        };
+
+        match index {
+            Some(index) => {
+                let typed = match self.recalled.take() {
+                    Some((_, typed)) => typed,
+                    None => self.text.clone(),
+                };
+                self.text = history[index].clone();
+                self.recalled = Some((index, typed));
+            }
+            None => {
+                if let Some((_, typed)) = self.recalled.take() {
+                    self.text = typed;
+                }
+            }
+        }
+        self.end();
+    }
+
+    /// Complete the text, for Tab: as far as all the candidates agree, or if
+    /// that adds nothing, to each of them in turn on each press. Returns
+    /// the candidates, to show which there are.
+    pub fn complete(&mut self) -> Vec<String> {
+        let Some(completer) = self.completer else {
+            return Vec::new();
+        };
+
+        // Still on the candidate the last Tab gave, so on to the next
+        if let Some((candidates, index)) = self.cycle.as_mut()
+            && candidates.get(*index) == Some(&self.text)
+        {
+            *index = (*index + 1) % candidates.len();
+            self.text = candidates[*index].clone();
+            let candidates = candidates.clone();
+            self.end();
+            return candidates;
+        }
+
+        self.cycle = None;
+        let candidates = completer(&self.text);
+        if candidates.is_empty() {
+            return candidates;
+        }
+        let common = common_prefix(&candidates);
+        if char_len(&common) > char_len(&self.text) {
+            self.text = common;
+        } else if candidates.len() > 1 {
+            self.text = candidates[0].clone();
+            self.cycle = Some((candidates.clone(), 0));
+        }
+        self.end();
+
+        candidates
+    }
+
+    pub fn insert(&mut self, c: char) {
+        let at = byte_idx(&self.text, self.cursor);
+        self.text.insert(at, c);
+        self.cursor += 1;
+    }
+
+    pub fn backspace(&mut self) {
+        if self.cursor == 0 {
+            return;
+        }
+
+        self.cursor -= 1;
+        let at = byte_idx(&self.text, self.cursor);
+        self.text.remove(at);
+    }
+
+    pub fn delete(&mut self) {
+        if self.cursor < char_len(&self.text) {
+            let at = byte_idx(&self.text, self.cursor);
+            self.text.remove(at);
+        }
+    }
+
+    /// Delete everything from the cursor to the end
+    pub fn kill_to_end(&mut self) {
+        let at = byte_idx(&self.text, self.cursor);
+        self.text.truncate(at);
+    }
+
+    /// Delete the word before the cursor
+    pub fn delete_word_back(&mut self) {
+        let start = prev_word_start(&self.text, self.cursor, "");
+        let range = byte_idx(&self.text, start)..byte_idx(&self.text, self.cursor);
+        self.text.replace_range(range, "");
+        self.cursor = start;
+    }
+
+    pub fn left(&mut self) {
+        self.cursor = self.cursor.saturating_sub(1);
+    }
+
+    pub fn right(&mut self) {
+        self.cursor = (self.cursor + 1).min(char_len(&self.text));
+    }
+
+    pub fn home(&mut self) {
+        self.cursor = 0;
+    }
+
+    pub fn end(&mut self) {
+        self.cursor = char_len(&self.text);
+    }
+}
+
+/// The longest start all of `words` share
+fn common_prefix(words: &[String]) -> String {
+    let first = &words[0];
+    let len = words[1..].iter().fold(first.len(), |len, word| {
+        let differs = first.char_indices().zip(word.chars()).find(|((_, a), b)| a != b);
+        differs.map_or(len.min(word.len()), |((i, _), _)| i.min(len))
+    });
+
+    first[..len].to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn prompt(text: &str) -> Prompt {
+        let mut prompt = Prompt::new();
+        text.chars().for_each(|c| prompt.insert(c));
+        prompt
+    }
+
+    #[test]
+    fn keys_at_the_start_stay_inside_the_text() {
+        let mut p = prompt("abc");
+        p.home();
+        for _ in 0..5 {
+            p.left();
+            p.backspace();
+        }
+        assert_eq!((p.text.as_str(), p.cursor), ("abc", 0));
+
+        p.delete();
+        assert_eq!((p.text.as_str(), p.cursor), ("bc", 0));
+    }
+
+    #[test]
+    fn keys_at_the_end_stay_inside_the_text() {
+        let mut p = prompt("abc");
+        for _ in 0..5 {
+            p.right();
+            p.delete();
+        }
+        assert_eq!((p.text.as_str(), p.cursor), ("abc", 3));
+
+        p.backspace();
+        assert_eq!((p.text.as_str(), p.cursor), ("ab", 2));
+    }
+
+    #[test]
+    fn an_empty_prompt_takes_any_key() {
+        let mut p = Prompt::new();
+        p.left();
+        p.right();
+        p.backspace();
+        p.delete();
+        p.home();
+        p.end();
+        p.kill_to_end();
+        p.delete_word_back();
+        assert_eq!((p.text.as_str(), p.cursor), ("", 0));
+    }
+
+    #[test]
+    fn multibyte_text_is_edited_a_char_at_a_time() {
+        let mut p = prompt("naïve→日本");
+        p.left();
+        p.backspace();
+        assert_eq!(p.text, "naïve→本");
+        p.delete();
+        assert_eq!(p.text, "naïve→");
+
+        p.home();
+        p.right();
+        p.right();
+        p.delete();
+        p.insert('i');
+        assert_eq!(p.text, "naive→");
+        assert_eq!(p.shown(), ("naive→", "nai"));
+
+        p.end();
+        p.backspace();
+        p.backspace();
+        assert_eq!((p.text.as_str(), p.cursor), ("naiv", 4));
+    }
+
+    #[test]
+    fn typing_after_home_goes_in_front() {
+        let mut p = prompt("main.rs");
+        p.home();
+        p.insert('/');
+        assert_eq!(p.text, "/main.rs");
+        assert_eq!(p.cursor, 1);
+    }
+}
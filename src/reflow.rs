@@ -0,0 +1,246 @@
+// wfemto - A toy text editor
+// Written in 2025 by Dana Larose <ywg.dana@gmail.com>
+//
+// To the extent possible under law, the author(s) have dedicated all copyright
+// and related and neighboring rights to this software to the public domain
+// worldwide. This software is distributed without any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication along
+// with this software. If not,
+// see <http://creativecommons.org/publicdomain/zero/1.0/>.
+
+// Filling paragraphs of prose to a width: the words are joined up and broken
+// again at spaces so no line runs past the fill column, unless a single word
+// is too long to fit anywhere. A prefix every line of a paragraph shares,
+// like the "// " of a comment or the "> " of a quote, is kept on each line.
+
+use crate::text::char_len;
+
+/// Comment and quote markers that count as part of a line's prefix
+const MARKERS: &[&str] = &["///", "//!", "//", "#", ">", "--", ";"];
+
+/// Reflow the paragraphs in `lines`, which are separated by lines with
+/// nothing past their prefix, to `width` columns. `cursor` is a (row, col)
+/// in `lines` to follow, and comes back as where the same text ended up.
+pub fn reflow(lines: &[String], width: usize, cursor: Option<(usize, usize)>) -> (Vec<String>, Option<(usize, usize)>) {
+    let mut out = Vec::new();
+    let mut moved = None;
+    let mut start = 0;
+    while start < lines.len() {
+        if is_blank(&lines[start]) {
+            if cursor.is_some_and(|(row, _)| row == start) {
+                moved = Some((out.len(), 0));
+            }
+            out.push(lines[start].clone());
+            start += 1;
+            continue;
+        }
+
+        let end = (start..lines.len()).find(|&i| is_blank(&lines[i])).unwrap_or(lines.len());
+        let inside = cursor.filter(|&(row, _)| (start..end).contains(&row)).map(|(row, col)| (row - start, col));
+        let (filled, at) = fill(&lines[start..end], width, inside);
+        if let Some((row, col)) = at {
+            moved = Some((out.len() + row, col));
+        }
+        out.extend(filled);
+        start = end;
+    }
+
+    (out, moved)
+}
+
+/// Rows start..end of the paragraph `row` is in, or None on a blank line
+pub fn paragraph(lines: &[String], row: usize) -> Option<(usize, usize)> {
+    if is_blank(&lines[row]) {
+        return None;
+    }
+
+    let start = (0..row).rev().find(|&i| is_blank(&lines[i])).map_or(0, |i| i + 1);
+    let end = (row..lines.len()).find(|&i| is_blank(&lines[i])).unwrap_or(lines.len());
+    Some((start, end))
+}
+
+/// The indentation and comment or quote marker `line` starts with,
+/// including the spaces after the marker
+pub fn line_prefix(line: &str) -> &str {
+    let indent = line.len() - line.trim_start().len();
+    let rest = &line[indent..];
+    let Some(marker) = MARKERS.iter().find(|m| rest.starts_with(*m)) else {
+        return &line[..indent];
+    };
+
+    let after = &rest[marker.len()..];
+    let spaces = after.len() - after.trim_start().len();
+    &line[..indent + marker.len() + spaces]
+}
+
+/// Where to break `line` so it fits in `width` columns: the char index of
+/// the last space that leaves it no wider, or failing that the first space
+/// after a word too long to fit. None if it fits or has nowhere to break.
+pub fn wrap_point(line: &str, width: usize) -> Option<usize> {
+    if char_len(line) <= width {
+        return None;
+    }
+
+    // Spaces that end a word, not counting any in the prefix
+    let chars: Vec<char> = line.chars().collect();
+    let mut ends = (char_len(line_prefix(line)) + 1..chars.len())
+        .filter(|&i| chars[i].is_whitespace() && !chars[i - 1].is_whitespace());
+    let first = ends.next()?;
+    Some(std::iter::once(first).chain(ends).take_while(|&i| i <= width).last().unwrap_or(first))
+}
+
+/// `line` cut into pieces of at most `width` chars, at spaces where there
+/// are any, for showing somewhere narrower than it. The spaces broken at
+/// are dropped.
+pub fn wrap(line: &str, width: usize) -> Vec<String> {
+    let width = width.max(1);
+    let mut rest: Vec<char> = line.chars().collect();
+    let mut pieces = Vec::new();
+    while rest.len() > width {
+        let at = rest[..=width].iter().rposition(|&c| c == ' ').filter(|&i| i > 0).unwrap_or(width);
+        pieces.push(rest[..at].iter().collect());
+        let skip = if rest[at] == ' ' { at + 1 } else { at };
+        rest.drain(..skip);
+    }
+    pieces.push(rest.into_iter().collect());
+
+    pieces
+}
+
+fn is_blank(line: &str) -> bool {
+    line.len() == line_prefix(line).len()
+}
+
+/// The prefix all of `lines` start with
+fn common_prefix(lines: &[String]) -> &str {
+    let first = line_prefix(&lines[0]);
+    let mut len = first.len();
+    for line in &lines[1..] {
+        let prefix = line_prefix(line);
+        len = first.char_indices()
+            .zip(prefix.chars())
+            .find(|((_, a), b)| a != b)
+            .map_or(len.min(prefix.len()), |((i, _), _)| i.min(len));
+    }
+
+    &first[..len]
+}
+
+/// One paragraph filled to `width`, and where `cursor` went
+fn fill(lines: &[String], width: usize, cursor: Option<(usize, usize)>) -> (Vec<String>, Option<(usize, usize)>) {
+    let common = common_prefix(lines);
+    // A marker written without its space on some lines (a bare "//") still
+    // gets the space it usually has
+    let first = line_prefix(&lines[0]);
+    let prefix = if !common.is_empty() && !common.ends_with(char::is_whitespace) { first } else { common };
+    let prefix_len = char_len(prefix);
+
+    // How much text comes before the cursor, not counting spaces or prefixes
+    let before = cursor.map(|(row, col)| {
+        let content = |line: &String, to: usize| -> usize {
+            line.chars().take(to).skip(char_len(common)).filter(|c| !c.is_whitespace()).count()
+        };
+        lines[..row].iter().map(|line| content(line, usize::MAX)).sum::<usize>() + content(&lines[row], col)
+    });
+
+    let mut out: Vec<String> = Vec::new();
+    let mut used = 0; // Text columns on the last line, past the prefix
+    let mut seen = 0; // Non-space chars placed so far
+    let mut at = None;
+    for word in lines.iter().flat_map(|line| line[common.len()..].split_whitespace()) {
+        let len = char_len(word);
+        if out.is_empty() || (used > 0 && prefix_len + used + 1 + len > width) {
+            out.push(prefix.to_string());
+            used = 0;
+        }
+        let last = out.len() - 1;
+        if used > 0 {
+            out[last].push(' ');
+            used += 1;
+        }
+
+        if at.is_none() && before.is_some_and(|b| b <= seen + len) {
+            at = before.map(|b| (last, prefix_len + used + (b - seen)));
+        }
+        out[last].push_str(word);
+        used += len;
+        seen += len;
+    }
+
+    if out.is_empty() {
+        out.push(prefix.trim_end().to_string());
+    }
+    let at = at.or_else(|| cursor.map(|_| (out.len() - 1, char_len(&out[out.len() - 1]))));
+    (out, at)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(text: &[&str]) -> Vec<String> {
+        text.iter().map(|line| line.to_string()).collect()
+    }
+
+    #[test]
+    fn wrap_breaks_at_spaces() {
+        assert_eq!(wrap("the quick brown fox", 10), ["the quick", "brown fox"]);
+        assert_eq!(wrap("short", 10), ["short"]);
+        assert_eq!(wrap("", 10), [""]);
+    }
+
+    #[test]
+    fn wrap_cuts_a_word_too_long_for_the_width() {
+        assert_eq!(wrap("abcdefghijkl mn", 5), ["abcde", "fghij", "kl mn"]);
+    }
+
+    #[test]
+    fn wrap_point_is_the_last_space_that_fits() {
+        assert_eq!(wrap_point("the quick brown fox", 10), Some(9));
+        assert_eq!(wrap_point("the quick", 10), None);
+    }
+
+    #[test]
+    fn wrap_point_after_a_word_too_long_to_fit() {
+        assert_eq!(wrap_point("abcdefghijkl mn", 5), Some(12));
+        assert_eq!(wrap_point("abcdefghijkl", 5), None);
+    }
+
+    #[test]
+    fn wrap_point_never_breaks_in_the_prefix() {
+        assert_eq!(wrap_point("//   aa bb cc", 9), Some(7));
+        assert_eq!(wrap_point("//   abcdefgh", 5), None);
+    }
+
+    #[test]
+    fn fill_keeps_the_prefix_on_every_line() {
+        let (filled, _) = fill(&lines(&["// one two three", "// four five"]), 12, None);
+        assert_eq!(filled, ["// one two", "// three", "// four five"]);
+    }
+
+    #[test]
+    fn fill_gives_a_bare_marker_its_space() {
+        let (filled, _) = fill(&lines(&["// one", "//two"]), 20, None);
+        assert_eq!(filled, ["// one two"]);
+    }
+
+    #[test]
+    fn fill_puts_a_word_too_long_on_a_line_of_its_own() {
+        let (filled, _) = fill(&lines(&["> supercalifragilistic is long"]), 10, None);
+        assert_eq!(filled, ["> supercalifragilistic", "> is long"]);
+    }
+
+    #[test]
+    fn fill_follows_the_cursor() {
+        let (filled, cursor) = fill(&lines(&["aaa", "bbb ccc"]), 7, Some((1, 5)));
+        assert_eq!(filled, ["aaa bbb", "ccc"]);
+        assert_eq!(cursor, Some((1, 1)));
+    }
+
+    #[test]
+    fn reflow_keeps_paragraphs_apart() {
+        let (out, _) = reflow(&lines(&["# a b", "#", "# c"]), 3, None);
+        assert_eq!(out, ["# a", "# b", "#", "# c"]);
+    }
+}
@@ -0,0 +1,72 @@
+// wfemto - A toy text editor
+// Written in 2025 by Dana Larose <ywg.dana@gmail.com>
+//
+// To the extent possible under law, the author(s) have dedicated all copyright
+// and related and neighboring rights to this software to the public domain
+// worldwide. This software is distributed without any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication along
+// with this software. If not,
+// see <http://creativecommons.org/publicdomain/zero/1.0/>.
+
+// The last few things copied or cut, newest first, so that something copied
+// a while ago can still be pasted after copying something else.
+
+use std::collections::VecDeque;
+
+use crate::text::char_len;
+
+/// How many copies the ring keeps
+pub const RING_SIZE: usize = 10;
+
+/// Longest an entry is shown in the list before it's cut short
+const SUMMARY_CHARS: usize = 40;
+
+#[derive(Default)]
+pub struct ClipRing {
+    entries: VecDeque<String>,
+}
+
+impl ClipRing {
+    pub fn new() -> Self {
+        ClipRing::default()
+    }
+
+    /// Make `text` the newest entry. Copying the same text again moves it to
+    /// the front rather than storing it twice.
+    pub fn push(&mut self, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+
+        self.entries.retain(|e| e != text);
+        self.entries.push_front(text.to_string());
+        self.entries.truncate(RING_SIZE);
+    }
+
+    pub fn get(&self, index: usize) -> Option<&str> {
+        self.entries.get(index).map(String::as_str)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Numbered one-line descriptions of the entries, for picking from
+    pub fn summaries(&self) -> Vec<String> {
+        self.entries.iter().enumerate().map(|(i, text)| {
+            let first = text.lines().find(|l| !l.trim().is_empty()).unwrap_or("").trim();
+            let mut shown: String = first.chars().take(SUMMARY_CHARS).collect();
+            let more_lines = text.trim_end_matches('\n').contains('\n');
+            if char_len(first) > SUMMARY_CHARS || more_lines {
+                shown.push('…');
+            }
+            // Keys 1-9 then 0 pick the first ten
+            format!("{}  {}", (i + 1) % 10, shown)
+        }).collect()
+    }
+}
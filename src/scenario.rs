@@ -0,0 +1,296 @@
+// wfemto - A toy text editor
+// Written in 2025 by Dana Larose <ywg.dana@gmail.com>
+//
+// To the extent possible under law, the author(s) have dedicated all copyright
+// and related and neighboring rights to this software to the public domain
+// worldwide. This software is distributed without any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication along
+// with this software. If not,
+// see <http://creativecommons.org/publicdomain/zero/1.0/>.
+
+// Scripted runs of the editor without a window: the keys and text of a
+// fixture in tests/scenarios are fed to handle_key() and handle_text() the way
+// the event loop would, and what the buffer ended up as is checked against the
+// fixture's expectations. One step per line:
+//
+//     # a comment
+//     type some text     Text typed, exactly as it is after "type "
+//     key Ctrl+Z         A key, with any of Ctrl+, Shift+ and Alt+ in front
+//     key Down *5        ...pressed five times
+//     idle               Nothing typed for long enough that the word at the
+//                        cursor has its other occurrences highlighted
+//     expect line 3 xyz  Line 3 of the buffer is "xyz" (lines count from 1)
+//     expect lines 4     The buffer has 4 lines
+//     expect cursor 3:7  The cursor is on line 3, column 7
+//     expect top 10      Line 10 is the first one in view
+//     expect left 5      Column 5 is the first one in view
+//     expect file a.txt  The current buffer's file name
+//     expect modified    The buffer has unsaved changes (or "unmodified")
+//     expect prompt      A prompt is open (or "edit" for none)
+//     expect selected    There's a selection (or "unselected")
+//     expect occurrences 3  3 occurrences of the word are highlighted
+//     expect repeat 4    A count of 4 waits for the next command (0 for none)
+//     expect pending     The first key of a pair waits for its second (or
+//                        "ready" for none)
+
+use sdl2::keyboard::Keycode;
+
+use crate::config::Config;
+use crate::keymap::Mods;
+use crate::occurrences;
+use crate::{EDITOR_COLS, EDITOR_ROWS, EditorMode, ListPopup, SpellPicker, TextEditor, WindowInfo};
+
+/// A window as it would be set up for a font with 8x16 cells
+pub fn window_info() -> WindowInfo {
+    WindowInfo {
+        rows: EDITOR_ROWS,
+        cols: EDITOR_COLS,
+        char_width: 8,
+        char_height: 16,
+        scroll_margin: Config::default().scroll_margin,
+        text_top: 0,
+        status_y: 0,
+        prompt_y: 0,
+        margin_left: 0,
+        margin_top: 0,
+        text_left: 0,
+        scale: 1.0,
+    }
+}
+
+/// An editor on the default config, minus undo history kept on disk
+pub fn editor() -> TextEditor {
+    let config = Config { persistent_undo: false, ..Config::default() };
+    let mut editor = TextEditor::new(config);
+    for buffer in &mut editor.buffers {
+        buffer.persistent_undo = false;
+    }
+
+    editor
+}
+
+/// The key called `name`, as written in a fixture
+fn keycode(name: &str) -> Option<Keycode> {
+    let key = match name {
+        "Return" | "Enter" => Keycode::Return,
+        "Escape" | "Esc" => Keycode::Escape,
+        "Backspace" => Keycode::Backspace,
+        "Delete" => Keycode::Delete,
+        "Tab" => Keycode::Tab,
+        "Space" => Keycode::Space,
+        "Up" => Keycode::Up,
+        "Down" => Keycode::Down,
+        "Left" => Keycode::Left,
+        "Right" => Keycode::Right,
+        "Home" => Keycode::Home,
+        "End" => Keycode::End,
+        "PageUp" => Keycode::PageUp,
+        "PageDown" => Keycode::PageDown,
+        "Period" => Keycode::Period,
+        "F1" => Keycode::F1,
+        "F6" => Keycode::F6,
+        // Letters and digits are their own lowercase ASCII
+        _ if name.len() == 1 && name.chars().all(|c| c.is_ascii_alphanumeric()) => {
+            return Keycode::from_i32(name.to_ascii_lowercase().as_bytes()[0] as i32);
+        }
+        _ => return None,
+    };
+
+    Some(key)
+}
+
+/// "Ctrl+Shift+Z" as a key and its modifiers
+fn parse_key(text: &str) -> Option<(Keycode, Mods)> {
+    let mut mods = Mods::default();
+    let mut rest = text;
+    loop {
+        if let Some(after) = rest.strip_prefix("Ctrl+") {
+            mods.ctrl = true;
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix("Shift+") {
+            mods.shift = true;
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix("Alt+") {
+            mods.alt = true;
+            rest = after;
+        } else {
+            break;
+        }
+    }
+
+    keycode(rest).map(|key| (key, mods))
+}
+
+/// Run the fixture `script`, called `name` in failures, on `editor`
+pub fn run(name: &str, script: &str, editor: &mut TextEditor) {
+    let base = window_info();
+    for (number, line) in script.lines().enumerate() {
+        let place = format!("{}:{}: {}", name, number + 1, line);
+        let line = line.trim_end();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (step, arg) = line.split_once(' ').unwrap_or((line, ""));
+        match step {
+            "type" => {
+                let window_info = editor.layout(&base);
+                editor.handle_text(arg, &window_info);
+            }
+            "key" => {
+                let (key, times) = match arg.split_once(" *") {
+                    Some((key, times)) => (key, times.parse().unwrap_or_else(|_| panic!("{}: bad count", place))),
+                    None => (arg, 1),
+                };
+                let (keycode, mods) = parse_key(key).unwrap_or_else(|| panic!("{}: unknown key", place));
+                for _ in 0..times {
+                    let window_info = editor.layout(&base);
+                    assert!(!editor.handle_key(keycode, mods, &window_info), "{}: quit", place);
+                }
+            }
+            "idle" => {
+                let rows = editor.layout(&base).rows as usize;
+                editor.occurrences.update(&editor.buffers[editor.current], rows);
+                std::thread::sleep(occurrences::DELAY);
+                editor.occurrences.update(&editor.buffers[editor.current], rows);
+            }
+            "expect" => expect(editor, arg, &place),
+            _ => panic!("{}: unknown step", place),
+        }
+    }
+}
+
+fn expect(editor: &TextEditor, what: &str, place: &str) {
+    let buf = editor.buf();
+    let (kind, arg) = what.split_once(' ').unwrap_or((what, ""));
+    let number = || arg.parse::<usize>().unwrap_or_else(|_| panic!("{}: bad number", place));
+    match kind {
+        "line" => {
+            let (row, text) = arg.split_once(' ').unwrap_or((arg, ""));
+            let row: usize = row.parse().unwrap_or_else(|_| panic!("{}: bad line number", place));
+            assert_eq!(buf.lines.get(row - 1).map(String::as_str), Some(text), "{}", place);
+        }
+        "lines" => assert_eq!(buf.lines.len(), number(), "{}", place),
+        "cursor" => {
+            let (row, col) = arg.split_once(':').unwrap_or_else(|| panic!("{}: want row:col", place));
+            let want = (row.parse::<usize>().ok(), col.parse::<usize>().ok());
+            assert_eq!((Some(buf.buffer_row + 1), Some(buf.buffer_col + 1)), want, "{}", place);
+        }
+        "top" => assert_eq!(buf.top_line + 1, number(), "{}", place),
+        "left" => assert_eq!(buf.buffer_col_offset + 1, number(), "{}", place),
+        "file" => assert_eq!(buf.filename, arg, "{}", place),
+        "modified" => assert!(buf.is_modified, "{}", place),
+        "unmodified" => assert!(!buf.is_modified, "{}", place),
+        "prompt" => assert!(editor.in_prompt(), "{}", place),
+        "edit" => assert!(editor.mode == EditorMode::Edit && !editor.in_prompt(), "{}", place),
+        "selected" => assert!(buf.selection().is_some(), "{}", place),
+        "unselected" => assert!(buf.selection().is_none(), "{}", place),
+        "occurrences" => assert_eq!(editor.occurrences.matches.len(), number(), "{}", place),
+        "repeat" => assert_eq!(editor.repeat.unwrap_or(0), number(), "{}", place),
+        "pending" => assert!(editor.pending.is_some(), "{}", place),
+        "ready" => assert!(editor.pending.is_none(), "{}", place),
+        _ => panic!("{}: unknown expectation", place),
+    }
+}
+
+macro_rules! scenario {
+    ($test:ident, $file:literal) => {
+        #[test]
+        fn $test() {
+            let script = include_str!(concat!("../tests/scenarios/", $file));
+            run($file, script, &mut editor());
+        }
+    };
+}
+
+scenario!(typing_a_paragraph, "typing.txt");
+scenario!(opening_a_file, "open.txt");
+scenario!(movement_keys, "movement.txt");
+scenario!(undo_and_redo, "undo.txt");
+scenario!(cursor_desync_regressions, "desync.txt");
+scenario!(escape_from_a_prompt, "prompt_escape.txt");
+scenario!(escape_a_prompt_then_a_selection, "escape_prompt_selection.txt");
+scenario!(escape_a_selection_then_occurrences, "escape_selection_occurrences.txt");
+scenario!(escape_occurrences_then_a_count, "escape_occurrences_repeat.txt");
+scenario!(escape_a_pending_chord, "escape_chord.txt");
+scenario!(buffers_keep_their_own_history, "buffers.txt");
+scenario!(wide_characters, "wide.txt");
+scenario!(exotic_codepoints, "exotic.txt");
+
+/// Spellings for "line" at the start of `row` of the current buffer
+fn spell_picker(editor: &TextEditor, row: usize) -> SpellPicker {
+    let items = vec!["lime".to_string(), "Add 'line' to your words".to_string()];
+    let buf = editor.buf();
+    SpellPicker {
+        buffer_id: buf.id,
+        generation: buf.generation,
+        row,
+        start: 0,
+        end: 4,
+        word: "line".to_string(),
+        list: ListPopup::new(items),
+    }
+}
+
+#[test]
+fn a_spelling_is_picked_for_the_word() {
+    let mut editor = editor();
+    run("long", "key Ctrl+O\ntype tests/scenarios/long.txt\nkey Return\nkey Home", &mut editor);
+    editor.spell_picker = Some(spell_picker(&editor, 2));
+    run("long", "key Return\nexpect line 3 lime 3\nexpect lines 100", &mut editor);
+}
+
+#[test]
+fn a_spell_picker_is_dropped_when_its_text_changes() {
+    let mut editor = editor();
+    run("long", "key Ctrl+O\ntype tests/scenarios/long.txt\nkey Return\nkey Home", &mut editor);
+    editor.spell_picker = Some(spell_picker(&editor, 49));
+
+    // Changed by something other than a key, like a reload
+    let window_info = window_info();
+    editor.buf_mut().replace_lines(10, 100, Vec::new(), &window_info);
+    run("long", "key Return\nexpect lines 11\nexpect line 2 line 1", &mut editor);
+    assert!(editor.spell_picker.is_none());
+}
+
+#[test]
+fn a_picker_is_dropped_when_another_pane_takes_over() {
+    let mut editor = editor();
+    run("long", "key Ctrl+O\ntype tests/scenarios/long.txt\nkey Return\nkey Down *49", &mut editor);
+    let long = editor.current;
+    run("poem", "key Ctrl+O\ntype tests/scenarios/poem.txt\nkey Return", &mut editor);
+    let poem = editor.current;
+    editor.current = long;
+    run("long", "key Right\nexpect cursor 50:2", &mut editor);
+
+    // Offered for line 50 of long.txt, then the poem's pane is clicked on
+    editor.spell_picker = Some(spell_picker(&editor, 49));
+    editor.current = poem;
+    run("poem", "key Return\nexpect lines 5\nexpect line 2 Tyger Tyger, burning bright,", &mut editor);
+    assert!(editor.spell_picker.is_none());
+    assert_eq!(editor.buffers[long].lines[49], "line 50");
+}
+
+#[test]
+fn a_big_paste_says_so_before_it_goes_in() {
+    let mut editor = editor();
+    editor.config.paste_confirm_lines = 3;
+    editor.ring.push("one\ntwo\nthree\n");
+    run("paste", "key Ctrl+V\nexpect lines 1", &mut editor);
+    assert!(editor.mode == EditorMode::ConfirmPaste);
+
+    run("paste", "key y\nexpect lines 1", &mut editor);
+    assert_eq!(editor.message.as_deref(), Some("Pasting 3 lines…"));
+    editor.finish_paste(&window_info());
+    run("paste", "expect lines 3\nexpect line 3 three\nexpect cursor 3:6", &mut editor);
+}
+
+#[test]
+fn deleting_the_last_empty_line_cuts_nothing() {
+    let mut editor = editor();
+    run("delete", "type gone\nkey Ctrl+Shift+K\nkey Ctrl+Shift+K *3\nexpect lines 1\nexpect line 1 ", &mut editor);
+
+    assert_eq!(editor.ring.len(), 1);
+    assert_eq!(editor.ring.get(0), Some("gone\n"));
+}
@@ -0,0 +1,108 @@
+// wfemto - A toy text editor
+// Written in 2025 by Dana Larose <ywg.dana@gmail.com>
+//
+// To the extent possible under law, the author(s) have dedicated all copyright
+// and related and neighboring rights to this software to the public domain
+// worldwide. This software is distributed without any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication along
+// with this software. If not,
+// see <http://creativecommons.org/publicdomain/zero/1.0/>.
+
+// Finding text in a buffer. Positions are (row, char column), like the
+// cursor. Several needles can be looked for at once, in which case whichever
+// turns up first wins.
+
+use std::ops::Range;
+
+use crate::text::is_word_char;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Match {
+    pub row: usize,
+    pub col: usize,
+    pub len: usize, // In chars
+}
+
+pub struct Query<'a> {
+    pub needles: &'a [String],
+    pub whole_word: bool, // Only match where there's no word character either side
+    pub ignore_case: bool,
+}
+
+impl Query<'_> {
+    /// The first match in `chars` starting at or after `from`, as (col, len)
+    pub fn find_in_line(&self, chars: &[char], from: usize) -> Option<(usize, usize)> {
+        let needles: Vec<Vec<char>> = self.needles.iter()
+            .filter(|n| !n.is_empty())
+            .map(|n| n.chars().collect())
+            .collect();
+
+        (from..chars.len()).find_map(|col| {
+            needles.iter()
+                .find(|needle| self.matches_at(chars, col, needle))
+                .map(|needle| (col, needle.len()))
+        })
+    }
+
+    /// Whether any needle appears anywhere in `line`
+    pub fn is_match(&self, line: &str) -> bool {
+        if !self.whole_word && !self.ignore_case {
+            return self.needles.iter().any(|n| !n.is_empty() && line.contains(n.as_str()));
+        }
+
+        let chars: Vec<char> = line.chars().collect();
+        self.find_in_line(&chars, 0).is_some()
+    }
+
+    fn matches_at(&self, chars: &[char], col: usize, needle: &[char]) -> bool {
+        let end = col + needle.len();
+        if end > chars.len() {
+            return false;
+        }
+        let same = if self.ignore_case {
+            chars[col..end].iter().zip(needle).all(|(a, b)| a.to_lowercase().eq(b.to_lowercase()))
+        } else {
+            chars[col..end] == *needle
+        };
+        if !same {
+            return false;
+        }
+
+        !self.whole_word
+            || ((col == 0 || !is_word_char(chars[col - 1]))
+                && (end == chars.len() || !is_word_char(chars[end])))
+    }
+
+    /// Every match in the lines `rows`, without overlaps
+    pub fn find_all(&self, lines: &[String], rows: Range<usize>) -> Vec<Match> {
+        let mut found = Vec::new();
+        for row in rows {
+            let chars: Vec<char> = lines[row].chars().collect();
+            let mut from = 0;
+            while let Some((col, len)) = self.find_in_line(&chars, from) {
+                found.push(Match { row, col, len });
+                from = col + len.max(1);
+            }
+        }
+
+        found
+    }
+
+    /// The next match at or after `from`, wrapping around past the end of
+    /// the buffer back to the top
+    pub fn find_next(&self, lines: &[String], from: (usize, usize)) -> Option<Match> {
+        let (from_row, from_col) = from;
+        let rows = (from_row..lines.len()).chain(0..=from_row.min(lines.len().saturating_sub(1)));
+
+        for (i, row) in rows.enumerate() {
+            let chars: Vec<char> = lines[row].chars().collect();
+            let start = if i == 0 { from_col } else { 0 };
+            if let Some((col, len)) = self.find_in_line(&chars, start) {
+                return Some(Match { row, col, len });
+            }
+        }
+
+        None
+    }
+}
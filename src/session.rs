@@ -0,0 +1,154 @@
+// wfemto - A toy text editor
+// Written in 2025 by Dana Larose <ywg.dana@gmail.com>
+//
+// To the extent possible under law, the author(s) have dedicated all copyright
+// and related and neighboring rights to this software to the public domain
+// worldwide. This software is distributed without any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication along
+// with this software. If not,
+// see <http://creativecommons.org/publicdomain/zero/1.0/>.
+
+
+// Named sessions: the files open, where each was scrolled to with its
+// cursor, and which one was in front, kept in the config dir under
+// sessions/ to pick up again later. The file is a line per setting, led by
+// a word saying what it is, so a session written by a later version can
+// still be read for what this one understands:
+//
+//     wfemto-session 1
+//     current 1
+//     file 12 4 0 0 src/main.rs
+//
+// with a file's cursor row and column, top line and column offset before
+// its path.
+
+use std::fs;
+use std::path::PathBuf;
+
+use crate::config;
+
+/// The first line, with the version of the format it was written in
+const HEADER: &str = "wfemto-session";
+const VERSION: u32 = 1;
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SessionFile {
+    pub path: String,
+    pub row: usize,
+    pub col: usize,
+    pub top_line: usize,
+    pub col_offset: usize,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Session {
+    pub files: Vec<SessionFile>,
+    pub current: usize, // Index into files
+}
+
+impl Session {
+    pub fn encode(&self) -> String {
+        let mut text = format!("{} {}\ncurrent {}\n", HEADER, VERSION, self.current);
+        for f in &self.files {
+            text.push_str(&format!("file {} {} {} {} {}\n", f.row, f.col, f.top_line, f.col_offset, f.path));
+        }
+
+        text
+    }
+
+    /// Lines this version doesn't know are skipped
+    pub fn decode(text: &str) -> Result<Session, String> {
+        let mut lines = text.lines();
+        let version = lines.next()
+            .and_then(|header| header.strip_prefix(HEADER))
+            .and_then(|v| v.trim().parse::<u32>().ok())
+            .ok_or("not a session file")?;
+        if version > VERSION {
+            log_info!("session written by a newer version ({}); reading what's understood", version);
+        }
+
+        let mut session = Session::default();
+        for line in lines {
+            let (key, rest) = line.split_once(' ').unwrap_or((line, ""));
+            match key {
+                "current" => session.current = rest.trim().parse().map_err(|_| format!("bad line: {}", line))?,
+                "file" => {
+                    let mut parts = rest.splitn(5, ' ');
+                    let mut number = || parts.next().and_then(|n| n.parse().ok()).ok_or(format!("bad line: {}", line));
+                    let (row, col, top_line, col_offset) = (number()?, number()?, number()?, number()?);
+                    let path = parts.next().filter(|p| !p.is_empty()).ok_or(format!("bad line: {}", line))?;
+                    session.files.push(SessionFile { path: path.to_string(), row, col, top_line, col_offset });
+                }
+                _ => {}
+            }
+        }
+        session.current = session.current.min(session.files.len().saturating_sub(1));
+
+        Ok(session)
+    }
+}
+
+/// Where session `name` is kept
+fn session_path(name: &str) -> Result<PathBuf, String> {
+    if name.is_empty() || name.contains(['/', '\\']) || name.starts_with('.') {
+        return Err(format!("'{}' isn't a usable session name", name));
+    }
+    let dir = config::config_dir().ok_or("no config directory to keep sessions in")?;
+
+    Ok(dir.join("sessions").join(name))
+}
+
+pub fn save(name: &str, session: &Session) -> Result<(), String> {
+    let path = session_path(name)?;
+    path.parent().map_or(Ok(()), fs::create_dir_all)
+        .and_then(|()| fs::write(&path, session.encode()))
+        .map_err(|e| format!("unable to write {}: {}", path.display(), e))
+}
+
+pub fn load(name: &str) -> Result<Session, String> {
+    let path = session_path(name)?;
+    let text = fs::read_to_string(&path).map_err(|e| format!("unable to read {}: {}", path.display(), e))?;
+    Session::decode(&text).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(path: &str, row: usize, col: usize) -> SessionFile {
+        SessionFile { path: path.to_string(), row, col, top_line: row.saturating_sub(5), col_offset: 0 }
+    }
+
+    #[test]
+    fn encode_then_decode_gives_the_same_session() {
+        let session = Session {
+            files: vec![file("src/main.rs", 12, 4), file("my notes/todo list.txt", 3, 0), file(" padded ", 0, 0)],
+            current: 1,
+        };
+        assert_eq!(Session::decode(&session.encode()), Ok(session));
+        assert_eq!(Session::decode(&Session::default().encode()), Ok(Session::default()));
+    }
+
+    #[test]
+    fn unknown_lines_are_skipped() {
+        let text = "wfemto-session 2\ncurrent 0\ntheme dark\nfile 1 2 3 4 a b.rs\nsplit 0.5\n";
+        let want = Session { files: vec![SessionFile { path: "a b.rs".to_string(), row: 1, col: 2, top_line: 3, col_offset: 4 }], current: 0 };
+        assert_eq!(Session::decode(text), Ok(want));
+    }
+
+    #[test]
+    fn current_stays_within_the_files() {
+        let session = Session::decode("wfemto-session 1\ncurrent 5\nfile 0 0 0 0 a.rs\n").unwrap();
+        assert_eq!(session.current, 0);
+    }
+
+    #[test]
+    fn bad_sessions_are_errors() {
+        assert!(Session::decode("").is_err());
+        assert!(Session::decode("file 0 0 0 0 a.rs\n").is_err());
+        assert!(Session::decode("wfemto-session 1\nfile 1 2 x 4 a.rs\n").is_err());
+        assert!(Session::decode("wfemto-session 1\nfile 1 2 3 4 \n").is_err());
+        assert!(Session::decode("wfemto-session 1\ncurrent first\n").is_err());
+    }
+}
@@ -0,0 +1,74 @@
+// wfemto - A toy text editor
+// Written in 2025 by Dana Larose <ywg.dana@gmail.com>
+//
+// To the extent possible under law, the author(s) have dedicated all copyright
+// and related and neighboring rights to this software to the public domain
+// worldwide. This software is distributed without any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication along
+// with this software. If not,
+// see <http://creativecommons.org/publicdomain/zero/1.0/>.
+
+// Snippets are templates from the config file:
+//
+//     snippet.todo = TODO($date): $0
+//     snippet.entry = ## $date\n\n$0
+//
+// Config values are single lines, so \n, \t and \\ are escapes. $date is
+// replaced by the date in the configured format and $0 marks where the cursor
+// should end up (the end of the text if there isn't one).
+
+pub struct Expansion {
+    pub text: String,
+    pub cursor: Option<usize>, // Char offset into text
+}
+
+pub fn expand(template: &str, date: &str) -> Expansion {
+    let mut text = String::new();
+    let mut cursor = None;
+    let mut chars = template.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('n') => text.push('\n'),
+                Some('t') => text.push('\t'),
+                Some(other) => text.push(other),
+                None => text.push('\\'),
+            },
+            '$' if chars.peek() == Some(&'0') => {
+                chars.next();
+                cursor.get_or_insert(text.chars().count());
+            }
+            '$' if followed_by(&chars, "date") => {
+                for _ in 0..4 {
+                    chars.next();
+                }
+                text.push_str(date);
+            }
+            _ => text.push(c),
+        }
+    }
+
+    Expansion { text, cursor }
+}
+
+fn followed_by(chars: &std::iter::Peekable<std::str::Chars>, word: &str) -> bool {
+    chars.clone().take(word.len()).eq(word.chars())
+}
+
+/// Where the char `offset` chars into `text` lands if `text` is inserted at
+/// `start`
+pub fn position_after(start: (usize, usize), text: &str, offset: usize) -> (usize, usize) {
+    let (mut row, mut col) = start;
+    for c in text.chars().take(offset) {
+        if c == '\n' {
+            row += 1;
+            col = 0;
+        } else {
+            col += 1;
+        }
+    }
+
+    (row, col)
+}
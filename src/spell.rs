@@ -0,0 +1,195 @@
+// wfemto - A toy text editor
+// Written in 2025 by Dana Larose <ywg.dana@gmail.com>
+//
+// To the extent possible under law, the author(s) have dedicated all copyright
+// and related and neighboring rights to this software to the public domain
+// worldwide. This software is distributed without any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication along
+// with this software. If not,
+// see <http://creativecommons.org/publicdomain/zero/1.0/>.
+
+
+// Spell checking for prose. Words are looked up in a word list (words.txt
+// found where the font is, or failing that the system's) along with the
+// user's own list in the config dir, which words can be added to. What's
+// wrong with a line is kept by the line's text, so a line is only checked
+// again once it changes.
+
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::config;
+
+/// The word list looked for beside the font
+pub const WORDS_PATH: &str = "words.txt";
+
+/// Where most Unix systems keep a word list, for when there isn't one of
+/// our own
+const SYSTEM_WORDS: &str = "/usr/share/dict/words";
+
+/// Lines whose results are kept before starting over
+const MAX_CACHED: usize = 10_000;
+
+/// Most suggestions offered for a word...
+const MAX_SUGGESTIONS: usize = 8;
+/// ...each at most this many edits from it
+const MAX_DISTANCE: usize = 2;
+
+pub struct Checker {
+    words: HashSet<String>, // Lowercase
+    user_path: Option<PathBuf>,
+    checked: HashMap<String, Vec<(usize, usize)>>, // Line -> columns of its unknown words
+}
+
+impl Checker {
+    /// Load `word_list` (or the system's) and the user's words. Either can
+    /// be missing; with neither there's nothing to check against.
+    pub fn load(word_list: Option<&Path>) -> Checker {
+        let user_path = config::config_dir().map(|d| d.join("words"));
+        let mut words = HashSet::new();
+        let lists = [word_list.map(Path::to_path_buf), Some(PathBuf::from(SYSTEM_WORDS))];
+        if let Some(text) = lists.iter().flatten().find_map(|path| fs::read_to_string(path).ok()) {
+            words.extend(text.lines().map(|w| w.trim().to_lowercase()).filter(|w| !w.is_empty()));
+        }
+        if let Some(text) = user_path.as_ref().and_then(|path| fs::read_to_string(path).ok()) {
+            words.extend(text.lines().map(|w| w.trim().to_lowercase()).filter(|w| !w.is_empty()));
+        }
+
+        Checker { words, user_path, checked: HashMap::new() }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+
+    /// Columns start..end of the words in `line` that aren't in the lists
+    pub fn unknown_words(&mut self, line: &str) -> &[(usize, usize)] {
+        if !self.checked.contains_key(line) {
+            if self.checked.len() >= MAX_CACHED {
+                self.checked.clear();
+            }
+            let chars: Vec<char> = line.chars().collect();
+            let unknown = words_in(&chars).into_iter()
+                .filter(|&(start, end)| !self.is_known(&chars[start..end].iter().collect::<String>()))
+                .collect();
+            self.checked.insert(line.to_string(), unknown);
+        }
+
+        &self.checked[line]
+    }
+
+    /// Whether `word` is spelled right, or is something (an acronym, or an
+    /// identifier in a comment) that isn't worth checking
+    pub fn is_known(&self, word: &str) -> bool {
+        let checkable = word.chars().count() > 1
+            && word.chars().skip(1).all(|c| !c.is_uppercase());
+        if !checkable || self.words.is_empty() {
+            return true;
+        }
+
+        let lower = word.to_lowercase();
+        self.words.contains(&lower)
+            || lower.strip_suffix("'s").is_some_and(|stem| self.words.contains(stem))
+    }
+
+    /// Known words close to `word`, nearest first, capitalized as it is
+    pub fn suggest(&self, word: &str) -> Vec<String> {
+        let lower = word.to_lowercase();
+        let len = lower.chars().count();
+        let mut found: Vec<(usize, &String)> = self.words.iter()
+            .filter(|w| w.chars().count().abs_diff(len) <= MAX_DISTANCE)
+            .filter_map(|w| Some((edit_distance(&lower, w).filter(|&d| d <= MAX_DISTANCE)?, w)))
+            .collect();
+        found.sort();
+        found.truncate(MAX_SUGGESTIONS);
+
+        let capital = word.chars().next().is_some_and(char::is_uppercase);
+        found.into_iter().map(|(_, w)| {
+            let mut chars = w.chars();
+            match chars.next() {
+                Some(first) if capital => first.to_uppercase().chain(chars).collect(),
+                _ => w.clone(),
+            }
+        }).collect()
+    }
+
+    /// Add `word` to the user's list, on disk as well
+    pub fn add(&mut self, word: &str) -> Result<(), String> {
+        let path = self.user_path.as_ref().ok_or("no config directory for a word list")?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        }
+        let mut file = OpenOptions::new().create(true).append(true).open(path).map_err(|e| e.to_string())?;
+        writeln!(file, "{}", word).map_err(|e| e.to_string())?;
+
+        self.words.insert(word.to_lowercase());
+        self.checked.clear();
+        Ok(())
+    }
+}
+
+/// Whether `filename` is prose, to be checked throughout rather than just
+/// in its comments and strings
+pub fn is_prose(filename: &str) -> bool {
+    matches!(config::filetype_key(filename), Some("md" | "txt"))
+}
+
+/// Columns start..end of the word `col` is in or just after, if any
+pub fn word_at(line: &str, col: usize) -> Option<(usize, usize)> {
+    let chars: Vec<char> = line.chars().collect();
+    words_in(&chars).into_iter().find(|&(start, end)| (start..=end).contains(&col))
+}
+
+/// Runs of letters, with apostrophes inside them (don't, it's) counted in.
+/// Runs touching digits or underscores are identifiers, and left out.
+fn words_in(chars: &[char]) -> Vec<(usize, usize)> {
+    let mut words = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        if !chars[i].is_alphabetic() {
+            i += 1;
+            continue;
+        }
+
+        let start = i;
+        while i < chars.len()
+            && (chars[i].is_alphabetic()
+                || (chars[i] == '\'' && chars.get(i + 1).is_some_and(|c| c.is_alphabetic())))
+        {
+            i += 1;
+        }
+        let touching = |c: Option<&char>| c.is_some_and(|&c| c.is_alphanumeric() || c == '_');
+        if !touching(start.checked_sub(1).and_then(|s| chars.get(s))) && !touching(chars.get(i)) {
+            words.push((start, i));
+        }
+        while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+            i += 1;
+        }
+    }
+
+    words
+}
+
+/// Single-char insertions, deletions and substitutions to turn `a` into
+/// `b`, or None once it's plainly more than MAX_DISTANCE
+fn edit_distance(a: &str, b: &str) -> Option<usize> {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for i in 1..=a.len() {
+        let mut row = vec![i; b.len() + 1];
+        for j in 1..=b.len() {
+            let substitute = prev[j - 1] + usize::from(a[i - 1] != b[j - 1]);
+            row[j] = substitute.min(prev[j] + 1).min(row[j - 1] + 1);
+        }
+        if row.iter().min().is_some_and(|&m| m > MAX_DISTANCE) {
+            return None;
+        }
+        prev = row;
+    }
+
+    Some(prev[b.len()])
+}
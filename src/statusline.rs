@@ -0,0 +1,104 @@
+// wfemto - A toy text editor
+// Written in 2025 by Dana Larose <ywg.dana@gmail.com>
+//
+// To the extent possible under law, the author(s) have dedicated all copyright
+// and related and neighboring rights to this software to the public domain
+// worldwide. This software is distributed without any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication along
+// with this software. If not,
+// see <http://creativecommons.org/publicdomain/zero/1.0/>.
+
+// What the status bar shows while editing, set with status_format in the
+// config. The format is text with %-fields filled in for the current buffer:
+//
+//     %f  file name            %l  line number
+//     %m  * if modified        %c  column number
+//     %b  buffer number        %p  percent through the file
+//     %B  number of buffers    %e  line endings (LF, CRLF or CR)
+//     %r  [RO], [watch], [REC] %n  language
+//     %M  NORMAL/INSERT        %i  indentation
+//     %R  repeat count         %E  encoding
+//     %h  Markdown heading     %%  a plain %
+//
+// and %= to split it into a part on the left and a part on the right.
+
+/// Which characters after a % are fields
+const FIELDS: &str = "fmbBrMRlcpeEnih";
+
+pub const DEFAULT_FORMAT: &str = "[%b/%B] %f%m%r%M%R  %i  %l:%c";
+
+#[derive(Clone, Debug, PartialEq)]
+enum Segment {
+    Text(String),
+    Field(char),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct StatusFormat {
+    left: Vec<Segment>,
+    right: Vec<Segment>,
+}
+
+impl Default for StatusFormat {
+    fn default() -> Self {
+        StatusFormat::parse(DEFAULT_FORMAT)
+    }
+}
+
+impl StatusFormat {
+    /// Anything after a % that isn't a field is kept as it was typed, with a
+    /// warning in the log
+    pub fn parse(format: &str) -> StatusFormat {
+        let mut sides = (Vec::new(), Vec::new());
+        let mut on_right = false;
+        let mut text = String::new();
+        let mut chars = format.chars();
+
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                text.push(c);
+                continue;
+            }
+
+            let side = if on_right { &mut sides.1 } else { &mut sides.0 };
+            match chars.next() {
+                Some('%') => text.push('%'),
+                Some('=') if !on_right => {
+                    flush(&mut text, side);
+                    on_right = true;
+                }
+                Some(f) if FIELDS.contains(f) => {
+                    flush(&mut text, side);
+                    side.push(Segment::Field(f));
+                }
+                other => {
+                    let typed: String = other.map_or(String::from("%"), |o| format!("%{}", o));
+                    log_warn!("status_format: unknown field '{}'", typed);
+                    text.push_str(&typed);
+                }
+            }
+        }
+        flush(&mut text, if on_right { &mut sides.1 } else { &mut sides.0 });
+
+        StatusFormat { left: sides.0, right: sides.1 }
+    }
+
+    /// The left and right parts, with `field` giving the text for each field
+    pub fn render(&self, field: impl Fn(char) -> String) -> (String, String) {
+        let render = |segments: &[Segment]| segments.iter()
+            .map(|s| match s {
+                Segment::Text(text) => text.clone(),
+                Segment::Field(f) => field(*f),
+            })
+            .collect::<String>();
+
+        (render(&self.left), render(&self.right))
+    }
+}
+
+fn flush(text: &mut String, side: &mut Vec<Segment>) {
+    if !text.is_empty() {
+        side.push(Segment::Text(std::mem::take(text)));
+    }
+}
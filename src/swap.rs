@@ -0,0 +1,100 @@
+// wfemto - A toy text editor
+// Written in 2025 by Dana Larose <ywg.dana@gmail.com>
+//
+// To the extent possible under law, the author(s) have dedicated all copyright
+// and related and neighboring rights to this software to the public domain
+// worldwide. This software is distributed without any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication along
+// with this software. If not,
+// see <http://creativecommons.org/publicdomain/zero/1.0/>.
+
+// Crash recovery. While a buffer has unsaved changes its text is copied
+// every few seconds to a swap file beside the file (.name.wfswp), which goes
+// away once the changes are saved or thrown out. If wfemto dies, the swap is
+// still there the next time the file is opened and can be recovered.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::clock;
+
+/// How often swap files are brought up to date
+pub const INTERVAL: Duration = Duration::from_secs(4);
+
+const HEADER: &str = "wfemto-swap 1";
+
+pub struct Swap {
+    pub pid: u32,   // Of the wfemto that wrote it
+    pub saved: i64, // Seconds since the epoch
+    pub lines: Vec<String>,
+}
+
+impl Swap {
+    /// Whether the wfemto that wrote the swap is still running, in which
+    /// case the file is open there and this isn't a crash
+    pub fn is_live(&self) -> bool {
+        self.pid != std::process::id() && process_exists(self.pid)
+    }
+}
+
+#[cfg(unix)]
+fn process_exists(pid: u32) -> bool {
+    // SAFETY: signal 0 only checks that the process exists; nothing is sent
+    let found = unsafe { libc::kill(pid as libc::pid_t, 0) == 0 };
+    found || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+#[cfg(not(unix))]
+fn process_exists(_pid: u32) -> bool {
+    false
+}
+
+/// Buffers without a real file behind them don't get swap files
+pub fn wanted(filename: &str) -> bool {
+    !filename.is_empty() && !filename.starts_with('*')
+}
+
+fn swap_path(filename: &str) -> Option<PathBuf> {
+    let path = Path::new(filename);
+    let name = path.file_name()?.to_string_lossy();
+    Some(path.with_file_name(format!(".{}.wfswp", name)))
+}
+
+pub fn write(filename: &str, lines: &[String]) -> Result<(), String> {
+    let path = swap_path(filename).ok_or_else(|| format!("no swap file for {}", filename))?;
+    let mut text = format!("{}\n{}\n{}\n", HEADER, std::process::id(), clock::now_secs());
+    text.push_str(&lines.join("\n"));
+
+    fs::write(&path, text).map_err(|e| e.to_string())
+}
+
+/// The swap file left for `filename`, if there is one
+pub fn read(filename: &str) -> Option<Swap> {
+    let path = swap_path(filename)?;
+    let text = fs::read_to_string(&path).ok()?;
+    let mut parts = text.splitn(4, '\n');
+    if parts.next()? != HEADER {
+        log_warn!("ignoring {}, which isn't a swap file this version can read", path.display());
+        return None;
+    }
+
+    let pid = parts.next()?.parse().ok()?;
+    let saved = parts.next()?.parse().ok()?;
+    let lines = parts.next()?.split('\n').map(String::from).collect();
+
+    Some(Swap { pid, saved, lines })
+}
+
+pub fn remove(filename: &str) {
+    let Some(path) = swap_path(filename) else {
+        return;
+    };
+
+    match fs::remove_file(&path) {
+        Ok(()) => log_debug!("removed {}", path.display()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => log_warn!("unable to remove {}: {}", path.display(), e),
+    }
+}
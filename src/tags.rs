@@ -0,0 +1,216 @@
+// wfemto - A toy text editor
+// Written in 2025 by Dana Larose <ywg.dana@gmail.com>
+//
+// To the extent possible under law, the author(s) have dedicated all copyright
+// and related and neighboring rights to this software to the public domain
+// worldwide. This software is distributed without any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication along
+// with this software. If not,
+// see <http://creativecommons.org/publicdomain/zero/1.0/>.
+
+// Looking up definitions in a ctags "tags" file. Each line is
+//
+//     name<TAB>file<TAB>address;"<TAB>extension fields...
+//
+// where the address is a line number or a search pattern like /^fn foo() {$/.
+// The file is found by walking up from the directory of the file being
+// edited, and names in it are relative to where it lives.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Address {
+    Line(usize), // 1-based, as ctags writes them
+    Pattern(String),
+}
+
+#[derive(Clone, Debug)]
+pub struct Tag {
+    pub name: String,
+    pub file: PathBuf,
+    pub address: Address,
+}
+
+impl Tag {
+    /// How the tag is listed when there are several to pick from
+    pub fn describe(&self) -> String {
+        let file = self.file.display();
+        match &self.address {
+            Address::Line(n) => format!("{}:{}", file, n),
+            Address::Pattern(p) => {
+                format!("{}: {}", file, p.trim_start_matches('^').trim_end_matches('$').trim())
+            }
+        }
+    }
+}
+
+impl Address {
+    /// The (0-based) row the address points at in `lines`. A pattern that
+    /// no longer matches exactly falls back to the first line containing it.
+    pub fn locate(&self, lines: &[String]) -> Option<usize> {
+        match self {
+            Address::Line(n) => Some(n.saturating_sub(1).min(lines.len().saturating_sub(1))),
+            Address::Pattern(pattern) => {
+                let (anchored_start, rest) = match pattern.strip_prefix('^') {
+                    Some(rest) => (true, rest),
+                    None => (false, pattern.as_str()),
+                };
+                let (anchored_end, text) = match rest.strip_suffix('$') {
+                    Some(text) if !text.ends_with('\\') => (true, text),
+                    _ => (false, rest),
+                };
+
+                let exact = lines.iter().position(|line| match (anchored_start, anchored_end) {
+                    (true, true) => line == text,
+                    (true, false) => line.starts_with(text),
+                    (false, true) => line.ends_with(text),
+                    (false, false) => line.contains(text),
+                });
+                exact.or_else(|| lines.iter().position(|line| line.contains(text.trim())))
+            }
+        }
+    }
+}
+
+/// The closest tags file at or above `dir`
+pub fn find_tags_file(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors().map(|d| d.join("tags")).find(|p| p.is_file())
+}
+
+/// Every tag called `name` in the tags file at `path`
+pub fn lookup(path: &Path, name: &str) -> Result<Vec<Tag>, String> {
+    let text = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let dir = path.parent().unwrap_or(Path::new("."));
+
+    let tags = text.lines()
+        .filter(|line| line.split('\t').next() == Some(name))
+        .filter_map(parse_line)
+        .map(|mut tag| {
+            if tag.file.is_relative() {
+                tag.file = dir.join(&tag.file);
+            }
+            tag
+        })
+        .collect();
+
+    Ok(tags)
+}
+
+/// One line of a tags file. The !_TAG_ header lines and anything malformed
+/// give None.
+pub fn parse_line(line: &str) -> Option<Tag> {
+    if line.starts_with("!_TAG_") {
+        return None;
+    }
+
+    let mut fields = line.splitn(3, '\t');
+    let name = fields.next()?;
+    let file = fields.next()?;
+    let rest = fields.next()?;
+
+    // The address ends at ;" (where the extension fields start) or, in old
+    // style files, at the end of the line
+    let address = match rest.find(";\"\t").or_else(|| rest.strip_suffix(";\"").map(str::len)) {
+        Some(end) => &rest[..end],
+        None => rest,
+    };
+
+    Some(Tag {
+        name: name.to_string(),
+        file: PathBuf::from(file),
+        address: parse_address(address)?,
+    })
+}
+
+fn parse_address(address: &str) -> Option<Address> {
+    if let Ok(n) = address.trim().parse::<usize>() {
+        return Some(Address::Line(n));
+    }
+
+    // /forward/ or ?backward? -- the direction doesn't matter here
+    let delim = address.chars().next().filter(|&c| c == '/' || c == '?')?;
+    let body = address[1..].strip_suffix(delim)?;
+
+    // Inside the pattern only the delimiter and backslash are escaped
+    let mut pattern = String::new();
+    let mut chars = body.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, chars.peek()) {
+            ('\\', Some(&next)) if next == delim || next == '\\' => {
+                pattern.push(next);
+                chars.next();
+            }
+            _ => pattern.push(c),
+        }
+    }
+
+    Some(Address::Pattern(pattern))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXTURE: &str = "tests/tags/tags";
+
+    fn source() -> Vec<String> {
+        fs::read_to_string("tests/tags/src/shapes.rs").unwrap().lines().map(String::from).collect()
+    }
+
+    #[test]
+    fn the_tags_file_is_found_above_the_source() {
+        assert_eq!(find_tags_file(Path::new("tests/tags/src")), Some(PathBuf::from(FIXTURE)));
+        assert_eq!(find_tags_file(Path::new("tests/tags")), Some(PathBuf::from(FIXTURE)));
+    }
+
+    #[test]
+    fn lookup_finds_every_tag_with_the_name() {
+        let areas = lookup(Path::new(FIXTURE), "area").unwrap();
+        assert_eq!(areas.len(), 2);
+        assert!(areas.iter().all(|tag| tag.file == Path::new("tests/tags/src/shapes.rs")));
+        assert_eq!(areas[0].describe(), "tests/tags/src/shapes.rs: pub fn area(&self) -> f64 {");
+
+        assert!(lookup(Path::new(FIXTURE), "missing").unwrap().is_empty());
+        assert!(lookup(Path::new(FIXTURE), "!_TAG_FILE_FORMAT").unwrap().is_empty());
+        assert!(lookup(Path::new("tests/tags/nothing"), "area").is_err());
+    }
+
+    #[test]
+    fn tags_locate_their_lines_in_the_source() {
+        let lines = source();
+        let row = |name: &str| lookup(Path::new(FIXTURE), name).unwrap()[0].address.locate(&lines);
+        assert_eq!(row("Circle"), Some(0));
+        assert_eq!(row("Square"), Some(4));
+        assert_eq!(row("area"), Some(9));
+        assert_eq!(row("origin"), Some(21));
+        assert_eq!(row("slashes"), Some(20));
+        // No longer matching exactly, so the first line containing it
+        assert_eq!(row("stale"), Some(10));
+    }
+
+    #[test]
+    fn addresses_are_line_numbers_or_patterns() {
+        let tag = parse_line("main\tmain.rs\t42;\"\tf").unwrap();
+        assert_eq!(tag.address, Address::Line(42));
+        assert_eq!(tag.describe(), "main.rs:42");
+
+        let tag = parse_line("a\ta.c\t?^int a(void) {$?").unwrap();
+        assert_eq!(tag.address, Address::Pattern(String::from("^int a(void) {$")));
+        let tag = parse_line("b\tb.c\t/^x = \\/\\\\ y$/;\"\tv").unwrap();
+        assert_eq!(tag.address, Address::Pattern(String::from("^x = /\\ y$")));
+
+        assert!(parse_line("no tabs here").is_none());
+        assert!(parse_line("c\tc.c\tnot an address").is_none());
+        assert!(parse_line("!_TAG_PROGRAM_NAME\tctags\t//").is_none());
+    }
+
+    #[test]
+    fn a_line_past_the_end_goes_to_the_last() {
+        let lines = vec![String::from("one"), String::from("two")];
+        assert_eq!(Address::Line(99).locate(&lines), Some(1));
+        assert_eq!(Address::Line(0).locate(&lines), Some(0));
+        assert_eq!(Address::Pattern(String::from("^three$")).locate(&lines), None);
+    }
+}
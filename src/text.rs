@@ -0,0 +1,373 @@
+// wfemto - A toy text editor
+// Written in 2025 by Dana Larose <ywg.dana@gmail.com>
+//
+// To the extent possible under law, the author(s) have dedicated all copyright
+// and related and neighboring rights to this software to the public domain
+// worldwide. This software is distributed without any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication along
+// with this software. If not,
+// see <http://creativecommons.org/publicdomain/zero/1.0/>.
+
+// Helpers for indexing into lines of text. Columns throughout the editor
+// count chars, not bytes, so the cursor can never end up in the middle of a
+// multibyte character; these convert between the two. Also where words
+// start and end, for everything that moves, selects or deletes by the word.
+
+use std::borrow::Cow;
+
+/// Whether `c` can be part of a word (an identifier, for most languages)
+pub fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// What a char counts as when working out where words start and end. Runs
+/// of word chars, of punctuation and of spaces are each a word's worth.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CharClass {
+    Word,
+    Punctuation,
+    Space,
+}
+
+/// The class of `c`, where `word_chars` are more chars to count as word
+/// chars, like the - of a CSS property name
+pub fn char_class(c: char, word_chars: &str) -> CharClass {
+    if c.is_whitespace() {
+        CharClass::Space
+    } else if is_word_char(c) || word_chars.contains(c) {
+        CharClass::Word
+    } else {
+        CharClass::Punctuation
+    }
+}
+
+/// Columns start..end of the run of chars of one class at `col`, or just
+/// before it at the end of the line: what a double-click selects
+pub fn class_run(line: &str, col: usize, word_chars: &str) -> (usize, usize) {
+    let chars: Vec<char> = line.chars().collect();
+    if chars.is_empty() {
+        return (0, 0);
+    }
+
+    let col = col.min(chars.len() - 1);
+    let class = char_class(chars[col], word_chars);
+    let same = |i: &usize| char_class(chars[*i], word_chars) == class;
+    let start = (0..col).rev().take_while(same).last().unwrap_or(col);
+    let end = (col..chars.len()).take_while(same).last().map_or(col, |i| i + 1);
+    (start, end)
+}
+
+/// The column just past the next word after `col`: any spaces are skipped,
+/// then a run of word chars or of punctuation
+pub fn next_word_end(line: &str, col: usize, word_chars: &str) -> usize {
+    let chars: Vec<char> = line.chars().collect();
+    let mut col = col.min(chars.len());
+    while col < chars.len() && chars[col].is_whitespace() {
+        col += 1;
+    }
+    if let Some(&c) = chars.get(col) {
+        let class = char_class(c, word_chars);
+        while col < chars.len() && char_class(chars[col], word_chars) == class {
+            col += 1;
+        }
+    }
+
+    col
+}
+
+/// Columns start..end of the word the cursor at `col` is in or just after
+pub fn word_at(line: &str, col: usize) -> Option<(usize, usize)> {
+    let chars: Vec<char> = line.chars().collect();
+    let col = col.min(chars.len());
+    let start = (0..col).rev().take_while(|&i| is_word_char(chars[i])).last().unwrap_or(col);
+    let end = (col..chars.len()).take_while(|&i| is_word_char(chars[i])).last().map_or(col, |i| i + 1);
+
+    if start == end { None } else { Some((start, end)) }
+}
+
+/// The column the word before `col` starts at: any spaces are skipped back
+/// over, then a run of word chars or of punctuation, as Ctrl+W deletes
+pub fn prev_word_start(line: &str, col: usize, word_chars: &str) -> usize {
+    let chars: Vec<char> = line.chars().collect();
+    let mut col = col.min(chars.len());
+    while col > 0 && chars[col - 1].is_whitespace() {
+        col -= 1;
+    }
+    if col > 0 {
+        let class = char_class(chars[col - 1], word_chars);
+        while col > 0 && char_class(chars[col - 1], word_chars) == class {
+            col -= 1;
+        }
+    }
+
+    col
+}
+
+/// `line` with the chars either side of `col` swapped (the last two, at
+/// the end of the line), and the column just past them
+pub fn transpose_chars(line: &str, col: usize) -> Result<(String, usize), String> {
+    let mut chars: Vec<char> = line.chars().collect();
+    if chars.len() < 2 {
+        return Err(String::from("Not enough characters to transpose"));
+    }
+    if col == 0 {
+        return Err(String::from("No character before the cursor"));
+    }
+
+    let right = col.min(chars.len() - 1);
+    chars.swap(right - 1, right);
+    Ok((chars.into_iter().collect(), right + 1))
+}
+
+/// `line` with the word the cursor at `col` is in (or the one before it)
+/// swapped with the word after, whatever is between them left alone, and
+/// the column just past the pair
+pub fn transpose_words(line: &str, col: usize) -> Result<(String, usize), String> {
+    let chars: Vec<char> = line.chars().collect();
+    let first = match word_at(line, col) {
+        Some(word) => word,
+        None => {
+            let Some(last) = (0..col.min(chars.len())).rev().find(|&i| is_word_char(chars[i])) else {
+                return Err(String::from("No word to transpose"));
+            };
+            (prev_word_start(line, last + 1, ""), last + 1)
+        }
+    };
+    let Some(second_start) = (first.1..chars.len()).find(|&i| is_word_char(chars[i])) else {
+        return Err(String::from("No word after this one"));
+    };
+    let second_end = (second_start..chars.len()).find(|&i| !is_word_char(chars[i])).unwrap_or(chars.len());
+
+    let mut out: String = chars[..first.0].iter().collect();
+    out.extend(&chars[second_start..second_end]);
+    out.extend(&chars[first.1..second_start]);
+    out.extend(&chars[first.0..first.1]);
+    out.extend(&chars[second_end..]);
+    Ok((out, second_end))
+}
+
+/// Number of chars (columns) in a line
+pub fn char_len(line: &str) -> usize {
+    line.chars().count()
+}
+
+/// Byte offset of the char at column `col`, or the end of the line if `col`
+/// is past it.
+pub fn byte_idx(line: &str, col: usize) -> usize {
+    line.char_indices().nth(col).map(|(i, _)| i).unwrap_or(line.len())
+}
+
+/// The text in columns `from..to`, clamped to the line
+pub fn col_slice(line: &str, from: usize, to: usize) -> &str {
+    let start = byte_idx(line, from);
+    let end = byte_idx(line, to.max(from));
+
+    &line[start..end]
+}
+
+/// How a control character other than tab is shown: ^ and the key it's
+/// Ctrl+ of, like ^L for a form feed, or ^? for DEL. The text itself is
+/// left alone; it's only drawn this way.
+pub fn caret_notation(c: char) -> Option<[char; 2]> {
+    match c {
+        '\t' => None,
+        '\0'..='\x1f' => Some(['^', (c as u8 + 0x40) as char]),
+        '\x7f' => Some(['^', '?']),
+        _ => None,
+    }
+}
+
+/// Whether `c` is East Asian wide (CJK, Hangul, fullwidth forms) or an
+/// emoji, which a monospace font draws two cells wide. Close enough to
+/// Unicode's East_Asian_Width table for the scripts people actually type.
+pub fn is_wide(c: char) -> bool {
+    matches!(c as u32,
+        0x1100..=0x115F         // Hangul Jamo
+        | 0x2E80..=0x303E       // CJK radicals, punctuation
+        | 0x3041..=0x33FF       // Kana, CJK compatibility
+        | 0x3400..=0x4DBF       // CJK extension A
+        | 0x4E00..=0x9FFF       // CJK unified ideographs
+        | 0xA000..=0xA4CF       // Yi
+        | 0xAC00..=0xD7A3       // Hangul syllables
+        | 0xF900..=0xFAFF       // CJK compatibility ideographs
+        | 0xFE30..=0xFE4F       // CJK compatibility forms
+        | 0xFF00..=0xFF60       // Fullwidth forms
+        | 0xFFE0..=0xFFE6
+        | 0x1F300..=0x1F64F     // Pictographs, emoticons
+        | 0x1F900..=0x1F9FF     // Supplemental symbols and pictographs
+        | 0x20000..=0x3FFFD)    // CJK extensions B and on
+}
+
+/// Screen columns `c` takes up anywhere but a tab stop
+fn char_cells(c: char) -> usize {
+    if is_wide(c) || caret_notation(c).is_some() { 2 } else { 1 }
+}
+
+/// How many screen columns the chars before `col` take up, with each tab
+/// advancing to the next multiple of `tab_width`, and wide and control
+/// characters taking two.
+pub fn display_col(line: &str, col: usize, tab_width: usize) -> usize {
+    let tab_width = tab_width.max(1);
+    line.chars().take(col).fold(0, |x, c| {
+        if c == '\t' { (x / tab_width + 1) * tab_width } else { x + char_cells(c) }
+    })
+}
+
+/// The text in columns `from..to` as it appears on screen: tabs become
+/// spaces out to the next tab stop, where the stops are measured from the
+/// start of the line rather than from `from`, and control characters are
+/// written in caret notation.
+pub fn expand_tabs(line: &str, from: usize, to: usize, tab_width: usize) -> String {
+    let tab_width = tab_width.max(1);
+    let mut x = display_col(line, from, tab_width);
+    let mut out = String::new();
+
+    for c in col_slice(line, from, to).chars() {
+        if c == '\t' {
+            let next = (x / tab_width + 1) * tab_width;
+            out.extend(std::iter::repeat_n(' ', next - x));
+            x = next;
+        } else if let Some(shown) = caret_notation(c) {
+            out.extend(shown);
+            x += 2;
+        } else {
+            out.push(c);
+            x += char_cells(c);
+        }
+    }
+
+    out
+}
+
+/// `text` with every char `drawable` says no to swapped for `placeholder`,
+/// borrowed as it is when there are none
+pub fn replace_missing(text: &str, placeholder: char, drawable: impl Fn(char) -> bool) -> Cow<'_, str> {
+    if text.chars().all(&drawable) {
+        return Cow::Borrowed(text);
+    }
+
+    Cow::Owned(text.chars().map(|c| if drawable(c) { c } else { placeholder }).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LINE: &str = "let x = foo-bar(1);";
+
+    #[test]
+    fn char_classes() {
+        assert_eq!(char_class('a', ""), CharClass::Word);
+        assert_eq!(char_class('_', ""), CharClass::Word);
+        assert_eq!(char_class('é', ""), CharClass::Word);
+        assert_eq!(char_class('-', ""), CharClass::Punctuation);
+        assert_eq!(char_class('-', "-"), CharClass::Word);
+        assert_eq!(char_class('\t', "\t"), CharClass::Space);
+    }
+
+    #[test]
+    fn class_run_is_what_a_double_click_selects() {
+        assert_eq!(class_run(LINE, 9, ""), (8, 11));
+        assert_eq!(class_run(LINE, 9, "-"), (8, 15));
+        assert_eq!(class_run(LINE, 15, ""), (15, 16));
+        assert_eq!(class_run("a   b", 2, ""), (1, 4));
+        assert_eq!(class_run("héllo wörld", 8, ""), (6, 11));
+    }
+
+    #[test]
+    fn class_run_at_the_ends_of_a_line() {
+        assert_eq!(class_run(LINE, 0, ""), (0, 3));
+        assert_eq!(class_run(LINE, 19, ""), (17, 19));
+        assert_eq!(class_run(LINE, 100, ""), (17, 19));
+        assert_eq!(class_run("", 5, ""), (0, 0));
+    }
+
+    #[test]
+    fn next_word_end_skips_spaces_then_one_run() {
+        assert_eq!(next_word_end(LINE, 0, ""), 3);
+        assert_eq!(next_word_end(LINE, 3, ""), 5);
+        assert_eq!(next_word_end(LINE, 7, ""), 11);
+        assert_eq!(next_word_end(LINE, 7, "-"), 15);
+        assert_eq!(next_word_end(LINE, 15, ""), 16);
+        assert_eq!(next_word_end(LINE, 19, ""), 19);
+        assert_eq!(next_word_end("x   ", 1, ""), 4);
+    }
+
+    #[test]
+    fn prev_word_start_skips_spaces_then_one_run() {
+        assert_eq!(prev_word_start(LINE, 19, ""), 17);
+        assert_eq!(prev_word_start(LINE, 11, ""), 8);
+        assert_eq!(prev_word_start(LINE, 15, "-"), 8);
+        assert_eq!(prev_word_start(LINE, 8, ""), 6);
+        assert_eq!(prev_word_start(LINE, 0, ""), 0);
+        assert_eq!(prev_word_start("  ab", 2, ""), 0);
+        assert_eq!(prev_word_start("héllo wörld", 100, ""), 6);
+    }
+
+    #[test]
+    fn word_at_the_cursor_or_just_before_it() {
+        assert_eq!(word_at("foo bar", 3), Some((0, 3)));
+        assert_eq!(word_at("foo bar", 4), Some((4, 7)));
+        assert_eq!(word_at("foo-bar", 4), Some((4, 7)));
+        assert_eq!(word_at("a - b", 2), None);
+        assert_eq!(word_at("", 0), None);
+    }
+
+    #[test]
+    fn is_wide_at_the_edges_of_its_ranges() {
+        let wide = |c: u32| is_wide(char::from_u32(c).unwrap());
+        assert!(!wide(0x10FF) && wide(0x1100) && wide(0x115F) && !wide(0x1160));
+        assert!(!wide(0x4DC0) && wide(0x4E00) && wide(0x9FFF) && wide(0xA000));
+        assert!(wide(0xFF60) && !wide(0xFF61));
+        assert!(!wide(0x1F2FF) && wide(0x1F300) && wide(0x1F64F) && !wide(0x1F650));
+        assert!(wide(0x20000) && wide(0x3FFFD) && !wide(0x3FFFE));
+        assert!(!is_wide('a') && !is_wide('é') && !is_wide('\u{301}'));
+    }
+
+    #[test]
+    fn display_col_counts_wide_chars_twice() {
+        assert_eq!(display_col("ab中文😀cd", 7, 4), 10);
+        assert_eq!(display_col("ab中文😀cd", 4, 4), 6);
+        assert_eq!(display_col("中\u{1}x", 3, 4), 5);
+    }
+
+    #[test]
+    fn tabs_after_wide_chars_go_to_the_next_stop() {
+        assert_eq!(display_col("a中\tb", 4, 4), 5);
+        assert_eq!(display_col("中文\tx", 4, 4), 9);
+        assert_eq!(display_col("😀\t", 2, 4), 4);
+        assert_eq!(display_col("中文中\t", 4, 4), 8);
+    }
+
+    #[test]
+    fn expand_tabs_after_wide_chars() {
+        assert_eq!(expand_tabs("中文\tx", 0, 4, 4), "中文    x");
+        assert_eq!(expand_tabs("a中\tb", 2, 4, 4), " b");
+        assert_eq!(expand_tabs("😀\t\u{1}", 1, 3, 4), "  ^A");
+    }
+
+    /// A font that only has the Basic Multilingual Plane, minus private use
+    /// and control characters
+    fn bmp_only(c: char) -> bool {
+        u32::from(c) <= 0xFFFF && !('\u{E000}'..='\u{F8FF}').contains(&c) && !c.is_control()
+    }
+
+    #[test]
+    fn missing_chars_become_the_placeholder() {
+        assert!(matches!(replace_missing("plain 中文", '?', bmp_only), Cow::Borrowed("plain 中文")));
+        assert_eq!(replace_missing("a😀b\u{E000}c", '?', bmp_only), "a?b?c");
+        assert_eq!(replace_missing("", '?', |_| false), "");
+    }
+
+    #[test]
+    fn exotic_codepoints_come_out_drawable() {
+        let text = std::fs::read_to_string("tests/exotic.txt").unwrap();
+        for line in text.lines() {
+            let shown = expand_tabs(line, 0, char_len(line), 4);
+            let drawn = replace_missing(&shown, '\u{25A1}', bmp_only);
+            assert!(drawn.chars().all(|c| bmp_only(c) && !c.is_control()), "{:?} came out as {:?}", line, drawn);
+            assert_eq!(char_len(&drawn), char_len(&shown), "{:?}", line);
+        }
+    }
+}
@@ -0,0 +1,98 @@
+// wfemto - A toy text editor
+// Written in 2025 by Dana Larose <ywg.dana@gmail.com>
+//
+// To the extent possible under law, the author(s) have dedicated all copyright
+// and related and neighboring rights to this software to the public domain
+// worldwide. This software is distributed without any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication along
+// with this software. If not,
+// see <http://creativecommons.org/publicdomain/zero/1.0/>.
+
+// The editor's colours in one place. They're kept as plain RGB triples
+// rather than SDL colours so things that don't draw to the screen (like the
+// HTML export) can use them too.
+
+use crate::highlight::TokenKind;
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct Rgb(pub u8, pub u8, pub u8);
+
+impl Rgb {
+    /// Read a colour written #rrggbb (the # is optional)
+    pub fn parse(text: &str) -> Result<Rgb, String> {
+        let hex = text.trim().trim_start_matches('#');
+        let channel = |i: usize| hex.get(i..i + 2).and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (hex.len(), channel(0), channel(2), channel(4)) {
+            (6, Some(r), Some(g), Some(b)) => Ok(Rgb(r, g, b)),
+            _ => Err(format!("expected a colour like #d9d9d6, got '{}'", text)),
+        }
+    }
+
+    /// As a CSS colour, #rrggbb
+    pub fn hex(self) -> String {
+        format!("#{:02x}{:02x}{:02x}", self.0, self.1, self.2)
+    }
+}
+
+pub struct Theme {
+    pub background: Rgb,
+    pub foreground: Rgb,
+    pub keyword: Rgb,
+    pub type_name: Rgb,
+    pub number: Rgb,
+    pub string: Rgb,
+    pub comment: Rgb,
+    pub marker: Rgb,
+    pub selection: Rgb,
+    pub occurrence: Rgb, // Other places the word under the cursor appears
+    pub column_guide: Rgb,
+    pub line_number: Rgb,
+    pub cursor: Rgb,
+    pub status_bg: Rgb,
+    pub status_fg: Rgb,
+    pub git_added: Rgb,
+    pub git_modified: Rgb,
+    pub git_deleted: Rgb,
+    pub misspelled: Rgb, // Underlining unknown words
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme {
+            background: Rgb(255, 255, 255),
+            foreground: Rgb(0, 0, 0),
+            keyword: Rgb(0, 0, 160),
+            type_name: Rgb(0, 110, 110),
+            number: Rgb(150, 80, 0),
+            string: Rgb(160, 30, 30),
+            comment: Rgb(110, 110, 110),
+            marker: Rgb(200, 0, 120),
+            selection: Rgb(179, 215, 255),
+            occurrence: Rgb(234, 234, 210),
+            column_guide: Rgb(240, 240, 245),
+            line_number: Rgb(160, 160, 160),
+            cursor: Rgb(128, 128, 128),
+            status_bg: Rgb(217, 217, 214),
+            status_fg: Rgb(89, 89, 88),
+            git_added: Rgb(60, 170, 60),
+            git_modified: Rgb(60, 110, 220),
+            git_deleted: Rgb(210, 50, 50),
+            misspelled: Rgb(230, 30, 30),
+        }
+    }
+}
+
+impl Theme {
+    pub fn token_colour(&self, kind: TokenKind) -> Rgb {
+        match kind {
+            TokenKind::Text => self.foreground,
+            TokenKind::Keyword => self.keyword,
+            TokenKind::Type => self.type_name,
+            TokenKind::Number => self.number,
+            TokenKind::String => self.string,
+            TokenKind::Comment => self.comment,
+            TokenKind::Marker => self.marker,
+        }
+    }
+}
@@ -0,0 +1,225 @@
+// wfemto - A toy text editor
+// Written in 2025 by Dana Larose <ywg.dana@gmail.com>
+//
+// To the extent possible under law, the author(s) have dedicated all copyright
+// and related and neighboring rights to this software to the public domain
+// worldwide. This software is distributed without any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication along
+// with this software. If not,
+// see <http://creativecommons.org/publicdomain/zero/1.0/>.
+
+// Undo history. Every edit, however small, is stored the same way: a run of
+// lines starting at some row was replaced by a different run of lines. That
+// keeps undo and redo trivial (swap the two runs back) at the cost of storing
+// a whole line for a one character change, which is fine for a toy editor.
+
+/// What produced a change. Only consecutive typing gets merged into a
+/// single undo step; everything else stands alone.
+#[derive(Clone, Copy, PartialEq)]
+pub enum ChangeKind {
+    Typing,
+    Other,
+}
+
+#[derive(Clone)]
+pub struct Change {
+    pub row: usize,
+    pub old: Vec<String>,
+    pub new: Vec<String>,
+    pub cursor_before: (usize, usize),
+    pub cursor_after: (usize, usize),
+    pub kind: ChangeKind,
+    pub group: u64, // Changes sharing a group are undone and redone together
+}
+
+#[derive(Default)]
+pub struct UndoStack {
+    pub undo: Vec<Change>,
+    pub redo: Vec<Change>,
+    next_group: u64,
+    group_depth: usize,
+    open_group: u64,
+    clean_at: Option<usize>, // undo.len() when the text matched the file on disk
+}
+
+impl UndoStack {
+    pub fn new() -> Self {
+        UndoStack { clean_at: Some(0), ..Default::default() }
+    }
+
+    /// Start a run of changes that should be undone as one step. Groups nest;
+    /// only the outermost one counts.
+    pub fn begin_group(&mut self) {
+        if self.group_depth == 0 {
+            self.next_group += 1;
+            self.open_group = self.next_group;
+        }
+        self.group_depth += 1;
+    }
+
+    pub fn end_group(&mut self) {
+        self.group_depth = self.group_depth.saturating_sub(1);
+    }
+
+    /// Add a change that has just been made to the text
+    pub fn record(&mut self, mut change: Change) {
+        self.redo.clear();
+        if self.clean_at.is_some_and(|n| n > self.undo.len()) {
+            // The saved state was in the redo history we just threw away
+            self.clean_at = None;
+        }
+
+        if self.try_merge(&change) {
+            return;
+        }
+
+        change.group = if self.group_depth > 0 {
+            self.open_group
+        } else {
+            self.next_group += 1;
+            self.next_group
+        };
+        self.undo.push(change);
+    }
+
+    /// Say where the cursor ended up after the change just recorded, for an
+    /// edit that moves it on afterwards. Redo puts it back there.
+    pub fn set_cursor_after(&mut self, cursor: (usize, usize)) {
+        if let Some(top) = self.undo.last_mut() {
+            top.cursor_after = cursor;
+        }
+    }
+
+    /// Fold a typed character into the previous step if it directly
+    /// continues it. A new word starts a new step so undo doesn't throw away
+    /// a whole line of typing at once.
+    fn try_merge(&mut self, change: &Change) -> bool {
+        if change.kind != ChangeKind::Typing || self.clean_at == Some(self.undo.len()) {
+            return false;
+        }
+
+        let Some(top) = self.undo.last_mut() else {
+            return false;
+        };
+        // Inside a group, only merge with typing from the same group
+        let same_group = self.group_depth == 0 || top.group == self.open_group;
+        let continues = same_group
+            && top.kind == ChangeKind::Typing
+            && top.row == change.row
+            && top.new.len() == 1
+            && change.old.len() == 1
+            && change.new.len() == 1
+            && top.cursor_after == change.cursor_before;
+        if !continues {
+            return false;
+        }
+
+        let line = &change.new[0];
+        let typed = line.chars().nth(change.cursor_before.1);
+        let previous = change.cursor_before.1.checked_sub(1).and_then(|c| line.chars().nth(c));
+        if let (Some(typed), Some(previous)) = (typed, previous)
+            && !typed.is_whitespace() && previous.is_whitespace()
+        {
+            return false;
+        }
+
+        top.new = change.new.clone();
+        top.cursor_after = change.cursor_after;
+
+        true
+    }
+
+    /// Take the most recent step (all the changes in its group), newest first
+    pub fn pop_undo(&mut self) -> Vec<Change> {
+        let Some(group) = self.undo.last().map(|c| c.group) else {
+            return Vec::new();
+        };
+
+        let mut changes = Vec::new();
+        while self.undo.last().is_some_and(|c| c.group == group) {
+            let change = self.undo.pop().unwrap();
+            self.redo.push(change.clone());
+            changes.push(change);
+        }
+
+        changes
+    }
+
+    /// Take the most recently undone step, oldest change first
+    pub fn pop_redo(&mut self) -> Vec<Change> {
+        let Some(group) = self.redo.last().map(|c| c.group) else {
+            return Vec::new();
+        };
+
+        let mut changes = Vec::new();
+        while self.redo.last().is_some_and(|c| c.group == group) {
+            let change = self.redo.pop().unwrap();
+            self.undo.push(change.clone());
+            changes.push(change);
+        }
+
+        changes
+    }
+
+    /// Note that the text now matches what's on disk
+    pub fn mark_clean(&mut self) {
+        self.clean_at = Some(self.undo.len());
+    }
+
+    /// The undo and redo histories as text, for undofile to keep. Each change
+    /// is a header line followed by its old and new lines.
+    pub fn encode(&self) -> String {
+        let mut out = format!("{} {}\n", self.undo.len(), self.redo.len());
+        for c in self.undo.iter().chain(&self.redo) {
+            let kind = if c.kind == ChangeKind::Typing { 'T' } else { 'O' };
+            out.push_str(&format!("{} {} {} {} {} {} {} {} {}\n",
+                c.row, c.old.len(), c.new.len(),
+                c.cursor_before.0, c.cursor_before.1, c.cursor_after.0, c.cursor_after.1,
+                kind, c.group));
+            for line in c.old.iter().chain(&c.new) {
+                out.push_str(line);
+                out.push('\n');
+            }
+        }
+
+        out
+    }
+
+    /// Read back what encode() wrote. Anything malformed gives None. The
+    /// result counts as clean, since it's only kept for text saved to disk.
+    pub fn decode(text: &str) -> Option<UndoStack> {
+        let mut lines = text.split('\n');
+        let mut counts = lines.next()?.split(' ').map(str::parse::<usize>);
+        let (undo_count, redo_count) = (counts.next()?.ok()?, counts.next()?.ok()?);
+
+        let mut stack = UndoStack::new();
+        for i in 0..undo_count + redo_count {
+            let fields: Vec<&str> = lines.next()?.split(' ').collect();
+            let [row, old, new, before_row, before_col, after_row, after_col, kind, group] = fields[..] else {
+                return None;
+            };
+            let num = |s: &str| s.parse::<usize>().ok();
+            let mut take = |n| (0..n).map(|_| lines.next().map(String::from)).collect::<Option<Vec<_>>>();
+            let change = Change {
+                row: num(row)?,
+                old: take(num(old)?)?,
+                new: take(num(new)?)?,
+                cursor_before: (num(before_row)?, num(before_col)?),
+                cursor_after: (num(after_row)?, num(after_col)?),
+                kind: if kind == "T" { ChangeKind::Typing } else { ChangeKind::Other },
+                group: group.parse().ok()?,
+            };
+            stack.next_group = stack.next_group.max(change.group);
+            if i < undo_count { stack.undo.push(change) } else { stack.redo.push(change) }
+        }
+        stack.mark_clean();
+
+        Some(stack)
+    }
+
+    /// Whether undoing/redoing has landed back on the saved text
+    pub fn is_clean(&self) -> bool {
+        self.clean_at == Some(self.undo.len())
+    }
+}
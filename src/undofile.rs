@@ -0,0 +1,94 @@
+// wfemto - A toy text editor
+// Written in 2025 by Dana Larose <ywg.dana@gmail.com>
+//
+// To the extent possible under law, the author(s) have dedicated all copyright
+// and related and neighboring rights to this software to the public domain
+// worldwide. This software is distributed without any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication along
+// with this software. If not,
+// see <http://creativecommons.org/publicdomain/zero/1.0/>.
+
+// Undo history kept between sessions. When a buffer's text matches its file
+// on disk, its history is written to $XDG_CACHE_HOME/wfemto/undo (or
+// ~/.cache/wfemto/undo), in a file named for the file's full path. Opening
+// the file again picks the history back up, as long as the file hasn't been
+// changed since; otherwise the old history is quietly dropped.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::undo::UndoStack;
+
+/// First line of an undo file. Bump the number whenever the format changes,
+/// and files written by older versions will be ignored rather than misread.
+const HEADER: &str = "wfemto-undo 1";
+
+fn undo_dir() -> Option<PathBuf> {
+    let base = match std::env::var_os("XDG_CACHE_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(std::env::var_os("HOME").filter(|h| !h.is_empty())?).join(".cache"),
+    };
+
+    Some(base.join("wfemto").join("undo"))
+}
+
+/// 64-bit FNV-1a, which unlike std's hasher is the same from one build to
+/// the next
+fn fnv(bytes: impl IntoIterator<Item = u8>) -> u64 {
+    bytes.into_iter().fold(0xcbf29ce484222325, |hash, b| (hash ^ b as u64).wrapping_mul(0x100000001b3))
+}
+
+fn content_hash(lines: &[String]) -> u64 {
+    fnv(lines.iter().flat_map(|l| l.bytes().chain(std::iter::once(b'\n'))))
+}
+
+fn undo_path(filename: &str) -> Option<PathBuf> {
+    let path = fs::canonicalize(filename).ok()?;
+    let key = fnv(path.to_string_lossy().bytes());
+    Some(undo_dir()?.join(format!("{:016x}", key)))
+}
+
+/// Keep `history` for `filename`, whose text is `lines`
+pub fn save(filename: &str, lines: &[String], history: &UndoStack) {
+    let Some(path) = undo_path(filename) else {
+        return;
+    };
+
+    let text = format!("{}\n{:016x}\n{}", HEADER, content_hash(lines), history.encode());
+    let result = path.parent().map_or(Ok(()), fs::create_dir_all)
+        .and_then(|()| fs::write(&path, text));
+    match result {
+        Ok(()) => log_debug!("saved undo history for {} to {}", filename, path.display()),
+        Err(e) => log_warn!("unable to save undo history to {}: {}", path.display(), e),
+    }
+}
+
+/// The history saved for `filename`, if it was saved when the file's text
+/// was `lines`
+pub fn load(filename: &str, lines: &[String]) -> Option<UndoStack> {
+    let path = undo_path(filename)?;
+    let text = fs::read_to_string(&path).ok()?;
+    let mut parts = text.splitn(3, '\n');
+    if parts.next()? != HEADER {
+        log_info!("ignoring {}, written by a different version", path.display());
+        return None;
+    }
+    if parts.next()? != format!("{:016x}", content_hash(lines)) {
+        log_debug!("{} has changed since its undo history was saved", filename);
+        return None;
+    }
+
+    let history = UndoStack::decode(parts.next()?);
+    if history.is_none() {
+        log_warn!("ignoring unreadable undo history {}", path.display());
+    }
+
+    history
+}
+
+/// Whether there's any point keeping history for a buffer with this name.
+/// Unnamed buffers and ones like *grep* have no file behind them.
+pub fn wanted(filename: &str) -> bool {
+    !filename.is_empty() && !filename.starts_with('*') && Path::new(filename).is_file()
+}
@@ -0,0 +1,55 @@
+// wfemto - A toy text editor
+// Written in 2025 by Dana Larose <ywg.dana@gmail.com>
+//
+// To the extent possible under law, the author(s) have dedicated all copyright
+// and related and neighboring rights to this software to the public domain
+// worldwide. This software is distributed without any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication along
+// with this software. If not,
+// see <http://creativecommons.org/publicdomain/zero/1.0/>.
+
+// Working out which character was meant by what's typed into the insert
+// character prompt: a hex codepoint ("2192", "U+1F600") or one of a handful
+// of names for characters that are awkward to type.
+
+/// Names accepted in place of a codepoint
+const NAMES: &[(&str, char)] = &[
+    ("nbsp", '\u{00A0}'),
+    ("en dash", '\u{2013}'),
+    ("em dash", '\u{2014}'),
+    ("ellipsis", '\u{2026}'),
+    ("bullet", '\u{2022}'),
+    ("degree", '\u{00B0}'),
+    ("copyright", '\u{00A9}'),
+    ("section", '\u{00A7}'),
+    ("left arrow", '\u{2190}'),
+    ("right arrow", '\u{2192}'),
+    ("lsquo", '\u{2018}'),
+    ("rsquo", '\u{2019}'),
+    ("ldquo", '\u{201C}'),
+    ("rdquo", '\u{201D}'),
+    ("zwsp", '\u{200B}'),
+];
+
+pub fn parse(input: &str) -> Result<char, String> {
+    let input = input.trim();
+    if input.is_empty() {
+        return Err(String::from("Type a hex codepoint or a name"));
+    }
+
+    let name = input.to_lowercase();
+    if let Some((_, c)) = NAMES.iter().find(|(n, _)| *n == name) {
+        return Ok(*c);
+    }
+
+    let hex = name.strip_prefix("u+").unwrap_or(&name);
+    let Ok(code) = u32::from_str_radix(hex, 16) else {
+        return Err(format!("'{}' isn't a hex codepoint or a known name", input));
+    };
+    match char::from_u32(code) {
+        Some(c) => Ok(c),
+        None if (0xD800..=0xDFFF).contains(&code) => Err(format!("U+{:04X} is a surrogate", code)),
+        None => Err(format!("U+{:X} is past the end of Unicode", code)),
+    }
+}
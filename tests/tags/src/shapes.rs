@@ -0,0 +1,22 @@
+pub struct Circle {
+    pub radius: f64,
+}
+
+pub struct Square {
+    pub side: f64,
+}
+
+impl Circle {
+    pub fn area(&self) -> f64 {
+        3.14159 * self.radius * self.radius
+    }
+}
+
+impl Square {
+    pub fn area(&self) -> f64 {
+        self.side * self.side
+    }
+}
+
+/* a path/with/slashes */
+pub fn origin() -> (f64, f64) { (0.0, 0.0) }